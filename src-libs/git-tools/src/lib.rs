@@ -590,7 +590,7 @@ impl GitService {
         Ok(())
     }
 
-    fn signature_with_fallback<'a>(&self, repo: &'a Repository) -> Result<git2::Signature<'a>, GitServiceError> {
+    pub fn signature_with_fallback<'a>(&self, repo: &'a Repository) -> Result<git2::Signature<'a>, GitServiceError> {
         match repo.signature() {
             Ok(sig) => Ok(sig),
             Err(_) => git2::Signature::now("Vibe Git", "noreply@vibegit.com").map_err(GitServiceError::from)
@@ -0,0 +1,590 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use zstd::stream::decode_all;
+
+use super::{
+    storage::{CheckpointStorage, FileCheckpointStorage, StorageBackend, StorageSettings},
+    Checkpoint, CheckpointPaths, TimelineNode,
+};
+
+/// Version of the archive layout written by `export_timeline`
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Name of the manifest entry, always written first in the archive
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// Name of the optional session transcript entry
+const SESSION_ENTRY: &str = "session.jsonl";
+
+/// Describes the contents of a timeline archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    /// Archive layout version
+    pub format_version: u32,
+    /// Timestamp when the archive was created
+    pub exported_at: DateTime<Utc>,
+    /// Session the timeline was exported from
+    pub session_id: String,
+    /// Project the timeline was exported from
+    pub project_id: String,
+    /// IDs of all checkpoints in the archive
+    pub checkpoint_ids: Vec<String>,
+    /// Every file in the archive (except the manifest) with its integrity hash
+    pub entries: Vec<ArchiveEntry>,
+}
+
+/// A single file stored in a timeline archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntry {
+    /// Path relative to the archive root
+    pub path: String,
+    /// SHA-256 of the stored bytes
+    pub sha256: String,
+    /// Size of the stored bytes
+    pub size: u64,
+}
+
+/// Summary of an export or import operation
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineArchiveSummary {
+    /// Path of the archive file
+    pub archive_path: PathBuf,
+    /// Session ID of the exported or imported timeline
+    pub session_id: String,
+    /// Project ID of the exported or imported timeline
+    pub project_id: String,
+    /// Number of checkpoints in the archive
+    pub checkpoint_count: usize,
+    /// Number of content pool blobs in the archive
+    pub content_blob_count: usize,
+    /// Total size of all archived files before compression
+    pub total_bytes: u64,
+}
+
+/// Export a session timeline into a single tar.zst archive
+///
+/// Only content pool blobs referenced by a checkpoint are included. The
+/// session transcript is added too when present so the importer can continue
+/// the conversation, not just restore files.
+pub fn export_timeline(
+    claude_dir: &Path,
+    project_id: &str,
+    session_id: &str,
+    archive_path: &Path,
+) -> Result<TimelineArchiveSummary> {
+    ensure_file_backend(claude_dir, project_id)?;
+
+    let paths = CheckpointPaths::new(&claude_dir.to_path_buf(), project_id, session_id);
+    let storage = FileCheckpointStorage::new(claude_dir.to_path_buf());
+
+    if !paths.timeline_file.exists() {
+        anyhow::bail!("No timeline found for session: {}", session_id);
+    }
+    let timeline = storage.load_timeline(&paths.timeline_file)?;
+
+    let mut checkpoint_ids = Vec::new();
+    if let Some(root) = &timeline.root_node {
+        collect_ids(root, &mut checkpoint_ids);
+    }
+
+    // Gather (archive path, source file) pairs
+    let mut files: Vec<(String, PathBuf)> =
+        vec![("timeline.json".to_string(), paths.timeline_file.clone())];
+    let mut referenced_hashes = HashSet::new();
+
+    for checkpoint_id in &checkpoint_ids {
+        files.push((
+            format!("checkpoints/{}/metadata.json", checkpoint_id),
+            paths.checkpoint_metadata_file(checkpoint_id),
+        ));
+        files.push((
+            format!("checkpoints/{}/messages.jsonl", checkpoint_id),
+            paths.checkpoint_messages_file(checkpoint_id),
+        ));
+
+        let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
+        if !refs_dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(&refs_dir)? {
+            let ref_path = entry?.path();
+            if ref_path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let ref_json =
+                fs::read_to_string(&ref_path).context("Failed to read file reference")?;
+            let ref_metadata: serde_json::Value =
+                serde_json::from_str(&ref_json).context("Failed to parse file reference")?;
+            if let Some(hash) = ref_metadata["hash"].as_str() {
+                if !hash.is_empty() {
+                    referenced_hashes.insert(hash.to_string());
+                }
+            }
+            let file_name = ref_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            files.push((
+                format!("files/refs/{}/{}", checkpoint_id, file_name),
+                ref_path.clone(),
+            ));
+        }
+    }
+
+    let content_pool_dir = paths.files_dir.join("content_pool");
+    let mut hashes: Vec<&String> = referenced_hashes.iter().collect();
+    hashes.sort();
+    let mut content_blob_count = 0;
+    for hash in hashes {
+        let blob = content_pool_dir.join(hash);
+        if blob.exists() {
+            files.push((format!("files/content_pool/{}", hash), blob));
+            content_blob_count += 1;
+        } else {
+            log::warn!("Content file missing for hash: {}", hash);
+        }
+    }
+
+    let session_file = claude_dir
+        .join("projects")
+        .join(project_id)
+        .join(format!("{}.jsonl", session_id));
+    if session_file.exists() {
+        files.push((SESSION_ENTRY.to_string(), session_file));
+    }
+
+    // Hash everything up front so the manifest can lead the archive
+    let mut entries = Vec::with_capacity(files.len());
+    let mut contents = Vec::with_capacity(files.len());
+    for (archive_name, source) in files {
+        let data =
+            fs::read(&source).with_context(|| format!("Failed to read {}", source.display()))?;
+        entries.push(ArchiveEntry {
+            path: archive_name.clone(),
+            sha256: sha256_hex(&data),
+            size: data.len() as u64,
+        });
+        contents.push((archive_name, data));
+    }
+
+    let manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        exported_at: Utc::now(),
+        session_id: session_id.to_string(),
+        project_id: project_id.to_string(),
+        checkpoint_ids: checkpoint_ids.clone(),
+        entries,
+    };
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).context("Failed to serialize manifest")?;
+
+    if let Some(parent) = archive_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create archive directory")?;
+    }
+    let file = fs::File::create(archive_path).context("Failed to create archive file")?;
+    let encoder = zstd::stream::Encoder::new(file, 3).context("Failed to create zstd encoder")?;
+    let mut builder = tar::Builder::new(encoder);
+
+    append_entry(&mut builder, MANIFEST_ENTRY, &manifest_json)?;
+    for (archive_name, data) in &contents {
+        append_entry(&mut builder, archive_name, data)?;
+    }
+    builder
+        .into_inner()
+        .context("Failed to finish archive")?
+        .finish()
+        .context("Failed to finish zstd stream")?;
+
+    Ok(TimelineArchiveSummary {
+        archive_path: archive_path.to_path_buf(),
+        session_id: session_id.to_string(),
+        project_id: project_id.to_string(),
+        checkpoint_count: checkpoint_ids.len(),
+        content_blob_count,
+        total_bytes: manifest.entries.iter().map(|e| e.size).sum(),
+    })
+}
+
+/// Import a timeline archive into a project
+///
+/// Every entry is verified against the manifest hash, and content pool blobs
+/// are additionally checked against their content address, before anything is
+/// written. Checkpoint metadata is rewritten to the target project and session.
+pub fn import_timeline(
+    claude_dir: &Path,
+    archive_path: &Path,
+    target_project_id: &str,
+    target_session_id: Option<&str>,
+) -> Result<TimelineArchiveSummary> {
+    ensure_plain_id("project id", target_project_id)?;
+    ensure_file_backend(claude_dir, target_project_id)?;
+
+    let file = fs::File::open(archive_path).context("Failed to open archive file")?;
+    let decoder = zstd::stream::Decoder::new(file).context("Failed to create zstd decoder")?;
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut manifest: Option<ArchiveManifest> = None;
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+
+    for entry in archive
+        .entries()
+        .context("Failed to read archive entries")?
+    {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        let entry_path = entry.path().context("Invalid entry path")?.into_owned();
+        let name = safe_entry_name(&entry_path)?;
+
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to read archive entry {}", name))?;
+
+        if name == MANIFEST_ENTRY {
+            manifest = Some(serde_json::from_slice(&data).context("Failed to parse manifest")?);
+        } else {
+            contents.insert(name, data);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow::anyhow!("Archive has no manifest"))?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        anyhow::bail!(
+            "Unsupported archive format version: {}",
+            manifest.format_version
+        );
+    }
+
+    verify_entries(&manifest, &contents)?;
+
+    // The manifest is untrusted input and the id becomes a path component
+    let session_id = target_session_id
+        .unwrap_or(&manifest.session_id)
+        .to_string();
+    ensure_plain_id("session id", &session_id)?;
+    let paths = CheckpointPaths::new(&claude_dir.to_path_buf(), target_project_id, &session_id);
+    if paths.timeline_file.exists() {
+        anyhow::bail!(
+            "A timeline already exists for session {} in project {}",
+            session_id,
+            target_project_id
+        );
+    }
+
+    let base_dir = paths
+        .timeline_file
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Invalid timeline path"))?
+        .to_path_buf();
+    let session_file = claude_dir
+        .join("projects")
+        .join(target_project_id)
+        .join(format!("{}.jsonl", session_id));
+
+    let mut content_blob_count = 0;
+    for entry in &manifest.entries {
+        let data = &contents[&entry.path];
+
+        let (target, data) = if entry.path == SESSION_ENTRY {
+            if session_file.exists() {
+                log::warn!(
+                    "Session file already exists, keeping it: {}",
+                    session_file.display()
+                );
+                continue;
+            }
+            (session_file.clone(), data.clone())
+        } else if entry.path == "timeline.json" {
+            let mut timeline: super::SessionTimeline =
+                serde_json::from_slice(data).context("Failed to parse archived timeline")?;
+            timeline.session_id = session_id.clone();
+            if let Some(root) = &mut timeline.root_node {
+                retarget_node(root, target_project_id, &session_id);
+            }
+            (
+                base_dir.join(&entry.path),
+                serde_json::to_vec_pretty(&timeline).context("Failed to serialize timeline")?,
+            )
+        } else if entry.path.starts_with("checkpoints/") && entry.path.ends_with("/metadata.json") {
+            let mut checkpoint: Checkpoint = serde_json::from_slice(data)
+                .context("Failed to parse archived checkpoint metadata")?;
+            retarget_checkpoint(&mut checkpoint, target_project_id, &session_id);
+            (
+                base_dir.join(&entry.path),
+                serde_json::to_vec_pretty(&checkpoint)
+                    .context("Failed to serialize checkpoint metadata")?,
+            )
+        } else {
+            if entry.path.starts_with("files/content_pool/") {
+                content_blob_count += 1;
+            }
+            (base_dir.join(&entry.path), data.clone())
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).context("Failed to create import directory")?;
+        }
+        fs::write(&target, data)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+    }
+
+    Ok(TimelineArchiveSummary {
+        archive_path: archive_path.to_path_buf(),
+        session_id,
+        project_id: target_project_id.to_string(),
+        checkpoint_count: manifest.checkpoint_ids.len(),
+        content_blob_count,
+        total_bytes: manifest.entries.iter().map(|e| e.size).sum(),
+    })
+}
+
+/// Check every manifest entry against the extracted archive contents
+fn verify_entries(manifest: &ArchiveManifest, contents: &HashMap<String, Vec<u8>>) -> Result<()> {
+    for entry in &manifest.entries {
+        let data = contents
+            .get(&entry.path)
+            .ok_or_else(|| anyhow::anyhow!("Archive is missing entry: {}", entry.path))?;
+
+        if data.len() as u64 != entry.size || sha256_hex(data) != entry.sha256 {
+            anyhow::bail!("Integrity check failed for entry: {}", entry.path);
+        }
+
+        // Content pool blobs are addressed by the hash of their uncompressed content
+        if let Some(hash) = entry.path.strip_prefix("files/content_pool/") {
+            let content = decode_all(&data[..])
+                .with_context(|| format!("Failed to decompress blob {}", hash))?;
+            if sha256_hex(&content) != hash {
+                anyhow::bail!("Content hash mismatch for blob: {}", hash);
+            }
+        }
+    }
+
+    if let Some(extra) = contents
+        .keys()
+        .find(|name| !manifest.entries.iter().any(|e| &e.path == *name))
+    {
+        anyhow::bail!(
+            "Archive contains an entry not listed in the manifest: {}",
+            extra
+        );
+    }
+
+    Ok(())
+}
+
+/// Validate an archive entry path and return it as a forward-slash string
+fn safe_entry_name(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => anyhow::bail!("Unsafe path in archive: {}", path.display()),
+        }
+    }
+    if parts.is_empty() {
+        anyhow::bail!("Empty path in archive");
+    }
+    Ok(parts.join("/"))
+}
+
+/// Reject ids that are not a single plain path component
+fn ensure_plain_id(kind: &str, id: &str) -> Result<()> {
+    if id.is_empty() || id == "." || id == ".." || id.contains(['/', '\\', '\0']) {
+        anyhow::bail!("Invalid {}: {:?}", kind, id);
+    }
+    Ok(())
+}
+
+fn append_entry<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, name, data)
+        .with_context(|| format!("Failed to add {} to archive", name))
+}
+
+fn retarget_node(node: &mut TimelineNode, project_id: &str, session_id: &str) {
+    retarget_checkpoint(&mut node.checkpoint, project_id, session_id);
+    for child in &mut node.children {
+        retarget_node(child, project_id, session_id);
+    }
+}
+
+fn retarget_checkpoint(checkpoint: &mut Checkpoint, project_id: &str, session_id: &str) {
+    checkpoint.project_id = project_id.to_string();
+    checkpoint.session_id = session_id.to_string();
+}
+
+fn collect_ids(node: &TimelineNode, ids: &mut Vec<String>) {
+    ids.push(node.checkpoint.id.clone());
+    for child in &node.children {
+        collect_ids(child, ids);
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Archives carry content pool blobs, so only file-backed projects can use them
+fn ensure_file_backend(claude_dir: &Path, project_id: &str) -> Result<()> {
+    if StorageSettings::load(claude_dir, project_id)?.backend != StorageBackend::Files {
+        anyhow::bail!("Timeline archives require file-based checkpoint storage");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{
+        storage::calculate_file_hash, CheckpointMetadata, CheckpointTrigger, FileSnapshot,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let source = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(source.path().to_path_buf());
+        storage.init_storage("project-a", "session-1").unwrap();
+
+        let content = "fn main() {}\n";
+        let checkpoint = Checkpoint {
+            id: "cp-1".to_string(),
+            session_id: "session-1".to_string(),
+            project_id: "project-a".to_string(),
+            message_index: 0,
+            timestamp: Utc::now(),
+            description: Some("first".to_string()),
+            parent_checkpoint_id: None,
+            metadata: CheckpointMetadata {
+                total_tokens: 42,
+                model_used: "test".to_string(),
+                user_prompt: "hello".to_string(),
+                file_changes: 1,
+                snapshot_size: 0,
+            },
+            trigger: CheckpointTrigger::Manual,
+        };
+        let snapshot = FileSnapshot {
+            checkpoint_id: "cp-1".to_string(),
+            file_path: PathBuf::from("src/main.rs"),
+            content: content.to_string(),
+            hash: calculate_file_hash(content),
+            is_deleted: false,
+            permissions: None,
+            size: content.len() as u64,
+        };
+        storage
+            .save_checkpoint("project-a", "session-1", &checkpoint, vec![snapshot], "{}")
+            .unwrap();
+
+        let archive_path = source.path().join("export.tar.zst");
+        let exported =
+            export_timeline(source.path(), "project-a", "session-1", &archive_path).unwrap();
+        assert_eq!(exported.checkpoint_count, 1);
+        assert_eq!(exported.content_blob_count, 1);
+
+        let target = TempDir::new().unwrap();
+        let imported =
+            import_timeline(target.path(), &archive_path, "project-b", Some("session-2")).unwrap();
+        assert_eq!(imported.checkpoint_count, 1);
+
+        let target_storage = FileCheckpointStorage::new(target.path().to_path_buf());
+        let (restored, files, messages) = target_storage
+            .load_checkpoint("project-b", "session-2", "cp-1")
+            .unwrap();
+        assert_eq!(restored.project_id, "project-b");
+        assert_eq!(restored.session_id, "session-2");
+        assert_eq!(messages, "{}");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].content, content);
+
+        // Importing the same session twice must not clobber it
+        assert!(
+            import_timeline(target.path(), &archive_path, "project-b", Some("session-2")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_import_rejects_path_traversal_ids() {
+        let source = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(source.path().to_path_buf());
+        storage.init_storage("project-a", "session-1").unwrap();
+        let checkpoint = Checkpoint {
+            id: "cp-1".to_string(),
+            session_id: "session-1".to_string(),
+            project_id: "project-a".to_string(),
+            message_index: 0,
+            timestamp: Utc::now(),
+            description: None,
+            parent_checkpoint_id: None,
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "test".to_string(),
+                user_prompt: String::new(),
+                file_changes: 0,
+                snapshot_size: 0,
+            },
+            trigger: CheckpointTrigger::Manual,
+        };
+        storage
+            .save_checkpoint("project-a", "session-1", &checkpoint, vec![], "{}")
+            .unwrap();
+        let exported = source.path().join("export.tar.zst");
+        export_timeline(source.path(), "project-a", "session-1", &exported).unwrap();
+
+        // Rewrite the manifest to point outside the projects directory
+        let data = decode_all(fs::File::open(&exported).unwrap()).unwrap();
+        let mut entries = Vec::new();
+        for entry in tar::Archive::new(data.as_slice()).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes).unwrap();
+            if name == MANIFEST_ENTRY {
+                let mut manifest: ArchiveManifest = serde_json::from_slice(&bytes).unwrap();
+                manifest.session_id = "../../../escaped".to_string();
+                bytes = serde_json::to_vec(&manifest).unwrap();
+            }
+            entries.push((name, bytes));
+        }
+        let hostile = source.path().join("hostile.tar.zst");
+        let encoder = zstd::stream::Encoder::new(fs::File::create(&hostile).unwrap(), 3).unwrap();
+        let mut builder = tar::Builder::new(encoder);
+        for (name, bytes) in &entries {
+            append_entry(&mut builder, name, bytes).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let target = TempDir::new().unwrap();
+        let claude_dir = target.path().join(".claude");
+        let err = import_timeline(&claude_dir, &hostile, "project-b", None).unwrap_err();
+        assert!(err.to_string().contains("Invalid session id"));
+        assert!(!target.path().join("escaped").exists());
+
+        // Caller-supplied ids are checked the same way
+        for (project, session) in [("..", "s"), ("a/b", "s"), ("p", ""), ("p", "a\\b")] {
+            assert!(import_timeline(&claude_dir, &exported, project, Some(session)).is_err());
+        }
+        assert!(fs::read_dir(target.path()).unwrap().next().is_none());
+    }
+}
@@ -0,0 +1,494 @@
+use anyhow::{Context, Result};
+use git2::{Delta, IndexEntry, IndexTime, Repository};
+use git_tools::GitService;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+use super::{
+    storage::{
+        read_checkpoint_record, remove_checkpoint_record, update_timeline_with_checkpoint,
+        write_checkpoint_record, CheckpointStorage, StorageBackend, DEFAULT_COMPRESSION_LEVEL,
+    },
+    Checkpoint, CheckpointDiff, CheckpointPaths, CheckpointResult, FileDiff, FileSnapshot,
+};
+
+/// Namespace of the hidden refs that hold checkpoint commits
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/vibe/checkpoints";
+
+/// Tree entry listing every snapshot of a checkpoint, including deletions
+const MANIFEST_FILE: &str = ".vibe-checkpoint.json";
+
+/// Manifest record for one file snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    path: PathBuf,
+    hash: String,
+    is_deleted: bool,
+    permissions: Option<u32>,
+    size: u64,
+}
+
+/// Stores file snapshots as commits in the project's own git repository
+///
+/// Each checkpoint becomes a commit on `refs/vibe/checkpoints/<session>/<checkpoint>`
+/// whose parent is the parent checkpoint's commit, so forks show up as git
+/// history and can be inspected with `git log` or `git diff`. Metadata and
+/// messages stay in `.timelines` like the file backend.
+pub struct GitCheckpointStorage {
+    claude_dir: PathBuf,
+    repo_path: PathBuf,
+    compression_level: i32,
+}
+
+impl GitCheckpointStorage {
+    /// Open git-backed storage for the repository containing `repo_path`
+    pub fn open(claude_dir: PathBuf, repo_path: &Path) -> Result<Self> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("No git repository at {}", repo_path.display()))?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow::anyhow!("Checkpoints cannot be stored in a bare repository"))?;
+
+        Ok(Self {
+            claude_dir,
+            repo_path: workdir.to_path_buf(),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+        })
+    }
+
+    /// Repository that receives the checkpoint commits
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    /// Name of the hidden ref for a checkpoint
+    pub fn checkpoint_ref(session_id: &str, checkpoint_id: &str) -> String {
+        format!("{}/{}/{}", CHECKPOINT_REF_PREFIX, session_id, checkpoint_id)
+    }
+
+    fn repo(&self) -> Result<Repository> {
+        GitService::new()
+            .open_repo(&self.repo_path)
+            .context("Failed to open checkpoint repository")
+    }
+
+    fn checkpoint_commit<'r>(
+        repo: &'r Repository,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<git2::Commit<'r>> {
+        let ref_name = Self::checkpoint_ref(session_id, checkpoint_id);
+        repo.find_reference(&ref_name)
+            .and_then(|r| r.peel_to_commit())
+            .with_context(|| format!("Checkpoint commit not found: {}", ref_name))
+    }
+
+    /// Path of a snapshot inside the checkpoint tree, if it stays within the repository
+    fn tree_path(&self, file_path: &Path) -> Option<String> {
+        let relative = if file_path.is_absolute() {
+            file_path.strip_prefix(&self.repo_path).ok()?
+        } else {
+            file_path
+        };
+
+        let normal = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !normal || relative.as_os_str().is_empty() {
+            return None;
+        }
+
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn add_blob(
+        repo: &Repository,
+        index: &mut git2::Index,
+        path: &str,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<()> {
+        let id = repo.blob(content).context("Failed to write blob")?;
+        let entry = IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            file_size: content.len() as u32,
+            id,
+            flags: path.len().min(0xfff) as u16,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        };
+        index
+            .add(&entry)
+            .context("Failed to add snapshot to tree")?;
+        Ok(())
+    }
+
+    fn read_manifest(repo: &Repository, tree: &git2::Tree) -> Result<Vec<ManifestEntry>> {
+        let entry = tree
+            .get_path(Path::new(MANIFEST_FILE))
+            .context("Checkpoint commit has no manifest")?;
+        let blob = repo.find_blob(entry.id())?;
+        serde_json::from_slice(blob.content()).context("Failed to parse checkpoint manifest")
+    }
+}
+
+impl CheckpointStorage for GitCheckpointStorage {
+    fn claude_dir(&self) -> &PathBuf {
+        &self.claude_dir
+    }
+
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::Git
+    }
+
+    fn save_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint: &Checkpoint,
+        file_snapshots: Vec<FileSnapshot>,
+        messages: &str,
+    ) -> Result<CheckpointResult> {
+        let repo = self.repo()?;
+        let mut index = git2::Index::new()?;
+        let mut manifest = Vec::new();
+        let mut warnings = Vec::new();
+
+        for snapshot in &file_snapshots {
+            let Some(path) = self.tree_path(&snapshot.file_path) else {
+                warnings.push(format!(
+                    "Failed to save {}: path is outside the repository",
+                    snapshot.file_path.display()
+                ));
+                continue;
+            };
+
+            if !snapshot.is_deleted {
+                let executable = snapshot.permissions.is_some_and(|p| p & 0o111 != 0);
+                let mode = if executable { 0o100755 } else { 0o100644 };
+                if let Err(e) =
+                    Self::add_blob(&repo, &mut index, &path, snapshot.content.as_bytes(), mode)
+                {
+                    warnings.push(format!(
+                        "Failed to save {}: {}",
+                        snapshot.file_path.display(),
+                        e
+                    ));
+                    continue;
+                }
+            }
+
+            manifest.push(ManifestEntry {
+                path: snapshot.file_path.clone(),
+                hash: snapshot.hash.clone(),
+                is_deleted: snapshot.is_deleted,
+                permissions: snapshot.permissions,
+                size: snapshot.size,
+            });
+        }
+
+        let files_processed = manifest.len();
+        let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+        Self::add_blob(&repo, &mut index, MANIFEST_FILE, &manifest_json, 0o100644)?;
+
+        let tree_id = index.write_tree_to(&repo).context("Failed to write tree")?;
+        let tree = repo.find_tree(tree_id)?;
+
+        // Chain onto the parent checkpoint so forks appear as git history
+        let parent_commit = match &checkpoint.parent_checkpoint_id {
+            Some(parent_id) => match Self::checkpoint_commit(&repo, session_id, parent_id) {
+                Ok(commit) => Some(commit),
+                Err(e) => {
+                    log::warn!(
+                        "Committing checkpoint {} without parent: {}",
+                        checkpoint.id,
+                        e
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        let parents: Vec<&git2::Commit> = parent_commit.iter().collect();
+
+        let signature = GitService::new().signature_with_fallback(&repo)?;
+        let message = format!(
+            "{}\n\nCheckpoint-Id: {}\nSession-Id: {}\nMessage-Index: {}\n",
+            checkpoint.description.as_deref().unwrap_or("Checkpoint"),
+            checkpoint.id,
+            session_id,
+            checkpoint.message_index
+        );
+        let ref_name = Self::checkpoint_ref(session_id, &checkpoint.id);
+        repo.commit(
+            Some(&ref_name),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )
+        .context("Failed to commit checkpoint")?;
+
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        write_checkpoint_record(&paths, checkpoint, messages, self.compression_level)?;
+        update_timeline_with_checkpoint(self, &paths.timeline_file, checkpoint, &file_snapshots)?;
+
+        Ok(CheckpointResult {
+            checkpoint: checkpoint.clone(),
+            files_processed,
+            warnings,
+        })
+    }
+
+    fn load_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<(Checkpoint, Vec<FileSnapshot>, String)> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let (checkpoint, messages) = read_checkpoint_record(&paths, checkpoint_id)?;
+
+        let repo = self.repo()?;
+        let tree = Self::checkpoint_commit(&repo, session_id, checkpoint_id)?.tree()?;
+
+        let mut snapshots = Vec::new();
+        for entry in Self::read_manifest(&repo, &tree)? {
+            let content = if entry.is_deleted {
+                String::new()
+            } else {
+                let path = self
+                    .tree_path(&entry.path)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid snapshot path in manifest"))?;
+                let blob = tree
+                    .get_path(Path::new(&path))
+                    .and_then(|e| repo.find_blob(e.id()))
+                    .with_context(|| format!("Snapshot missing from commit: {}", path))?;
+                String::from_utf8(blob.content().to_vec())
+                    .context("Invalid UTF-8 in file content")?
+            };
+
+            snapshots.push(FileSnapshot {
+                checkpoint_id: checkpoint_id.to_string(),
+                file_path: entry.path,
+                content,
+                hash: entry.hash,
+                is_deleted: entry.is_deleted,
+                permissions: entry.permissions,
+                size: entry.size,
+            });
+        }
+
+        Ok((checkpoint, snapshots, messages))
+    }
+
+    fn remove_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<()> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        remove_checkpoint_record(&paths, checkpoint_id)?;
+
+        let repo = self.repo()?;
+        let ref_name = Self::checkpoint_ref(session_id, checkpoint_id);
+        if let Ok(mut reference) = repo.find_reference(&ref_name) {
+            reference
+                .delete()
+                .context("Failed to delete checkpoint ref")?;
+        }
+
+        Ok(())
+    }
+
+    /// Unreferenced commits and blobs are left to `git gc`
+    fn garbage_collect_content(&self, _project_id: &str, _session_id: &str) -> Result<usize> {
+        Ok(0)
+    }
+
+    /// Diff the two checkpoint commits with libgit2
+    fn diff_checkpoints(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        from_checkpoint_id: &str,
+        to_checkpoint_id: &str,
+    ) -> Result<CheckpointDiff> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let (from_checkpoint, _) = read_checkpoint_record(&paths, from_checkpoint_id)
+            .context("Failed to load source checkpoint")?;
+        let (to_checkpoint, _) = read_checkpoint_record(&paths, to_checkpoint_id)
+            .context("Failed to load target checkpoint")?;
+
+        let repo = self.repo()?;
+        let from_tree = Self::checkpoint_commit(&repo, session_id, from_checkpoint_id)?.tree()?;
+        let to_tree = Self::checkpoint_commit(&repo, session_id, to_checkpoint_id)?.tree()?;
+        let diff = repo.diff_tree_to_tree(Some(&from_tree), Some(&to_tree), None)?;
+
+        let mut modified_files = Vec::new();
+        let mut added_files = Vec::new();
+        let mut deleted_files = Vec::new();
+
+        for idx in 0..diff.deltas().len() {
+            let Some(delta) = diff.get_delta(idx) else {
+                continue;
+            };
+            let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+                continue;
+            };
+            if path == Path::new(MANIFEST_FILE) {
+                continue;
+            }
+            let path = path.to_path_buf();
+
+            match delta.status() {
+                Delta::Added => added_files.push(path),
+                Delta::Deleted => deleted_files.push(path),
+                _ => {
+                    let (additions, deletions, diff_content) =
+                        match git2::Patch::from_diff(&diff, idx)? {
+                            Some(mut patch) => {
+                                let (_, additions, deletions) = patch.line_stats()?;
+                                let content = patch
+                                    .to_buf()
+                                    .ok()
+                                    .and_then(|buf| buf.as_str().map(String::from));
+                                (additions, deletions, content)
+                            }
+                            None => (0, 0, None),
+                        };
+
+                    modified_files.push(FileDiff {
+                        path,
+                        additions,
+                        deletions,
+                        diff_content,
+                    });
+                }
+            }
+        }
+
+        let token_delta = (to_checkpoint.metadata.total_tokens as i64)
+            - (from_checkpoint.metadata.total_tokens as i64);
+
+        Ok(CheckpointDiff {
+            from_checkpoint_id: from_checkpoint_id.to_string(),
+            to_checkpoint_id: to_checkpoint_id.to_string(),
+            modified_files,
+            added_files,
+            deleted_files,
+            token_delta,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{storage::calculate_file_hash, CheckpointMetadata, CheckpointTrigger};
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn checkpoint(id: &str, parent: Option<&str>) -> Checkpoint {
+        Checkpoint {
+            id: id.to_string(),
+            session_id: "session".to_string(),
+            project_id: "project".to_string(),
+            message_index: 0,
+            timestamp: Utc::now(),
+            description: None,
+            parent_checkpoint_id: parent.map(String::from),
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "test".to_string(),
+                user_prompt: String::new(),
+                file_changes: 1,
+                snapshot_size: 0,
+            },
+            trigger: CheckpointTrigger::Manual,
+        }
+    }
+
+    fn snapshot(checkpoint_id: &str, path: &str, content: &str, is_deleted: bool) -> FileSnapshot {
+        FileSnapshot {
+            checkpoint_id: checkpoint_id.to_string(),
+            file_path: PathBuf::from(path),
+            content: content.to_string(),
+            hash: calculate_file_hash(content),
+            is_deleted,
+            permissions: None,
+            size: content.len() as u64,
+        }
+    }
+
+    #[test]
+    fn test_checkpoints_as_commits_on_hidden_refs() {
+        let claude_dir = TempDir::new().unwrap();
+        let repo_dir = TempDir::new().unwrap();
+        let repo = Repository::init(repo_dir.path()).unwrap();
+
+        let storage =
+            GitCheckpointStorage::open(claude_dir.path().to_path_buf(), repo_dir.path()).unwrap();
+        storage.init_storage("project", "session").unwrap();
+
+        storage
+            .save_checkpoint(
+                "project",
+                "session",
+                &checkpoint("a", None),
+                vec![snapshot("a", "src/main.rs", "fn main() {}\n", false)],
+                "{}",
+            )
+            .unwrap();
+        storage
+            .save_checkpoint(
+                "project",
+                "session",
+                &checkpoint("b", Some("a")),
+                vec![
+                    snapshot("b", "src/main.rs", "fn main() {\n    run();\n}\n", false),
+                    snapshot("b", "README.md", "", true),
+                    snapshot("b", "../outside.txt", "nope", false),
+                ],
+                "{}",
+            )
+            .unwrap();
+
+        // The second commit chains onto the first
+        let b = GitCheckpointStorage::checkpoint_commit(&repo, "session", "b").unwrap();
+        let a = GitCheckpointStorage::checkpoint_commit(&repo, "session", "a").unwrap();
+        assert_eq!(b.parent_id(0).unwrap(), a.id());
+
+        let (_, files, messages) = storage.load_checkpoint("project", "session", "b").unwrap();
+        assert_eq!(messages, "{}");
+        assert_eq!(files.len(), 2);
+        let main = files
+            .iter()
+            .find(|f| f.file_path == Path::new("src/main.rs"))
+            .unwrap();
+        assert!(main.content.contains("run();"));
+        assert!(files.iter().any(|f| f.is_deleted));
+
+        let diff = storage
+            .diff_checkpoints("project", "session", "a", "b")
+            .unwrap();
+        assert_eq!(diff.modified_files.len(), 1);
+        assert_eq!(diff.modified_files[0].additions, 3);
+        assert!(diff.modified_files[0].diff_content.is_some());
+
+        storage
+            .remove_checkpoint("project", "session", "b")
+            .unwrap();
+        assert!(repo
+            .find_reference(&GitCheckpointStorage::checkpoint_ref("session", "b"))
+            .is_err());
+    }
+}
@@ -8,9 +8,11 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use super::{
+    retention::{self, RetentionResult},
     storage::{self, CheckpointStorage},
-    Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
-    FileSnapshot, FileState, FileTracker, SessionTimeline,
+    BranchComparison, BranchPruneResult, Checkpoint, CheckpointMetadata, CheckpointPaths,
+    CheckpointResult, CheckpointStrategy, CheckpointTrigger, FileSnapshot, FileState, FileTracker,
+    SessionTimeline, TimelineBranch,
 };
 
 /// Manages checkpoint operations for a session
@@ -19,7 +21,7 @@ pub struct CheckpointManager {
     session_id: String,
     project_path: PathBuf,
     file_tracker: Arc<RwLock<FileTracker>>,
-    pub storage: Arc<CheckpointStorage>,
+    pub storage: Arc<dyn CheckpointStorage>,
     timeline: Arc<RwLock<SessionTimeline>>,
     current_messages: Arc<RwLock<Vec<String>>>, // JSONL messages
 }
@@ -32,7 +34,7 @@ impl CheckpointManager {
         project_path: PathBuf,
         claude_dir: PathBuf,
     ) -> Result<Self> {
-        let storage = Arc::new(CheckpointStorage::new(claude_dir.clone()));
+        let storage = storage::open_storage(&claude_dir, &project_id)?;
 
         // Initialize storage
         storage.init_storage(&project_id, &session_id)?;
@@ -60,6 +62,11 @@ impl CheckpointManager {
         })
     }
 
+    /// Claude project this manager's timeline belongs to
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
     /// Track a new message in the session
     pub async fn track_message(&self, jsonl_message: String) -> Result<()> {
         let mut messages = self.current_messages.write().await;
@@ -85,6 +92,48 @@ impl CheckpointManager {
         Ok(())
     }
 
+    /// Session JSONL Claude writes for this manager's session
+    pub fn session_file(&self) -> PathBuf {
+        self.storage
+            .claude_dir()
+            .join("projects")
+            .join(&self.project_id)
+            .join(format!("{}.jsonl", self.session_id))
+    }
+
+    /// Bring the tracked messages in line with the session JSONL on disk
+    ///
+    /// Lines appended since the last sync are tracked like any other
+    /// message. When the file no longer starts with the tracked messages
+    /// (it was rewritten by a restore or compaction) tracking starts over
+    /// from the file. Returns how many lines were newly tracked.
+    pub async fn sync_session_messages(&self) -> Result<usize> {
+        let session_file = self.session_file();
+        if !session_file.exists() {
+            return Ok(0);
+        }
+
+        let content = fs::read_to_string(&session_file)
+            .with_context(|| format!("Failed to read session file {}", session_file.display()))?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        let tracked = {
+            let mut messages = self.current_messages.write().await;
+            let is_prefix =
+                messages.len() <= lines.len() && messages.iter().zip(&lines).all(|(a, b)| a == b);
+            if !is_prefix {
+                messages.clear();
+            }
+            messages.len()
+        };
+
+        for line in &lines[tracked..] {
+            self.track_message(line.to_string()).await?;
+        }
+
+        Ok(lines.len() - tracked)
+    }
+
     /// Track file operations from tool usage
     async fn track_tool_operation(&self, tool: &str, input: &serde_json::Value) -> Result<()> {
         match tool.to_lowercase().as_str() {
@@ -124,7 +173,7 @@ impl CheckpointManager {
                 .unwrap_or_else(Utc::now);
 
             (
-                storage::CheckpointStorage::calculate_file_hash(&content),
+                storage::calculate_file_hash(&content),
                 true,
                 metadata.len(),
                 modified,
@@ -189,6 +238,21 @@ impl CheckpointManager {
         &self,
         description: Option<String>,
         parent_checkpoint_id: Option<String>,
+    ) -> Result<CheckpointResult> {
+        self.create_checkpoint_with_trigger(
+            description,
+            parent_checkpoint_id,
+            CheckpointTrigger::Manual,
+        )
+        .await
+    }
+
+    /// Create a checkpoint, recording what triggered it
+    pub async fn create_checkpoint_with_trigger(
+        &self,
+        description: Option<String>,
+        parent_checkpoint_id: Option<String>,
+        trigger: CheckpointTrigger,
     ) -> Result<CheckpointResult> {
         let messages = self.current_messages.read().await;
         let message_index = messages.len().saturating_sub(1);
@@ -235,7 +299,7 @@ impl CheckpointManager {
         }
 
         // Generate checkpoint ID early so snapshots reference it
-        let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
+        let checkpoint_id = storage::generate_checkpoint_id();
 
         // Create file snapshots
         let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
@@ -262,11 +326,12 @@ impl CheckpointManager {
                 model_used,
                 user_prompt,
                 file_changes: file_snapshots.len(),
-                snapshot_size: storage::CheckpointStorage::estimate_checkpoint_size(
+                snapshot_size: storage::estimate_checkpoint_size(
                     &messages.join("\n"),
                     &file_snapshots,
                 ),
             },
+            trigger,
         };
 
         // Save checkpoint
@@ -279,8 +344,17 @@ impl CheckpointManager {
             &messages_content,
         )?;
 
+        // Enforce the project's retention policy now that the new checkpoint is on disk
+        if let Err(e) = retention::enforce_project_policy(
+            self.storage.as_ref(),
+            &self.project_id,
+            &self.session_id,
+        ) {
+            log::warn!("Failed to apply checkpoint retention policy: {}", e);
+        }
+
         // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
-        let claude_dir = self.storage.claude_dir.clone();
+        let claude_dir = self.storage.claude_dir().clone();
         let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
         let updated_timeline = self.storage.load_timeline(&paths.timeline_file)?;
         {
@@ -412,7 +486,7 @@ impl CheckpointManager {
 
             let (content, exists, permissions, size, current_hash) = if full_path.exists() {
                 let content = fs::read_to_string(&full_path).unwrap_or_default();
-                let current_hash = storage::CheckpointStorage::calculate_file_hash(&content);
+                let current_hash = storage::calculate_file_hash(&content);
 
                 // Don't skip based on hash - if is_modified is true, we should snapshot it
                 // The hash check in track_file_modification already determined if it changed
@@ -570,9 +644,11 @@ impl CheckpointManager {
             current_messages.push(line.to_string());
         }
 
-        // Update timeline
+        // Update timeline, following the branch whose tip we restored to
         let mut timeline = self.timeline.write().await;
         timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+        timeline.current_branch = timeline.branch_at(checkpoint_id).map(String::from);
+        self.save_timeline(&timeline)?;
 
         // Update file tracker
         let mut tracker = self.file_tracker.write().await;
@@ -657,11 +733,12 @@ impl CheckpointManager {
         }
     }
 
-    /// Fork from a checkpoint
+    /// Fork from a checkpoint, optionally naming the new branch
     pub async fn fork_from_checkpoint(
         &self,
         checkpoint_id: &str,
         description: Option<String>,
+        branch_name: Option<String>,
     ) -> Result<CheckpointResult> {
         // Load the checkpoint to fork from
         let (_base_checkpoint, _, _) =
@@ -671,6 +748,17 @@ impl CheckpointManager {
         // Restore to that checkpoint first
         self.restore_checkpoint(checkpoint_id).await?;
 
+        // The fork must not advance the branch we restored onto
+        {
+            let mut timeline = self.timeline.write().await;
+            timeline.current_branch = None;
+            if let Some(name) = branch_name {
+                Self::insert_branch(&mut timeline, name.clone(), checkpoint_id, None)?;
+                timeline.current_branch = Some(name);
+            }
+            self.save_timeline(&timeline)?;
+        }
+
         // Create a new checkpoint with the fork
         let fork_description =
             description.unwrap_or_else(|| format!("Fork from checkpoint {}", &checkpoint_id[..8]));
@@ -679,6 +767,191 @@ impl CheckpointManager {
             .await
     }
 
+    /// List the named branches of the timeline, most recently created first
+    pub async fn list_branches(&self) -> Vec<TimelineBranch> {
+        let timeline = self.timeline.read().await;
+        let mut branches: Vec<TimelineBranch> = timeline.branches.values().cloned().collect();
+        branches.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        branches
+    }
+
+    /// Name a branch ending at the given checkpoint (defaults to the current checkpoint)
+    pub async fn create_branch(
+        &self,
+        name: String,
+        checkpoint_id: Option<String>,
+        description: Option<String>,
+    ) -> Result<TimelineBranch> {
+        let mut timeline = self.timeline.write().await;
+
+        let tip_checkpoint_id = checkpoint_id
+            .or_else(|| timeline.current_checkpoint_id.clone())
+            .ok_or_else(|| anyhow::anyhow!("No checkpoint to create branch from"))?;
+
+        let branch = Self::insert_branch(&mut timeline, name, &tip_checkpoint_id, description)?;
+        if timeline.current_checkpoint_id.as_deref() == Some(tip_checkpoint_id.as_str()) {
+            timeline.current_branch = Some(branch.name.clone());
+        }
+        self.save_timeline(&timeline)?;
+
+        Ok(branch)
+    }
+
+    /// Switch to a named branch by restoring its tip checkpoint
+    pub async fn switch_branch(&self, name: &str) -> Result<CheckpointResult> {
+        let tip_checkpoint_id = {
+            let timeline = self.timeline.read().await;
+            timeline
+                .branches
+                .get(name)
+                .map(|b| b.tip_checkpoint_id.clone())
+                .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", name))?
+        };
+
+        let result = self.restore_checkpoint(&tip_checkpoint_id).await?;
+
+        // Several branches can share a tip, so pin the one that was asked for
+        let mut timeline = self.timeline.write().await;
+        timeline.current_branch = Some(name.to_string());
+        self.save_timeline(&timeline)?;
+
+        Ok(result)
+    }
+
+    /// Compare the tips of two named branches
+    pub async fn compare_branches(
+        &self,
+        base_branch: &str,
+        compare_branch: &str,
+    ) -> Result<BranchComparison> {
+        let (base_path, compare_path) = {
+            let timeline = self.timeline.read().await;
+            let path_of = |name: &str| -> Result<Vec<String>> {
+                let branch = timeline
+                    .branches
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", name))?;
+                let path = timeline
+                    .path_to_checkpoint(&branch.tip_checkpoint_id)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Branch tip not found: {}", branch.tip_checkpoint_id)
+                    })?;
+                Ok(path.into_iter().map(|c| c.id.clone()).collect())
+            };
+            (path_of(base_branch)?, path_of(compare_branch)?)
+        };
+
+        // Both paths start at the root, so the shared prefix ends at the common ancestor
+        let shared = base_path
+            .iter()
+            .zip(compare_path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let common_ancestor_id = shared.checked_sub(1).map(|i| base_path[i].clone());
+
+        let (base_tip, compare_tip) = match (base_path.last(), compare_path.last()) {
+            (Some(base_tip), Some(compare_tip)) => (base_tip, compare_tip),
+            _ => anyhow::bail!("Branch has no checkpoints"),
+        };
+
+        let diff = self.storage.diff_checkpoints(
+            &self.project_id,
+            &self.session_id,
+            base_tip,
+            compare_tip,
+        )?;
+
+        Ok(BranchComparison {
+            base_branch: base_branch.to_string(),
+            compare_branch: compare_branch.to_string(),
+            common_ancestor_id,
+            base_only_checkpoints: base_path.len() - shared,
+            compare_only_checkpoints: compare_path.len() - shared,
+            diff,
+        })
+    }
+
+    /// Prune a branch and garbage collect the content only it referenced
+    pub async fn prune_branch(&self, name: &str) -> Result<BranchPruneResult> {
+        let mut timeline = self.timeline.write().await;
+
+        // Make sure the on-disk timeline reflects the current checkpoint before pruning
+        self.save_timeline(&timeline)?;
+        let result = self
+            .storage
+            .prune_branch(&self.project_id, &self.session_id, name)?;
+
+        let paths = CheckpointPaths::new(
+            self.storage.claude_dir(),
+            &self.project_id,
+            &self.session_id,
+        );
+        *timeline = self.storage.load_timeline(&paths.timeline_file)?;
+
+        Ok(result)
+    }
+
+    /// Apply the project's retention policy to this session right away
+    pub async fn apply_retention_policy(&self) -> Result<RetentionResult> {
+        let mut timeline = self.timeline.write().await;
+
+        // The on-disk timeline must know the current checkpoint so it stays protected
+        self.save_timeline(&timeline)?;
+        let result = retention::enforce_project_policy(
+            self.storage.as_ref(),
+            &self.project_id,
+            &self.session_id,
+        )?;
+
+        let paths = CheckpointPaths::new(
+            self.storage.claude_dir(),
+            &self.project_id,
+            &self.session_id,
+        );
+        *timeline = self.storage.load_timeline(&paths.timeline_file)?;
+
+        Ok(result)
+    }
+
+    /// Add a new named branch to the timeline
+    fn insert_branch(
+        timeline: &mut SessionTimeline,
+        name: String,
+        tip_checkpoint_id: &str,
+        description: Option<String>,
+    ) -> Result<TimelineBranch> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            anyhow::bail!("Branch name cannot be empty");
+        }
+        if timeline.branches.contains_key(&name) {
+            anyhow::bail!("Branch already exists: {}", name);
+        }
+        if timeline.find_checkpoint(tip_checkpoint_id).is_none() {
+            anyhow::bail!("Checkpoint not found: {}", tip_checkpoint_id);
+        }
+
+        let branch = TimelineBranch {
+            name: name.clone(),
+            tip_checkpoint_id: tip_checkpoint_id.to_string(),
+            created_at: Utc::now(),
+            description,
+        };
+        timeline.branches.insert(name, branch.clone());
+
+        Ok(branch)
+    }
+
+    /// Persist the in-memory timeline to disk
+    fn save_timeline(&self, timeline: &SessionTimeline) -> Result<()> {
+        let paths = CheckpointPaths::new(
+            self.storage.claude_dir(),
+            &self.project_id,
+            &self.session_id,
+        );
+        self.storage.save_timeline(&paths.timeline_file, timeline)
+    }
+
     /// Check if auto-checkpoint should be triggered
     pub async fn should_auto_checkpoint(&self, message: &str) -> bool {
         let timeline = self.timeline.read().await;
@@ -756,7 +1029,7 @@ impl CheckpointManager {
         timeline.checkpoint_strategy = checkpoint_strategy;
 
         // Save updated timeline
-        let claude_dir = self.storage.claude_dir.clone();
+        let claude_dir = self.storage.claude_dir().clone();
         let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
         self.storage
             .save_timeline(&paths.timeline_file, &timeline)?;
@@ -785,3 +1058,92 @@ impl CheckpointManager {
             .max()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const USER_LINE: &str = r#"{"type":"user","message":{"role":"user","content":"write a"}}"#;
+    const TOOL_LINE: &str = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","name":"Write","input":{"file_path":"a.txt"}}]}}"#;
+    const RESULT_LINE: &str = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","content":"ok"}]}}"#;
+
+    #[tokio::test]
+    async fn test_sync_session_messages_snapshots_transcript_for_restore() {
+        let temp = TempDir::new().unwrap();
+        let claude_dir = temp.path().join("claude");
+        let project = temp.path().join("project");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("a.txt"), "v1").unwrap();
+
+        let manager = CheckpointManager::new(
+            "proj".to_string(),
+            "sid".to_string(),
+            project.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+        let session_file = manager.session_file();
+        assert_eq!(
+            session_file,
+            claude_dir.join("projects").join("proj").join("sid.jsonl")
+        );
+
+        // No transcript yet
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 0);
+
+        fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        fs::write(&session_file, format!("{}\n{}\n", USER_LINE, TOOL_LINE)).unwrap();
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 2);
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 0);
+        let first = manager.create_checkpoint(None, None).await.unwrap();
+
+        fs::write(
+            &session_file,
+            format!("{}\n{}\n{}\n", USER_LINE, TOOL_LINE, RESULT_LINE),
+        )
+        .unwrap();
+        fs::write(project.join("a.txt"), "v2").unwrap();
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 1);
+        manager.create_checkpoint(None, None).await.unwrap();
+
+        let restored = manager
+            .restore_checkpoint(&first.checkpoint.id)
+            .await
+            .unwrap();
+        let (_, _, messages) = manager
+            .storage
+            .load_checkpoint("proj", "sid", &restored.checkpoint.id)
+            .unwrap();
+        assert_eq!(messages, format!("{}\n{}", USER_LINE, TOOL_LINE));
+        assert_eq!(fs::read_to_string(project.join("a.txt")).unwrap(), "v1");
+
+        // The restored transcript matches what is tracked
+        fs::write(&session_file, &messages).unwrap();
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sync_session_messages_restarts_after_rewrite() {
+        let temp = TempDir::new().unwrap();
+        let claude_dir = temp.path().join("claude");
+        let project = temp.path().join("project");
+        fs::create_dir_all(&project).unwrap();
+
+        let manager =
+            CheckpointManager::new("proj".to_string(), "sid".to_string(), project, claude_dir)
+                .await
+                .unwrap();
+        let session_file = manager.session_file();
+        fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+
+        fs::write(&session_file, format!("{}\n{}\n", USER_LINE, TOOL_LINE)).unwrap();
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 2);
+
+        // Rewritten transcript that no longer starts with the tracked lines
+        fs::write(&session_file, format!("{}\n\n", RESULT_LINE)).unwrap();
+        assert_eq!(manager.sync_session_messages().await.unwrap(), 1);
+        assert_eq!(*manager.current_messages.read().await, vec![RESULT_LINE]);
+    }
+}
@@ -3,7 +3,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+pub mod archive;
+pub mod git_storage;
 pub mod manager;
+pub mod retention;
 pub mod state;
 pub mod storage;
 
@@ -27,6 +30,20 @@ pub struct Checkpoint {
     pub parent_checkpoint_id: Option<String>,
     /// Metadata about the checkpoint
     pub metadata: CheckpointMetadata,
+    /// What caused this checkpoint to be created
+    #[serde(default)]
+    pub trigger: CheckpointTrigger,
+}
+
+/// What caused a checkpoint to be created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointTrigger {
+    /// Created explicitly by the user
+    #[default]
+    Manual,
+    /// Created by the auto-checkpoint strategy
+    Auto,
 }
 
 /// Metadata associated with a checkpoint
@@ -93,6 +110,26 @@ pub struct SessionTimeline {
     pub checkpoint_strategy: CheckpointStrategy,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
+    /// Named branches, keyed by branch name
+    #[serde(default)]
+    pub branches: HashMap<String, TimelineBranch>,
+    /// Name of the branch the current checkpoint belongs to, if any
+    #[serde(default)]
+    pub current_branch: Option<String>,
+}
+
+/// A named line of work in the timeline tree, identified by its tip checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineBranch {
+    /// Branch name, unique within the timeline
+    pub name: String,
+    /// ID of the newest checkpoint on this branch
+    pub tip_checkpoint_id: String,
+    /// Timestamp when the branch was named
+    pub created_at: DateTime<Utc>,
+    /// Optional user-provided description
+    pub description: Option<String>,
 }
 
 /// Strategy for automatic checkpoint creation
@@ -157,6 +194,36 @@ pub struct CheckpointDiff {
     pub token_delta: i64,
 }
 
+/// Comparison between the tips of two named branches
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchComparison {
+    /// Branch used as the diff source
+    pub base_branch: String,
+    /// Branch used as the diff target
+    pub compare_branch: String,
+    /// Newest checkpoint shared by both branches
+    pub common_ancestor_id: Option<String>,
+    /// Checkpoints only reachable from the base branch
+    pub base_only_checkpoints: usize,
+    /// Checkpoints only reachable from the compare branch
+    pub compare_only_checkpoints: usize,
+    /// File and token differences between the two tips
+    pub diff: CheckpointDiff,
+}
+
+/// Result of pruning a branch from the timeline
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchPruneResult {
+    /// Name of the pruned branch
+    pub branch_name: String,
+    /// IDs of the checkpoints that were removed
+    pub removed_checkpoint_ids: Vec<String>,
+    /// Number of content pool files garbage collected
+    pub content_files_removed: usize,
+}
+
 /// Diff for a single file
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileDiff {
@@ -186,6 +253,24 @@ impl SessionTimeline {
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
             total_checkpoints: 0,
+            branches: HashMap::new(),
+            current_branch: None,
+        }
+    }
+
+    /// List every checkpoint in the tree, parents before children
+    pub fn list_checkpoints(&self) -> Vec<Checkpoint> {
+        let mut checkpoints = Vec::new();
+        if let Some(root) = &self.root_node {
+            Self::collect_checkpoints(root, &mut checkpoints);
+        }
+        checkpoints
+    }
+
+    fn collect_checkpoints(node: &TimelineNode, checkpoints: &mut Vec<Checkpoint>) {
+        checkpoints.push(node.checkpoint.clone());
+        for child in &node.children {
+            Self::collect_checkpoints(child, checkpoints);
         }
     }
 
@@ -209,6 +294,127 @@ impl SessionTimeline {
 
         None
     }
+
+    /// Get the checkpoints from the root down to the given checkpoint (inclusive)
+    pub fn path_to_checkpoint(&self, checkpoint_id: &str) -> Option<Vec<&Checkpoint>> {
+        let root = self.root_node.as_ref()?;
+        let mut path = Vec::new();
+        if Self::collect_path(root, checkpoint_id, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    fn collect_path<'a>(
+        node: &'a TimelineNode,
+        checkpoint_id: &str,
+        path: &mut Vec<&'a Checkpoint>,
+    ) -> bool {
+        path.push(&node.checkpoint);
+        if node.checkpoint.id == checkpoint_id {
+            return true;
+        }
+
+        for child in &node.children {
+            if Self::collect_path(child, checkpoint_id, path) {
+                return true;
+            }
+        }
+
+        path.pop();
+        false
+    }
+
+    /// Find the name of the branch whose tip is the given checkpoint
+    pub fn branch_at(&self, checkpoint_id: &str) -> Option<&str> {
+        self.branches
+            .values()
+            .find(|b| b.tip_checkpoint_id == checkpoint_id)
+            .map(|b| b.name.as_str())
+    }
+
+    /// Detach the subtree rooted at the given checkpoint and return the IDs it contained
+    pub fn remove_subtree(&mut self, checkpoint_id: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+
+        match self.root_node.take() {
+            Some(root) if root.checkpoint.id == checkpoint_id => {
+                Self::collect_ids(&root, &mut removed);
+            }
+            Some(mut root) => {
+                Self::remove_from_children(&mut root, checkpoint_id, &mut removed);
+                self.root_node = Some(root);
+            }
+            None => {}
+        }
+
+        self.total_checkpoints = self.total_checkpoints.saturating_sub(removed.len());
+        removed
+    }
+
+    fn remove_from_children(
+        node: &mut TimelineNode,
+        checkpoint_id: &str,
+        removed: &mut Vec<String>,
+    ) -> bool {
+        if let Some(pos) = node
+            .children
+            .iter()
+            .position(|c| c.checkpoint.id == checkpoint_id)
+        {
+            let child = node.children.remove(pos);
+            Self::collect_ids(&child, removed);
+            return true;
+        }
+
+        node.children
+            .iter_mut()
+            .any(|child| Self::remove_from_children(child, checkpoint_id, removed))
+    }
+
+    /// Remove a single checkpoint, attaching its children to its parent
+    ///
+    /// Returns the IDs of the re-parented children, or `None` if the checkpoint
+    /// was not found or is the root (which has no parent to attach to).
+    pub fn splice_out(&mut self, checkpoint_id: &str) -> Option<Vec<String>> {
+        let root = self.root_node.as_mut()?;
+        if root.checkpoint.id == checkpoint_id {
+            return None;
+        }
+
+        let reparented = Self::splice_from_children(root, checkpoint_id)?;
+        self.total_checkpoints = self.total_checkpoints.saturating_sub(1);
+        Some(reparented)
+    }
+
+    fn splice_from_children(node: &mut TimelineNode, checkpoint_id: &str) -> Option<Vec<String>> {
+        if let Some(pos) = node
+            .children
+            .iter()
+            .position(|c| c.checkpoint.id == checkpoint_id)
+        {
+            let removed = node.children.remove(pos);
+            let mut reparented = Vec::new();
+            for (offset, mut child) in removed.children.into_iter().enumerate() {
+                child.checkpoint.parent_checkpoint_id = Some(node.checkpoint.id.clone());
+                reparented.push(child.checkpoint.id.clone());
+                node.children.insert(pos + offset, child);
+            }
+            return Some(reparented);
+        }
+
+        node.children
+            .iter_mut()
+            .find_map(|child| Self::splice_from_children(child, checkpoint_id))
+    }
+
+    fn collect_ids(node: &TimelineNode, ids: &mut Vec<String>) {
+        ids.push(node.checkpoint.id.clone());
+        for child in &node.children {
+            Self::collect_ids(child, ids);
+        }
+    }
 }
 
 /// Checkpoint storage paths
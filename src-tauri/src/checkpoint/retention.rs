@@ -0,0 +1,623 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::{
+    storage::{CheckpointStorage, FileCheckpointStorage, StorageBackend, StorageSettings},
+    Checkpoint, CheckpointPaths, CheckpointTrigger, SessionTimeline,
+};
+
+/// Per-project rules for pruning old checkpoints
+///
+/// Limits are applied oldest-first and never remove protected checkpoints:
+/// the root, the current checkpoint, branch tips, and (when enabled) manual,
+/// labelled and every-Nth checkpoints. The byte quota is therefore best effort.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Maximum bytes on disk per session timeline
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+    /// Maximum age of a checkpoint in days
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// Maximum number of checkpoints per session
+    #[serde(default)]
+    pub max_checkpoints: Option<usize>,
+    /// Keep every Nth checkpoint (by creation order) regardless of the limits
+    #[serde(default)]
+    pub keep_every_nth: Option<usize>,
+    /// Never remove checkpoints the user created manually
+    #[serde(default = "default_true")]
+    pub keep_manual: bool,
+    /// Never remove checkpoints that have a description
+    #[serde(default = "default_true")]
+    pub keep_labelled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: None,
+            max_age_days: None,
+            max_checkpoints: None,
+            keep_every_nth: None,
+            keep_manual: true,
+            keep_labelled: true,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Whether the policy sets any limit at all
+    pub fn has_limits(&self) -> bool {
+        self.max_total_bytes.is_some()
+            || self.max_age_days.is_some()
+            || self.max_checkpoints.is_some()
+    }
+}
+
+/// Result of applying a retention policy to a session
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionResult {
+    /// IDs of the checkpoints that were removed
+    pub removed_checkpoint_ids: Vec<String>,
+    /// Number of content pool files garbage collected
+    pub content_files_removed: usize,
+    /// Bytes used by the session timeline before the policy ran
+    pub bytes_before: u64,
+    /// Bytes used by the session timeline after the policy ran
+    pub bytes_after: u64,
+}
+
+/// Disk usage of a single session timeline
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStorageUsage {
+    /// Session ID
+    pub session_id: String,
+    /// Number of checkpoints in the timeline
+    pub checkpoint_count: usize,
+    /// Bytes used by checkpoint metadata and compressed messages
+    pub checkpoint_bytes: u64,
+    /// Bytes used by the compressed content pool
+    pub content_pool_bytes: u64,
+    /// Number of unique blobs in the content pool
+    pub content_blob_count: usize,
+    /// Uncompressed size of all file snapshots referenced by checkpoints
+    pub referenced_bytes: u64,
+    /// Uncompressed size of the unique file contents actually stored
+    pub unique_bytes: u64,
+    /// `referenced_bytes / unique_bytes`; above 1.0 means deduplication saved space
+    pub dedup_ratio: f64,
+    /// Total bytes on disk for this session
+    pub total_bytes: u64,
+}
+
+/// Disk usage of all session timelines in a project
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStorageUsage {
+    /// Project ID
+    pub project_id: String,
+    /// Usage per session, largest first
+    pub sessions: Vec<SessionStorageUsage>,
+    /// Total bytes on disk across all sessions
+    pub total_bytes: u64,
+    /// Dedup ratio across all sessions
+    pub dedup_ratio: f64,
+}
+
+/// Path of the retention policy file for a project
+pub fn policy_file(claude_dir: &Path, project_id: &str) -> PathBuf {
+    timelines_dir(claude_dir, project_id).join("retention.json")
+}
+
+fn timelines_dir(claude_dir: &Path, project_id: &str) -> PathBuf {
+    claude_dir
+        .join("projects")
+        .join(project_id)
+        .join(".timelines")
+}
+
+/// Load the retention policy for a project, falling back to the default
+pub fn load_policy(claude_dir: &Path, project_id: &str) -> Result<RetentionPolicy> {
+    let path = policy_file(claude_dir, project_id);
+    if !path.exists() {
+        return Ok(RetentionPolicy::default());
+    }
+
+    let json = fs::read_to_string(&path).context("Failed to read retention policy")?;
+    serde_json::from_str(&json).context("Failed to parse retention policy")
+}
+
+/// Save the retention policy for a project
+pub fn save_policy(claude_dir: &Path, project_id: &str, policy: &RetentionPolicy) -> Result<()> {
+    if policy.keep_every_nth == Some(0) {
+        anyhow::bail!("keepEveryNth must be at least 1");
+    }
+
+    let backend = StorageSettings::load(claude_dir, project_id)?.backend;
+    ensure_quota_supported(backend, policy)?;
+
+    let path = policy_file(claude_dir, project_id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create timelines directory")?;
+    }
+    let json = serde_json::to_string_pretty(policy).context("Failed to serialize policy")?;
+    fs::write(&path, json).context("Failed to write retention policy")
+}
+
+/// Reject byte quotas for git-backed projects
+///
+/// Git checkpoints are objects in the project's repository, shared with its
+/// history and with other sessions, so they can neither be measured nor
+/// reclaimed per session.
+pub fn ensure_quota_supported(backend: StorageBackend, policy: &RetentionPolicy) -> Result<()> {
+    if backend == StorageBackend::Git && policy.max_total_bytes.is_some() {
+        anyhow::bail!(
+            "Byte quotas are not supported with git checkpoint storage; limit checkpoints by age or count instead"
+        );
+    }
+    Ok(())
+}
+
+/// Load the project's policy and apply it to a session if it sets any limits
+pub fn enforce_project_policy(
+    storage: &dyn CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+) -> Result<RetentionResult> {
+    let policy = load_policy(storage.claude_dir(), project_id)?;
+    if !policy.has_limits() {
+        return Ok(RetentionResult::default());
+    }
+
+    apply_policy(storage, project_id, session_id, &policy)
+}
+
+/// Apply a retention policy to a session timeline
+pub fn apply_policy(
+    storage: &dyn CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+    policy: &RetentionPolicy,
+) -> Result<RetentionResult> {
+    ensure_quota_supported(storage.backend(), policy)?;
+
+    let paths = CheckpointPaths::new(storage.claude_dir(), project_id, session_id);
+    let mut timeline = storage.load_timeline(&paths.timeline_file)?;
+
+    let bytes_before = dir_size(&session_dir(&paths));
+
+    let mut checkpoints = timeline.list_checkpoints();
+    checkpoints.sort_by_key(|c| c.timestamp);
+
+    let protected = protected_checkpoints(&timeline, &checkpoints, policy);
+    let mut candidates: Vec<&Checkpoint> = checkpoints
+        .iter()
+        .filter(|c| !protected.contains(&c.id))
+        .collect();
+
+    let mut removed = Vec::new();
+
+    // Age limit
+    if let Some(days) = policy.max_age_days {
+        let cutoff = Utc::now() - Duration::days(days as i64);
+        let (expired, kept): (Vec<&Checkpoint>, Vec<&Checkpoint>) =
+            candidates.into_iter().partition(|c| c.timestamp < cutoff);
+        removed.extend(expired.into_iter().map(|c| c.id.clone()));
+        candidates = kept;
+    }
+
+    // Count limit
+    if let Some(max) = policy.max_checkpoints {
+        let excess = (checkpoints.len() - removed.len()).saturating_sub(max);
+        let take = excess.min(candidates.len());
+        removed.extend(candidates.drain(..take).map(|c| c.id.clone()));
+    }
+
+    for checkpoint_id in &removed {
+        remove_checkpoint(
+            storage,
+            project_id,
+            session_id,
+            &mut timeline,
+            checkpoint_id,
+        )?;
+    }
+    storage.save_timeline(&paths.timeline_file, &timeline)?;
+
+    let mut content_files_removed = if removed.is_empty() {
+        0
+    } else {
+        storage.garbage_collect_content(project_id, session_id)?
+    };
+
+    // Byte quota: drop the oldest remaining candidates until the session fits
+    if let Some(max_bytes) = policy.max_total_bytes {
+        let mut candidates = candidates.into_iter();
+        while dir_size(&session_dir(&paths)) > max_bytes {
+            let Some(checkpoint) = candidates.next() else {
+                log::warn!(
+                    "Session {} exceeds its {} byte quota but only protected checkpoints remain",
+                    session_id,
+                    max_bytes
+                );
+                break;
+            };
+
+            remove_checkpoint(
+                storage,
+                project_id,
+                session_id,
+                &mut timeline,
+                &checkpoint.id,
+            )?;
+            storage.save_timeline(&paths.timeline_file, &timeline)?;
+            content_files_removed += storage.garbage_collect_content(project_id, session_id)?;
+            removed.push(checkpoint.id.clone());
+        }
+    }
+
+    if !removed.is_empty() {
+        log::info!(
+            "Retention policy removed {} checkpoints from session {}",
+            removed.len(),
+            session_id
+        );
+    }
+
+    Ok(RetentionResult {
+        removed_checkpoint_ids: removed,
+        content_files_removed,
+        bytes_before,
+        bytes_after: dir_size(&session_dir(&paths)),
+    })
+}
+
+/// Report disk usage for every session timeline in a project
+pub fn project_storage_usage(claude_dir: &Path, project_id: &str) -> Result<ProjectStorageUsage> {
+    let mut sessions = Vec::new();
+
+    let dir = timelines_dir(claude_dir, project_id);
+    if dir.exists() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let session_id = entry.file_name().to_string_lossy().to_string();
+            let paths = CheckpointPaths::new(&claude_dir.to_path_buf(), project_id, &session_id);
+            if paths.timeline_file.exists() {
+                sessions.push(session_storage_usage(&paths, &session_id)?);
+            }
+        }
+    }
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.total_bytes));
+
+    let total_bytes = sessions.iter().map(|s| s.total_bytes).sum();
+    let referenced: u64 = sessions.iter().map(|s| s.referenced_bytes).sum();
+    let unique: u64 = sessions.iter().map(|s| s.unique_bytes).sum();
+
+    Ok(ProjectStorageUsage {
+        project_id: project_id.to_string(),
+        sessions,
+        total_bytes,
+        dedup_ratio: ratio(referenced, unique),
+    })
+}
+
+/// Compute disk usage and dedup statistics for one session timeline
+fn session_storage_usage(paths: &CheckpointPaths, session_id: &str) -> Result<SessionStorageUsage> {
+    let storage = FileCheckpointStorage::new(PathBuf::new());
+    let timeline = storage.load_timeline(&paths.timeline_file)?;
+    let checkpoints = timeline.list_checkpoints();
+
+    // Sum the logical size of every reference and the size of each distinct blob
+    let mut referenced_bytes = 0u64;
+    let mut unique_sizes: HashMap<String, u64> = HashMap::new();
+    let refs_dir = paths.files_dir.join("refs");
+    if refs_dir.exists() {
+        for entry in WalkDir::new(&refs_dir).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Ok(ref_json) = fs::read_to_string(path) else {
+                continue;
+            };
+            let Ok(ref_metadata) = serde_json::from_str::<serde_json::Value>(&ref_json) else {
+                continue;
+            };
+            let size = ref_metadata["size"].as_u64().unwrap_or(0);
+            referenced_bytes += size;
+            if let Some(hash) = ref_metadata["hash"].as_str() {
+                unique_sizes.insert(hash.to_string(), size);
+            }
+        }
+    }
+    let unique_bytes: u64 = unique_sizes.values().sum();
+
+    let content_pool_dir = paths.files_dir.join("content_pool");
+    let content_blob_count = if content_pool_dir.exists() {
+        fs::read_dir(&content_pool_dir)?.count()
+    } else {
+        0
+    };
+
+    Ok(SessionStorageUsage {
+        session_id: session_id.to_string(),
+        checkpoint_count: checkpoints.len(),
+        checkpoint_bytes: dir_size(&paths.checkpoints_dir),
+        content_pool_bytes: dir_size(&content_pool_dir),
+        content_blob_count,
+        referenced_bytes,
+        unique_bytes,
+        dedup_ratio: ratio(referenced_bytes, unique_bytes),
+        total_bytes: dir_size(&session_dir(paths)),
+    })
+}
+
+/// Work out which checkpoints a policy must never remove
+fn protected_checkpoints(
+    timeline: &SessionTimeline,
+    checkpoints: &[Checkpoint],
+    policy: &RetentionPolicy,
+) -> HashSet<String> {
+    let mut protected = HashSet::new();
+
+    if let Some(root) = &timeline.root_node {
+        protected.insert(root.checkpoint.id.clone());
+    }
+    if let Some(current) = &timeline.current_checkpoint_id {
+        protected.insert(current.clone());
+    }
+    protected.extend(
+        timeline
+            .branches
+            .values()
+            .map(|b| b.tip_checkpoint_id.clone()),
+    );
+
+    for (index, checkpoint) in checkpoints.iter().enumerate() {
+        let is_manual = checkpoint.trigger == CheckpointTrigger::Manual;
+        let is_labelled = checkpoint
+            .description
+            .as_deref()
+            .is_some_and(|d| !d.trim().is_empty());
+        let is_nth = policy
+            .keep_every_nth
+            .is_some_and(|n| n > 0 && index % n == 0);
+
+        if (policy.keep_manual && is_manual) || (policy.keep_labelled && is_labelled) || is_nth {
+            protected.insert(checkpoint.id.clone());
+        }
+    }
+
+    protected
+}
+
+/// Remove one checkpoint from disk and splice it out of the timeline tree
+fn remove_checkpoint(
+    storage: &dyn CheckpointStorage,
+    project_id: &str,
+    session_id: &str,
+    timeline: &mut SessionTimeline,
+    checkpoint_id: &str,
+) -> Result<()> {
+    let Some(reparented) = timeline.splice_out(checkpoint_id) else {
+        return Ok(());
+    };
+    storage.remove_checkpoint(project_id, session_id, checkpoint_id)?;
+
+    let paths = CheckpointPaths::new(storage.claude_dir(), project_id, session_id);
+
+    // Keep the children's on-disk metadata in line with the tree
+    for child_id in reparented {
+        let Some(node) = timeline.find_checkpoint(&child_id) else {
+            continue;
+        };
+        let metadata_json = serde_json::to_string_pretty(&node.checkpoint)
+            .context("Failed to serialize checkpoint metadata")?;
+        fs::write(paths.checkpoint_metadata_file(&child_id), metadata_json)
+            .context("Failed to write checkpoint metadata")?;
+    }
+
+    Ok(())
+}
+
+fn session_dir(paths: &CheckpointPaths) -> PathBuf {
+    paths
+        .timeline_file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn ratio(referenced: u64, unique: u64) -> f64 {
+    if unique == 0 {
+        1.0
+    } else {
+        referenced as f64 / unique as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{storage::calculate_file_hash, CheckpointMetadata, FileSnapshot};
+    use tempfile::TempDir;
+
+    fn save(storage: &dyn CheckpointStorage, id: &str, parent: Option<&str>, age_days: i64) {
+        save_with_trigger(storage, id, parent, age_days, CheckpointTrigger::Auto);
+    }
+
+    fn save_with_trigger(
+        storage: &dyn CheckpointStorage,
+        id: &str,
+        parent: Option<&str>,
+        age_days: i64,
+        trigger: CheckpointTrigger,
+    ) {
+        let content = format!("content of {}", id);
+        let checkpoint = Checkpoint {
+            id: id.to_string(),
+            session_id: "session".to_string(),
+            project_id: "project".to_string(),
+            message_index: 0,
+            timestamp: Utc::now() - Duration::days(age_days),
+            description: None,
+            parent_checkpoint_id: parent.map(String::from),
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "test".to_string(),
+                user_prompt: String::new(),
+                file_changes: 1,
+                snapshot_size: 0,
+            },
+            trigger,
+        };
+        let snapshot = FileSnapshot {
+            checkpoint_id: id.to_string(),
+            file_path: PathBuf::from("file.txt"),
+            hash: calculate_file_hash(&content),
+            size: content.len() as u64,
+            content,
+            is_deleted: false,
+            permissions: None,
+        };
+        storage
+            .save_checkpoint("project", "session", &checkpoint, vec![snapshot], "")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_age_and_count_limits_keep_tree_connected() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(temp_dir.path().to_path_buf());
+        storage.init_storage("project", "session").unwrap();
+
+        // root(a) -> b -> c -> d -> e, oldest first
+        save(&storage, "a", None, 10);
+        save(&storage, "b", Some("a"), 9);
+        save(&storage, "c", Some("b"), 8);
+        save(&storage, "d", Some("c"), 1);
+        save(&storage, "e", Some("d"), 0);
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(5),
+            max_checkpoints: Some(2),
+            ..Default::default()
+        };
+        let result = apply_policy(&storage, "project", "session", &policy).unwrap();
+
+        // b and c are too old; root and the current tip (e) are protected,
+        // so d goes to satisfy the count limit
+        assert_eq!(result.removed_checkpoint_ids, vec!["b", "c", "d"]);
+        assert_eq!(result.content_files_removed, 3);
+
+        let paths = CheckpointPaths::new(storage.claude_dir(), "project", "session");
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.total_checkpoints, 2);
+        let e = timeline.find_checkpoint("e").unwrap();
+        assert_eq!(e.checkpoint.parent_checkpoint_id.as_deref(), Some("a"));
+
+        let (e_on_disk, _, _) = storage.load_checkpoint("project", "session", "e").unwrap();
+        assert_eq!(e_on_disk.parent_checkpoint_id.as_deref(), Some("a"));
+
+        let usage = project_storage_usage(temp_dir.path(), "project").unwrap();
+        assert_eq!(usage.sessions.len(), 1);
+        assert_eq!(usage.sessions[0].checkpoint_count, 2);
+        assert_eq!(usage.sessions[0].content_blob_count, 2);
+    }
+
+    #[test]
+    fn test_default_policy_keeps_manual_and_prunes_auto_checkpoints() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(temp_dir.path().to_path_buf());
+        storage.init_storage("project", "session").unwrap();
+
+        for (id, parent, trigger) in [
+            ("a", None, CheckpointTrigger::Manual),
+            ("b", Some("a"), CheckpointTrigger::Auto),
+            ("c", Some("b"), CheckpointTrigger::Manual),
+            ("d", Some("c"), CheckpointTrigger::Auto),
+        ] {
+            save_with_trigger(&storage, id, parent, 0, trigger);
+        }
+
+        let policy = RetentionPolicy {
+            max_checkpoints: Some(2),
+            ..Default::default()
+        };
+        assert!(policy.keep_manual);
+        let result = apply_policy(&storage, "project", "session", &policy).unwrap();
+        assert_eq!(result.removed_checkpoint_ids, vec!["b"]);
+
+        let prune_manual = RetentionPolicy {
+            max_checkpoints: Some(1),
+            keep_manual: false,
+            ..Default::default()
+        };
+        let result = apply_policy(&storage, "project", "session", &prune_manual).unwrap();
+        assert_eq!(result.removed_checkpoint_ids, vec!["c"]);
+
+        // Older stored policies without the field get the same default
+        let stored: RetentionPolicy = serde_json::from_str(r#"{"maxCheckpoints": 2}"#).unwrap();
+        assert!(stored.keep_manual);
+        assert!(stored.keep_labelled);
+    }
+
+    #[test]
+    fn test_git_projects_reject_byte_quotas() {
+        let temp_dir = TempDir::new().unwrap();
+        let settings = StorageSettings {
+            backend: StorageBackend::Git,
+            repo_path: Some(temp_dir.path().join("repo")),
+        };
+        settings.save(temp_dir.path(), "project").unwrap();
+
+        let quota = RetentionPolicy {
+            max_total_bytes: Some(1024),
+            ..Default::default()
+        };
+        let err = save_policy(temp_dir.path(), "project", &quota).unwrap_err();
+        assert!(err.to_string().contains("git checkpoint storage"));
+        assert!(!policy_file(temp_dir.path(), "project").exists());
+
+        let count = RetentionPolicy {
+            max_checkpoints: Some(10),
+            ..Default::default()
+        };
+        save_policy(temp_dir.path(), "project", &count).unwrap();
+        assert_eq!(
+            load_policy(temp_dir.path(), "project")
+                .unwrap()
+                .max_checkpoints,
+            Some(10)
+        );
+
+        assert!(ensure_quota_supported(StorageBackend::Files, &quota).is_ok());
+    }
+}
@@ -98,6 +98,16 @@ impl CheckpointState {
         managers.remove(session_id)
     }
 
+    /// Removes every CheckpointManager that belongs to a project
+    ///
+    /// Used when a project's storage backend changes so managers reopen it
+    pub async fn remove_project_managers(&self, project_id: &str) -> usize {
+        let mut managers = self.managers.write().await;
+        let before = managers.len();
+        managers.retain(|_, manager| manager.project_id() != project_id);
+        before - managers.len()
+    }
+
     /// Clears all managers
     ///
     /// This is useful for cleanup during application shutdown
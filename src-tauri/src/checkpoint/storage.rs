@@ -1,32 +1,145 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use uuid::Uuid;
 use zstd::stream::{decode_all, encode_all};
 
 use super::{
-    Checkpoint, CheckpointPaths, CheckpointResult, FileSnapshot, SessionTimeline, TimelineNode,
+    git_storage::GitCheckpointStorage, BranchPruneResult, Checkpoint, CheckpointDiff,
+    CheckpointPaths, CheckpointResult, FileDiff, FileSnapshot, SessionTimeline, TimelineBranch,
+    TimelineNode,
 };
 
-/// Manages checkpoint storage operations
-pub struct CheckpointStorage {
-    pub claude_dir: PathBuf,
-    compression_level: i32,
+/// Name of the branch created for the first checkpoint of a timeline
+pub const DEFAULT_BRANCH_NAME: &str = "main";
+
+/// Default zstd compression level for stored messages and content
+pub(super) const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Where a project keeps the file contents of its checkpoints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// Compressed content pool next to the timeline
+    #[default]
+    Files,
+    /// Commits on hidden refs in the project's own git repository
+    Git,
 }
 
-impl CheckpointStorage {
-    /// Create a new checkpoint storage instance
-    pub fn new(claude_dir: PathBuf) -> Self {
-        Self {
-            claude_dir,
-            compression_level: 3, // Default zstd compression level
+/// Per-project checkpoint storage settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageSettings {
+    /// Selected storage backend
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// Repository that receives checkpoint commits when using the git backend
+    #[serde(default)]
+    pub repo_path: Option<PathBuf>,
+}
+
+impl StorageSettings {
+    fn settings_file(claude_dir: &Path, project_id: &str) -> PathBuf {
+        claude_dir
+            .join("projects")
+            .join(project_id)
+            .join(".timelines")
+            .join("storage.json")
+    }
+
+    /// Load the storage settings for a project, falling back to the default
+    pub fn load(claude_dir: &Path, project_id: &str) -> Result<Self> {
+        let path = Self::settings_file(claude_dir, project_id);
+        if !path.exists() {
+            return Ok(Self::default());
         }
+
+        let json = fs::read_to_string(&path).context("Failed to read storage settings")?;
+        serde_json::from_str(&json).context("Failed to parse storage settings")
     }
 
+    /// Save the storage settings for a project
+    pub fn save(&self, claude_dir: &Path, project_id: &str) -> Result<()> {
+        let path = Self::settings_file(claude_dir, project_id);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create timelines directory")?;
+        }
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize settings")?;
+        fs::write(&path, json).context("Failed to write storage settings")
+    }
+}
+
+/// Open the storage backend configured for a project
+pub fn open_storage(claude_dir: &Path, project_id: &str) -> Result<Arc<dyn CheckpointStorage>> {
+    let settings = StorageSettings::load(claude_dir, project_id)?;
+
+    match settings.backend {
+        StorageBackend::Files => Ok(Arc::new(FileCheckpointStorage::new(
+            claude_dir.to_path_buf(),
+        ))),
+        StorageBackend::Git => {
+            let repo_path = settings
+                .repo_path
+                .ok_or_else(|| anyhow::anyhow!("Git checkpoint storage has no repository"))?;
+            Ok(Arc::new(GitCheckpointStorage::open(
+                claude_dir.to_path_buf(),
+                &repo_path,
+            )?))
+        }
+    }
+}
+
+/// Storage backend for checkpoints
+///
+/// Timelines and checkpoint metadata always live under `.timelines`; backends
+/// only differ in where messages and file snapshots are kept. Timeline
+/// bookkeeping, diffs and pruning are provided on top of the backend operations.
+pub trait CheckpointStorage: Send + Sync {
+    /// Claude directory the timelines live under
+    fn claude_dir(&self) -> &PathBuf;
+
+    /// Which backend this is
+    fn backend(&self) -> StorageBackend;
+
+    /// Save a checkpoint and add it to the timeline
+    fn save_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint: &Checkpoint,
+        file_snapshots: Vec<FileSnapshot>,
+        messages: &str, // JSONL content up to checkpoint
+    ) -> Result<CheckpointResult>;
+
+    /// Load a checkpoint with its file snapshots and messages
+    fn load_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<(Checkpoint, Vec<FileSnapshot>, String)>;
+
+    /// Remove a checkpoint and its associated files
+    ///
+    /// Shared content is left in place; use `garbage_collect_content` for that.
+    fn remove_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<()>;
+
+    /// Garbage collect content no checkpoint references any more
+    fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize>;
+
     /// Initialize checkpoint storage for a session
-    pub fn init_storage(&self, project_id: &str, session_id: &str) -> Result<()> {
-        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+    fn init_storage(&self, project_id: &str, session_id: &str) -> Result<()> {
+        let paths = CheckpointPaths::new(self.claude_dir(), project_id, session_id);
 
         // Create directory structure
         fs::create_dir_all(&paths.checkpoints_dir)
@@ -42,59 +155,421 @@ impl CheckpointStorage {
         Ok(())
     }
 
-    /// Save a checkpoint to disk
-    pub fn save_checkpoint(
+    /// Save timeline to disk
+    fn save_timeline(&self, timeline_path: &Path, timeline: &SessionTimeline) -> Result<()> {
+        let timeline_json =
+            serde_json::to_string_pretty(timeline).context("Failed to serialize timeline")?;
+        fs::write(timeline_path, timeline_json).context("Failed to write timeline")?;
+        Ok(())
+    }
+
+    /// Load timeline from disk
+    fn load_timeline(&self, timeline_path: &Path) -> Result<SessionTimeline> {
+        let timeline_json = fs::read_to_string(timeline_path).context("Failed to read timeline")?;
+        let timeline: SessionTimeline =
+            serde_json::from_str(&timeline_json).context("Failed to parse timeline")?;
+        Ok(timeline)
+    }
+
+    /// Find the Claude projects that hold a timeline for the given session
+    fn find_session_projects(&self, session_id: &str) -> Result<Vec<String>> {
+        let projects_dir = self.claude_dir().join("projects");
+        if !projects_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut project_ids = Vec::new();
+        for entry in fs::read_dir(&projects_dir).context("Failed to read projects directory")? {
+            let entry = entry?;
+            let project_id = entry.file_name().to_string_lossy().to_string();
+            let paths = CheckpointPaths::new(self.claude_dir(), &project_id, session_id);
+            if paths.timeline_file.exists() {
+                project_ids.push(project_id);
+            }
+        }
+
+        project_ids.sort();
+        Ok(project_ids)
+    }
+
+    /// Compute the file and token differences between two checkpoints
+    fn diff_checkpoints(
         &self,
         project_id: &str,
         session_id: &str,
-        checkpoint: &Checkpoint,
-        file_snapshots: Vec<FileSnapshot>,
-        messages: &str, // JSONL content up to checkpoint
-    ) -> Result<CheckpointResult> {
-        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
-        let checkpoint_dir = paths.checkpoint_dir(&checkpoint.id);
+        from_checkpoint_id: &str,
+        to_checkpoint_id: &str,
+    ) -> Result<CheckpointDiff> {
+        let (from_checkpoint, from_files, _) = self
+            .load_checkpoint(project_id, session_id, from_checkpoint_id)
+            .context("Failed to load source checkpoint")?;
+        let (to_checkpoint, to_files, _) = self
+            .load_checkpoint(project_id, session_id, to_checkpoint_id)
+            .context("Failed to load target checkpoint")?;
+
+        // Build file maps
+        let from_map: HashMap<&PathBuf, &FileSnapshot> =
+            from_files.iter().map(|f| (&f.file_path, f)).collect();
+        let to_map: HashMap<&PathBuf, &FileSnapshot> =
+            to_files.iter().map(|f| (&f.file_path, f)).collect();
+
+        // Calculate differences
+        let mut modified_files = Vec::new();
+        let mut added_files = Vec::new();
+        let mut deleted_files = Vec::new();
+
+        // Check for modified and deleted files
+        for (path, from_file) in &from_map {
+            if let Some(to_file) = to_map.get(path) {
+                if from_file.hash != to_file.hash {
+                    // File was modified
+                    let additions = to_file.content.lines().count();
+                    let deletions = from_file.content.lines().count();
+
+                    modified_files.push(FileDiff {
+                        path: (*path).clone(),
+                        additions,
+                        deletions,
+                        diff_content: None, // TODO: Generate actual diff
+                    });
+                }
+            } else {
+                // File was deleted
+                deleted_files.push((*path).clone());
+            }
+        }
 
-        // Create checkpoint directory
-        fs::create_dir_all(&checkpoint_dir).context("Failed to create checkpoint directory")?;
+        // Check for added files
+        for path in to_map.keys() {
+            if !from_map.contains_key(path) {
+                added_files.push((*path).clone());
+            }
+        }
 
-        // Save checkpoint metadata
-        let metadata_path = paths.checkpoint_metadata_file(&checkpoint.id);
-        let metadata_json = serde_json::to_string_pretty(checkpoint)
-            .context("Failed to serialize checkpoint metadata")?;
-        fs::write(&metadata_path, metadata_json).context("Failed to write checkpoint metadata")?;
+        // Calculate token delta
+        let token_delta = (to_checkpoint.metadata.total_tokens as i64)
+            - (from_checkpoint.metadata.total_tokens as i64);
+
+        Ok(CheckpointDiff {
+            from_checkpoint_id: from_checkpoint_id.to_string(),
+            to_checkpoint_id: to_checkpoint_id.to_string(),
+            modified_files,
+            added_files,
+            deleted_files,
+            token_delta,
+        })
+    }
 
-        // Save messages (compressed)
-        let messages_path = paths.checkpoint_messages_file(&checkpoint.id);
-        let compressed_messages = encode_all(messages.as_bytes(), self.compression_level)
-            .context("Failed to compress messages")?;
-        fs::write(&messages_path, compressed_messages)
-            .context("Failed to write compressed messages")?;
+    /// Remove a named branch and every checkpoint that only it references
+    ///
+    /// Checkpoints shared with other branches or with the current checkpoint
+    /// are kept; everything from the fork point down is removed, followed by
+    /// garbage collection of the content pool.
+    fn prune_branch(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        branch_name: &str,
+    ) -> Result<BranchPruneResult> {
+        let paths = CheckpointPaths::new(self.claude_dir(), project_id, session_id);
+        let mut timeline = self.load_timeline(&paths.timeline_file)?;
 
-        // Save file snapshots
-        let mut warnings = Vec::new();
-        let mut files_processed = 0;
+        if timeline.current_branch.as_deref() == Some(branch_name) {
+            anyhow::bail!("Cannot prune the current branch: {}", branch_name);
+        }
 
-        for snapshot in &file_snapshots {
-            match self.save_file_snapshot(&paths, snapshot) {
-                Ok(_) => files_processed += 1,
-                Err(e) => warnings.push(format!(
-                    "Failed to save {}: {}",
-                    snapshot.file_path.display(),
-                    e
-                )),
+        let branch = timeline
+            .branches
+            .get(branch_name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", branch_name))?;
+
+        let branch_path: Vec<String> = timeline
+            .path_to_checkpoint(&branch.tip_checkpoint_id)
+            .ok_or_else(|| anyhow::anyhow!("Branch tip not found: {}", branch.tip_checkpoint_id))?
+            .into_iter()
+            .map(|c| c.id.clone())
+            .collect();
+
+        // Everything on the way to another branch tip or the current checkpoint must survive
+        let mut protected = HashSet::new();
+        let other_tips = timeline
+            .branches
+            .values()
+            .filter(|b| b.name != branch_name)
+            .map(|b| b.tip_checkpoint_id.clone())
+            .chain(timeline.current_checkpoint_id.clone());
+        for tip in other_tips {
+            if let Some(path) = timeline.path_to_checkpoint(&tip) {
+                protected.extend(path.into_iter().map(|c| c.id.clone()));
             }
         }
 
-        // Update timeline
-        self.update_timeline_with_checkpoint(&paths.timeline_file, checkpoint, &file_snapshots)?;
+        // Walk back from the tip removing checkpoints only this branch needs,
+        // stopping at anything protected or with other children (unnamed forks)
+        let mut removed_checkpoint_ids = Vec::new();
+        for id in branch_path.iter().rev() {
+            let is_leaf = timeline
+                .find_checkpoint(id)
+                .is_some_and(|node| node.children.is_empty());
+            if protected.contains(id) || !is_leaf {
+                break;
+            }
+            removed_checkpoint_ids.extend(timeline.remove_subtree(id));
+        }
 
-        Ok(CheckpointResult {
-            checkpoint: checkpoint.clone(),
-            files_processed,
-            warnings,
+        for checkpoint_id in &removed_checkpoint_ids {
+            if let Err(e) = self.remove_checkpoint(project_id, session_id, checkpoint_id) {
+                log::warn!("Failed to remove checkpoint {}: {}", checkpoint_id, e);
+            }
+        }
+
+        timeline.branches.remove(branch_name);
+        self.save_timeline(&paths.timeline_file, &timeline)?;
+
+        let content_files_removed = if removed_checkpoint_ids.is_empty() {
+            0
+        } else {
+            self.garbage_collect_content(project_id, session_id)?
+        };
+
+        Ok(BranchPruneResult {
+            branch_name: branch_name.to_string(),
+            removed_checkpoint_ids,
+            content_files_removed,
         })
     }
 
+    /// Clean up old checkpoints based on retention policy
+    fn cleanup_old_checkpoints(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        keep_count: usize,
+    ) -> Result<usize> {
+        let paths = CheckpointPaths::new(self.claude_dir(), project_id, session_id);
+        let timeline = self.load_timeline(&paths.timeline_file)?;
+
+        // Collect all checkpoint IDs in chronological order
+        let mut all_checkpoints = timeline.list_checkpoints();
+
+        // Sort by timestamp (oldest first)
+        all_checkpoints.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+        // Keep only the most recent checkpoints
+        let to_remove = all_checkpoints.len().saturating_sub(keep_count);
+        let mut removed_count = 0;
+
+        for checkpoint in all_checkpoints.into_iter().take(to_remove) {
+            if self
+                .remove_checkpoint(project_id, session_id, &checkpoint.id)
+                .is_ok()
+            {
+                removed_count += 1;
+            }
+        }
+
+        // Run garbage collection to clean up orphaned content
+        if removed_count > 0 {
+            match self.garbage_collect_content(project_id, session_id) {
+                Ok(gc_count) => {
+                    log::info!("Garbage collected {} orphaned content files", gc_count);
+                }
+                Err(e) => {
+                    log::warn!("Failed to garbage collect content: {}", e);
+                }
+            }
+        }
+
+        Ok(removed_count)
+    }
+}
+
+/// Update timeline with a new checkpoint
+pub(super) fn update_timeline_with_checkpoint(
+    storage: &dyn CheckpointStorage,
+    timeline_path: &Path,
+    checkpoint: &Checkpoint,
+    file_snapshots: &[FileSnapshot],
+) -> Result<()> {
+    let mut timeline = storage.load_timeline(timeline_path)?;
+
+    let new_node = TimelineNode {
+        checkpoint: checkpoint.clone(),
+        children: Vec::new(),
+        file_snapshot_ids: file_snapshots.iter().map(|s| s.hash.clone()).collect(),
+    };
+
+    // If this is the first checkpoint
+    if timeline.root_node.is_none() {
+        timeline.root_node = Some(new_node);
+        timeline.current_checkpoint_id = Some(checkpoint.id.clone());
+
+        // The first checkpoint starts the default branch
+        if timeline.branches.is_empty() {
+            timeline.branches.insert(
+                DEFAULT_BRANCH_NAME.to_string(),
+                TimelineBranch {
+                    name: DEFAULT_BRANCH_NAME.to_string(),
+                    tip_checkpoint_id: checkpoint.id.clone(),
+                    created_at: checkpoint.timestamp,
+                    description: None,
+                },
+            );
+            timeline.current_branch = Some(DEFAULT_BRANCH_NAME.to_string());
+        }
+    } else if let Some(parent_id) = &checkpoint.parent_checkpoint_id {
+        // Check if parent exists before modifying
+        let parent_exists = timeline.find_checkpoint(parent_id).is_some();
+
+        if parent_exists {
+            if let Some(root) = &mut timeline.root_node {
+                add_child_to_node(root, parent_id, new_node)?;
+                timeline.current_checkpoint_id = Some(checkpoint.id.clone());
+            }
+            advance_current_branch(&mut timeline, parent_id, &checkpoint.id);
+        } else {
+            anyhow::bail!("Parent checkpoint not found: {}", parent_id);
+        }
+    }
+
+    timeline.total_checkpoints += 1;
+    storage.save_timeline(timeline_path, &timeline)?;
+
+    Ok(())
+}
+
+/// Move the current branch tip onto a new child checkpoint
+///
+/// If the parent is not the tip of the current branch, the new checkpoint
+/// starts an anonymous fork and the timeline is detached from any branch.
+fn advance_current_branch(timeline: &mut SessionTimeline, parent_id: &str, child_id: &str) {
+    let branch = timeline
+        .current_branch
+        .as_ref()
+        .and_then(|name| timeline.branches.get_mut(name));
+
+    match branch {
+        Some(branch) if branch.tip_checkpoint_id == parent_id => {
+            branch.tip_checkpoint_id = child_id.to_string();
+        }
+        _ => timeline.current_branch = None,
+    }
+}
+
+/// Recursively add a child node to the timeline tree
+fn add_child_to_node(node: &mut TimelineNode, parent_id: &str, child: TimelineNode) -> Result<()> {
+    if node.checkpoint.id == parent_id {
+        node.children.push(child);
+        return Ok(());
+    }
+
+    for child_node in &mut node.children {
+        if add_child_to_node(child_node, parent_id, child.clone()).is_ok() {
+            return Ok(());
+        }
+    }
+
+    anyhow::bail!("Parent checkpoint not found: {}", parent_id)
+}
+
+/// Write checkpoint metadata and compressed messages to the checkpoint directory
+pub(super) fn write_checkpoint_record(
+    paths: &CheckpointPaths,
+    checkpoint: &Checkpoint,
+    messages: &str,
+    compression_level: i32,
+) -> Result<()> {
+    let checkpoint_dir = paths.checkpoint_dir(&checkpoint.id);
+
+    // Create checkpoint directory
+    fs::create_dir_all(&checkpoint_dir).context("Failed to create checkpoint directory")?;
+
+    // Save checkpoint metadata
+    let metadata_path = paths.checkpoint_metadata_file(&checkpoint.id);
+    let metadata_json = serde_json::to_string_pretty(checkpoint)
+        .context("Failed to serialize checkpoint metadata")?;
+    fs::write(&metadata_path, metadata_json).context("Failed to write checkpoint metadata")?;
+
+    // Save messages (compressed)
+    let messages_path = paths.checkpoint_messages_file(&checkpoint.id);
+    let compressed_messages = encode_all(messages.as_bytes(), compression_level)
+        .context("Failed to compress messages")?;
+    fs::write(&messages_path, compressed_messages)
+        .context("Failed to write compressed messages")?;
+
+    Ok(())
+}
+
+/// Read checkpoint metadata and messages from the checkpoint directory
+pub(super) fn read_checkpoint_record(
+    paths: &CheckpointPaths,
+    checkpoint_id: &str,
+) -> Result<(Checkpoint, String)> {
+    // Load checkpoint metadata
+    let metadata_path = paths.checkpoint_metadata_file(checkpoint_id);
+    let metadata_json =
+        fs::read_to_string(&metadata_path).context("Failed to read checkpoint metadata")?;
+    let checkpoint: Checkpoint =
+        serde_json::from_str(&metadata_json).context("Failed to parse checkpoint metadata")?;
+
+    // Load messages
+    let messages_path = paths.checkpoint_messages_file(checkpoint_id);
+    let compressed_messages =
+        fs::read(&messages_path).context("Failed to read compressed messages")?;
+    let messages = String::from_utf8(
+        decode_all(&compressed_messages[..]).context("Failed to decompress messages")?,
+    )
+    .context("Invalid UTF-8 in messages")?;
+
+    Ok((checkpoint, messages))
+}
+
+/// Remove the checkpoint directory holding metadata and messages
+pub(super) fn remove_checkpoint_record(paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
+    let checkpoint_dir = paths.checkpoint_dir(checkpoint_id);
+    if checkpoint_dir.exists() {
+        fs::remove_dir_all(&checkpoint_dir).context("Failed to remove checkpoint directory")?;
+    }
+    Ok(())
+}
+
+/// Calculate hash of file content
+pub fn calculate_file_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Generate a new checkpoint ID
+pub fn generate_checkpoint_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Estimate storage size for a checkpoint
+pub fn estimate_checkpoint_size(messages: &str, file_snapshots: &[FileSnapshot]) -> u64 {
+    let messages_size = messages.len() as u64;
+    let files_size: u64 = file_snapshots.iter().map(|s| s.content.len() as u64).sum();
+
+    // Estimate compressed size (typically 20-30% of original for text)
+    (messages_size + files_size) / 4
+}
+
+/// Stores file snapshots in a content-addressable pool next to the timeline
+pub struct FileCheckpointStorage {
+    claude_dir: PathBuf,
+    compression_level: i32,
+}
+
+impl FileCheckpointStorage {
+    /// Create a new checkpoint storage instance
+    pub fn new(claude_dir: PathBuf) -> Self {
+        Self {
+            claude_dir,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+
     /// Save a single file snapshot
     fn save_file_snapshot(&self, paths: &CheckpointPaths, snapshot: &FileSnapshot) -> Result<()> {
         // Use content-addressable storage: store files by their hash
@@ -143,37 +618,6 @@ impl CheckpointStorage {
         Ok(())
     }
 
-    /// Load a checkpoint from disk
-    pub fn load_checkpoint(
-        &self,
-        project_id: &str,
-        session_id: &str,
-        checkpoint_id: &str,
-    ) -> Result<(Checkpoint, Vec<FileSnapshot>, String)> {
-        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
-
-        // Load checkpoint metadata
-        let metadata_path = paths.checkpoint_metadata_file(checkpoint_id);
-        let metadata_json =
-            fs::read_to_string(&metadata_path).context("Failed to read checkpoint metadata")?;
-        let checkpoint: Checkpoint =
-            serde_json::from_str(&metadata_json).context("Failed to parse checkpoint metadata")?;
-
-        // Load messages
-        let messages_path = paths.checkpoint_messages_file(checkpoint_id);
-        let compressed_messages =
-            fs::read(&messages_path).context("Failed to read compressed messages")?;
-        let messages = String::from_utf8(
-            decode_all(&compressed_messages[..]).context("Failed to decompress messages")?,
-        )
-        .context("Invalid UTF-8 in messages")?;
-
-        // Load file snapshots
-        let file_snapshots = self.load_file_snapshots(&paths, checkpoint_id)?;
-
-        Ok((checkpoint, file_snapshots, messages))
-    }
-
     /// Load all file snapshots for a checkpoint
     fn load_file_snapshots(
         &self,
@@ -236,162 +680,79 @@ impl CheckpointStorage {
 
         Ok(snapshots)
     }
+}
 
-    /// Save timeline to disk
-    pub fn save_timeline(&self, timeline_path: &Path, timeline: &SessionTimeline) -> Result<()> {
-        let timeline_json =
-            serde_json::to_string_pretty(timeline).context("Failed to serialize timeline")?;
-        fs::write(timeline_path, timeline_json).context("Failed to write timeline")?;
-        Ok(())
+impl CheckpointStorage for FileCheckpointStorage {
+    fn claude_dir(&self) -> &PathBuf {
+        &self.claude_dir
     }
 
-    /// Load timeline from disk
-    pub fn load_timeline(&self, timeline_path: &Path) -> Result<SessionTimeline> {
-        let timeline_json = fs::read_to_string(timeline_path).context("Failed to read timeline")?;
-        let timeline: SessionTimeline =
-            serde_json::from_str(&timeline_json).context("Failed to parse timeline")?;
-        Ok(timeline)
+    fn backend(&self) -> StorageBackend {
+        StorageBackend::Files
     }
 
-    /// Update timeline with a new checkpoint
-    fn update_timeline_with_checkpoint(
+    /// Save a checkpoint to disk
+    fn save_checkpoint(
         &self,
-        timeline_path: &Path,
+        project_id: &str,
+        session_id: &str,
         checkpoint: &Checkpoint,
-        file_snapshots: &[FileSnapshot],
-    ) -> Result<()> {
-        let mut timeline = self.load_timeline(timeline_path)?;
-
-        let new_node = TimelineNode {
-            checkpoint: checkpoint.clone(),
-            children: Vec::new(),
-            file_snapshot_ids: file_snapshots.iter().map(|s| s.hash.clone()).collect(),
-        };
-
-        // If this is the first checkpoint
-        if timeline.root_node.is_none() {
-            timeline.root_node = Some(new_node);
-            timeline.current_checkpoint_id = Some(checkpoint.id.clone());
-        } else if let Some(parent_id) = &checkpoint.parent_checkpoint_id {
-            // Check if parent exists before modifying
-            let parent_exists = timeline.find_checkpoint(parent_id).is_some();
-
-            if parent_exists {
-                if let Some(root) = &mut timeline.root_node {
-                    Self::add_child_to_node(root, parent_id, new_node)?;
-                    timeline.current_checkpoint_id = Some(checkpoint.id.clone());
-                }
-            } else {
-                anyhow::bail!("Parent checkpoint not found: {}", parent_id);
-            }
-        }
-
-        timeline.total_checkpoints += 1;
-        self.save_timeline(timeline_path, &timeline)?;
-
-        Ok(())
-    }
+        file_snapshots: Vec<FileSnapshot>,
+        messages: &str,
+    ) -> Result<CheckpointResult> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        write_checkpoint_record(&paths, checkpoint, messages, self.compression_level)?;
 
-    /// Recursively add a child node to the timeline tree
-    fn add_child_to_node(
-        node: &mut TimelineNode,
-        parent_id: &str,
-        child: TimelineNode,
-    ) -> Result<()> {
-        if node.checkpoint.id == parent_id {
-            node.children.push(child);
-            return Ok(());
-        }
+        // Save file snapshots
+        let mut warnings = Vec::new();
+        let mut files_processed = 0;
 
-        for child_node in &mut node.children {
-            if Self::add_child_to_node(child_node, parent_id, child.clone()).is_ok() {
-                return Ok(());
+        for snapshot in &file_snapshots {
+            match self.save_file_snapshot(&paths, snapshot) {
+                Ok(_) => files_processed += 1,
+                Err(e) => warnings.push(format!(
+                    "Failed to save {}: {}",
+                    snapshot.file_path.display(),
+                    e
+                )),
             }
         }
 
-        anyhow::bail!("Parent checkpoint not found: {}", parent_id)
-    }
-
-    /// Calculate hash of file content
-    pub fn calculate_file_hash(content: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
-        format!("{:x}", hasher.finalize())
-    }
-
-    /// Generate a new checkpoint ID
-    pub fn generate_checkpoint_id() -> String {
-        Uuid::new_v4().to_string()
-    }
-
-    /// Estimate storage size for a checkpoint
-    pub fn estimate_checkpoint_size(messages: &str, file_snapshots: &[FileSnapshot]) -> u64 {
-        let messages_size = messages.len() as u64;
-        let files_size: u64 = file_snapshots.iter().map(|s| s.content.len() as u64).sum();
+        // Update timeline
+        update_timeline_with_checkpoint(self, &paths.timeline_file, checkpoint, &file_snapshots)?;
 
-        // Estimate compressed size (typically 20-30% of original for text)
-        (messages_size + files_size) / 4
+        Ok(CheckpointResult {
+            checkpoint: checkpoint.clone(),
+            files_processed,
+            warnings,
+        })
     }
 
-    /// Clean up old checkpoints based on retention policy
-    pub fn cleanup_old_checkpoints(
+    /// Load a checkpoint from disk
+    fn load_checkpoint(
         &self,
         project_id: &str,
         session_id: &str,
-        keep_count: usize,
-    ) -> Result<usize> {
+        checkpoint_id: &str,
+    ) -> Result<(Checkpoint, Vec<FileSnapshot>, String)> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
-        let timeline = self.load_timeline(&paths.timeline_file)?;
-
-        // Collect all checkpoint IDs in chronological order
-        let mut all_checkpoints = Vec::new();
-        if let Some(root) = &timeline.root_node {
-            Self::collect_checkpoints(root, &mut all_checkpoints);
-        }
-
-        // Sort by timestamp (oldest first)
-        all_checkpoints.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-        // Keep only the most recent checkpoints
-        let to_remove = all_checkpoints.len().saturating_sub(keep_count);
-        let mut removed_count = 0;
-
-        for checkpoint in all_checkpoints.into_iter().take(to_remove) {
-            if self.remove_checkpoint(&paths, &checkpoint.id).is_ok() {
-                removed_count += 1;
-            }
-        }
-
-        // Run garbage collection to clean up orphaned content
-        if removed_count > 0 {
-            match self.garbage_collect_content(project_id, session_id) {
-                Ok(gc_count) => {
-                    log::info!("Garbage collected {} orphaned content files", gc_count);
-                }
-                Err(e) => {
-                    log::warn!("Failed to garbage collect content: {}", e);
-                }
-            }
-        }
+        let (checkpoint, messages) = read_checkpoint_record(&paths, checkpoint_id)?;
 
-        Ok(removed_count)
-    }
+        // Load file snapshots
+        let file_snapshots = self.load_file_snapshots(&paths, checkpoint_id)?;
 
-    /// Collect all checkpoints from the tree in order
-    fn collect_checkpoints(node: &TimelineNode, checkpoints: &mut Vec<Checkpoint>) {
-        checkpoints.push(node.checkpoint.clone());
-        for child in &node.children {
-            Self::collect_checkpoints(child, checkpoints);
-        }
+        Ok((checkpoint, file_snapshots, messages))
     }
 
     /// Remove a checkpoint and its associated files
-    fn remove_checkpoint(&self, paths: &CheckpointPaths, checkpoint_id: &str) -> Result<()> {
-        // Remove checkpoint metadata directory
-        let checkpoint_dir = paths.checkpoint_dir(checkpoint_id);
-        if checkpoint_dir.exists() {
-            fs::remove_dir_all(&checkpoint_dir).context("Failed to remove checkpoint directory")?;
-        }
+    fn remove_checkpoint(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<()> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        remove_checkpoint_record(&paths, checkpoint_id)?;
 
         // Remove file references for this checkpoint
         let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
@@ -406,7 +767,7 @@ impl CheckpointStorage {
     }
 
     /// Garbage collect unreferenced content from the content pool
-    pub fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize> {
+    fn garbage_collect_content(&self, project_id: &str, session_id: &str) -> Result<usize> {
         let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
         let content_pool_dir = paths.files_dir.join("content_pool");
         let refs_dir = paths.files_dir.join("refs");
@@ -416,7 +777,7 @@ impl CheckpointStorage {
         }
 
         // Collect all referenced hashes
-        let mut referenced_hashes = std::collections::HashSet::new();
+        let mut referenced_hashes = HashSet::new();
 
         if refs_dir.exists() {
             for checkpoint_entry in fs::read_dir(&refs_dir)? {
@@ -458,3 +819,174 @@ impl CheckpointStorage {
         Ok(removed_count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointMetadata, CheckpointTrigger};
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn checkpoint(id: &str, parent: Option<&str>) -> Checkpoint {
+        Checkpoint {
+            id: id.to_string(),
+            session_id: "session".to_string(),
+            project_id: "project".to_string(),
+            message_index: 0,
+            timestamp: Utc::now(),
+            description: None,
+            parent_checkpoint_id: parent.map(String::from),
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "test".to_string(),
+                user_prompt: String::new(),
+                file_changes: 1,
+                snapshot_size: 0,
+            },
+            trigger: CheckpointTrigger::Manual,
+        }
+    }
+
+    fn snapshot(checkpoint_id: &str, content: &str) -> FileSnapshot {
+        FileSnapshot {
+            checkpoint_id: checkpoint_id.to_string(),
+            file_path: PathBuf::from("main.rs"),
+            content: content.to_string(),
+            hash: calculate_file_hash(content),
+            is_deleted: false,
+            permissions: None,
+            size: content.len() as u64,
+        }
+    }
+
+    #[test]
+    fn test_branch_tracking_and_prune() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(temp_dir.path().to_path_buf());
+        storage.init_storage("project", "session").unwrap();
+        let paths = CheckpointPaths::new(storage.claude_dir(), "project", "session");
+
+        let save = |id: &str, parent: Option<&str>, content: &str| {
+            storage
+                .save_checkpoint(
+                    "project",
+                    "session",
+                    &checkpoint(id, parent),
+                    vec![snapshot(id, content)],
+                    "",
+                )
+                .unwrap();
+        };
+
+        // a -> b stays on main, a -> c forks off anonymously
+        save("a", None, "v1");
+        save("b", Some("a"), "v2");
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(
+            timeline.current_branch.as_deref(),
+            Some(DEFAULT_BRANCH_NAME)
+        );
+        assert_eq!(
+            timeline.branches[DEFAULT_BRANCH_NAME].tip_checkpoint_id,
+            "b"
+        );
+
+        save("c", Some("a"), "v3");
+        let mut timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(timeline.current_branch, None);
+        assert_eq!(
+            timeline.branches[DEFAULT_BRANCH_NAME].tip_checkpoint_id,
+            "b"
+        );
+
+        // Name the fork and move back onto main before pruning it
+        timeline.branches.insert(
+            "experiment".to_string(),
+            TimelineBranch {
+                name: "experiment".to_string(),
+                tip_checkpoint_id: "c".to_string(),
+                created_at: Utc::now(),
+                description: None,
+            },
+        );
+        timeline.current_checkpoint_id = Some("b".to_string());
+        timeline.current_branch = Some(DEFAULT_BRANCH_NAME.to_string());
+        storage
+            .save_timeline(&paths.timeline_file, &timeline)
+            .unwrap();
+
+        let result = storage
+            .prune_branch("project", "session", "experiment")
+            .unwrap();
+        assert_eq!(result.removed_checkpoint_ids, vec!["c".to_string()]);
+        assert_eq!(result.content_files_removed, 1);
+
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert!(timeline.find_checkpoint("c").is_none());
+        assert!(timeline.find_checkpoint("b").is_some());
+        assert!(!timeline.branches.contains_key("experiment"));
+        assert_eq!(timeline.total_checkpoints, 2);
+        assert!(!paths.checkpoint_dir("c").exists());
+
+        // The current branch is protected
+        assert!(storage
+            .prune_branch("project", "session", DEFAULT_BRANCH_NAME)
+            .is_err());
+    }
+
+    #[test]
+    fn test_prune_branch_keeps_unnamed_forks() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileCheckpointStorage::new(temp_dir.path().to_path_buf());
+        storage.init_storage("project", "session").unwrap();
+        let paths = CheckpointPaths::new(storage.claude_dir(), "project", "session");
+
+        let save = |id: &str, parent: Option<&str>| {
+            storage
+                .save_checkpoint(
+                    "project",
+                    "session",
+                    &checkpoint(id, parent),
+                    vec![snapshot(id, id)],
+                    "",
+                )
+                .unwrap();
+        };
+
+        // main: a -> b; experiment: a -> c -> d; unnamed fork c -> e
+        save("a", None);
+        save("b", Some("a"));
+        save("c", Some("a"));
+        save("d", Some("c"));
+        save("e", Some("c"));
+
+        let mut timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        timeline.branches.insert(
+            "experiment".to_string(),
+            TimelineBranch {
+                name: "experiment".to_string(),
+                tip_checkpoint_id: "d".to_string(),
+                created_at: Utc::now(),
+                description: None,
+            },
+        );
+        timeline.current_checkpoint_id = Some("b".to_string());
+        timeline.current_branch = Some(DEFAULT_BRANCH_NAME.to_string());
+        storage
+            .save_timeline(&paths.timeline_file, &timeline)
+            .unwrap();
+
+        let result = storage
+            .prune_branch("project", "session", "experiment")
+            .unwrap();
+        assert_eq!(result.removed_checkpoint_ids, vec!["d".to_string()]);
+
+        // c still carries the unnamed fork e
+        let timeline = storage.load_timeline(&paths.timeline_file).unwrap();
+        assert!(timeline.find_checkpoint("d").is_none());
+        assert!(timeline.find_checkpoint("c").is_some());
+        assert!(timeline.find_checkpoint("e").is_some());
+        assert!(paths.checkpoint_dir("e").exists());
+        assert_eq!(timeline.total_checkpoints, 4);
+    }
+}
@@ -0,0 +1,436 @@
+//! Per-agent MCP server assignment
+//!
+//! An agent's settings name the MCP servers it may use, optionally pinned to
+//! a scope. When a teammate starts, the assigned servers are resolved against
+//! its project and written to a dedicated `--mcp-config` file, which is
+//! passed with `--strict-mcp-config` so the agent sees only those servers.
+//! Agents without an assignment keep inheriting every configured server.
+//!
+//! `${secret:NAME}` references in the generated file are rewritten to
+//! environment references and the secret values handed to the Claude
+//! process, so they are never written to disk.
+
+use log::info;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use super::agents::{get_agent, Agent, AgentDb};
+use crate::mcp::config as mcp_config;
+use crate::mcp::{ConfigScope, ServerConfig};
+
+/// One MCP server assigned to an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "AssignmentRepr")]
+pub struct AgentMcpServer {
+    pub name: String,
+    /// Scope to take the server from; any scope (by precedence) when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<ConfigScope>,
+}
+
+/// Assignments may be stored as bare names or as `{name, scope}` objects
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AssignmentRepr {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        scope: Option<ConfigScope>,
+    },
+}
+
+impl From<AssignmentRepr> for AgentMcpServer {
+    fn from(repr: AssignmentRepr) -> Self {
+        match repr {
+            AssignmentRepr::Name(name) => Self { name, scope: None },
+            AssignmentRepr::Full { name, scope } => Self { name, scope },
+        }
+    }
+}
+
+/// Read `mcp_servers` from agent settings. Older settings stored an inline
+/// `mcpServers`-style object; its keys are taken as server names. Anything
+/// unreadable counts as no assignment rather than failing the whole settings.
+pub fn deserialize_assignments<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<AgentMcpServer>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<JsonValue>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(JsonValue::Object(entries)) => Some(
+            entries
+                .keys()
+                .map(|name| AgentMcpServer {
+                    name: name.clone(),
+                    scope: None,
+                })
+                .collect(),
+        ),
+        Some(value @ JsonValue::Array(_)) => serde_json::from_value(value).ok(),
+        _ => None,
+    })
+}
+
+/// Pick the assigned servers out of `available`, which is in precedence
+/// order. Fails on the first assignment that matches nothing.
+pub fn select_servers(
+    assignments: &[AgentMcpServer],
+    available: &[ServerConfig],
+) -> Result<Vec<ServerConfig>, String> {
+    let mut selected: Vec<ServerConfig> = Vec::new();
+    for assignment in assignments {
+        if selected.iter().any(|s| s.name == assignment.name) {
+            continue;
+        }
+        let server = available
+            .iter()
+            .find(|s| {
+                s.name == assignment.name && assignment.scope.is_none_or(|scope| s.scope == scope)
+            })
+            .ok_or_else(|| match assignment.scope {
+                Some(scope) => format!(
+                    "MCP server '{}' is not configured in {} scope",
+                    assignment.name,
+                    scope.as_str()
+                ),
+                None => format!("MCP server '{}' is not configured", assignment.name),
+            })?;
+        selected.push(server.clone());
+    }
+    Ok(selected)
+}
+
+/// Check that every assignment names a server that exists: user servers
+/// anywhere, project and local servers in at least one of the app's projects
+pub fn validate_assignments(
+    conn: &Connection,
+    assignments: &[AgentMcpServer],
+) -> Result<(), String> {
+    if assignments.is_empty() {
+        return Ok(());
+    }
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT working_dir FROM projects WHERE working_dir IS NOT NULL")
+        .map_err(|e| e.to_string())?;
+    let project_paths: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut available = mcp_config::load_all_servers(None)?;
+    for path in &project_paths {
+        available.extend(
+            mcp_config::load_all_servers(Some(path))?
+                .into_iter()
+                .filter(|s| s.scope != ConfigScope::User),
+        );
+    }
+    select_servers(assignments, &available).map(|_| ())
+}
+
+/// A generated `--mcp-config` file and the environment the process needs
+pub struct AgentMcpConfig {
+    pub path: PathBuf,
+    /// Secret values referenced by the file
+    pub env: HashMap<String, String>,
+}
+
+impl AgentMcpConfig {
+    /// Point a Claude command at the config and hand it the secret values
+    pub fn apply(&self, cmd: &mut tokio::process::Command) {
+        cmd.arg("--mcp-config")
+            .arg(&self.path)
+            .arg("--strict-mcp-config")
+            .envs(&self.env);
+    }
+}
+
+/// Write the MCP config for a Claude run outside the team, which inherits
+/// every configured server. As for agents without assignments, it is only
+/// needed when a server uses secrets; runs in one project share the file.
+pub fn write_project_mcp_config(
+    app_data_dir: &Path,
+    project_path: &str,
+) -> Result<Option<AgentMcpConfig>, String> {
+    let key: String = project_path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    write_agent_mcp_config(app_data_dir, &format!("project{}", key), project_path, None)
+}
+
+/// Rewrite secret references of `servers` to environment references
+pub fn export_secrets(
+    servers: &[ServerConfig],
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> Result<(Vec<ServerConfig>, HashMap<String, String>), String> {
+    let mut env = crate::secrets::SecretEnv::default();
+    let servers = servers
+        .iter()
+        .map(|server| {
+            server
+                .map_strings(|value| crate::secrets::export_refs(value, &mut env, &lookup))
+                .map_err(|e| format!("MCP server '{}': {}", server.name, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok((servers, env.vars))
+}
+
+/// Write `<app data>/mcp-configs/<session_id>.json` for an agent. With
+/// assignments, the file holds just the assigned servers. Without, it is only
+/// needed when the inherited servers use secrets, and then holds all of them.
+/// `${VAR}` placeholders are left for Claude to expand.
+pub fn write_agent_mcp_config(
+    app_data_dir: &Path,
+    session_id: &str,
+    project_path: &str,
+    assignments: Option<&[AgentMcpServer]>,
+) -> Result<Option<AgentMcpConfig>, String> {
+    let servers = match assignments {
+        Some(assignments) => {
+            let available = mcp_config::load_all_servers(Some(project_path))?;
+            select_servers(assignments, &available)?
+        }
+        None => {
+            let servers = mcp_config::load_servers(Some(project_path))?;
+            if !servers.iter().any(ServerConfig::uses_secrets) {
+                return Ok(None);
+            }
+            servers
+        }
+    };
+    let (servers, env) = export_secrets(&servers, crate::secrets::resolve)?;
+
+    let dir = app_data_dir.join("mcp-configs");
+    let path = write_mcp_config(&dir, session_id, &servers)?;
+    info!(
+        "Wrote MCP config for session {} with servers {:?}",
+        session_id,
+        servers.iter().map(|s| s.name.as_str()).collect::<Vec<_>>()
+    );
+    Ok(Some(AgentMcpConfig { path, env }))
+}
+
+fn write_mcp_config(
+    dir: &Path,
+    session_id: &str,
+    servers: &[ServerConfig],
+) -> Result<PathBuf, String> {
+    let entries: serde_json::Map<String, JsonValue> = servers
+        .iter()
+        .map(|s| (s.name.clone(), s.to_json()))
+        .collect();
+    let content = serde_json::to_string_pretty(&serde_json::json!({ "mcpServers": entries }))
+        .map_err(|e| format!("Failed to serialize MCP config: {}", e))?;
+
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.json", session_id));
+    fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Set the MCP servers an agent may use. `None` clears the assignment so the
+/// agent inherits every configured server again; an empty list gives it none.
+#[tauri::command]
+pub async fn set_agent_mcp_servers(
+    db: State<'_, AgentDb>,
+    agent_id: String,
+    servers: Option<Vec<AgentMcpServer>>,
+) -> Result<Agent, String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        if let Some(servers) = &servers {
+            validate_assignments(&conn, servers)?;
+        }
+
+        let settings: Option<String> = conn
+            .query_row(
+                "SELECT settings FROM agents WHERE id = ?1",
+                params![agent_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Agent not found: {}", e))?;
+        let mut settings = settings
+            .and_then(|s| serde_json::from_str::<JsonValue>(&s).ok())
+            .filter(JsonValue::is_object)
+            .unwrap_or_else(|| serde_json::json!({}));
+        settings["mcp_servers"] = serde_json::to_value(&servers).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "UPDATE agents SET settings = ?1 WHERE id = ?2",
+            params![settings.to_string(), agent_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    get_agent(db, agent_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_assignments_select_servers_by_name_and_scope() {
+        let claude = json!({
+            "mcpServers": {
+                "postgres": {"command": "pg-user"},
+                "search": {"type": "http", "url": "https://example.com/mcp"}
+            }
+        });
+        let project = json!({"mcpServers": {"postgres": {"command": "pg-project"}}});
+        let available = mcp_config::all_servers_from(Some(&claude), Some(&project), Some("/app"));
+
+        let assignments: Option<Vec<AgentMcpServer>> = deserialize_assignments(json!([
+            "postgres",
+            {"name": "postgres", "scope": "user"}
+        ]))
+        .unwrap();
+        let assignments = assignments.unwrap();
+        let selected = select_servers(&assignments[..1], &available).unwrap();
+        assert_eq!(selected[0].command.as_deref(), Some("pg-project"));
+        let selected = select_servers(&assignments[1..], &available).unwrap();
+        assert_eq!(selected[0].command.as_deref(), Some("pg-user"));
+
+        let missing = [AgentMcpServer {
+            name: "search".to_string(),
+            scope: Some(ConfigScope::Project),
+        }];
+        let err = select_servers(&missing, &available).unwrap_err();
+        assert!(err.contains("project scope"), "{}", err);
+
+        // Legacy inline configs keep their names
+        let legacy = deserialize_assignments(json!({"search": {"url": "x"}})).unwrap();
+        assert_eq!(legacy.unwrap()[0].name, "search");
+    }
+
+    #[test]
+    fn test_generated_config_contains_only_assigned_servers() {
+        let claude = json!({"mcpServers": {
+            "postgres": {"command": "pg", "env": {
+                "PGPASSWORD": "${secret:PG_PASSWORD}",
+                "PGHOST": "${PGHOST}"
+            }},
+            "github": {"command": "gh"}
+        }});
+        let available = mcp_config::all_servers_from(Some(&claude), None, None);
+        let servers = select_servers(
+            &[AgentMcpServer {
+                name: "postgres".to_string(),
+                scope: None,
+            }],
+            &available,
+        )
+        .unwrap();
+
+        let (servers, env) = export_secrets(&servers, |name| {
+            assert_eq!(name, "PG_PASSWORD");
+            Ok("hunter2".to_string())
+        })
+        .unwrap();
+        assert_eq!(env["VIBE_SECRET_PG_PASSWORD"], "hunter2");
+
+        let dir = TempDir::new().unwrap();
+        let path = write_mcp_config(dir.path(), "session-1", &servers).unwrap();
+        let written: JsonValue = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        let names: Vec<&String> = written["mcpServers"].as_object().unwrap().keys().collect();
+        assert_eq!(names, vec!["postgres"]);
+        let env = &written["mcpServers"]["postgres"]["env"];
+        assert_eq!(env["PGPASSWORD"], "${VIBE_SECRET_PG_PASSWORD}");
+        assert_eq!(env["PGHOST"], "${PGHOST}");
+    }
+
+    #[test]
+    fn test_unreadable_assignments_count_as_none_and_duplicates_select_once() {
+        assert_eq!(deserialize_assignments(JsonValue::Null).unwrap(), None);
+        assert_eq!(deserialize_assignments(json!("postgres")).unwrap(), None);
+        assert_eq!(deserialize_assignments(json!([1, 2])).unwrap(), None);
+        assert_eq!(
+            deserialize_assignments(json!([{"name": "pg", "scope": "global"}])).unwrap(),
+            None
+        );
+        assert_eq!(
+            deserialize_assignments(json!([])).unwrap(),
+            Some(Vec::new())
+        );
+
+        let claude = json!({"mcpServers": {"postgres": {"command": "pg"}}});
+        let available = mcp_config::all_servers_from(Some(&claude), None, None);
+        assert!(select_servers(&[], &available).unwrap().is_empty());
+        let twice = deserialize_assignments(json!(["postgres", "postgres"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(select_servers(&twice, &available).unwrap().len(), 1);
+
+        let missing = deserialize_assignments(json!(["github"])).unwrap().unwrap();
+        let err = select_servers(&missing, &available).unwrap_err();
+        assert_eq!(err, "MCP server 'github' is not configured");
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(validate_assignments(&conn, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_generated_config_reports_missing_secrets_and_unwritable_dirs() {
+        let claude = json!({"mcpServers": {
+            "github": {"type": "http", "url": "https://example.com/mcp",
+                "headers": {"Authorization": "Bearer ${secret:GH_TOKEN}"}}
+        }});
+        let servers = mcp_config::all_servers_from(Some(&claude), None, None);
+        let err = export_secrets(&servers, |name| Err(format!("{} is locked", name))).unwrap_err();
+        assert!(err.starts_with("MCP server 'github'"), "{}", err);
+        assert!(err.contains("GH_TOKEN is locked"), "{}", err);
+
+        let dir = TempDir::new().unwrap();
+        let path = write_mcp_config(dir.path(), "empty", &[]).unwrap();
+        let written: JsonValue = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(written, json!({"mcpServers": {}}));
+
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, "").unwrap();
+        let err = write_mcp_config(&file, "session-1", &[]).unwrap_err();
+        assert!(err.starts_with("Failed to create"), "{}", err);
+    }
+
+    #[test]
+    fn test_apply_points_claude_at_the_config() {
+        let config = AgentMcpConfig {
+            path: PathBuf::from("/data/mcp-configs/project-work.json"),
+            env: HashMap::from([("VIBE_SECRET_TOKEN".to_string(), "t0k3n".to_string())]),
+        };
+        let mut cmd = tokio::process::Command::new("claude");
+        cmd.args(["-p", "hello"]);
+        config.apply(&mut cmd);
+
+        let cmd = cmd.as_std();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(
+            args,
+            vec![
+                "-p",
+                "hello",
+                "--mcp-config",
+                "/data/mcp-configs/project-work.json",
+                "--strict-mcp-config"
+            ]
+        );
+        let env: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            env,
+            vec![(
+                std::ffi::OsStr::new("VIBE_SECRET_TOKEN"),
+                Some(std::ffi::OsStr::new("t0k3n"))
+            )]
+        );
+    }
+}
@@ -12,6 +12,11 @@ use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, Manager, State};
 use uuid::Uuid;
 
+use super::agent_mcp::write_project_mcp_config;
+use super::run_metrics::{save_turn_metrics, TurnMetricsCollector};
+use super::teammate::start_checkpoint_tracking;
+use crate::checkpoint::state::CheckpointState;
+
 // Sidecar support removed; using system binary execution only
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use tokio::process::Command;
@@ -437,6 +442,21 @@ pub fn init_database_with_path(db_path: &std::path::Path) -> SqliteResult<Connec
         [],
     )?;
 
+    // Add cost column to messages, filled from teammate "result" messages
+    let _ = conn.execute("ALTER TABLE messages ADD COLUMN cost_usd REAL", []);
+
+    // Create model_pricing table and seed default prices
+    super::pricing::init_pricing_table(&conn)?;
+
+    // Create usage index tables (ingested JSONL files and their usage entries)
+    super::usage_index::init_usage_index_tables(&conn)?;
+
+    // Create budgets and budget alert state tables
+    super::budget::init_budget_tables(&conn)?;
+
+    // Create per-turn metrics table for agent runs
+    super::run_metrics::init_run_metrics_table(&conn)?;
+
     Ok(conn)
 }
 
@@ -444,6 +464,11 @@ pub fn init_database_with_path(db_path: &std::path::Path) -> SqliteResult<Connec
 #[tauri::command]
 pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_agents(&conn)
+}
+
+/// All agents, newest first
+pub fn load_agents(conn: &Connection) -> Result<Vec<Agent>, String> {
 
     let mut stmt = conn
         .prepare("SELECT id, name, icon, color, nickname, gender, agent_type, system_prompt, default_task, model, tools, enable_file_read, enable_file_write, enable_network, hooks, settings, role_type, created_at, updated_at FROM agents ORDER BY created_at DESC")
@@ -626,7 +651,11 @@ pub async fn list_project_agents(
     project_id: String,
 ) -> Result<Vec<Agent>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_project_agents(&conn, &project_id)
+}
 
+/// Agents of a project, newest first
+pub fn load_project_agents(conn: &Connection, project_id: &str) -> Result<Vec<Agent>, String> {
     // Query agents via project_agents table (the canonical way)
     let mut stmt = conn
         .prepare(
@@ -642,7 +671,7 @@ pub async fn list_project_agents(
         .map_err(|e| e.to_string())?;
 
     let agents = stmt
-        .query_map([&project_id], |row| {
+        .query_map([project_id], |row| {
             Ok(Agent {
                 id: Some(row.get(0)?),
                 name: row.get(1)?,
@@ -693,6 +722,71 @@ pub async fn create_agent(
     role_type: Option<String>,
 ) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    insert_agent(
+        &conn,
+        NewAgent {
+            name,
+            icon,
+            color,
+            nickname,
+            gender,
+            agent_type,
+            system_prompt,
+            default_task,
+            model,
+            tools,
+            enable_file_read,
+            enable_file_write,
+            enable_network,
+            hooks,
+            settings,
+            role_type,
+        },
+    )
+}
+
+/// Fields of a new agent, as sent by the frontend
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewAgent {
+    pub name: String,
+    pub icon: String,
+    pub color: Option<String>,
+    pub nickname: Option<String>,
+    pub gender: Option<String>,
+    pub agent_type: Option<String>,
+    pub system_prompt: String,
+    pub default_task: Option<String>,
+    pub model: Option<String>,
+    pub tools: Option<String>,
+    pub enable_file_read: Option<bool>,
+    pub enable_file_write: Option<bool>,
+    pub enable_network: Option<bool>,
+    pub hooks: Option<String>,
+    pub settings: Option<String>,
+    pub role_type: Option<String>,
+}
+
+/// Insert an agent, filling in defaults for omitted fields
+pub fn insert_agent(conn: &Connection, agent: NewAgent) -> Result<Agent, String> {
+    let NewAgent {
+        name,
+        icon,
+        color,
+        nickname,
+        gender,
+        agent_type,
+        system_prompt,
+        default_task,
+        model,
+        tools,
+        enable_file_read,
+        enable_file_write,
+        enable_network,
+        hooks,
+        settings,
+        role_type,
+    } = agent;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
     let agent_type = agent_type.unwrap_or_else(|| "general-purpose".to_string());
     let enable_file_read = enable_file_read.unwrap_or(true);
@@ -700,6 +794,15 @@ pub async fn create_agent(
     let enable_network = enable_network.unwrap_or(false);
     let role_type = role_type.unwrap_or_else(|| "teamlead".to_string());
 
+    // Assigned MCP servers must exist
+    if let Some(settings) = &settings {
+        let parsed: super::teammate::AgentSettings = serde_json::from_str(settings)
+            .map_err(|e| format!("Invalid agent settings: {}", e))?;
+        if let Some(servers) = &parsed.mcp_servers {
+            super::agent_mcp::validate_assignments(conn, servers)?;
+        }
+    }
+
     let id = Uuid::new_v4().to_string();
 
     conn.execute(
@@ -709,37 +812,7 @@ pub async fn create_agent(
     .map_err(|e| e.to_string())?;
 
     // Fetch the created agent
-    let agent = conn
-        .query_row(
-            "SELECT id, name, icon, color, nickname, gender, agent_type, system_prompt, default_task, model, tools, enable_file_read, enable_file_write, enable_network, hooks, settings, role_type, created_at, updated_at FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    color: row.get(3)?,
-                    nickname: row.get(4)?,
-                    gender: row.get(5)?,
-                    agent_type: row.get(6)?,
-                    system_prompt: row.get(7)?,
-                    default_task: row.get(8)?,
-                    model: row.get(9)?,
-                    tools: row.get(10)?,
-                    enable_file_read: row.get(11)?,
-                    enable_file_write: row.get(12)?,
-                    enable_network: row.get(13)?,
-                    hooks: row.get(14)?,
-                    settings: row.get(15)?,
-                    role_type: row.get(16).ok(),
-                    created_at: row.get(17)?,
-                    updated_at: row.get(18)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(agent)
+    load_agent(conn, &id)
 }
 
 /// Update an existing agent
@@ -762,6 +835,68 @@ pub async fn update_agent(
     gender: Option<String>,
 ) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    update_agent_fields(
+        &conn,
+        &id,
+        AgentUpdate {
+            name,
+            icon,
+            system_prompt,
+            default_task,
+            model,
+            enable_file_read,
+            enable_file_write,
+            enable_network,
+            hooks,
+            role_type,
+            color,
+            nickname,
+            gender,
+        },
+    )
+}
+
+/// Editable fields of an agent; `None` leaves the optional columns as they
+/// are, except `default_task`, `model` and `hooks`, which are always written
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUpdate {
+    pub name: String,
+    pub icon: String,
+    pub system_prompt: String,
+    pub default_task: Option<String>,
+    pub model: Option<String>,
+    pub enable_file_read: Option<bool>,
+    pub enable_file_write: Option<bool>,
+    pub enable_network: Option<bool>,
+    pub hooks: Option<String>,
+    pub role_type: Option<String>,
+    pub color: Option<String>,
+    pub nickname: Option<String>,
+    pub gender: Option<String>,
+}
+
+/// Apply `update` to agent `id` and return the stored result
+pub fn update_agent_fields(
+    conn: &Connection,
+    id: &str,
+    update: AgentUpdate,
+) -> Result<Agent, String> {
+    let AgentUpdate {
+        name,
+        icon,
+        system_prompt,
+        default_task,
+        model,
+        enable_file_read,
+        enable_file_write,
+        enable_network,
+        hooks,
+        role_type,
+        color,
+        nickname,
+        gender,
+    } = update;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
 
     // Build dynamic query based on provided parameters
@@ -816,7 +951,7 @@ pub async fn update_agent(
 
     param_count += 1;
     query.push_str(&format!(" WHERE id = ?{}", param_count));
-    params_vec.push(Box::new(id.clone()));
+    params_vec.push(Box::new(id.to_string()));
 
     conn.execute(
         &query,
@@ -825,44 +960,18 @@ pub async fn update_agent(
     .map_err(|e| e.to_string())?;
 
     // Fetch the updated agent
-    let agent = conn
-        .query_row(
-            "SELECT id, name, icon, color, nickname, gender, agent_type, system_prompt, default_task, model, tools, enable_file_read, enable_file_write, enable_network, hooks, settings, role_type, created_at, updated_at FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    color: row.get(3)?,
-                    nickname: row.get(4)?,
-                    gender: row.get(5)?,
-                    agent_type: row.get(6)?,
-                    system_prompt: row.get(7)?,
-                    default_task: row.get(8)?,
-                    model: row.get(9)?,
-                    tools: row.get(10)?,
-                    enable_file_read: row.get(11)?,
-                    enable_file_write: row.get(12)?,
-                    enable_network: row.get(13)?,
-                    hooks: row.get(14)?,
-                    settings: row.get(15)?,
-                    role_type: row.get(16).ok(),
-                    created_at: row.get(17)?,
-                    updated_at: row.get(18)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(agent)
+    load_agent(conn, id)
 }
 
 /// Delete an agent
 #[tauri::command]
 pub async fn delete_agent(db: State<'_, AgentDb>, id: String) -> Result<(), String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    remove_agent(&conn, &id)
+}
 
+/// Delete agent `id`
+pub fn remove_agent(conn: &Connection, id: &str) -> Result<(), String> {
     conn.execute("DELETE FROM agents WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
 
@@ -873,7 +982,11 @@ pub async fn delete_agent(db: State<'_, AgentDb>, id: String) -> Result<(), Stri
 #[tauri::command]
 pub async fn get_agent(db: State<'_, AgentDb>, id: String) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_agent(&conn, &id)
+}
 
+/// Load an agent by id
+pub fn load_agent(conn: &Connection, id: &str) -> Result<Agent, String> {
     let agent = conn
         .query_row(
             "SELECT id, name, icon, color, nickname, gender, agent_type, system_prompt, default_task, model, tools, enable_file_read, enable_file_write, enable_network, hooks, settings, role_type, created_at, updated_at FROM agents WHERE id = ?1",
@@ -1168,6 +1281,15 @@ async fn spawn_agent_system(
     // Build the command
     let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
 
+    // Hand over the secrets the inherited MCP servers refer to
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    if let Some(mcp_config) = write_project_mcp_config(&app_data_dir, &project_path)? {
+        mcp_config.apply(&mut cmd);
+    }
+
     // Spawn the process
     info!("🚀 Spawning Claude system process...");
     let mut child = cmd.spawn().map_err(|e| {
@@ -1224,12 +1346,19 @@ async fn spawn_agent_system(
     let run_id_for_stdout = run_id.clone();
     let run_id_for_stderr = run_id.clone();
     let run_id_for_wait = run_id.clone();
+    let checkpoints = app
+        .try_state::<CheckpointState>()
+        .map(|state| state.inner().clone());
+    let project_path_for_checkpoints = project_path.clone();
 
     // Spawn tasks to read stdout
     let stdout_task = tokio::spawn(async move {
         info!("📖 Starting to read Claude stdout...");
         let mut lines = stdout_reader.lines();
         let mut line_count = 0;
+        let mut turn_collector = TurnMetricsCollector::new(chrono::Utc::now());
+        // Checkpointing starts once the init message names the session
+        let mut checkpoint_tx = None;
 
         while let Ok(Some(line)) = lines.next_line().await {
             line_count += 1;
@@ -1259,7 +1388,10 @@ async fn spawn_agent_system(
             let _ = registry_clone.append_live_output(run_id_for_stdout.clone(), &line);
 
             // Extract session ID from JSONL output
+            let mut started_session = None;
             if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
+                turn_collector.observe(&json, chrono::Utc::now());
+
                 // Claude Code uses "session_id" (underscore), not "sessionId"
                 if json.get("type").and_then(|t| t.as_str()) == Some("system")
                     && json.get("subtype").and_then(|s| s.as_str()) == Some("init")
@@ -1269,6 +1401,7 @@ async fn spawn_agent_system(
                             if current_session_id.is_empty() {
                                 *current_session_id = sid.to_string();
                                 info!("🔑 Extracted session ID: {}", sid);
+                                started_session = Some(sid.to_string());
 
                                 // Update database immediately with session ID
                                 if let Ok(conn) = Connection::open(&db_path_for_stdout) {
@@ -1295,6 +1428,20 @@ async fn spawn_agent_system(
                 }
             }
 
+            if let Some(sid) = started_session {
+                checkpoint_tx = start_checkpoint_tracking(
+                    checkpoints.as_ref(),
+                    std::sync::Arc::new(app_handle.clone()),
+                    "agent-checkpoint",
+                    &sid,
+                    &project_path_for_checkpoints,
+                )
+                .await;
+            }
+            if let Some(tx) = &checkpoint_tx {
+                let _ = tx.send(line.clone());
+            }
+
             // Emit the line to the frontend with run_id for isolation
             let _ = app_handle.emit(&format!("agent-output:{}", run_id_for_stdout), &line);
             // Also emit to the generic event for backward compatibility
@@ -1305,6 +1452,7 @@ async fn spawn_agent_system(
             "📖 Finished reading Claude stdout. Total lines: {}",
             line_count
         );
+        turn_collector.finish()
     });
 
     let app_handle_stderr = app.clone();
@@ -1431,7 +1579,7 @@ async fn spawn_agent_system(
 
         // Wait for reading tasks to complete
         info!("⏳ Waiting for stdout/stderr reading to complete...");
-        let _ = stdout_task.await;
+        let turns = stdout_task.await.unwrap_or_default();
         let _ = stderr_task.await;
 
         let duration_ms = start_time.elapsed().as_millis() as i64;
@@ -1448,7 +1596,7 @@ async fn spawn_agent_system(
         info!("✅ Claude process execution monitoring complete");
 
         // Update the run record with session ID and mark as completed - open a new connection
-        if let Ok(conn) = Connection::open(&db_path_for_monitor) {
+        if let Ok(mut conn) = Connection::open(&db_path_for_monitor) {
             info!(
                 "🔄 Updating database with extracted session ID: {}",
                 extracted_session_id
@@ -1468,6 +1616,12 @@ async fn spawn_agent_system(
                     error!("❌ Failed to update agent run {} with session ID: {}", run_id, e);
                 }
             }
+
+            if let Err(e) = save_turn_metrics(&mut conn, &run_id, &turns) {
+                error!("❌ Failed to save turn metrics for run {}: {}", run_id, e);
+            } else {
+                info!("📊 Saved {} turn metrics for run {}", turns.len(), run_id);
+            }
         } else {
             error!(
                 "❌ Failed to open database to update session ID for run {}",
@@ -0,0 +1,1117 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult, Row};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use uuid::Uuid;
+
+use super::agents::AgentDb;
+use super::usage::refresh_usage_index;
+use crate::events::EventEmitter;
+use crate::process::ProcessRegistry;
+
+/// What a budget applies to
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// All indexed Claude usage
+    Global,
+    /// Every team member of one vibe project (`scope_id` is the project id)
+    Project,
+    /// One agent across all projects it is a member of (`scope_id` is the agent id)
+    Agent,
+}
+
+impl BudgetScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BudgetScope::Global => "global",
+            BudgetScope::Project => "project",
+            BudgetScope::Agent => "agent",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "project" => BudgetScope::Project,
+            "agent" => BudgetScope::Agent,
+            _ => BudgetScope::Global,
+        }
+    }
+}
+
+/// Window a budget's spend is summed over (UTC calendar periods)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Weekly => "weekly",
+            BudgetPeriod::Monthly => "monthly",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "weekly" => BudgetPeriod::Weekly,
+            "monthly" => BudgetPeriod::Monthly,
+            _ => BudgetPeriod::Daily,
+        }
+    }
+
+    fn noun(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Daily => "day",
+            BudgetPeriod::Weekly => "week",
+            BudgetPeriod::Monthly => "month",
+        }
+    }
+
+    /// First day of the period containing `today`; weeks start on Monday
+    pub fn start(&self, today: NaiveDate) -> NaiveDate {
+        match self {
+            BudgetPeriod::Daily => today,
+            BudgetPeriod::Weekly => {
+                today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64)
+            }
+            BudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+        }
+    }
+}
+
+/// What happens once a budget's hard cap is exceeded
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Only emit `budget-warning`
+    #[default]
+    Notify,
+    /// Refuse to deliver messages to agents in scope until the period ends
+    Pause,
+    /// Stop running teammates in scope and refuse to start or message them
+    /// until the period ends
+    Stop,
+}
+
+impl BudgetAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BudgetAction::Notify => "notify",
+            BudgetAction::Pause => "pause",
+            BudgetAction::Stop => "stop",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "pause" => BudgetAction::Pause,
+            "stop" => BudgetAction::Stop,
+            _ => BudgetAction::Notify,
+        }
+    }
+}
+
+fn default_thresholds() -> Vec<f64> {
+    vec![0.8]
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A spend cap in USD and/or tokens
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Budget {
+    pub id: Option<String>,
+    pub name: String,
+    pub scope: BudgetScope,
+    pub scope_id: Option<String>,
+    pub period: BudgetPeriod,
+    pub limit_usd: Option<f64>,
+    pub limit_tokens: Option<u64>,
+    /// Fractions of the limit (e.g. 0.5, 0.8) that emit `budget-warning`; reaching 1.0 always does
+    #[serde(default = "default_thresholds")]
+    pub warning_thresholds: Vec<f64>,
+    #[serde(default)]
+    pub action: BudgetAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Budget {
+    /// Share of the tighter of the two limits used by the given spend
+    fn usage_fraction(&self, spent_usd: f64, spent_tokens: u64) -> f64 {
+        let by_cost = self
+            .limit_usd
+            .filter(|l| *l > 0.0)
+            .map(|l| spent_usd / l)
+            .unwrap_or(0.0);
+        let by_tokens = self
+            .limit_tokens
+            .filter(|l| *l > 0)
+            .map(|l| spent_tokens as f64 / l as f64)
+            .unwrap_or(0.0);
+        by_cost.max(by_tokens)
+    }
+}
+
+/// Spend of a budget in its current period
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    pub period_start: String,
+    pub spent_usd: f64,
+    pub spent_tokens: u64,
+    pub usage_fraction: f64,
+    pub exceeded: bool,
+}
+
+/// Payload of the `budget-warning` event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetWarning {
+    pub status: BudgetStatus,
+    /// Highest threshold crossed by this evaluation
+    pub threshold: f64,
+    /// Action applied because the hard cap was exceeded, if any
+    pub action_taken: Option<BudgetAction>,
+}
+
+/// Create the budget tables
+pub fn init_budget_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS budgets (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            scope TEXT NOT NULL DEFAULT 'global',
+            scope_id TEXT,
+            period TEXT NOT NULL DEFAULT 'daily',
+            limit_usd REAL,
+            limit_tokens INTEGER,
+            warning_thresholds TEXT NOT NULL DEFAULT '[0.8]',
+            action TEXT NOT NULL DEFAULT 'notify',
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS update_budgets_timestamp
+         AFTER UPDATE ON budgets
+         FOR EACH ROW
+         BEGIN
+             UPDATE budgets SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+         END",
+        [],
+    )?;
+
+    // Alert state per budget and period, so each threshold fires once
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS budget_alerts (
+            budget_id TEXT NOT NULL,
+            period_start TEXT NOT NULL,
+            last_threshold REAL NOT NULL DEFAULT 0,
+            exceeded BOOLEAN NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (budget_id, period_start)
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+const BUDGET_COLUMNS: &str = "id, name, scope, scope_id, period, limit_usd, limit_tokens, warning_thresholds, action, enabled, created_at, updated_at";
+
+fn budget_from_row(row: &Row) -> SqliteResult<Budget> {
+    let thresholds: String = row.get(7)?;
+    Ok(Budget {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        scope: BudgetScope::parse(&row.get::<_, String>(2)?),
+        scope_id: row.get(3)?,
+        period: BudgetPeriod::parse(&row.get::<_, String>(4)?),
+        limit_usd: row.get(5)?,
+        limit_tokens: row.get::<_, Option<i64>>(6)?.map(|v| v as u64),
+        warning_thresholds: serde_json::from_str(&thresholds)
+            .unwrap_or_else(|_| default_thresholds()),
+        action: BudgetAction::parse(&row.get::<_, String>(8)?),
+        enabled: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+fn read_budgets(conn: &Connection) -> SqliteResult<Vec<Budget>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM budgets ORDER BY created_at",
+        BUDGET_COLUMNS
+    ))?;
+    let budgets = stmt
+        .query_map([], budget_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(budgets)
+}
+
+/// Enabled budgets that cover usage of `agent_id` in `project_id`
+fn budgets_in_scope(
+    conn: &Connection,
+    project_id: &str,
+    agent_id: &str,
+) -> SqliteResult<Vec<Budget>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM budgets
+         WHERE enabled = 1
+           AND (scope = 'global'
+                OR (scope = 'project' AND scope_id = ?1)
+                OR (scope = 'agent' AND scope_id = ?2))",
+        BUDGET_COLUMNS
+    ))?;
+    let budgets = stmt
+        .query_map(params![project_id, agent_id], budget_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(budgets)
+}
+
+/// Sum the indexed usage a budget covers since `period_start`
+fn budget_spend(
+    conn: &Connection,
+    budget: &Budget,
+    period_start: &str,
+) -> SqliteResult<(f64, u64)> {
+    let (project_id, agent_id) = match budget.scope {
+        BudgetScope::Global => (None, None),
+        BudgetScope::Project => (budget.scope_id.as_deref(), None),
+        BudgetScope::Agent => (None, budget.scope_id.as_deref()),
+    };
+    conn.query_row(
+        "SELECT COALESCE(SUM(u.cost), 0),
+                COALESCE(SUM(u.input_tokens + u.output_tokens + u.cache_creation_tokens + u.cache_read_tokens), 0)
+         FROM usage_entries u
+         LEFT JOIN project_agents pa ON pa.id = u.session_id
+         WHERE u.usage_date >= ?1
+           AND (?2 IS NULL OR pa.project_id = ?2)
+           AND (?3 IS NULL OR pa.agent_id = ?3)",
+        params![period_start, project_id, agent_id],
+        |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)),
+    )
+}
+
+fn budget_status(
+    conn: &Connection,
+    budget: &Budget,
+    today: NaiveDate,
+) -> SqliteResult<BudgetStatus> {
+    let period_start = budget.period.start(today).format("%Y-%m-%d").to_string();
+    let (spent_usd, spent_tokens) = budget_spend(conn, budget, &period_start)?;
+    let usage_fraction = budget.usage_fraction(spent_usd, spent_tokens);
+    Ok(BudgetStatus {
+        budget: budget.clone(),
+        period_start,
+        spent_usd,
+        spent_tokens,
+        usage_fraction,
+        exceeded: usage_fraction >= 1.0,
+    })
+}
+
+/// Highest threshold (the hard cap at 1.0 included) crossed by `fraction`
+/// that has not been alerted yet in this period
+fn next_alert_threshold(thresholds: &[f64], last_threshold: f64, fraction: f64) -> Option<f64> {
+    thresholds
+        .iter()
+        .copied()
+        .chain(std::iter::once(1.0))
+        .filter(|t| *t > last_threshold && *t <= fraction)
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+fn validate_budget(budget: &Budget) -> Result<(), String> {
+    if budget.name.trim().is_empty() {
+        return Err("Budget name must not be empty".to_string());
+    }
+    if budget.scope != BudgetScope::Global
+        && budget.scope_id.as_deref().is_none_or(|s| s.is_empty())
+    {
+        return Err(format!(
+            "A {} budget needs a scope_id",
+            budget.scope.as_str()
+        ));
+    }
+    if budget.limit_usd.is_none() && budget.limit_tokens.is_none() {
+        return Err("A budget needs a USD or token limit".to_string());
+    }
+    if budget.limit_usd.is_some_and(|l| !l.is_finite() || l <= 0.0) {
+        return Err("USD limit must be positive".to_string());
+    }
+    if budget.limit_tokens == Some(0) {
+        return Err("Token limit must be positive".to_string());
+    }
+    if budget
+        .warning_thresholds
+        .iter()
+        .any(|t| !t.is_finite() || *t <= 0.0)
+    {
+        return Err("Warning thresholds must be positive fractions of the limit".to_string());
+    }
+    Ok(())
+}
+
+/// Store the alert state of a budget for one period
+fn record_alert_state(
+    conn: &Connection,
+    budget_id: &str,
+    period_start: &str,
+    last_threshold: f64,
+    exceeded: bool,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO budget_alerts (budget_id, period_start, last_threshold, exceeded)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(budget_id, period_start) DO UPDATE SET
+            last_threshold = excluded.last_threshold,
+            exceeded = excluded.exceeded,
+            updated_at = CURRENT_TIMESTAMP",
+        params![budget_id, period_start, last_threshold, exceeded],
+    )
+    .map_err(|e| format!("Failed to record budget alert: {}", e))?;
+    Ok(())
+}
+
+/// Re-evaluate a budget's current period against current spend.
+///
+/// Thresholds that are no longer crossed (say, after raising the limit) may
+/// fire again, those still crossed are not repeated, and an exceeded cap
+/// keeps blocking.
+fn sync_alert_state(conn: &Connection, budget: &Budget, today: NaiveDate) -> Result<(), String> {
+    let Some(id) = &budget.id else {
+        return Ok(());
+    };
+    let status = budget_status(conn, budget, today)
+        .map_err(|e| format!("Failed to compute budget spend: {}", e))?;
+    let last_threshold: f64 = conn
+        .query_row(
+            "SELECT last_threshold FROM budget_alerts WHERE budget_id = ?1 AND period_start = ?2",
+            params![id, status.period_start],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0.0);
+    let crossed =
+        next_alert_threshold(&budget.warning_thresholds, 0.0, status.usage_fraction).unwrap_or(0.0);
+    record_alert_state(
+        conn,
+        id,
+        &status.period_start,
+        last_threshold.min(crossed),
+        status.exceeded,
+    )
+}
+
+/// First enabled budget with one of `actions` that covers `agent_id` in
+/// `project_id` and was exceeded in its current period
+fn exceeded_budget(
+    conn: &Connection,
+    project_id: &str,
+    agent_id: &str,
+    actions: &[BudgetAction],
+) -> Result<Option<Budget>, String> {
+    let today = Utc::now().date_naive();
+    let budgets = budgets_in_scope(conn, project_id, agent_id).map_err(|e| e.to_string())?;
+
+    for budget in budgets.into_iter().filter(|b| actions.contains(&b.action)) {
+        let Some(id) = &budget.id else {
+            continue;
+        };
+        let period_start = budget.period.start(today).format("%Y-%m-%d").to_string();
+        let exceeded: Option<bool> = conn
+            .query_row(
+                "SELECT exceeded FROM budget_alerts WHERE budget_id = ?1 AND period_start = ?2",
+                params![id, period_start],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if exceeded == Some(true) {
+            return Ok(Some(budget));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Why delivery to `agent_id` in `project_id` is refused, if a `pause` or
+/// `stop` budget covering it was exceeded in its current period
+pub fn delivery_block(
+    conn: &Connection,
+    project_id: &str,
+    agent_id: &str,
+) -> Result<Option<String>, String> {
+    let budget = exceeded_budget(
+        conn,
+        project_id,
+        agent_id,
+        &[BudgetAction::Pause, BudgetAction::Stop],
+    )?;
+    Ok(budget.map(|budget| {
+        let consequence = match budget.action {
+            BudgetAction::Stop => "teammates in scope are stopped",
+            _ => "message delivery is paused",
+        };
+        format!(
+            "Budget '{}' exceeded for this {}; {}",
+            budget.name,
+            budget.period.noun(),
+            consequence
+        )
+    }))
+}
+
+/// Why `agent_id` may not start in `project_id`, if a `stop` budget
+/// covering it was exceeded in its current period
+pub fn start_block(
+    conn: &Connection,
+    project_id: &str,
+    agent_id: &str,
+) -> Result<Option<String>, String> {
+    let budget = exceeded_budget(conn, project_id, agent_id, &[BudgetAction::Stop])?;
+    Ok(budget.map(|budget| {
+        format!(
+            "Budget '{}' exceeded for this {}; teammates in scope cannot start",
+            budget.name,
+            budget.period.noun()
+        )
+    }))
+}
+
+/// Session ids of the teammates a stop action applies to
+fn teammates_in_scope(
+    conn: &Connection,
+    registry: &ProcessRegistry,
+    budget: &Budget,
+) -> Result<Vec<String>, String> {
+    let query = match budget.scope {
+        BudgetScope::Global => {
+            return Ok(registry
+                .get_running_teammate_agents()?
+                .into_iter()
+                .map(|p| p.run_id)
+                .collect())
+        }
+        BudgetScope::Project => "SELECT id FROM project_agents WHERE project_id = ?1",
+        BudgetScope::Agent => "SELECT id FROM project_agents WHERE agent_id = ?1",
+    };
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(params![budget.scope_id], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<SqliteResult<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// Record newly crossed thresholds of the budgets covering `agent_id` in
+/// `project_id` as of `today`.
+///
+/// Returns the warnings to emit and the session ids a `stop` budget applies
+/// to while its cap stays exceeded.
+fn check_budgets(
+    conn: &Connection,
+    registry: &ProcessRegistry,
+    project_id: &str,
+    agent_id: &str,
+    today: NaiveDate,
+) -> Result<(Vec<BudgetWarning>, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let mut to_stop = Vec::new();
+
+    let budgets = budgets_in_scope(conn, project_id, agent_id)
+        .map_err(|e| format!("Failed to load budgets: {}", e))?;
+    for budget in budgets {
+        let Some(id) = budget.id.clone() else {
+            continue;
+        };
+        let status = budget_status(conn, &budget, today)
+            .map_err(|e| format!("Failed to compute budget spend: {}", e))?;
+
+        let (last_threshold, was_exceeded): (f64, bool) = conn
+            .query_row(
+                "SELECT last_threshold, exceeded FROM budget_alerts WHERE budget_id = ?1 AND period_start = ?2",
+                params![id, status.period_start],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or((0.0, false));
+
+        if status.exceeded && budget.action == BudgetAction::Stop {
+            to_stop.extend(teammates_in_scope(conn, registry, &budget)?);
+        }
+
+        let next = next_alert_threshold(
+            &budget.warning_thresholds,
+            last_threshold,
+            status.usage_fraction,
+        );
+        // The blocks read `exceeded`, so keep it current even when no new
+        // threshold was crossed
+        if next.is_some() || status.exceeded != was_exceeded {
+            record_alert_state(
+                conn,
+                &id,
+                &status.period_start,
+                next.unwrap_or(last_threshold),
+                status.exceeded,
+            )?;
+        }
+        let Some(threshold) = next else {
+            continue;
+        };
+
+        let action_taken =
+            (status.exceeded && !was_exceeded && budget.action != BudgetAction::Notify)
+                .then_some(budget.action);
+        warnings.push(BudgetWarning {
+            status,
+            threshold,
+            action_taken,
+        });
+    }
+
+    Ok((warnings, to_stop))
+}
+
+/// Evaluate the budgets covering a teammate after it reported a `result`.
+///
+/// Refreshes the usage index, emits `budget-warning` for every newly crossed
+/// threshold and applies the hard-cap action: `pause` and `stop` are enforced
+/// at delivery time through `delivery_block`, `stop` also at start through
+/// `start_block` and kills the running teammates in scope on every
+/// evaluation while the cap stays exceeded.
+pub async fn evaluate_budgets(
+    events: Arc<dyn EventEmitter>,
+    db: Arc<Mutex<Connection>>,
+    registry: Arc<ProcessRegistry>,
+    project_id: String,
+    agent_id: String,
+) -> Result<(), String> {
+    let blocking_registry = registry.clone();
+    let (warnings, to_stop) = tokio::task::spawn_blocking(move || {
+        let agent_db = AgentDb(db.clone());
+        refresh_usage_index(&agent_db)?;
+
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        check_budgets(
+            &conn,
+            &blocking_registry,
+            &project_id,
+            &agent_id,
+            Utc::now().date_naive(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Budget evaluation task failed: {}", e))??;
+
+    for warning in &warnings {
+        warn!(
+            "Budget '{}' at {:.0}% (${:.4}, {} tokens)",
+            warning.status.budget.name,
+            warning.status.usage_fraction * 100.0,
+            warning.status.spent_usd,
+            warning.status.spent_tokens
+        );
+        events.emit("budget-warning", warning);
+    }
+
+    for session_id in to_stop {
+        if registry.exists(&session_id)? {
+            info!("Stopping teammate {}: budget exceeded", session_id);
+            registry.kill_process(session_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// List all budgets
+#[tauri::command]
+pub async fn list_budgets(db: State<'_, AgentDb>) -> Result<Vec<Budget>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    read_budgets(&conn).map_err(|e| format!("Failed to list budgets: {}", e))
+}
+
+/// Insert or update a budget and bring its alert state in line with it
+fn upsert_budget(conn: &Connection, budget: &Budget, today: NaiveDate) -> Result<Budget, String> {
+    let id = budget
+        .id
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let thresholds = serde_json::to_string(&budget.warning_thresholds)
+        .map_err(|e| format!("Failed to serialize thresholds: {}", e))?;
+    let scope_id = match budget.scope {
+        BudgetScope::Global => None,
+        _ => budget.scope_id.clone(),
+    };
+
+    conn.execute(
+        "INSERT INTO budgets (id, name, scope, scope_id, period, limit_usd, limit_tokens, warning_thresholds, action, enabled)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            scope = excluded.scope,
+            scope_id = excluded.scope_id,
+            period = excluded.period,
+            limit_usd = excluded.limit_usd,
+            limit_tokens = excluded.limit_tokens,
+            warning_thresholds = excluded.warning_thresholds,
+            action = excluded.action,
+            enabled = excluded.enabled",
+        params![
+            id,
+            budget.name,
+            budget.scope.as_str(),
+            scope_id,
+            budget.period.as_str(),
+            budget.limit_usd,
+            budget.limit_tokens.map(|v| v as i64),
+            thresholds,
+            budget.action.as_str(),
+            budget.enabled,
+        ],
+    )
+    .map_err(|e| format!("Failed to save budget: {}", e))?;
+
+    let saved = conn
+        .query_row(
+            &format!("SELECT {} FROM budgets WHERE id = ?1", BUDGET_COLUMNS),
+            params![id],
+            budget_from_row,
+        )
+        .map_err(|e| format!("Failed to load budget: {}", e))?;
+
+    // Limits may have changed: re-evaluate rather than forget the period's
+    // state, so an edit never lifts a cap that is still exceeded
+    sync_alert_state(conn, &saved, today)?;
+    Ok(saved)
+}
+
+/// Create or update a budget
+#[tauri::command]
+pub async fn save_budget(db: State<'_, AgentDb>, budget: Budget) -> Result<Budget, String> {
+    log::info!("Saving budget: {}", budget.name);
+    validate_budget(&budget)?;
+    refresh_usage_index(&db)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    upsert_budget(&conn, &budget, Utc::now().date_naive())
+}
+
+/// Delete a budget and its alert state
+#[tauri::command]
+pub async fn delete_budget(db: State<'_, AgentDb>, id: String) -> Result<(), String> {
+    log::info!("Deleting budget: {}", id);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM budget_alerts WHERE budget_id = ?1",
+        params![id],
+    )
+    .map_err(|e| format!("Failed to delete budget alerts: {}", e))?;
+    conn.execute("DELETE FROM budgets WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete budget: {}", e))?;
+    Ok(())
+}
+
+/// Current-period spend of every enabled budget, optionally only those
+/// covering a project (global budgets, the project's own and its agents')
+#[tauri::command]
+pub async fn get_budget_status(
+    db: State<'_, AgentDb>,
+    project_id: Option<String>,
+) -> Result<Vec<BudgetStatus>, String> {
+    refresh_usage_index(&db)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let project_agents: Vec<String> = match &project_id {
+        Some(project_id) => conn
+            .prepare("SELECT agent_id FROM project_agents WHERE project_id = ?1")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![project_id], |row| row.get(0))?
+                    .collect::<SqliteResult<Vec<_>>>()
+            })
+            .map_err(|e| e.to_string())?,
+        None => Vec::new(),
+    };
+
+    let today = Utc::now().date_naive();
+    read_budgets(&conn)
+        .map_err(|e| format!("Failed to list budgets: {}", e))?
+        .into_iter()
+        .filter(|b| b.enabled)
+        .filter(|b| match (&project_id, b.scope) {
+            (None, _) | (_, BudgetScope::Global) => true,
+            (Some(project_id), BudgetScope::Project) => b.scope_id.as_ref() == Some(project_id),
+            (Some(_), BudgetScope::Agent) => b
+                .scope_id
+                .as_ref()
+                .is_some_and(|id| project_agents.contains(id)),
+        })
+        .map(|b| {
+            budget_status(&conn, &b, today)
+                .map_err(|e| format!("Failed to compute budget spend: {}", e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+
+    #[test]
+    fn test_periods_and_thresholds() {
+        let day = NaiveDate::from_ymd_opt(2025, 6, 19).unwrap(); // Thursday
+        assert_eq!(BudgetPeriod::Daily.start(day), day);
+        assert_eq!(
+            BudgetPeriod::Weekly.start(day),
+            NaiveDate::from_ymd_opt(2025, 6, 16).unwrap()
+        );
+        assert_eq!(
+            BudgetPeriod::Monthly.start(day),
+            NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()
+        );
+
+        let thresholds = [0.5, 0.8];
+        assert_eq!(next_alert_threshold(&thresholds, 0.0, 0.4), None);
+        assert_eq!(next_alert_threshold(&thresholds, 0.0, 0.85), Some(0.8));
+        assert_eq!(next_alert_threshold(&thresholds, 0.8, 0.9), None);
+        assert_eq!(next_alert_threshold(&thresholds, 0.8, 1.2), Some(1.0));
+        assert_eq!(next_alert_threshold(&thresholds, 1.0, 3.0), None);
+    }
+
+    #[test]
+    fn test_period_starts_across_week_and_month_boundaries() {
+        let sunday = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        assert_eq!(
+            BudgetPeriod::Weekly.start(sunday),
+            NaiveDate::from_ymd_opt(2025, 5, 26).unwrap()
+        );
+        let monday = NaiveDate::from_ymd_opt(2025, 6, 2).unwrap();
+        assert_eq!(BudgetPeriod::Weekly.start(monday), monday);
+
+        let new_year = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        assert_eq!(
+            BudgetPeriod::Weekly.start(new_year),
+            NaiveDate::from_ymd_opt(2025, 12, 29).unwrap()
+        );
+        assert_eq!(BudgetPeriod::Monthly.start(new_year), new_year);
+        let leap_day = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(
+            BudgetPeriod::Monthly.start(leap_day),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_thresholds_crossed_together_alert_once_at_the_highest() {
+        // Unordered and duplicate thresholds still pick the highest crossed
+        let thresholds = [0.8, 0.5, 0.8];
+        assert_eq!(next_alert_threshold(&thresholds, 0.0, 0.95), Some(0.8));
+        // A jump past every warning lands on the hard cap only
+        assert_eq!(next_alert_threshold(&thresholds, 0.0, 2.0), Some(1.0));
+        // Thresholds above the cap alert after it
+        assert_eq!(next_alert_threshold(&[1.5], 1.0, 1.6), Some(1.5));
+        assert_eq!(next_alert_threshold(&[], 0.0, 0.99), None);
+        assert_eq!(next_alert_threshold(&[], 0.0, 1.0), Some(1.0));
+    }
+
+    fn budget(limit_usd: Option<f64>, limit_tokens: Option<u64>) -> Budget {
+        Budget {
+            id: None,
+            name: "cap".to_string(),
+            scope: BudgetScope::Global,
+            scope_id: None,
+            period: BudgetPeriod::Daily,
+            limit_usd,
+            limit_tokens,
+            warning_thresholds: default_thresholds(),
+            action: BudgetAction::Notify,
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_budget_rejects_bad_limits_scopes_and_thresholds() {
+        assert!(validate_budget(&budget(Some(5.0), None)).is_ok());
+        assert!(validate_budget(&budget(None, Some(1000))).is_ok());
+
+        let mut b = budget(Some(5.0), None);
+        b.name = "  ".to_string();
+        assert!(validate_budget(&b).unwrap_err().contains("name"));
+
+        let mut b = budget(Some(5.0), None);
+        b.scope = BudgetScope::Project;
+        assert!(validate_budget(&b).unwrap_err().contains("scope_id"));
+        b.scope_id = Some(String::new());
+        assert!(validate_budget(&b).unwrap_err().contains("scope_id"));
+
+        assert!(validate_budget(&budget(None, None))
+            .unwrap_err()
+            .contains("limit"));
+        for bad in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(validate_budget(&budget(Some(bad), None))
+                .unwrap_err()
+                .contains("USD limit"));
+        }
+        assert!(validate_budget(&budget(None, Some(0)))
+            .unwrap_err()
+            .contains("Token limit"));
+
+        for bad in [0.0, -0.5, f64::NAN] {
+            let mut b = budget(Some(5.0), None);
+            b.warning_thresholds = vec![0.5, bad];
+            assert!(validate_budget(&b).unwrap_err().contains("thresholds"));
+        }
+    }
+
+    #[test]
+    fn test_usage_fraction_uses_the_tighter_limit() {
+        assert_eq!(budget(Some(10.0), None).usage_fraction(5.0, 900), 0.5);
+        assert_eq!(budget(Some(10.0), Some(1000)).usage_fraction(5.0, 900), 0.9);
+        assert_eq!(budget(None, Some(1000)).usage_fraction(50.0, 0), 0.0);
+    }
+
+    fn team_db() -> Connection {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name, working_dir) VALUES ('p1', 'Demo', '/work/demo');
+             INSERT INTO agents (id, name, icon, system_prompt, role_type) VALUES
+                ('lead', 'Lead', 'bot', '', 'teamlead'),
+                ('dev', 'Dev', 'bot', '', 'teammate');
+             INSERT INTO project_agents (id, project_id, agent_id) VALUES
+                ('pa-lead', 'p1', 'lead'),
+                ('pa-dev', 'p1', 'dev');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn add_usage(conn: &Connection, session_id: &str, date: NaiveDate, cost: f64) {
+        conn.execute(
+            "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path)
+             VALUES ('f', ?1 || 'T00:00:00Z', ?1, 'claude-sonnet-4', 10, ?2, ?3, '/work/demo')",
+            params![date.format("%Y-%m-%d").to_string(), cost, session_id],
+        )
+        .unwrap();
+    }
+
+    fn add_budget(
+        conn: &Connection,
+        id: &str,
+        scope: &str,
+        scope_id: Option<&str>,
+        limit_usd: f64,
+        action: &str,
+    ) {
+        conn.execute(
+            "INSERT INTO budgets (id, name, scope, scope_id, period, limit_usd, warning_thresholds, action)
+             VALUES (?1, ?1, ?2, ?3, 'daily', ?4, '[0.5]', ?5)",
+            params![id, scope, scope_id, limit_usd, action],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_check_budgets_blocks_delivery_and_start() {
+        let conn = team_db();
+        let registry = ProcessRegistry::new();
+        let today = Utc::now().date_naive();
+        add_budget(&conn, "team-cap", "project", Some("p1"), 5.0, "stop");
+        add_budget(&conn, "dev-cap", "agent", Some("dev"), 2.0, "pause");
+        add_budget(&conn, "global-cap", "global", None, 100.0, "notify");
+
+        add_usage(&conn, "pa-dev", today, 3.0);
+        let (warnings, to_stop) = check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        let fired: Vec<(&str, f64, Option<BudgetAction>)> = warnings
+            .iter()
+            .map(|w| (w.status.budget.name.as_str(), w.threshold, w.action_taken))
+            .collect();
+        assert_eq!(
+            fired,
+            vec![
+                ("team-cap", 0.5, None),
+                ("dev-cap", 1.0, Some(BudgetAction::Pause))
+            ]
+        );
+        assert!(to_stop.is_empty());
+
+        // Pausing blocks messages to the agent in scope only, and never a start
+        let reason = delivery_block(&conn, "p1", "dev").unwrap().unwrap();
+        assert!(reason.contains("'dev-cap'") && reason.contains("paused"));
+        assert_eq!(delivery_block(&conn, "p1", "lead").unwrap(), None);
+        assert_eq!(start_block(&conn, "p1", "dev").unwrap(), None);
+
+        add_usage(&conn, "pa-lead", today, 3.0);
+        let (warnings, mut to_stop) = check_budgets(&conn, &registry, "p1", "lead", today).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].status.budget.name, "team-cap");
+        assert_eq!(warnings[0].action_taken, Some(BudgetAction::Stop));
+        to_stop.sort();
+        assert_eq!(to_stop, vec!["pa-dev", "pa-lead"]);
+
+        let reason = delivery_block(&conn, "p1", "lead").unwrap().unwrap();
+        assert!(reason.contains("'team-cap'") && reason.contains("stopped"));
+        let reason = start_block(&conn, "p1", "lead").unwrap().unwrap();
+        assert!(reason.contains("cannot start"));
+
+        // Stop keeps applying while exceeded, without repeating the warning
+        let (warnings, to_stop) = check_budgets(&conn, &registry, "p1", "lead", today).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(to_stop.len(), 2);
+    }
+
+    #[test]
+    fn test_check_budgets_without_usage() {
+        let conn = team_db();
+        let registry = ProcessRegistry::new();
+        let today = Utc::now().date_naive();
+        add_budget(&conn, "team-cap", "project", Some("p1"), 5.0, "stop");
+
+        let (warnings, to_stop) = check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        assert!(warnings.is_empty());
+        assert!(to_stop.is_empty());
+        let alerts: i64 = conn
+            .query_row("SELECT COUNT(*) FROM budget_alerts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(alerts, 0);
+        assert_eq!(delivery_block(&conn, "p1", "dev").unwrap(), None);
+    }
+
+    #[test]
+    fn test_alerts_restart_with_each_period() {
+        let conn = team_db();
+        let registry = ProcessRegistry::new();
+        add_budget(&conn, "team-cap", "project", Some("p1"), 4.0, "notify");
+        conn.execute("UPDATE budgets SET period = 'weekly'", [])
+            .unwrap();
+
+        let sunday = NaiveDate::from_ymd_opt(2025, 6, 15).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2025, 6, 16).unwrap();
+        add_usage(&conn, "pa-dev", sunday, 5.0);
+        let (warnings, _) = check_budgets(&conn, &registry, "p1", "dev", sunday).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].threshold, 1.0);
+        assert_eq!(warnings[0].status.period_start, "2025-06-09");
+        // Notify budgets never take an action, even when exceeded
+        assert_eq!(warnings[0].action_taken, None);
+
+        // Last week's spend does not count towards the new week
+        add_usage(&conn, "pa-dev", monday, 2.5);
+        let (warnings, _) = check_budgets(&conn, &registry, "p1", "dev", monday).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].threshold, 0.5);
+        assert_eq!(warnings[0].status.period_start, "2025-06-16");
+        assert!(!warnings[0].status.exceeded);
+
+        let periods: i64 = conn
+            .query_row("SELECT COUNT(*) FROM budget_alerts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(periods, 2);
+    }
+
+    #[test]
+    fn test_editing_a_budget_keeps_its_exceeded_state_current() {
+        let conn = team_db();
+        let registry = ProcessRegistry::new();
+        let today = Utc::now().date_naive();
+        add_budget(&conn, "team-cap", "project", Some("p1"), 5.0, "stop");
+        add_usage(&conn, "pa-dev", today, 6.0);
+        check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        assert!(start_block(&conn, "p1", "dev").unwrap().is_some());
+
+        let mut saved = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM budgets WHERE id = 'team-cap'",
+                    BUDGET_COLUMNS
+                ),
+                [],
+                budget_from_row,
+            )
+            .unwrap();
+        // An edit that leaves the cap exceeded keeps blocking
+        saved.name = "Team cap".to_string();
+        saved = upsert_budget(&conn, &saved, today).unwrap();
+        assert!(start_block(&conn, "p1", "dev").unwrap().is_some());
+
+        // Raising the limit lifts the block and lets the cap fire again, but
+        // does not repeat the warning that is still crossed
+        saved.limit_usd = Some(10.0);
+        saved = upsert_budget(&conn, &saved, today).unwrap();
+        assert_eq!(start_block(&conn, "p1", "dev").unwrap(), None);
+        let (warnings, to_stop) = check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        assert!(warnings.is_empty() && to_stop.is_empty());
+        add_usage(&conn, "pa-dev", today, 5.0);
+        let (warnings, _) = check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].threshold, 1.0);
+        assert_eq!(warnings[0].action_taken, Some(BudgetAction::Stop));
+
+        // Raising it past the spend lifts the block straight away, and
+        // lowering it again restores it, before the next evaluation
+        saved.limit_usd = Some(30.0);
+        saved = upsert_budget(&conn, &saved, today).unwrap();
+        assert_eq!(start_block(&conn, "p1", "dev").unwrap(), None);
+        saved.limit_usd = Some(4.0);
+        upsert_budget(&conn, &saved, today).unwrap();
+        assert!(delivery_block(&conn, "p1", "dev").unwrap().is_some());
+
+        // An evaluation without a new threshold still records the change
+        conn.execute("UPDATE budgets SET limit_usd = 40.0", [])
+            .unwrap();
+        let (warnings, _) = check_budgets(&conn, &registry, "p1", "dev", today).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(delivery_block(&conn, "p1", "dev").unwrap(), None);
+    }
+
+    #[test]
+    fn test_blocks_ignore_past_periods_and_disabled_budgets() {
+        let conn = team_db();
+        let today = Utc::now().date_naive();
+        add_budget(&conn, "team-cap", "project", Some("p1"), 5.0, "stop");
+        conn.execute(
+            "INSERT INTO budget_alerts (budget_id, period_start, last_threshold, exceeded)
+             VALUES ('team-cap', '2000-01-01', 1.0, 1)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(start_block(&conn, "p1", "dev").unwrap(), None);
+
+        conn.execute(
+            "INSERT INTO budget_alerts (budget_id, period_start, last_threshold, exceeded)
+             VALUES ('team-cap', ?1, 1.0, 1)",
+            params![today.format("%Y-%m-%d").to_string()],
+        )
+        .unwrap();
+        assert!(start_block(&conn, "p1", "dev").unwrap().is_some());
+
+        conn.execute("UPDATE budgets SET enabled = 0", []).unwrap();
+        assert_eq!(start_block(&conn, "p1", "dev").unwrap(), None);
+        assert_eq!(delivery_block(&conn, "p1", "dev").unwrap(), None);
+    }
+}
@@ -10,6 +10,8 @@ use tauri::{AppHandle, Emitter, Manager};
 use tokio::process::{Child, Command};
 use tokio::sync::Mutex;
 
+use super::agent_mcp::write_project_mcp_config;
+
 /// Global state to track current Claude process
 ///
 /// ⚠️ DEPRECATED: 此结构已被 ProcessRegistry 替代
@@ -141,7 +143,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
 }
 
 /// Gets the path to the ~/.claude directory
-fn get_claude_dir() -> Result<PathBuf> {
+pub(crate) fn get_claude_dir() -> Result<PathBuf> {
     dirs::home_dir()
         .context("Could not find home directory")?
         .join(".claude")
@@ -312,6 +314,22 @@ fn create_system_command(claude_path: &str, args: Vec<String>, project_path: &st
     cmd
 }
 
+/// Hand the secrets the project's MCP servers refer to over to a Claude run
+fn attach_mcp_secrets(
+    app: &AppHandle,
+    cmd: &mut Command,
+    project_path: &str,
+) -> Result<(), String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    if let Some(mcp_config) = write_project_mcp_config(&app_data_dir, project_path)? {
+        mcp_config.apply(cmd);
+    }
+    Ok(())
+}
+
 /// Gets the user's home directory path
 #[tauri::command]
 pub async fn get_home_directory() -> Result<String, String> {
@@ -565,9 +583,14 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
 /// Reads the Claude settings file
 #[tauri::command]
 pub async fn get_claude_settings() -> Result<ClaudeSettings, String> {
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    read_claude_settings(&claude_dir)
+}
+
+/// settings.json of `claude_dir`, empty when there is none
+pub fn read_claude_settings(claude_dir: &std::path::Path) -> Result<ClaudeSettings, String> {
     log::info!("Reading Claude settings");
 
-    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let settings_path = claude_dir.join("settings.json");
 
     if !settings_path.exists() {
@@ -647,9 +670,17 @@ pub async fn get_system_prompt() -> Result<String, String> {
 /// Checks if Claude Code is installed and gets its version
 #[tauri::command]
 pub async fn check_claude_version(app: AppHandle) -> Result<ClaudeVersionStatus, String> {
+    check_claude_version_in(app.path().app_data_dir().ok().as_deref())
+}
+
+/// Version check against the binary `find_claude_binary_in` resolves for
+/// `app_data_dir` (web mode has no `AppHandle`)
+pub fn check_claude_version_in(
+    app_data_dir: Option<&std::path::Path>,
+) -> Result<ClaudeVersionStatus, String> {
     log::info!("Checking Claude Code version");
 
-    let claude_path = match find_claude_binary(&app) {
+    let claude_path = match crate::claude_binary::find_claude_binary_in(app_data_dir) {
         Ok(path) => path,
         Err(e) => {
             return Ok(ClaudeVersionStatus {
@@ -752,13 +783,21 @@ pub async fn save_system_prompt(content: String) -> Result<String, String> {
 /// Saves the Claude settings file
 #[tauri::command]
 pub async fn save_claude_settings(settings: serde_json::Value) -> Result<String, String> {
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    write_claude_settings(&claude_dir, &settings)
+}
+
+/// Replace settings.json of `claude_dir`
+pub fn write_claude_settings(
+    claude_dir: &std::path::Path,
+    settings: &serde_json::Value,
+) -> Result<String, String> {
     log::info!("Saving Claude settings");
 
-    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let settings_path = claude_dir.join("settings.json");
 
     // Pretty print the JSON with 2-space indentation
-    let json_string = serde_json::to_string_pretty(&settings)
+    let json_string = serde_json::to_string_pretty(settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
 
     fs::write(&settings_path, json_string)
@@ -952,7 +991,8 @@ pub async fn execute_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attach_mcp_secrets(&app, &mut cmd, &project_path)?;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -986,7 +1026,8 @@ pub async fn continue_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attach_mcp_secrets(&app, &mut cmd, &project_path)?;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -1023,7 +1064,8 @@ pub async fn resume_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attach_mcp_secrets(&app, &mut cmd, &project_path)?;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -1586,36 +1628,19 @@ fn search_files_recursive(
     Ok(())
 }
 
-/// Creates a checkpoint for the current session state
-#[tauri::command]
-pub async fn create_checkpoint(
-    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
-    session_id: String,
-    project_id: String,
-    project_path: String,
+/// Feed a session's JSONL transcript, up to `message_index` if given, to its
+/// checkpoint manager
+async fn load_session_messages(
+    manager: &crate::checkpoint::manager::CheckpointManager,
+    project_id: &str,
+    session_id: &str,
     message_index: Option<usize>,
-    description: Option<String>,
-) -> Result<crate::checkpoint::CheckpointResult, String> {
-    log::info!(
-        "Creating checkpoint for session: {} in project: {}",
-        session_id,
-        project_id
-    );
-
-    let manager = app
-        .get_or_create_manager(
-            session_id.clone(),
-            project_id.clone(),
-            PathBuf::from(&project_path),
-        )
-        .await
-        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
-
+) -> Result<(), String> {
     // Always load current session messages from the JSONL file
     let session_path = get_claude_dir()
         .map_err(|e| e.to_string())?
         .join("projects")
-        .join(&project_id)
+        .join(project_id)
         .join(format!("{}.jsonl", session_id));
 
     if session_path.exists() {
@@ -1640,6 +1665,36 @@ pub async fn create_checkpoint(
         }
     }
 
+    Ok(())
+}
+
+/// Creates a checkpoint for the current session state
+#[tauri::command]
+pub async fn create_checkpoint(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    message_index: Option<usize>,
+    description: Option<String>,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    log::info!(
+        "Creating checkpoint for session: {} in project: {}",
+        session_id,
+        project_id
+    );
+
+    let manager = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    load_session_messages(&manager, &project_id, &session_id, message_index).await?;
+
     manager
         .create_checkpoint(description, None)
         .await
@@ -1727,6 +1782,7 @@ pub async fn fork_from_checkpoint(
     project_path: String,
     new_session_id: String,
     description: Option<String>,
+    branch_name: Option<String>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
     log::info!(
         "Forking from checkpoint: {} to new session: {}",
@@ -1762,7 +1818,7 @@ pub async fn fork_from_checkpoint(
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     manager
-        .fork_from_checkpoint(&checkpoint_id, description)
+        .fork_from_checkpoint(&checkpoint_id, description, branch_name)
         .await
         .map_err(|e| format!("Failed to fork checkpoint: {}", e))
 }
@@ -1835,8 +1891,6 @@ pub async fn get_checkpoint_diff(
     session_id: String,
     project_id: String,
 ) -> Result<crate::checkpoint::CheckpointDiff, String> {
-    use crate::checkpoint::storage::CheckpointStorage;
-
     log::info!(
         "Getting diff between checkpoints: {} -> {}",
         from_checkpoint_id,
@@ -1844,74 +1898,356 @@ pub async fn get_checkpoint_diff(
     );
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let storage = CheckpointStorage::new(claude_dir);
-
-    // Load both checkpoints
-    let (from_checkpoint, from_files, _) = storage
-        .load_checkpoint(&project_id, &session_id, &from_checkpoint_id)
-        .map_err(|e| format!("Failed to load source checkpoint: {}", e))?;
-    let (to_checkpoint, to_files, _) = storage
-        .load_checkpoint(&project_id, &session_id, &to_checkpoint_id)
-        .map_err(|e| format!("Failed to load target checkpoint: {}", e))?;
-
-    // Build file maps
-    let mut from_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &from_files {
-        from_map.insert(file.file_path.clone(), file);
-    }
+    let storage = crate::checkpoint::storage::open_storage(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to open checkpoint storage: {}", e))?;
 
-    let mut to_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &to_files {
-        to_map.insert(file.file_path.clone(), file);
-    }
+    storage
+        .diff_checkpoints(&project_id, &session_id, &from_checkpoint_id, &to_checkpoint_id)
+        .map_err(|e| format!("Failed to diff checkpoints: {}", e))
+}
 
-    // Calculate differences
-    let mut modified_files = Vec::new();
-    let mut added_files = Vec::new();
-    let mut deleted_files = Vec::new();
-
-    // Check for modified and deleted files
-    for (path, from_file) in &from_map {
-        if let Some(to_file) = to_map.get(path) {
-            if from_file.hash != to_file.hash {
-                // File was modified
-                let additions = to_file.content.lines().count();
-                let deletions = from_file.content.lines().count();
-
-                modified_files.push(crate::checkpoint::FileDiff {
-                    path: path.clone(),
-                    additions,
-                    deletions,
-                    diff_content: None, // TODO: Generate actual diff
-                });
-            }
-        } else {
-            // File was deleted
-            deleted_files.push(path.clone());
+/// Lists the named branches of a session timeline
+#[tauri::command]
+pub async fn list_timeline_branches(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<Vec<crate::checkpoint::TimelineBranch>, String> {
+    log::info!("Listing timeline branches for session: {}", session_id);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    Ok(manager.list_branches().await)
+}
+
+/// Names a timeline branch ending at a checkpoint (the current one by default)
+#[tauri::command]
+pub async fn create_timeline_branch(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    name: String,
+    checkpoint_id: Option<String>,
+    description: Option<String>,
+) -> Result<crate::checkpoint::TimelineBranch, String> {
+    log::info!(
+        "Creating timeline branch '{}' for session: {}",
+        name,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .create_branch(name, checkpoint_id, description)
+        .await
+        .map_err(|e| format!("Failed to create branch: {}", e))
+}
+
+/// Switches the session to the tip of a named timeline branch
+#[tauri::command]
+pub async fn switch_timeline_branch(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    name: String,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    log::info!(
+        "Switching session {} to timeline branch '{}'",
+        session_id,
+        name
+    );
+
+    let manager = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    let result = manager
+        .switch_branch(&name)
+        .await
+        .map_err(|e| format!("Failed to switch branch: {}", e))?;
+
+    // Keep the session JSONL file in sync with the restored branch tip
+    let session_path = get_claude_dir()
+        .map_err(|e| e.to_string())?
+        .join("projects")
+        .join(&project_id)
+        .join(format!("{}.jsonl", session_id));
+
+    let (_, _, messages) = manager
+        .storage
+        .load_checkpoint(&project_id, &session_id, &result.checkpoint.id)
+        .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
+
+    fs::write(&session_path, messages)
+        .map_err(|e| format!("Failed to update session file: {}", e))?;
+
+    Ok(result)
+}
+
+/// Compares the tips of two named timeline branches
+#[tauri::command]
+pub async fn compare_timeline_branches(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    base_branch: String,
+    compare_branch: String,
+) -> Result<crate::checkpoint::BranchComparison, String> {
+    log::info!(
+        "Comparing timeline branches '{}' and '{}' for session: {}",
+        base_branch,
+        compare_branch,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .compare_branches(&base_branch, &compare_branch)
+        .await
+        .map_err(|e| format!("Failed to compare branches: {}", e))
+}
+
+/// Prunes a timeline branch and garbage collects its unreferenced content
+#[tauri::command]
+pub async fn prune_timeline_branch(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    name: String,
+) -> Result<crate::checkpoint::BranchPruneResult, String> {
+    log::info!(
+        "Pruning timeline branch '{}' for session: {}",
+        name,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .prune_branch(&name)
+        .await
+        .map_err(|e| format!("Failed to prune branch: {}", e))
+}
+
+/// Exports a session timeline into a portable tar.zst archive
+#[tauri::command]
+pub async fn export_session_timeline(
+    session_id: String,
+    project_id: String,
+    output_path: String,
+) -> Result<crate::checkpoint::archive::TimelineArchiveSummary, String> {
+    log::info!(
+        "Exporting timeline for session: {} to {}",
+        session_id,
+        output_path
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::archive::export_timeline(
+            &claude_dir,
+            &project_id,
+            &session_id,
+            &PathBuf::from(output_path),
+        )
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))?
+    .map_err(|e| format!("Failed to export timeline: {}", e))
+}
+
+/// Imports a timeline archive into a project, optionally under a new session ID
+#[tauri::command]
+pub async fn import_session_timeline(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    archive_path: String,
+    project_id: String,
+    session_id: Option<String>,
+) -> Result<crate::checkpoint::archive::TimelineArchiveSummary, String> {
+    log::info!(
+        "Importing timeline archive {} into project: {}",
+        archive_path,
+        project_id
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+
+    let summary = tokio::task::spawn_blocking(move || {
+        crate::checkpoint::archive::import_timeline(
+            &claude_dir,
+            &PathBuf::from(archive_path),
+            &project_id,
+            session_id.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Import task failed: {}", e))?
+    .map_err(|e| format!("Failed to import timeline: {}", e))?;
+
+    // Drop any cached manager so the imported timeline is loaded fresh
+    app.remove_manager(&summary.session_id).await;
+
+    Ok(summary)
+}
+
+/// Gets the checkpoint retention policy for a project
+#[tauri::command]
+pub async fn get_checkpoint_retention_policy(
+    project_id: String,
+) -> Result<crate::checkpoint::retention::RetentionPolicy, String> {
+    log::info!("Getting checkpoint retention policy for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    crate::checkpoint::retention::load_policy(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to load retention policy: {}", e))
+}
+
+/// Updates the checkpoint retention policy for a project
+#[tauri::command]
+pub async fn update_checkpoint_retention_policy(
+    project_id: String,
+    policy: crate::checkpoint::retention::RetentionPolicy,
+) -> Result<(), String> {
+    log::info!("Updating checkpoint retention policy for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    crate::checkpoint::retention::save_policy(&claude_dir, &project_id, &policy)
+        .map_err(|e| format!("Failed to save retention policy: {}", e))
+}
+
+/// Applies the project's retention policy to a session immediately
+#[tauri::command]
+pub async fn apply_checkpoint_retention(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+) -> Result<crate::checkpoint::retention::RetentionResult, String> {
+    log::info!("Applying checkpoint retention policy to session: {}", session_id);
+
+    let manager = app
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+
+    manager
+        .apply_retention_policy()
+        .await
+        .map_err(|e| format!("Failed to apply retention policy: {}", e))
+}
+
+/// Gets the checkpoint storage settings for a project
+#[tauri::command]
+pub async fn get_checkpoint_storage_settings(
+    project_id: String,
+) -> Result<crate::checkpoint::storage::StorageSettings, String> {
+    log::info!("Getting checkpoint storage settings for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    crate::checkpoint::storage::StorageSettings::load(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to load storage settings: {}", e))
+}
+
+/// Selects the checkpoint storage backend for a project
+///
+/// The backend can only change while the project has no checkpoints, since
+/// existing checkpoints are not migrated between backends.
+#[tauri::command]
+pub async fn set_checkpoint_storage_backend(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    project_id: String,
+    project_path: String,
+    backend: crate::checkpoint::storage::StorageBackend,
+) -> Result<crate::checkpoint::storage::StorageSettings, String> {
+    use crate::checkpoint::git_storage::GitCheckpointStorage;
+    use crate::checkpoint::storage::{StorageBackend, StorageSettings};
+
+    log::info!(
+        "Setting checkpoint storage backend for project {} to {:?}",
+        project_id,
+        backend
+    );
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+
+    let current = StorageSettings::load(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to load storage settings: {}", e))?;
+    if current.backend != backend {
+        let usage = crate::checkpoint::retention::project_storage_usage(&claude_dir, &project_id)
+            .map_err(|e| format!("Failed to inspect existing checkpoints: {}", e))?;
+        if usage.sessions.iter().any(|s| s.checkpoint_count > 0) {
+            return Err(format!(
+                "Project already has checkpoints stored with the {:?} backend",
+                current.backend
+            ));
         }
     }
 
-    // Check for added files
-    for (path, _) in &to_map {
-        if !from_map.contains_key(path) {
-            added_files.push(path.clone());
+    let policy = crate::checkpoint::retention::load_policy(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to load retention policy: {}", e))?;
+    crate::checkpoint::retention::ensure_quota_supported(backend, &policy)
+        .map_err(|e| e.to_string())?;
+
+    let repo_path = match backend {
+        StorageBackend::Files => None,
+        StorageBackend::Git => {
+            let storage = GitCheckpointStorage::open(claude_dir.clone(), &PathBuf::from(&project_path))
+                .map_err(|e| format!("Failed to open git repository: {}", e))?;
+            Some(storage.repo_path().to_path_buf())
         }
-    }
+    };
 
-    // Calculate token delta
-    let token_delta = (to_checkpoint.metadata.total_tokens as i64)
-        - (from_checkpoint.metadata.total_tokens as i64);
+    let settings = StorageSettings { backend, repo_path };
+    settings
+        .save(&claude_dir, &project_id)
+        .map_err(|e| format!("Failed to save storage settings: {}", e))?;
 
-    Ok(crate::checkpoint::CheckpointDiff {
-        from_checkpoint_id,
-        to_checkpoint_id,
-        modified_files,
-        added_files,
-        deleted_files,
-        token_delta,
+    // Cached managers still hold the previous backend
+    app.remove_project_managers(&project_id).await;
+
+    Ok(settings)
+}
+
+/// Reports checkpoint disk usage and dedup ratio for every session in a project
+#[tauri::command]
+pub async fn get_checkpoint_storage_usage(
+    project_id: String,
+) -> Result<crate::checkpoint::retention::ProjectStorageUsage, String> {
+    log::info!("Getting checkpoint storage usage for project: {}", project_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::retention::project_storage_usage(&claude_dir, &project_id)
     })
+    .await
+    .map_err(|e| format!("Storage usage task failed: {}", e))?
+    .map_err(|e| format!("Failed to compute storage usage: {}", e))
 }
 
 /// Tracks a message for checkpointing
@@ -1936,7 +2272,8 @@ pub async fn track_checkpoint_message(
         .map_err(|e| format!("Failed to track message: {}", e))
 }
 
-/// Checks if auto-checkpoint should be triggered
+/// Creates an automatic checkpoint when the session's strategy calls for one
+/// after `message`, returning whether it did
 #[tauri::command]
 pub async fn check_auto_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1948,11 +2285,23 @@ pub async fn check_auto_checkpoint(
     log::info!("Checking auto-checkpoint for session: {}", session_id);
 
     let manager = app
-        .get_or_create_manager(session_id.clone(), project_id, PathBuf::from(project_path))
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(project_path),
+        )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    Ok(manager.should_auto_checkpoint(&message).await)
+    if !manager.should_auto_checkpoint(&message).await {
+        return Ok(false);
+    }
+    load_session_messages(&manager, &project_id, &session_id, None).await?;
+    manager
+        .create_checkpoint_with_trigger(None, None, crate::checkpoint::CheckpointTrigger::Auto)
+        .await
+        .map_err(|e| format!("Failed to create checkpoint: {}", e))?;
+    Ok(true)
 }
 
 /// Triggers cleanup of old checkpoints
@@ -6,7 +6,16 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
-use tauri::AppHandle;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, State};
+
+use crate::mcp::config as mcp_config;
+use crate::mcp::host::{self, HostedServerSpec, HostedServerStatus, HostedState};
+use crate::mcp::lint::{self, ConfigFix, McpConfigReport};
+use crate::mcp::types::{Prompt, Resource, Tool};
+use crate::mcp::{ConfigScope, McpClient, ServerConfig, TransportKind};
+use crate::process::ProcessRegistryState;
 
 /// Helper function to create a std::process::Command with proper environment variables
 /// This ensures commands like Claude can find Node.js and other dependencies
@@ -25,7 +34,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String> {
 pub struct MCPServer {
     /// Server name/identifier
     pub name: String,
-    /// Transport type: "stdio" or "sse"
+    /// Transport type: "stdio", "sse" or "http"
     pub transport: String,
     /// Command to execute (for stdio)
     pub command: Option<String>,
@@ -33,8 +42,11 @@ pub struct MCPServer {
     pub args: Vec<String>,
     /// Environment variables
     pub env: HashMap<String, String>,
-    /// URL endpoint (for SSE)
+    /// URL endpoint (for SSE and HTTP)
     pub url: Option<String>,
+    /// HTTP headers (for SSE and HTTP), possibly with `${VAR}` placeholders
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
     /// Configuration scope: "local", "project", or "user"
     pub scope: String,
     /// Whether the server is currently active
@@ -44,7 +56,7 @@ pub struct MCPServer {
 }
 
 /// Server status information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ServerStatus {
     /// Whether the server is running
     pub running: bool,
@@ -52,6 +64,99 @@ pub struct ServerStatus {
     pub error: Option<String>,
     /// Last checked timestamp
     pub last_checked: Option<u64>,
+    /// Time to connect and complete the initialize handshake
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// Server name and version reported during initialize
+    #[serde(default)]
+    pub server_name: Option<String>,
+    #[serde(default)]
+    pub server_version: Option<String>,
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+    #[serde(default)]
+    pub tool_count: Option<usize>,
+    #[serde(default)]
+    pub resource_count: Option<usize>,
+    #[serde(default)]
+    pub prompt_count: Option<usize>,
+}
+
+/// Result of connecting to a server and listing what it offers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerProbe {
+    pub name: String,
+    pub scope: String,
+    pub transport: String,
+    pub status: ServerStatus,
+    pub instructions: Option<String>,
+    pub tools: Vec<Tool>,
+    pub resources: Vec<Resource>,
+    pub prompts: Vec<Prompt>,
+}
+
+/// How long a probe waits for the server (stdio servers run via npx may
+/// have to download their package first)
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a server's catalogue is served from the cache
+const CATALOG_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long the linter waits for a remote server to answer at all
+const URL_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A server's tools, resources and prompts as of its last probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerCatalog {
+    #[serde(flatten)]
+    pub probe: ServerProbe,
+    /// Whether this came from the cache rather than a fresh probe
+    pub from_cache: bool,
+    /// Seconds until the cached entry is refreshed
+    pub expires_in_secs: u64,
+}
+
+/// Result of invoking a tool with test arguments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInvocation {
+    pub server: String,
+    pub tool: String,
+    pub arguments: serde_json::Value,
+    /// Raw `tools/call` result
+    pub result: Option<serde_json::Value>,
+    /// Whether the tool reported an error (`isError`) or the call failed
+    pub is_error: bool,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+struct CachedProbe {
+    fetched: Instant,
+    probe: ServerProbe,
+}
+
+// Status of the most recent probe per server name
+lazy_static::lazy_static! {
+    static ref LAST_STATUS: Mutex<HashMap<String, ServerStatus>> = Mutex::new(HashMap::new());
+}
+
+// Probes keyed by the serialized server config, so editing a server's
+// config misses the cache and probes it again
+lazy_static::lazy_static! {
+    static ref CATALOG_CACHE: Mutex<HashMap<String, CachedProbe>> = Mutex::new(HashMap::new());
+}
+
+fn catalog_key(config: &ServerConfig) -> String {
+    serde_json::to_string(config).unwrap_or_else(|_| config.name.clone())
+}
+
+/// Status from the last probe of a server, if it was probed
+fn cached_status(name: &str) -> ServerStatus {
+    LAST_STATUS
+        .lock()
+        .ok()
+        .and_then(|statuses| statuses.get(name).cloned())
+        .unwrap_or_default()
 }
 
 /// MCP configuration for project scope (.mcp.json)
@@ -64,11 +169,22 @@ pub struct MCPProjectConfig {
 /// Individual server configuration in .mcp.json
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPServerConfig {
+    /// Transport type; absent for stdio servers written before it existed
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Request timeout in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
 }
 
 /// Result of adding a server
@@ -127,9 +243,63 @@ pub async fn mcp_add(
     env: HashMap<String, String>,
     url: Option<String>,
     scope: String,
+    headers: Option<HashMap<String, String>>,
+    timeout_ms: Option<u64>,
+    bearer_token_env: Option<String>,
+    store_as_secrets: Option<bool>,
 ) -> Result<AddServerResult, String> {
     info!("Adding MCP server: {} with transport: {}", name, transport);
 
+    // Move env and header values into the secret store so only
+    // `${secret:NAME}` references reach the command line and config files
+    let mut env = env;
+    let mut headers = headers.unwrap_or_default();
+    let moved = match store_as_secrets {
+        Some(true) => move_values_to_secrets(&name, &mut env)
+            .and_then(|_| move_values_to_secrets(&name, &mut headers)),
+        _ => Ok(()),
+    };
+    if let Err(e) = moved {
+        return Ok(AddServerResult {
+            success: false,
+            message: e,
+            server_name: None,
+        });
+    }
+
+    // Headers, timeouts and streamable HTTP go through add-json, which keeps
+    // `${VAR}` placeholders unexpanded in the stored config
+    if let Some(var) = bearer_token_env.filter(|v| !v.trim().is_empty()) {
+        headers.insert(
+            "Authorization".to_string(),
+            format!("Bearer ${{{}}}", var.trim()),
+        );
+    }
+    if TransportKind::parse(&transport) == Some(TransportKind::Http)
+        || !headers.is_empty()
+        || timeout_ms.is_some()
+    {
+        let Some(kind) = TransportKind::parse(&transport) else {
+            return Ok(AddServerResult {
+                success: false,
+                message: format!("Unsupported transport: {}", transport),
+                server_name: None,
+            });
+        };
+        let config = ServerConfig {
+            name: name.clone(),
+            scope: ConfigScope::Local,
+            transport: kind,
+            command,
+            args,
+            env,
+            url,
+            headers,
+            timeout_ms,
+        };
+        return mcp_add_json(app, name, config.to_json().to_string(), scope).await;
+    }
+
     // Prepare owned strings for environment variables
     let env_args: Vec<String> = env
         .iter()
@@ -208,6 +378,33 @@ pub async fn mcp_add(
     }
 }
 
+/// Store each literal value as secret `<server>_<KEY>` and replace it with a
+/// reference; values that already hold placeholders are left alone
+fn move_values_to_secrets(
+    server: &str,
+    values: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in values.iter_mut() {
+        if value.is_empty() || value.contains("${") {
+            continue;
+        }
+        let secret: String = format!("{}_{}", server, key)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        crate::secrets::with_store(|store| store.set(&secret, value))
+            .map_err(|e| format!("Failed to store {} as a secret: {}", key, e))?;
+        *value = format!("${{secret:{}}}", secret);
+    }
+    Ok(())
+}
+
 /// Lists all configured MCP servers
 #[tauri::command]
 pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
@@ -285,21 +482,32 @@ pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
                         let full_command = command_parts.join(" ");
                         info!("Full command for server '{}': {:?}", name, full_command);
 
+                        // Remote servers are listed as "<url> (SSE)" or "<url> (HTTP)"
+                        let remote = [(" (SSE)", "sse"), (" (HTTP)", "http")].iter().find_map(
+                            |(suffix, transport)| {
+                                full_command
+                                    .find(suffix)
+                                    .map(|pos| (full_command[..pos].trim().to_string(), *transport))
+                            },
+                        );
+
                         // For now, we'll create a basic server entry
+                        let (transport, command, url) = match remote {
+                            Some((url, transport)) => (transport.to_string(), None, Some(url)),
+                            // Default assumption
+                            None => ("stdio".to_string(), Some(full_command), None),
+                        };
                         servers.push(MCPServer {
                             name: name.clone(),
-                            transport: "stdio".to_string(), // Default assumption
-                            command: Some(full_command),
+                            transport,
+                            command,
                             args: vec![],
                             env: HashMap::new(),
-                            url: None,
+                            url,
+                            headers: HashMap::new(),
                             scope: "local".to_string(), // Default assumption
                             is_active: false,
-                            status: ServerStatus {
-                                running: false,
-                                error: None,
-                                last_checked: None,
-                            },
+                            status: cached_status(&name),
                         });
                         info!("Added server: {:?}", name);
 
@@ -344,10 +552,16 @@ pub async fn mcp_get(app: AppHandle, name: String) -> Result<MCPServer, String>
             let mut args = vec![];
             let env = HashMap::new();
             let mut url = None;
+            let mut headers = HashMap::new();
+            let mut in_headers = false;
 
             for line in output.lines() {
                 let line = line.trim();
 
+                if line.starts_with("Headers:") {
+                    in_headers = true;
+                    continue;
+                }
                 if line.starts_with("Scope:") {
                     let scope_part = line.replace("Scope:", "").trim().to_string();
                     if scope_part.to_lowercase().contains("local") {
@@ -373,23 +587,24 @@ pub async fn mcp_get(app: AppHandle, name: String) -> Result<MCPServer, String>
                 } else if line.starts_with("Environment:") {
                     // TODO: Parse environment variables if they're listed
                     // For now, we'll leave it empty
+                } else if let Some((key, value)) = line.split_once(':').filter(|_| in_headers) {
+                    headers.insert(key.trim().to_string(), value.trim().to_string());
+                    continue;
                 }
+                in_headers = false;
             }
 
             Ok(MCPServer {
+                status: cached_status(&name),
                 name,
                 transport,
                 command,
                 args,
                 env,
                 url,
+                headers,
                 scope,
                 is_active: false,
-                status: ServerStatus {
-                    running: false,
-                    error: None,
-                    last_checked: None,
-                },
             })
         }
         Err(e) => {
@@ -429,6 +644,27 @@ pub async fn mcp_add_json(
         name, scope
     );
 
+    // Check the config ourselves for a clearer error than the CLI's, and
+    // normalize transport aliases such as "streamable-http" to "http"
+    let json_config = match serde_json::from_str::<serde_json::Value>(&json_config)
+        .map_err(|e| format!("Invalid JSON: {}", e))
+        .and_then(|mut value| {
+            let config = ServerConfig::from_json(&name, ConfigScope::Local, &value)?;
+            if let Some(entry) = value.as_object_mut() {
+                entry.insert("type".to_string(), config.transport.as_str().into());
+            }
+            Ok(value.to_string())
+        }) {
+        Ok(json_config) => json_config,
+        Err(e) => {
+            return Ok(AddServerResult {
+                success: false,
+                message: e,
+                server_name: None,
+            })
+        }
+    };
+
     // Build command args
     let mut cmd_args = vec!["add-json", &name, &json_config];
 
@@ -517,47 +753,20 @@ pub async fn mcp_add_from_claude_desktop(
     for (name, server_config) in mcp_servers {
         info!("Importing server: {}", name);
 
-        // Convert Claude Desktop format to add-json format
-        let mut json_config = serde_json::Map::new();
-
-        // All Claude Desktop servers are stdio type
-        json_config.insert(
-            "type".to_string(),
-            serde_json::Value::String("stdio".to_string()),
-        );
-
-        // Add command
-        if let Some(command) = server_config.get("command").and_then(|v| v.as_str()) {
-            json_config.insert(
-                "command".to_string(),
-                serde_json::Value::String(command.to_string()),
-            );
-        } else {
-            failed_count += 1;
-            server_results.push(ImportServerResult {
-                name: name.clone(),
-                success: false,
-                error: Some("Missing command field".to_string()),
-            });
-            continue;
-        }
-
-        // Add args if present
-        if let Some(args) = server_config.get("args").and_then(|v| v.as_array()) {
-            json_config.insert("args".to_string(), args.clone().into());
-        } else {
-            json_config.insert("args".to_string(), serde_json::Value::Array(vec![]));
-        }
-
-        // Add env if present
-        if let Some(env) = server_config.get("env").and_then(|v| v.as_object()) {
-            json_config.insert("env".to_string(), env.clone().into());
-        } else {
-            json_config.insert(
-                "env".to_string(),
-                serde_json::Value::Object(serde_json::Map::new()),
-            );
-        }
+        // Convert Claude Desktop format to add-json format. Desktop entries
+        // without a type are stdio servers; remote ones carry a url and headers.
+        let json_config = match ServerConfig::from_json(name, ConfigScope::User, server_config) {
+            Ok(config) => config.to_json(),
+            Err(e) => {
+                failed_count += 1;
+                server_results.push(ImportServerResult {
+                    name: name.clone(),
+                    success: false,
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
 
         // Convert to JSON string
         let json_str = serde_json::to_string(&json_config)
@@ -610,12 +819,24 @@ pub async fn mcp_add_from_claude_desktop(
     })
 }
 
-/// Starts Claude Code as an MCP server
+/// Name under which `claude mcp serve` is hosted
+const CLAUDE_SERVE_NAME: &str = "claude-code";
+
+/// Starts Claude Code as an MCP server, hosted and supervised like any other
+/// hosted server
 #[tauri::command]
-pub async fn mcp_serve(app: AppHandle) -> Result<String, String> {
+pub async fn mcp_serve(
+    app: AppHandle,
+    registry: State<'_, ProcessRegistryState>,
+) -> Result<String, String> {
     info!("Starting Claude Code as MCP server");
 
-    // Start the server in a separate process
+    if host::get(CLAUDE_SERVE_NAME)
+        .is_some_and(|s| !matches!(s.state, HostedState::Stopped | HostedState::Failed))
+    {
+        return Ok("Claude Code MCP server is already running".to_string());
+    }
+
     let claude_path = match find_claude_binary(&app) {
         Ok(path) => path,
         Err(e) => {
@@ -624,31 +845,335 @@ pub async fn mcp_serve(app: AppHandle) -> Result<String, String> {
         }
     };
 
-    let mut cmd = create_command_with_env(&claude_path);
-    cmd.arg("mcp").arg("serve");
-
-    match cmd.spawn() {
-        Ok(_) => {
-            info!("Successfully started Claude Code MCP server");
+    let spec = HostedServerSpec {
+        name: CLAUDE_SERVE_NAME.to_string(),
+        command: claude_path,
+        args: vec!["mcp".to_string(), "serve".to_string()],
+        env: HashMap::new(),
+        cwd: None,
+        url: None,
+        transport: None,
+        auto_restart: true,
+        health_interval_secs: 30,
+    };
+    match host::start(registry.0.clone(), spec).await {
+        Ok(status) => {
+            info!(
+                "Successfully started Claude Code MCP server (PID: {:?})",
+                status.pid
+            );
             Ok("Claude Code MCP server started".to_string())
         }
         Err(e) => {
             error!("Failed to start MCP server: {}", e);
-            Err(e.to_string())
+            Err(e)
+        }
+    }
+}
+
+/// Start hosting an MCP server process
+#[tauri::command]
+pub async fn mcp_host_start(
+    registry: State<'_, ProcessRegistryState>,
+    spec: HostedServerSpec,
+) -> Result<HostedServerStatus, String> {
+    info!("Starting hosted MCP server: {}", spec.name);
+    host::start(registry.0.clone(), spec).await
+}
+
+/// Stop a hosted MCP server
+#[tauri::command]
+pub async fn mcp_host_stop(
+    registry: State<'_, ProcessRegistryState>,
+    name: String,
+) -> Result<HostedServerStatus, String> {
+    info!("Stopping hosted MCP server: {}", name);
+    host::stop(registry.0.clone(), &name).await
+}
+
+/// Restart a hosted MCP server
+#[tauri::command]
+pub async fn mcp_host_restart(
+    registry: State<'_, ProcessRegistryState>,
+    name: String,
+) -> Result<HostedServerStatus, String> {
+    info!("Restarting hosted MCP server: {}", name);
+    host::restart(registry.0.clone(), &name).await
+}
+
+/// Lists hosted MCP servers with their state and last health check
+#[tauri::command]
+pub async fn mcp_host_list() -> Result<Vec<HostedServerStatus>, String> {
+    Ok(host::list())
+}
+
+/// Captured stderr (and, for HTTP servers, stdout) of a hosted MCP server
+#[tauri::command]
+pub async fn mcp_host_logs(
+    registry: State<'_, ProcessRegistryState>,
+    name: String,
+) -> Result<String, String> {
+    let status = host::get(&name).ok_or_else(|| format!("MCP server '{}' is not hosted", name))?;
+    match status.run_id {
+        Some(run_id) => registry.0.get_live_output(run_id),
+        None => Ok(String::new()),
+    }
+}
+
+/// Connect to a server, run the initialize handshake and list its tools,
+/// resources and prompts. Never fails: problems end up in `status.error`.
+pub async fn probe_server(config: &ServerConfig) -> ServerProbe {
+    let mut probe = ServerProbe {
+        name: config.name.clone(),
+        scope: config.scope.as_str().to_string(),
+        transport: config.transport.as_str().to_string(),
+        status: ServerStatus {
+            last_checked: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            ..Default::default()
+        },
+        instructions: None,
+        tools: Vec::new(),
+        resources: Vec::new(),
+        prompts: Vec::new(),
+    };
+
+    let started = Instant::now();
+    match McpClient::connect(config, PROBE_TIMEOUT).await {
+        Ok(mut client) => {
+            let status = &mut probe.status;
+            status.running = true;
+            status.latency_ms = Some(started.elapsed().as_millis() as u64);
+            let server = client.server();
+            status.server_name = Some(server.server_info.name.clone());
+            status.server_version = Some(server.server_info.version.clone());
+            status.protocol_version = Some(server.protocol_version.clone());
+            probe.instructions = server.instructions.clone();
+
+            let mut errors = Vec::new();
+            match client.list_tools().await {
+                Ok(tools) => probe.tools = tools,
+                Err(e) => errors.push(format!("tools/list: {}", e)),
+            }
+            match client.list_resources().await {
+                Ok(resources) => probe.resources = resources,
+                Err(e) => errors.push(format!("resources/list: {}", e)),
+            }
+            match client.list_prompts().await {
+                Ok(prompts) => probe.prompts = prompts,
+                Err(e) => errors.push(format!("prompts/list: {}", e)),
+            }
+            client.close().await;
+
+            let status = &mut probe.status;
+            status.tool_count = Some(probe.tools.len());
+            status.resource_count = Some(probe.resources.len());
+            status.prompt_count = Some(probe.prompts.len());
+            if !errors.is_empty() {
+                status.error = Some(errors.join("; "));
+            }
+        }
+        Err(e) => {
+            error!("MCP server {} failed its probe: {}", config.name, e);
+            probe.status.error = Some(e.to_string());
+        }
+    }
+
+    if let Ok(mut statuses) = LAST_STATUS.lock() {
+        statuses.insert(config.name.clone(), probe.status.clone());
+    }
+    if let Ok(mut cache) = CATALOG_CACHE.lock() {
+        cache.retain(|_, cached| cached.fetched.elapsed() < CATALOG_TTL);
+        cache.insert(
+            catalog_key(config),
+            CachedProbe {
+                fetched: Instant::now(),
+                probe: probe.clone(),
+            },
+        );
+    }
+    probe
+}
+
+/// A server's catalogue, probing it when the cached one is missing, expired
+/// or was fetched for a different config
+async fn server_catalog(config: &ServerConfig, force_refresh: bool) -> ServerCatalog {
+    let key = catalog_key(config);
+    if !force_refresh {
+        let cached = CATALOG_CACHE.lock().ok().and_then(|cache| {
+            cache
+                .get(&key)
+                .filter(|cached| cached.fetched.elapsed() < CATALOG_TTL)
+                .map(|cached| (cached.probe.clone(), cached.fetched.elapsed()))
+        });
+        if let Some((probe, age)) = cached {
+            return ServerCatalog {
+                probe,
+                from_cache: true,
+                expires_in_secs: CATALOG_TTL.saturating_sub(age).as_secs(),
+            };
+        }
+    }
+    ServerCatalog {
+        probe: probe_server(config).await,
+        from_cache: false,
+        expires_in_secs: CATALOG_TTL.as_secs(),
+    }
+}
+
+/// Resolve a server by name from the config files, falling back to
+/// `claude mcp get` for servers defined somewhere we don't read
+async fn resolve_server(
+    app: &AppHandle,
+    name: &str,
+    project_path: Option<&str>,
+) -> Result<ServerConfig, String> {
+    if let Some(config) = mcp_config::find_server(name, project_path)? {
+        return Ok(config);
+    }
+    let server = mcp_get(app.clone(), name.to_string()).await?;
+    let transport = TransportKind::parse(&server.transport).unwrap_or(TransportKind::Stdio);
+    Ok(ServerConfig {
+        name: server.name,
+        scope: match server.scope.as_str() {
+            "project" => ConfigScope::Project,
+            "user" => ConfigScope::User,
+            _ => ConfigScope::Local,
+        },
+        transport,
+        command: server.command,
+        args: server.args,
+        env: server.env,
+        url: server.url,
+        headers: server.headers,
+        timeout_ms: None,
+    })
+}
+
+/// Probe one server and return everything it offers
+#[tauri::command]
+pub async fn mcp_probe_server(
+    app: AppHandle,
+    name: String,
+    project_path: Option<String>,
+) -> Result<ServerProbe, String> {
+    info!("Probing MCP server: {}", name);
+    let config = resolve_server(&app, &name, project_path.as_deref()).await?;
+    Ok(probe_server(&config).await)
+}
+
+/// Tools (with input schemas), resources and prompts of every configured
+/// server, served from a cache that expires after five minutes
+#[tauri::command]
+pub async fn mcp_get_tool_catalog(
+    project_path: Option<String>,
+    force_refresh: Option<bool>,
+) -> Result<Vec<ServerCatalog>, String> {
+    let servers = mcp_config::load_servers(project_path.as_deref())?;
+    let force_refresh = force_refresh.unwrap_or(false);
+    Ok(futures::future::join_all(
+        servers
+            .iter()
+            .map(|config| server_catalog(config, force_refresh)),
+    )
+    .await)
+}
+
+/// Catalogue of a single server, see `mcp_get_tool_catalog`
+#[tauri::command]
+pub async fn mcp_get_server_catalog(
+    app: AppHandle,
+    name: String,
+    project_path: Option<String>,
+    force_refresh: Option<bool>,
+) -> Result<ServerCatalog, String> {
+    let config = resolve_server(&app, &name, project_path.as_deref()).await?;
+    Ok(server_catalog(&config, force_refresh.unwrap_or(false)).await)
+}
+
+/// Invoke a tool on a server with test arguments and return the raw result
+#[tauri::command]
+pub async fn mcp_invoke_tool(
+    app: AppHandle,
+    name: String,
+    tool: String,
+    arguments: Option<serde_json::Value>,
+    project_path: Option<String>,
+) -> Result<ToolInvocation, String> {
+    info!("Invoking MCP tool {} on server {}", tool, name);
+
+    let arguments = arguments.unwrap_or_else(|| serde_json::json!({}));
+    if !arguments.is_object() {
+        return Err("Tool arguments must be a JSON object".to_string());
+    }
+    let config = resolve_server(&app, &name, project_path.as_deref()).await?;
+
+    let started = Instant::now();
+    let outcome = match McpClient::connect(&config, PROBE_TIMEOUT).await {
+        Ok(mut client) => {
+            let outcome = client.call_tool(&tool, arguments.clone()).await;
+            client.close().await;
+            outcome
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut invocation = ToolInvocation {
+        server: name,
+        tool,
+        arguments,
+        result: None,
+        is_error: false,
+        error: None,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    match outcome {
+        Ok(result) => {
+            invocation.is_error = result.get("isError").and_then(|v| v.as_bool()) == Some(true);
+            invocation.result = Some(result);
+        }
+        Err(e) => {
+            invocation.is_error = true;
+            invocation.error = Some(e.to_string());
         }
     }
+    Ok(invocation)
 }
 
 /// Tests connection to an MCP server
 #[tauri::command]
-pub async fn mcp_test_connection(app: AppHandle, name: String) -> Result<String, String> {
+pub async fn mcp_test_connection(
+    app: AppHandle,
+    name: String,
+    project_path: Option<String>,
+) -> Result<String, String> {
     info!("Testing connection to MCP server: {}", name);
 
-    // For now, we'll use the get command to test if the server exists
-    match execute_claude_mcp_command(&app, vec!["get", &name]) {
-        Ok(_) => Ok(format!("Connection to {} successful", name)),
-        Err(e) => Err(e.to_string()),
+    let config = resolve_server(&app, &name, project_path.as_deref()).await?;
+    let probe = probe_server(&config).await;
+    let status = probe.status;
+    if !status.running {
+        return Err(status
+            .error
+            .unwrap_or_else(|| format!("Could not connect to {}", name)));
+    }
+
+    let mut message = format!(
+        "Connected to {} ({} {}) in {} ms: {} tools, {} resources, {} prompts",
+        name,
+        status.server_name.unwrap_or_default(),
+        status.server_version.unwrap_or_default(),
+        status.latency_ms.unwrap_or_default(),
+        probe.tools.len(),
+        probe.resources.len(),
+        probe.prompts.len()
+    );
+    if let Some(error) = status.error {
+        message.push_str(&format!(" ({})", error));
     }
+    Ok(message)
 }
 
 /// Resets project-scoped server approval choices
@@ -668,14 +1193,20 @@ pub async fn mcp_reset_project_choices(app: AppHandle) -> Result<String, String>
     }
 }
 
-/// Gets the status of MCP servers
+/// Gets the status of MCP servers by probing every configured server
+/// (user servers, plus local and project ones when a project is given)
 #[tauri::command]
-pub async fn mcp_get_server_status() -> Result<HashMap<String, ServerStatus>, String> {
+pub async fn mcp_get_server_status(
+    project_path: Option<String>,
+) -> Result<HashMap<String, ServerStatus>, String> {
     info!("Getting MCP server status");
 
-    // TODO: Implement actual status checking
-    // For now, return empty status
-    Ok(HashMap::new())
+    let servers = mcp_config::load_servers(project_path.as_deref())?;
+    let probes = futures::future::join_all(servers.iter().map(probe_server)).await;
+    Ok(probes
+        .into_iter()
+        .map(|probe| (probe.name, probe.status))
+        .collect())
 }
 
 /// Reads .mcp.json from the current project
@@ -716,6 +1247,13 @@ pub async fn mcp_save_project_config(
 
     let mcp_json_path = PathBuf::from(&project_path).join(".mcp.json");
 
+    for (name, server) in &config.mcp_servers {
+        let value = serde_json::to_value(server)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        ServerConfig::from_json(name, ConfigScope::Project, &value)
+            .map_err(|e| format!("Invalid MCP server '{}': {}", name, e))?;
+    }
+
     let json_content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("Failed to serialize config: {}", e))?;
 
@@ -724,3 +1262,71 @@ pub async fn mcp_save_project_config(
 
     Ok("Project MCP configuration saved".to_string())
 }
+
+/// Lints the MCP configuration visible from a project: every definition
+/// across scopes with where it comes from, plus problems and suggested fixes.
+/// Remote servers are contacted unless `check_urls` is false.
+#[tauri::command]
+pub async fn mcp_lint_config(
+    project_path: Option<String>,
+    check_urls: Option<bool>,
+) -> Result<McpConfigReport, String> {
+    info!("Linting MCP configuration for {:?}", project_path);
+
+    let mut report = lint::lint(project_path.as_deref())?;
+    if check_urls.unwrap_or(true) {
+        lint::check_urls(&mut report, URL_CHECK_TIMEOUT).await;
+    }
+    Ok(report)
+}
+
+/// Applies a fix suggested by `mcp_lint_config` and returns the updated
+/// report (without URL checks)
+#[tauri::command]
+pub async fn mcp_apply_config_fix(
+    project_path: Option<String>,
+    fix: ConfigFix,
+) -> Result<McpConfigReport, String> {
+    info!("Applying MCP configuration fix: {:?}", fix);
+
+    lint::apply_fix(&fix, project_path.as_deref())?;
+    lint::lint(project_path.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catalog_is_cached_per_config_until_refreshed() {
+        let config = ServerConfig::from_json(
+            "catalog-cache-test",
+            ConfigScope::User,
+            &serde_json::json!({"command": "/nonexistent/mcp-catalog-test"}),
+        )
+        .unwrap();
+
+        let fresh = server_catalog(&config, false).await;
+        assert!(!fresh.from_cache);
+        assert!(!fresh.probe.status.running);
+        assert!(fresh.probe.status.error.is_some());
+        assert_eq!(fresh.expires_in_secs, CATALOG_TTL.as_secs());
+        assert_eq!(
+            cached_status("catalog-cache-test").error,
+            fresh.probe.status.error
+        );
+
+        let cached = server_catalog(&config, false).await;
+        assert!(cached.from_cache);
+        assert!(cached.expires_in_secs <= CATALOG_TTL.as_secs());
+        assert_eq!(cached.probe.status.error, fresh.probe.status.error);
+
+        // Editing the server's config or forcing a refresh probes again
+        let edited = ServerConfig {
+            args: vec!["--verbose".to_string()],
+            ..config.clone()
+        };
+        assert!(!server_catalog(&edited, false).await.from_cache);
+        assert!(!server_catalog(&config, true).await.from_cache);
+    }
+}
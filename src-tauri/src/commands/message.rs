@@ -7,6 +7,7 @@ use uuid::Uuid;
 
 use crate::commands::agents::AgentDb;
 use crate::commands::message_middleware::MessageMiddleware;
+use crate::commands::teammate::{start_teammate, TeamContext};
 use crate::process::ProcessRegistryState;
 
 /// Message structure for the database
@@ -24,6 +25,8 @@ pub struct Message {
     pub json_content: Option<String>,   // raw json from Claude
     pub message_type: String, // "user", "thinking", "response"
     pub created_at: String,
+    #[serde(default)]
+    pub cost_usd: Option<f64>, // cost reported by a "result" message
 }
 
 /// Parse @username from content and return the username if found
@@ -98,15 +101,13 @@ fn get_project_path(
 
 /// Start a teammate agent if not already running
 async fn start_teammate_agent_only(
-    app: AppHandle,
-    db: State<'_, AgentDb>,
-    registry: State<'_, ProcessRegistryState>,
+    ctx: &TeamContext,
     project_id: String,
     agent_id: String,
 ) -> Result<String, String> {
     // Get project path, model, and project_agents.id (run_id)
     let (project_path, model, project_agent_id, agent_id_clone, project_id_clone) = {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let conn = ctx.db.lock().map_err(|e| e.to_string())?;
         let project_path = get_project_path(&conn, &project_id)?;
 
         let mut stmt = conn
@@ -130,15 +131,13 @@ async fn start_teammate_agent_only(
     };
 
     // Try to start the teammate agent
-    let result = crate::commands::teammate::start_teammate_agent(
-        app.clone(),
+    let result = start_teammate(
+        ctx,
         project_agent_id.clone(),
         agent_id_clone.clone(),
         project_path.clone(),
         project_id_clone.clone(),
         Some(model.clone()),
-        db.clone(),
-        registry.clone(),
     )
     .await;
 
@@ -166,6 +165,19 @@ pub async fn send_message(
     sender_name: String,
     db: State<'_, AgentDb>,
     registry: State<'_, ProcessRegistryState>,
+) -> Result<Message, String> {
+    let ctx = TeamContext::from_app(&app, &db, &registry)?;
+    send_team_message(&ctx, project_id, content, sender, sender_name).await
+}
+
+/// Deliver a message to its @mentioned agent, or the TeamLead, starting the
+/// agent first if it is not running
+pub async fn send_team_message(
+    ctx: &TeamContext,
+    project_id: String,
+    content: String,
+    sender: String,
+    sender_name: String,
 ) -> Result<Message, String> {
     info!(
         "send_message: project_id={}, sender={}, content={}",
@@ -174,7 +186,7 @@ pub async fn send_message(
 
     // First, determine target agent and try to start it if not running
     let (target_agent_id, project_path) = {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let conn = ctx.db.lock().map_err(|e| e.to_string())?;
 
         // Parse target from content
         let target_username = parse_target_username(&content);
@@ -202,8 +214,8 @@ pub async fn send_message(
 
     // Try to send to running agent, or start new one
     // First check registry for running agent
-    let run_id = match registry
-        .0
+    let run_id = match ctx
+        .registry
         .find_teammate_run_id(&project_path, &target_agent_id)
     {
         Some(rid) => rid,
@@ -212,7 +224,7 @@ pub async fn send_message(
             // Check if there's a running Claude process for this session
             // Get project_agent_id from database
             let project_agent_id = {
-                let conn = db.0.lock().map_err(|e| e.to_string())?;
+                let conn = ctx.db.lock().map_err(|e| e.to_string())?;
                 let mut stmt = conn
                     .prepare("SELECT id FROM project_agents WHERE project_id = ?1 AND agent_id = ?2")
                     .map_err(|e| e.to_string())?;
@@ -221,15 +233,13 @@ pub async fn send_message(
             };
 
             // Check if a process with this session_id is actually running
-            if registry.0.exists(&project_agent_id).unwrap_or(false) {
+            if ctx.registry.exists(&project_agent_id).unwrap_or(false) {
                 // Process is running but not in our registry (app restart), use it
                 project_agent_id
             } else {
                 // No running process, start new one
                 let new_run_id = start_teammate_agent_only(
-                    app.clone(),
-                    db.clone(),
-                    registry.clone(),
+                    ctx,
                     project_id.clone(),
                     target_agent_id.clone(),
                 )
@@ -244,10 +254,9 @@ pub async fn send_message(
     };
 
     // Now use MessageMiddleware to handle the message
-    let middleware = MessageMiddleware::new(db.0.clone(), registry.0.clone());
+    let middleware = MessageMiddleware::new(ctx.db.clone(), ctx.registry.clone());
     middleware
         .handle_incoming(
-            app,
             project_id,
             content,
             sender,
@@ -263,13 +272,21 @@ pub async fn get_messages(
     db: State<'_, AgentDb>,
 ) -> Result<Vec<Message>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_messages(&conn, &project_id)
+}
 
+/// Conversation messages of a project, oldest first (init and result
+/// messages are left out)
+pub fn load_messages(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+) -> Result<Vec<Message>, String> {
     let mut stmt = conn
         .prepare(
             "SELECT m.id, m.project_id, m.sender_id, m.sender_name, m.target_id, m.target_name, m.content, m.message_type, m.created_at,
                     COALESCE(a.icon, NULL) as sender_icon,
                     COALESCE(a.color, NULL) as sender_color,
-                    m.json_content
+                    m.json_content, m.cost_usd
              FROM messages m
              LEFT JOIN agents a ON m.sender_id = a.id
              WHERE m.project_id = ?1
@@ -293,6 +310,7 @@ pub async fn get_messages(
                 json_content: row.get(11)?,  // json_content
                 message_type: row.get(7)?,
                 created_at: row.get(8)?,
+                cost_usd: row.get(12)?,
             })
         })
         .map_err(|e| e.to_string())?
@@ -349,6 +367,7 @@ pub async fn save_message_response(
         json_content: None,
         message_type,
         created_at: chrono::Utc::now().to_rfc3339(),
+        cost_usd: None,
     };
 
     {
@@ -420,6 +439,7 @@ pub fn save_message_response_internal(
         json_content: Some(json_content.to_string()),
         message_type: message_type.to_string(),
         created_at: chrono::Utc::now().to_rfc3339(),
+        cost_usd: None,
     };
 
     {
@@ -2,11 +2,13 @@ use log::info;
 use regex::Regex;
 use rusqlite::{params, Connection};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter};
 use uuid::Uuid;
 
 use crate::commands::agents::AgentDb;
+use crate::commands::budget;
+use crate::commands::usage_window;
 use crate::commands::message::Message;
+use crate::events::EventEmitter;
 use crate::process::ProcessRegistry;
 use crate::process::registry::build_claude_message;
 use crate::process::registry::parse_multimodal_input;
@@ -160,12 +162,18 @@ impl MessageMiddleware {
         .map_err(|e| format!("Agent not found for run_id: {}", e))
     }
 
+    /// Budget pause reason for delivering to an agent, if any
+    fn delivery_block(&self, project_id: &str, agent_id: &str) -> Result<Option<String>, String> {
+        let conn = self.db.lock().map_err(|e| e.to_string())?;
+        budget::delivery_block(&conn, project_id, agent_id)
+    }
+
     /// Save a message to database
     fn save_message(&self, message: &Message) -> Result<(), String> {
         let conn = self.db.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (id, project_id, sender_id, sender_name, target_id, target_name, content, json_content, message_type, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO messages (id, project_id, sender_id, sender_name, target_id, target_name, content, json_content, message_type, created_at, cost_usd)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 message.id,
                 message.project_id,
@@ -177,6 +185,7 @@ impl MessageMiddleware {
                 message.json_content,
                 message.message_type,
                 message.created_at,
+                message.cost_usd,
             ],
         )
         .map_err(|e| e.to_string())?;
@@ -193,7 +202,6 @@ impl MessageMiddleware {
     /// If the agent is not running, caller should start it first.
     pub async fn handle_incoming(
         &self,
-        app: AppHandle,
         project_id: String,
         content: String,
         sender: String,
@@ -259,6 +267,11 @@ impl MessageMiddleware {
                 .query_row(params![&project_id], |row| row.get::<_, String>(0))
                 .map_err(|e| format!("Project not found: {}", e))?;
 
+            // Refuse delivery while a pausing budget covering the target is exceeded
+            if let Some(reason) = budget::delivery_block(&conn, &project_id, &agent_id)? {
+                return Err(reason);
+            }
+
             // Build JSON using build_claude_message (no lock needed)
             let json_content = parse_multimodal_input(&content);
             let json_content = build_claude_message(&json_content).to_string();
@@ -295,6 +308,7 @@ impl MessageMiddleware {
             json_content: Some(json_content),
             message_type: MessageType::User.as_str().to_string(),
             created_at: chrono::Utc::now().to_rfc3339(),
+            cost_usd: None,
         };
 
         self.save_message(&message)?;
@@ -312,7 +326,7 @@ impl MessageMiddleware {
     /// 5. Push to frontend (skip system-init messages)
     pub async fn handle_outgoing(
         &self,
-        app: AppHandle,
+        events: Arc<dyn EventEmitter>,
         run_id: String,
         project_id: String,
         output: String,
@@ -337,6 +351,13 @@ impl MessageMiddleware {
         // Step 1: Parse message type from output
         let (message_type, content) = self.parse_agent_output(&output)?;
 
+        // Result messages carry the cost of the run
+        let cost_usd = if matches!(message_type, MessageType::Result) {
+            Self::parse_result_cost(&output)
+        } else {
+            None
+        };
+
         // Step 2: Get agent info
         let (agent_id, agent_name, agent_icon, agent_color) =
             self.get_agent_info_by_run_id(&project_id, &run_id)?;
@@ -355,11 +376,39 @@ impl MessageMiddleware {
             json_content: Some(output.clone()),
             message_type: message_type.as_str().to_string(),
             created_at: chrono::Utc::now().to_rfc3339(),
+            cost_usd,
         };
 
         // Step 4: Save to database
         self.save_message(&message)?;
 
+        // Result messages report finished usage: evaluate budgets and the usage
+        // window in the background
+        if matches!(message_type, MessageType::Result) {
+            let events = events.clone();
+            let db = self.db.clone();
+            let registry = self.registry.clone();
+            let project_id = project_id.clone();
+            let agent_id = message.sender_id.clone();
+            tokio::spawn(async move {
+                if let Err(e) = budget::evaluate_budgets(
+                    events.clone(),
+                    db.clone(),
+                    registry,
+                    project_id,
+                    agent_id,
+                )
+                .await
+                {
+                    log::warn!("Budget evaluation failed: {}", e);
+                }
+                // The budget pass refreshed the usage index
+                if let Err(e) = usage_window::check_active_window(events.as_ref(), &db) {
+                    log::warn!("Usage window check failed: {}", e);
+                }
+            });
+        }
+
         // Step 5: Check @mention and forward (if response)
         if matches!(message_type, MessageType::Response) {
             self.handle_mention_forward(&run_id, &project_id, &message.content).await?;
@@ -367,7 +416,7 @@ impl MessageMiddleware {
 
         // Step 6: Emit to frontend (skip system-init and result messages - they are saved but not displayed)
         if !should_skip_emit {
-            let _ = app.emit("new-message", &message);
+            events.emit("new-message", &message);
         }
 
         info!(
@@ -380,6 +429,14 @@ impl MessageMiddleware {
         Ok(Some(message))
     }
 
+    /// Extract the USD cost from a stream-json `result` message
+    fn parse_result_cost(output: &str) -> Option<f64> {
+        let json = serde_json::from_str::<serde_json::Value>(output).ok()?;
+        json.get("total_cost_usd")
+            .or_else(|| json.get("cost_usd"))
+            .and_then(|v| v.as_f64())
+    }
+
     /// Parse agent output to determine message type and content
     fn parse_agent_output(&self, output: &str) -> Result<(MessageType, String), String> {
         // Try to parse as JSON
@@ -483,6 +540,14 @@ impl MessageMiddleware {
                         "Detected @run_id: mention in output from {} to {}",
                         source_run_id, target_run_id
                     );
+                    if let Ok((target_agent_id, ..)) =
+                        self.get_agent_info_by_run_id(project_id, &target_run_id)
+                    {
+                        if let Some(reason) = self.delivery_block(project_id, &target_agent_id)? {
+                            info!("Message to {} not forwarded: {}", target_run_id, reason);
+                            return Ok(());
+                        }
+                    }
                     // Forward directly to the target run_id
                     self.registry
                         .send_to_process_async(&target_run_id, &message)
@@ -496,6 +561,11 @@ impl MessageMiddleware {
 
                     // Find the agent by name in the project
                     if let Some((target_agent_id, _)) = self.find_agent_by_name(project_id, &agent_name)? {
+                        if let Some(reason) = self.delivery_block(project_id, &target_agent_id)? {
+                            info!("Message to @{} not forwarded: {}", agent_name, reason);
+                            return Ok(());
+                        }
+
                         // Get project path
                         let project_path = self.get_project_path(project_id)?;
 
@@ -578,6 +648,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_result_cost() {
+        let output = r#"{"type":"result","subtype":"success","total_cost_usd":0.0421,"result":"done"}"#;
+        assert_eq!(MessageMiddleware::parse_result_cost(output), Some(0.0421));
+        assert_eq!(MessageMiddleware::parse_result_cost(r#"{"type":"result"}"#), None);
+    }
+
     #[test]
     fn test_no_mention() {
         let content = "Just a regular message";
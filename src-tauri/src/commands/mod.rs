@@ -1,11 +1,19 @@
+pub mod agent_mcp;
 pub mod agents;
+pub mod budget;
 pub mod claude;
 pub mod mcp;
 pub mod message;
 pub mod message_middleware;
 pub mod name_generator;
+pub mod pricing;
 pub mod proxy;
+pub mod run_metrics;
+pub mod secrets;
 pub mod slash_commands;
 pub mod storage;
 pub mod teammate;
 pub mod usage;
+pub mod usage_index;
+pub mod usage_report;
+pub mod usage_window;
@@ -0,0 +1,580 @@
+use super::agents::AgentDb;
+use super::usage_index;
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// A versioned price for every model whose name matches `model_pattern`.
+///
+/// Prices are USD per million tokens. A row applies to usage recorded on or
+/// after `effective_from` and, when set, strictly before `effective_to`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ModelPrice {
+    pub id: Option<i64>,
+    /// Case-insensitive glob matched against the model name, `*` matches any run of characters
+    pub model_pattern: String,
+    pub display_name: Option<String>,
+    pub input_price: f64,
+    pub output_price: f64,
+    #[serde(default)]
+    pub cache_write_price: f64,
+    #[serde(default)]
+    pub cache_read_price: f64,
+    /// First day (YYYY-MM-DD) this price applies to
+    pub effective_from: String,
+    /// First day (YYYY-MM-DD) this price no longer applies to
+    #[serde(default)]
+    pub effective_to: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Token counts of a single usage entry, grouped by price class
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokenCounts {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+}
+
+/// Result of importing a pricing JSON document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PricingImportResult {
+    pub imported: usize,
+    pub replaced: bool,
+}
+
+/// Prices shipped with the app, inserted when the table is empty.
+/// (pattern, display name, input, output, cache write, cache read, effective from)
+#[rustfmt::skip]
+const DEFAULT_PRICING: &[(&str, &str, f64, f64, f64, f64, &str)] = &[
+    ("*opus-4-5*", "Claude Opus 4.5", 5.0, 25.0, 6.25, 0.50, "2025-11-24"),
+    ("*opus-4*", "Claude Opus 4 / 4.1", 15.0, 75.0, 18.75, 1.50, "2025-05-22"),
+    ("*sonnet-4*", "Claude Sonnet 4 / 4.5", 3.0, 15.0, 3.75, 0.30, "2025-05-22"),
+    ("*haiku-4*", "Claude Haiku 4.5", 1.0, 5.0, 1.25, 0.10, "2025-10-15"),
+    ("*3-7-sonnet*", "Claude Sonnet 3.7", 3.0, 15.0, 3.75, 0.30, "2025-02-24"),
+    ("*3-5-sonnet*", "Claude Sonnet 3.5", 3.0, 15.0, 3.75, 0.30, "2024-06-20"),
+    ("*3-5-haiku*", "Claude Haiku 3.5", 0.80, 4.0, 1.0, 0.08, "2024-10-22"),
+    ("*3-opus*", "Claude Opus 3", 15.0, 75.0, 18.75, 1.50, "2024-03-04"),
+    ("*3-haiku*", "Claude Haiku 3", 0.25, 1.25, 0.30, 0.03, "2024-03-07"),
+];
+
+/// Create the `model_pricing` table and seed it with the default prices
+pub fn init_pricing_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS model_pricing (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model_pattern TEXT NOT NULL,
+            display_name TEXT,
+            input_price REAL NOT NULL,
+            output_price REAL NOT NULL,
+            cache_write_price REAL NOT NULL DEFAULT 0,
+            cache_read_price REAL NOT NULL DEFAULT 0,
+            effective_from TEXT NOT NULL,
+            effective_to TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(model_pattern, effective_from)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS update_model_pricing_timestamp
+         AFTER UPDATE ON model_pricing
+         FOR EACH ROW
+         BEGIN
+             UPDATE model_pricing SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+         END",
+        [],
+    )?;
+
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM model_pricing", [], |row| row.get(0))?;
+    if count == 0 {
+        for (pattern, name, input, output, cache_write, cache_read, from) in DEFAULT_PRICING {
+            conn.execute(
+                "INSERT INTO model_pricing (model_pattern, display_name, input_price, output_price, cache_write_price, cache_read_price, effective_from)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![pattern, name, input, output, cache_write, cache_read, from],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_prices(conn: &Connection) -> SqliteResult<Vec<ModelPrice>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, model_pattern, display_name, input_price, output_price, cache_write_price, cache_read_price, effective_from, effective_to, created_at, updated_at
+         FROM model_pricing ORDER BY model_pattern, effective_from",
+    )?;
+    let prices = stmt
+        .query_map([], |row| {
+            Ok(ModelPrice {
+                id: Some(row.get(0)?),
+                model_pattern: row.get(1)?,
+                display_name: row.get(2)?,
+                input_price: row.get(3)?,
+                output_price: row.get(4)?,
+                cache_write_price: row.get(5)?,
+                cache_read_price: row.get(6)?,
+                effective_from: row.get(7)?,
+                effective_to: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(prices)
+}
+
+fn validate_price(price: &ModelPrice) -> Result<(), String> {
+    if price.model_pattern.trim().is_empty() {
+        return Err("Model pattern must not be empty".to_string());
+    }
+    for (label, value) in [
+        ("input", price.input_price),
+        ("output", price.output_price),
+        ("cache write", price.cache_write_price),
+        ("cache read", price.cache_read_price),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!(
+                "Invalid {} price for '{}': {}",
+                label, price.model_pattern, value
+            ));
+        }
+    }
+    let from = NaiveDate::parse_from_str(&price.effective_from, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid effective_from date: {}", price.effective_from))?;
+    if let Some(to) = &price.effective_to {
+        let to = NaiveDate::parse_from_str(to, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid effective_to date: {}", to))?;
+        if to <= from {
+            return Err(format!(
+                "effective_to must be after effective_from for '{}'",
+                price.model_pattern
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn upsert_price(conn: &Connection, price: &ModelPrice) -> SqliteResult<i64> {
+    match price.id {
+        Some(id) => {
+            conn.execute(
+                "UPDATE model_pricing SET model_pattern = ?1, display_name = ?2, input_price = ?3, output_price = ?4,
+                 cache_write_price = ?5, cache_read_price = ?6, effective_from = ?7, effective_to = ?8
+                 WHERE id = ?9",
+                params![
+                    price.model_pattern,
+                    price.display_name,
+                    price.input_price,
+                    price.output_price,
+                    price.cache_write_price,
+                    price.cache_read_price,
+                    price.effective_from,
+                    price.effective_to,
+                    id
+                ],
+            )?;
+            Ok(id)
+        }
+        None => conn.query_row(
+            "INSERT INTO model_pricing (model_pattern, display_name, input_price, output_price, cache_write_price, cache_read_price, effective_from, effective_to)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(model_pattern, effective_from) DO UPDATE SET
+                display_name = excluded.display_name,
+                input_price = excluded.input_price,
+                output_price = excluded.output_price,
+                cache_write_price = excluded.cache_write_price,
+                cache_read_price = excluded.cache_read_price,
+                effective_to = excluded.effective_to
+             RETURNING id",
+            params![
+                price.model_pattern,
+                price.display_name,
+                price.input_price,
+                price.output_price,
+                price.cache_write_price,
+                price.cache_read_price,
+                price.effective_from,
+                price.effective_to
+            ],
+            |row| row.get(0),
+        ),
+    }
+}
+
+/// Match `model` against a `*` glob, ignoring case
+fn pattern_matches(pattern: &str, model: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let model = model.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == model;
+    }
+
+    let mut rest = model.as_str();
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if !rest.starts_with(first) {
+        return false;
+    }
+    rest = &rest[first.len()..];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// How specific a pattern is; longer literal text wins over broader globs
+fn pattern_specificity(pattern: &str) -> usize {
+    pattern.chars().filter(|c| *c != '*').count()
+}
+
+/// In-memory snapshot of the `model_pricing` table used to cost usage entries
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    prices: Vec<ModelPrice>,
+}
+
+impl PricingTable {
+    pub fn new(prices: Vec<ModelPrice>) -> Self {
+        Self { prices }
+    }
+
+    pub fn load(conn: &Connection) -> SqliteResult<Self> {
+        Ok(Self::new(read_prices(conn)?))
+    }
+
+    /// Load the pricing table from the app database
+    pub fn from_db(db: &AgentDb) -> Result<Self, String> {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        Self::load(&conn).map_err(|e| format!("Failed to load model pricing: {}", e))
+    }
+
+    /// Find the price for `model` effective at `timestamp` (RFC 3339 or YYYY-MM-DD).
+    ///
+    /// The most specific matching pattern wins. Within that pattern the latest
+    /// version whose range covers the date is used; usage older than every
+    /// known version falls back to the earliest one so it is not priced at zero.
+    pub fn price_for(&self, model: &str, timestamp: &str) -> Option<&ModelPrice> {
+        let date = timestamp.get(..10).unwrap_or(timestamp);
+        let best_specificity = self
+            .prices
+            .iter()
+            .filter(|p| pattern_matches(&p.model_pattern, model))
+            .map(|p| pattern_specificity(&p.model_pattern))
+            .max()?;
+        let candidates: Vec<&ModelPrice> = self
+            .prices
+            .iter()
+            .filter(|p| {
+                pattern_matches(&p.model_pattern, model)
+                    && pattern_specificity(&p.model_pattern) == best_specificity
+            })
+            .collect();
+
+        candidates
+            .iter()
+            .filter(|p| {
+                p.effective_from.as_str() <= date
+                    && p.effective_to.as_deref().is_none_or(|to| date < to)
+            })
+            .max_by(|a, b| a.effective_from.cmp(&b.effective_from))
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .filter(|p| p.effective_from.as_str() > date)
+                    .min_by(|a, b| a.effective_from.cmp(&b.effective_from))
+            })
+            .copied()
+    }
+
+    /// Cost in USD of `tokens` for `model` at the price effective at `timestamp`.
+    /// Returns 0 for models without a configured price.
+    pub fn cost(&self, model: &str, timestamp: &str, tokens: &TokenCounts) -> f64 {
+        let Some(price) = self.price_for(model, timestamp) else {
+            return 0.0;
+        };
+
+        (tokens.input_tokens as f64 * price.input_price
+            + tokens.output_tokens as f64 * price.output_price
+            + tokens.cache_creation_tokens as f64 * price.cache_write_price
+            + tokens.cache_read_tokens as f64 * price.cache_read_price)
+            / 1_000_000.0
+    }
+}
+
+/// Recompute indexed usage costs after the price table changed
+fn reprice_usage(conn: &Connection) -> Result<(), String> {
+    let pricing =
+        PricingTable::load(conn).map_err(|e| format!("Failed to load model pricing: {}", e))?;
+    let updated = usage_index::reprice(conn, &pricing)
+        .map_err(|e| format!("Failed to recompute usage costs: {}", e))?;
+    log::info!("Recomputed cost of {} usage entries", updated);
+    Ok(())
+}
+
+/// List all model prices
+#[tauri::command]
+pub async fn list_model_pricing(db: State<'_, AgentDb>) -> Result<Vec<ModelPrice>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    read_prices(&conn).map_err(|e| format!("Failed to list model pricing: {}", e))
+}
+
+/// Create or update a model price. Rows are keyed by id, or by
+/// (model_pattern, effective_from) when no id is given.
+#[tauri::command]
+pub async fn upsert_model_pricing(
+    db: State<'_, AgentDb>,
+    price: ModelPrice,
+) -> Result<ModelPrice, String> {
+    log::info!(
+        "Saving model pricing for {} from {}",
+        price.model_pattern,
+        price.effective_from
+    );
+    validate_price(&price)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let id =
+        upsert_price(&conn, &price).map_err(|e| format!("Failed to save model pricing: {}", e))?;
+    reprice_usage(&conn)?;
+
+    read_prices(&conn)
+        .map_err(|e| format!("Failed to load model pricing: {}", e))?
+        .into_iter()
+        .find(|p| p.id == Some(id))
+        .ok_or_else(|| format!("Model pricing {} not found after save", id))
+}
+
+/// Delete a model price
+#[tauri::command]
+pub async fn delete_model_pricing(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    log::info!("Deleting model pricing {}", id);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM model_pricing WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete model pricing: {}", e))?;
+    reprice_usage(&conn)?;
+    Ok(())
+}
+
+/// Import model prices from a JSON array of `ModelPrice` objects.
+/// With `replace` the existing table is cleared first; otherwise rows are
+/// merged on (model_pattern, effective_from). Ids in the document are ignored.
+#[tauri::command]
+pub async fn import_model_pricing(
+    db: State<'_, AgentDb>,
+    json_data: String,
+    replace: Option<bool>,
+) -> Result<PricingImportResult, String> {
+    let prices: Vec<ModelPrice> =
+        serde_json::from_str(&json_data).map_err(|e| format!("Invalid pricing JSON: {}", e))?;
+    for price in &prices {
+        validate_price(price)?;
+    }
+    let replace = replace.unwrap_or(false);
+    log::info!(
+        "Importing {} model prices (replace: {})",
+        prices.len(),
+        replace
+    );
+
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    if replace {
+        tx.execute("DELETE FROM model_pricing", [])
+            .map_err(|e| format!("Failed to clear model pricing: {}", e))?;
+    }
+    for price in &prices {
+        let price = ModelPrice {
+            id: None,
+            ..price.clone()
+        };
+        upsert_price(&tx, &price)
+            .map_err(|e| format!("Failed to import {}: {}", price.model_pattern, e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit model pricing: {}", e))?;
+    reprice_usage(&conn)?;
+
+    Ok(PricingImportResult {
+        imported: prices.len(),
+        replaced: replace,
+    })
+}
+
+/// Export all model prices as a JSON document accepted by `import_model_pricing`
+#[tauri::command]
+pub async fn export_model_pricing(db: State<'_, AgentDb>) -> Result<String, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let prices = read_prices(&conn).map_err(|e| format!("Failed to list model pricing: {}", e))?;
+    serde_json::to_string_pretty(&prices)
+        .map_err(|e| format!("Failed to serialize model pricing: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_cost(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn price(pattern: &str, from: &str, to: Option<&str>) -> ModelPrice {
+        ModelPrice {
+            id: None,
+            model_pattern: pattern.to_string(),
+            display_name: None,
+            input_price: 1.0,
+            output_price: 1.0,
+            cache_write_price: 0.0,
+            cache_read_price: 0.0,
+            effective_from: from.to_string(),
+            effective_to: to.map(str::to_string),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn seeded() -> PricingTable {
+        let conn = Connection::open_in_memory().unwrap();
+        init_pricing_table(&conn).unwrap();
+        PricingTable::load(&conn).unwrap()
+    }
+
+    #[test]
+    fn test_most_specific_pattern_and_effective_version_win() {
+        let mut table = seeded();
+        let tokens = TokenCounts {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            ..Default::default()
+        };
+
+        assert_cost(
+            table.cost("claude-opus-4-1-20250805", "2025-08-10T10:00:00Z", &tokens),
+            90.0,
+        );
+        assert_cost(
+            table.cost("claude-opus-4-5-20251101", "2025-12-01T10:00:00Z", &tokens),
+            30.0,
+        );
+        assert_cost(
+            table.cost("claude-3-5-haiku-20241022", "2025-01-01T00:00:00Z", &tokens),
+            4.8,
+        );
+        assert_cost(
+            table.cost("gpt-unknown", "2025-01-01T00:00:00Z", &tokens),
+            0.0,
+        );
+
+        // A price change only affects usage on or after its effective date
+        table.prices.push(ModelPrice {
+            id: None,
+            model_pattern: "*sonnet-4*".to_string(),
+            display_name: None,
+            input_price: 2.0,
+            output_price: 10.0,
+            cache_write_price: 0.0,
+            cache_read_price: 0.0,
+            effective_from: "2026-01-01".to_string(),
+            effective_to: None,
+            created_at: None,
+            updated_at: None,
+        });
+        assert_cost(
+            table.cost("claude-sonnet-4-20250514", "2025-12-31T23:59:59Z", &tokens),
+            18.0,
+        );
+        assert_cost(
+            table.cost("claude-sonnet-4-20250514", "2026-01-01T00:00:00Z", &tokens),
+            12.0,
+        );
+        // Usage older than every version falls back to the earliest price
+        assert_cost(
+            table.cost("claude-sonnet-4-20250514", "2025-01-01T00:00:00Z", &tokens),
+            18.0,
+        );
+    }
+
+    #[test]
+    fn test_glob_matching_is_anchored_and_case_insensitive() {
+        assert!(pattern_matches("*sonnet-4*", "Claude-Sonnet-4-20250514"));
+        assert!(pattern_matches("claude-3-opus*", "claude-3-opus-20240229"));
+        assert!(!pattern_matches(
+            "claude-3-opus*",
+            "anthropic/claude-3-opus"
+        ));
+        assert!(!pattern_matches("*3-haiku*", "claude-3-5-haiku-20241022"));
+        assert!(pattern_matches("exact-model", "EXACT-MODEL"));
+    }
+
+    #[test]
+    fn test_empty_table_prices_nothing() {
+        let table = PricingTable::default();
+        let tokens = TokenCounts {
+            input_tokens: 1_000_000,
+            ..Default::default()
+        };
+        assert!(table
+            .price_for("claude-sonnet-4", "2025-01-01T00:00:00Z")
+            .is_none());
+        assert_cost(
+            table.cost("claude-sonnet-4", "2025-01-01T00:00:00Z", &tokens),
+            0.0,
+        );
+    }
+
+    #[test]
+    fn test_effective_to_is_exclusive() {
+        let table = PricingTable::new(vec![
+            price("*sonnet*", "2025-01-01", Some("2025-06-01")),
+            price("*sonnet*", "2025-06-01", None),
+        ]);
+        let picked = table.price_for("claude-sonnet-4", "2025-06-01").unwrap();
+        assert_eq!(picked.effective_from, "2025-06-01");
+        let picked = table
+            .price_for("claude-sonnet-4", "2025-05-31T23:59:59Z")
+            .unwrap();
+        assert_eq!(picked.effective_from, "2025-01-01");
+    }
+
+    #[test]
+    fn test_validate_price_rejects_bad_dates_and_prices() {
+        assert!(validate_price(&price("*sonnet*", "2025-01-01", None)).is_ok());
+
+        let error = validate_price(&price("*sonnet*", "01/01/2025", None)).unwrap_err();
+        assert_eq!(error, "Invalid effective_from date: 01/01/2025");
+        let error =
+            validate_price(&price("*sonnet*", "2025-01-01", Some("2025-13-01"))).unwrap_err();
+        assert_eq!(error, "Invalid effective_to date: 2025-13-01");
+        let error =
+            validate_price(&price("*sonnet*", "2025-01-01", Some("2025-01-01"))).unwrap_err();
+        assert!(error.starts_with("effective_to must be after effective_from"));
+        assert!(validate_price(&price(" ", "2025-01-01", None)).is_err());
+
+        for bad in [-1.0, f64::NAN, f64::INFINITY] {
+            let mut invalid = price("*sonnet*", "2025-01-01", None);
+            invalid.output_price = bad;
+            assert!(validate_price(&invalid).is_err());
+        }
+    }
+}
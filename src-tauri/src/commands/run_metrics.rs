@@ -0,0 +1,697 @@
+//! Per-turn metrics for agent runs.
+//!
+//! A turn is one model response: it starts when the prompt or a tool result
+//! is handed to the model and ends with the last streamed event of that
+//! assistant message. Metrics are collected from the stream-json output while
+//! a run executes and stored in `run_turn_metrics`; runs recorded before that
+//! are backfilled from their session JSONL on first access.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+use tauri::State;
+
+use super::agents::{read_session_jsonl, AgentDb};
+
+/// Metrics of a single turn
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TurnMetrics {
+    pub turn_index: i64,
+    pub message_id: Option<String>,
+    pub model: Option<String>,
+    pub started_at: String,
+    pub ended_at: String,
+    /// From the prompt or tool result to the first streamed event of the response
+    pub time_to_first_token_ms: Option<i64>,
+    pub duration_ms: i64,
+    pub tool_calls: i64,
+    pub tool_errors: i64,
+    pub tool_calls_by_name: BTreeMap<String, i64>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_tokens: i64,
+    pub cache_read_tokens: i64,
+    /// Cache reads over all prompt tokens
+    pub cache_hit_ratio: Option<f64>,
+}
+
+/// Turn metrics aggregated over one run
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunMetricsSummary {
+    pub run_id: String,
+    pub created_at: Option<String>,
+    pub turn_count: i64,
+    pub avg_time_to_first_token_ms: Option<f64>,
+    pub max_time_to_first_token_ms: Option<i64>,
+    pub avg_turn_duration_ms: Option<f64>,
+    pub max_turn_duration_ms: Option<i64>,
+    pub total_tool_calls: i64,
+    pub total_tool_errors: i64,
+    pub tool_calls_per_turn: f64,
+    pub tool_error_rate: Option<f64>,
+    pub tool_calls_by_name: BTreeMap<String, i64>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_hit_ratio: Option<f64>,
+}
+
+/// Run detail: every turn plus the run summary
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunTurnMetrics {
+    pub run_id: String,
+    pub turns: Vec<TurnMetrics>,
+    pub summary: RunMetricsSummary,
+}
+
+fn cache_hit_ratio(input: i64, cache_creation: i64, cache_read: i64) -> Option<f64> {
+    let prompt_tokens = input + cache_creation + cache_read;
+    (prompt_tokens > 0).then(|| cache_read as f64 / prompt_tokens as f64)
+}
+
+struct OpenTurn {
+    metrics: TurnMetrics,
+    started: DateTime<Utc>,
+    last_event: DateTime<Utc>,
+}
+
+/// Builds turn metrics from stream-json (or session JSONL) events.
+/// Events carrying a `timestamp` use it; others use the time they were received.
+pub struct TurnMetricsCollector {
+    turns: Vec<TurnMetrics>,
+    current: Option<OpenTurn>,
+    turn_start: DateTime<Utc>,
+    /// tool_use id -> index of the turn that issued it
+    tool_owners: HashMap<String, usize>,
+}
+
+impl TurnMetricsCollector {
+    /// `started_at` is when the prompt was submitted
+    pub fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            turns: Vec::new(),
+            current: None,
+            turn_start: started_at,
+            tool_owners: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, json: &JsonValue, received_at: DateTime<Utc>) {
+        // Sub-agent traffic belongs to the Task tool call, not to this run's turns
+        if json.get("isSidechain").and_then(|v| v.as_bool()) == Some(true)
+            || json
+                .get("parent_tool_use_id")
+                .is_some_and(|v| v.is_string())
+        {
+            return;
+        }
+        let at = json
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or(received_at);
+
+        match json.get("type").and_then(|t| t.as_str()) {
+            // The CLI is ready once it reports init; startup is not model latency
+            Some("system")
+                if json.get("subtype").and_then(|s| s.as_str()) == Some("init")
+                    && self.turns.is_empty()
+                    && self.current.is_none() =>
+            {
+                self.turn_start = at;
+            }
+            Some("user") => {
+                self.close_turn();
+                self.turn_start = at;
+                self.record_tool_results(json);
+            }
+            Some("assistant") => self.record_assistant(json, at),
+            Some("result") => self.close_turn(),
+            _ => {}
+        }
+    }
+
+    fn record_assistant(&mut self, json: &JsonValue, at: DateTime<Utc>) {
+        let Some(message) = json.get("message") else {
+            return;
+        };
+        let message_id = message
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        // A different message without a user event in between starts a new turn
+        let previous_end = self
+            .current
+            .as_ref()
+            .filter(|c| message_id.is_none() || c.metrics.message_id != message_id)
+            .map(|c| c.last_event);
+        if let Some(previous_end) = previous_end {
+            self.close_turn();
+            self.turn_start = previous_end;
+        }
+        let turn_index = self.turns.len();
+        let turn_start = self.turn_start;
+        let current = self.current.get_or_insert_with(|| OpenTurn {
+            metrics: TurnMetrics {
+                turn_index: turn_index as i64,
+                message_id: message_id.clone(),
+                time_to_first_token_ms: Some((at - turn_start).num_milliseconds().max(0)),
+                ..Default::default()
+            },
+            started: turn_start,
+            last_event: at,
+        });
+        current.last_event = current.last_event.max(at);
+        let metrics = &mut current.metrics;
+
+        if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
+            metrics.model = Some(model.to_string());
+        }
+        // Every streamed event of a message repeats its usage so far
+        if let Some(usage) = message.get("usage") {
+            let get = |key: &str| usage.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+            metrics.input_tokens = metrics.input_tokens.max(get("input_tokens"));
+            metrics.output_tokens = metrics.output_tokens.max(get("output_tokens"));
+            metrics.cache_creation_tokens = metrics
+                .cache_creation_tokens
+                .max(get("cache_creation_input_tokens"));
+            metrics.cache_read_tokens = metrics
+                .cache_read_tokens
+                .max(get("cache_read_input_tokens"));
+        }
+
+        let blocks = message.get("content").and_then(|c| c.as_array());
+        for block in blocks.into_iter().flatten() {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                continue;
+            }
+            let Some(id) = block.get("id").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            if self.tool_owners.contains_key(id) {
+                continue;
+            }
+            self.tool_owners.insert(id.to_string(), turn_index);
+            let name = block
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            metrics.tool_calls += 1;
+            *metrics
+                .tool_calls_by_name
+                .entry(name.to_string())
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// Attribute failed tool results to the turn that issued the call
+    fn record_tool_results(&mut self, json: &JsonValue) {
+        let blocks = json
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array());
+        for block in blocks.into_iter().flatten() {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_result")
+                || block.get("is_error").and_then(|v| v.as_bool()) != Some(true)
+            {
+                continue;
+            }
+            let owner = block
+                .get("tool_use_id")
+                .and_then(|v| v.as_str())
+                .and_then(|id| self.tool_owners.get(id));
+            if let Some(turn) = owner.and_then(|&i| self.turns.get_mut(i)) {
+                turn.tool_errors += 1;
+            }
+        }
+    }
+
+    fn close_turn(&mut self) {
+        let Some(open) = self.current.take() else {
+            return;
+        };
+        let mut metrics = open.metrics;
+        metrics.started_at = open.started.to_rfc3339();
+        metrics.ended_at = open.last_event.to_rfc3339();
+        metrics.duration_ms = (open.last_event - open.started).num_milliseconds().max(0);
+        metrics.cache_hit_ratio = cache_hit_ratio(
+            metrics.input_tokens,
+            metrics.cache_creation_tokens,
+            metrics.cache_read_tokens,
+        );
+        self.turns.push(metrics);
+    }
+
+    pub fn finish(mut self) -> Vec<TurnMetrics> {
+        self.close_turn();
+        self.turns
+    }
+}
+
+/// Turn metrics from a session JSONL file
+pub fn turn_metrics_from_jsonl(jsonl_content: &str) -> Vec<TurnMetrics> {
+    let mut collector: Option<TurnMetricsCollector> = None;
+    for line in jsonl_content.lines() {
+        let Ok(json) = serde_json::from_str::<JsonValue>(line) else {
+            continue;
+        };
+        let Some(at) = json
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc))
+        else {
+            continue;
+        };
+        collector
+            .get_or_insert_with(|| TurnMetricsCollector::new(at))
+            .observe(&json, at);
+    }
+    collector.map(|c| c.finish()).unwrap_or_default()
+}
+
+/// Create the run_turn_metrics table
+pub fn init_run_metrics_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS run_turn_metrics (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            run_id TEXT NOT NULL,
+            turn_index INTEGER NOT NULL,
+            message_id TEXT,
+            model TEXT,
+            started_at TEXT NOT NULL,
+            ended_at TEXT NOT NULL,
+            time_to_first_token_ms INTEGER,
+            duration_ms INTEGER NOT NULL,
+            tool_calls INTEGER NOT NULL DEFAULT 0,
+            tool_errors INTEGER NOT NULL DEFAULT 0,
+            tool_calls_by_name TEXT NOT NULL DEFAULT '{}',
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_hit_ratio REAL,
+            UNIQUE(run_id, turn_index),
+            FOREIGN KEY (run_id) REFERENCES agent_runs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_run_turn_metrics_run ON run_turn_metrics(run_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Replace the stored turns of a run
+pub fn save_turn_metrics(
+    conn: &mut Connection,
+    run_id: &str,
+    turns: &[TurnMetrics],
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM run_turn_metrics WHERE run_id = ?1",
+        params![run_id],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO run_turn_metrics (run_id, turn_index, message_id, model, started_at,
+                ended_at, time_to_first_token_ms, duration_ms, tool_calls, tool_errors,
+                tool_calls_by_name, input_tokens, output_tokens, cache_creation_tokens,
+                cache_read_tokens, cache_hit_ratio)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        )?;
+        for t in turns {
+            let by_name =
+                serde_json::to_string(&t.tool_calls_by_name).unwrap_or_else(|_| "{}".to_string());
+            stmt.execute(params![
+                run_id,
+                t.turn_index,
+                t.message_id,
+                t.model,
+                t.started_at,
+                t.ended_at,
+                t.time_to_first_token_ms,
+                t.duration_ms,
+                t.tool_calls,
+                t.tool_errors,
+                by_name,
+                t.input_tokens,
+                t.output_tokens,
+                t.cache_creation_tokens,
+                t.cache_read_tokens,
+                t.cache_hit_ratio,
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+fn load_turn_metrics(conn: &Connection, run_id: &str) -> SqliteResult<Vec<TurnMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT turn_index, message_id, model, started_at, ended_at, time_to_first_token_ms,
+                duration_ms, tool_calls, tool_errors, tool_calls_by_name, input_tokens,
+                output_tokens, cache_creation_tokens, cache_read_tokens, cache_hit_ratio
+         FROM run_turn_metrics WHERE run_id = ?1 ORDER BY turn_index",
+    )?;
+    let turns = stmt
+        .query_map(params![run_id], |row| {
+            Ok(TurnMetrics {
+                turn_index: row.get(0)?,
+                message_id: row.get(1)?,
+                model: row.get(2)?,
+                started_at: row.get(3)?,
+                ended_at: row.get(4)?,
+                time_to_first_token_ms: row.get(5)?,
+                duration_ms: row.get(6)?,
+                tool_calls: row.get(7)?,
+                tool_errors: row.get(8)?,
+                tool_calls_by_name: serde_json::from_str(&row.get::<_, String>(9)?)
+                    .unwrap_or_default(),
+                input_tokens: row.get(10)?,
+                output_tokens: row.get(11)?,
+                cache_creation_tokens: row.get(12)?,
+                cache_read_tokens: row.get(13)?,
+                cache_hit_ratio: row.get(14)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(turns)
+}
+
+/// Aggregate the turns of one run
+pub fn summarize_turns(
+    run_id: &str,
+    created_at: Option<String>,
+    turns: &[TurnMetrics],
+) -> RunMetricsSummary {
+    let turn_count = turns.len() as i64;
+    let ttfts: Vec<i64> = turns
+        .iter()
+        .filter_map(|t| t.time_to_first_token_ms)
+        .collect();
+    let mean = |values: &[i64]| {
+        (!values.is_empty()).then(|| values.iter().sum::<i64>() as f64 / values.len() as f64)
+    };
+    let durations: Vec<i64> = turns.iter().map(|t| t.duration_ms).collect();
+
+    let mut tool_calls_by_name = BTreeMap::new();
+    for t in turns {
+        for (name, count) in &t.tool_calls_by_name {
+            *tool_calls_by_name.entry(name.clone()).or_insert(0) += count;
+        }
+    }
+    let total_tool_calls: i64 = turns.iter().map(|t| t.tool_calls).sum();
+    let total_tool_errors: i64 = turns.iter().map(|t| t.tool_errors).sum();
+    let input_tokens = turns.iter().map(|t| t.input_tokens).sum();
+    let cache_creation_tokens = turns.iter().map(|t| t.cache_creation_tokens).sum();
+    let cache_read_tokens = turns.iter().map(|t| t.cache_read_tokens).sum();
+
+    RunMetricsSummary {
+        run_id: run_id.to_string(),
+        created_at,
+        turn_count,
+        avg_time_to_first_token_ms: mean(&ttfts),
+        max_time_to_first_token_ms: ttfts.iter().copied().max(),
+        avg_turn_duration_ms: mean(&durations),
+        max_turn_duration_ms: durations.iter().copied().max(),
+        total_tool_calls,
+        total_tool_errors,
+        tool_calls_per_turn: if turn_count > 0 {
+            total_tool_calls as f64 / turn_count as f64
+        } else {
+            0.0
+        },
+        tool_error_rate: (total_tool_calls > 0)
+            .then(|| total_tool_errors as f64 / total_tool_calls as f64),
+        tool_calls_by_name,
+        input_tokens,
+        output_tokens: turns.iter().map(|t| t.output_tokens).sum(),
+        cache_creation_tokens,
+        cache_read_tokens,
+        cache_hit_ratio: cache_hit_ratio(input_tokens, cache_creation_tokens, cache_read_tokens),
+    }
+}
+
+/// Per-turn metrics of an agent run.
+/// Runs without stored metrics are backfilled from their session JSONL.
+#[tauri::command]
+pub async fn get_run_turn_metrics(
+    db: State<'_, AgentDb>,
+    run_id: String,
+) -> Result<RunTurnMetrics, String> {
+    let (turns, created_at, session_id, project_path) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let (created_at, session_id, project_path): (String, String, String) = conn
+            .query_row(
+                "SELECT created_at, session_id, project_path FROM agent_runs WHERE id = ?1",
+                params![run_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| format!("Agent run not found: {}", e))?;
+        let turns = load_turn_metrics(&conn, &run_id)
+            .map_err(|e| format!("Failed to load turn metrics: {}", e))?;
+        (turns, created_at, session_id, project_path)
+    };
+
+    let turns = if turns.is_empty() && !session_id.is_empty() {
+        match read_session_jsonl(&session_id, &project_path).await {
+            Ok(content) => {
+                let turns = turn_metrics_from_jsonl(&content);
+                if !turns.is_empty() {
+                    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+                    save_turn_metrics(&mut conn, &run_id, &turns)
+                        .map_err(|e| format!("Failed to save turn metrics: {}", e))?;
+                }
+                turns
+            }
+            Err(e) => {
+                log::warn!("No turn metrics for run {}: {}", run_id, e);
+                turns
+            }
+        }
+    } else {
+        turns
+    };
+
+    let summary = summarize_turns(&run_id, Some(created_at), &turns);
+    Ok(RunTurnMetrics {
+        run_id,
+        turns,
+        summary,
+    })
+}
+
+/// Run summaries of an agent's most recent runs (default 30), oldest first,
+/// for trend charts. Only runs with recorded turn metrics are included.
+#[tauri::command]
+pub async fn get_agent_run_metric_trends(
+    db: State<'_, AgentDb>,
+    agent_id: String,
+    limit: Option<u32>,
+) -> Result<Vec<RunMetricsSummary>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.id, r.created_at FROM agent_runs r
+             WHERE r.agent_id = ?1
+               AND EXISTS (SELECT 1 FROM run_turn_metrics m WHERE m.run_id = r.id)
+             ORDER BY r.created_at DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let runs = stmt
+        .query_map(params![agent_id, limit.unwrap_or(30)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<SqliteResult<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut trends = Vec::with_capacity(runs.len());
+    for (run_id, created_at) in runs.into_iter().rev() {
+        let turns = load_turn_metrics(&conn, &run_id)
+            .map_err(|e| format!("Failed to load turn metrics: {}", e))?;
+        trends.push(summarize_turns(&run_id, Some(created_at), &turns));
+    }
+    Ok(trends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2025-06-01T{}Z", time))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_turns_split_on_tool_results_and_attribute_errors() {
+        let usage = json!({
+            "input_tokens": 10,
+            "output_tokens": 50,
+            "cache_creation_input_tokens": 0,
+            "cache_read_input_tokens": 90
+        });
+        let events = [
+            (
+                "10:00:00",
+                json!({"type": "system", "subtype": "init", "session_id": "s"}),
+            ),
+            (
+                "10:00:02",
+                json!({"type": "assistant", "message": {"id": "m1", "model": "claude-sonnet-4",
+                "usage": usage, "content": [{"type": "text", "text": "Looking"}]}}),
+            ),
+            (
+                "10:00:03",
+                json!({"type": "assistant", "message": {"id": "m1", "usage": usage,
+                "content": [{"type": "tool_use", "id": "t1", "name": "Bash"},
+                            {"type": "tool_use", "id": "t2", "name": "Read"}]}}),
+            ),
+            (
+                "10:00:05",
+                json!({"type": "user", "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "t1", "is_error": true},
+                {"type": "tool_result", "tool_use_id": "t2"}]}}),
+            ),
+            (
+                "10:00:06",
+                json!({"type": "assistant", "parent_tool_use_id": "t2",
+                "message": {"id": "sub", "content": []}}),
+            ),
+            (
+                "10:00:06",
+                json!({"type": "assistant", "message": {"id": "m2", "usage": usage,
+                "content": [{"type": "tool_use", "id": "t3", "name": "Bash"}]}}),
+            ),
+            ("10:00:09", json!({"type": "result", "subtype": "success"})),
+        ];
+
+        let mut collector = TurnMetricsCollector::new(at("09:59:58"));
+        for (time, event) in &events {
+            collector.observe(event, at(time));
+        }
+        let turns = collector.finish();
+
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].time_to_first_token_ms, Some(2_000));
+        assert_eq!(turns[0].duration_ms, 3_000);
+        assert_eq!(turns[0].tool_calls, 2);
+        assert_eq!(turns[0].tool_errors, 1);
+        assert_eq!(turns[0].input_tokens, 10);
+        assert_eq!(turns[0].cache_hit_ratio, Some(0.9));
+        assert_eq!(turns[1].time_to_first_token_ms, Some(1_000));
+        assert_eq!(turns[1].tool_errors, 0);
+
+        let summary = summarize_turns("run", None, &turns);
+        assert_eq!(summary.total_tool_calls, 3);
+        assert_eq!(summary.tool_calls_by_name.get("Bash"), Some(&2));
+        assert_eq!(summary.tool_calls_per_turn, 1.5);
+        assert_eq!(summary.tool_error_rate, Some(1.0 / 3.0));
+        assert_eq!(summary.output_tokens, 100);
+    }
+
+    #[test]
+    fn test_summary_of_no_turns_is_empty() {
+        let summary = summarize_turns("run", None, &[]);
+        assert_eq!(summary.turn_count, 0);
+        assert_eq!(summary.avg_time_to_first_token_ms, None);
+        assert_eq!(summary.max_turn_duration_ms, None);
+        assert_eq!(summary.tool_calls_per_turn, 0.0);
+        assert_eq!(summary.tool_error_rate, None);
+        assert_eq!(summary.cache_hit_ratio, None);
+        assert!(summary.tool_calls_by_name.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_tool_results_and_empty_usage_are_ignored() {
+        let mut collector = TurnMetricsCollector::new(at("10:00:00"));
+        collector.observe(
+            &json!({"type": "assistant", "message": {"id": "m1",
+                "content": [{"type": "tool_use", "id": "t1", "name": "Bash"},
+                            {"type": "tool_use", "name": "Read"}]}}),
+            at("10:00:01"),
+        );
+        collector.observe(
+            &json!({"type": "user", "message": {"content": [
+                {"type": "tool_result", "tool_use_id": "other", "is_error": true},
+                {"type": "tool_result", "is_error": true}]}}),
+            at("10:00:02"),
+        );
+        // Events without a message or a type change nothing
+        collector.observe(&json!({"type": "assistant"}), at("10:00:03"));
+        collector.observe(&json!({"subtype": "init"}), at("10:00:03"));
+
+        let turns = collector.finish();
+        assert_eq!(turns.len(), 1);
+        // Tool calls without an id cannot be matched to results and are not counted
+        assert_eq!(turns[0].tool_calls, 1);
+        assert_eq!(turns[0].tool_errors, 0);
+        assert_eq!(turns[0].cache_hit_ratio, None);
+        assert_eq!(turns[0].model, None);
+    }
+
+    #[test]
+    fn test_jsonl_skips_malformed_untimed_and_sidechain_lines() {
+        assert!(turn_metrics_from_jsonl("").is_empty());
+        assert!(turn_metrics_from_jsonl("not json\n{}\n").is_empty());
+
+        let jsonl = [
+            "{broken",
+            r#"{"type":"assistant","message":{"id":"early"}}"#,
+            r#"{"type":"user","timestamp":"2025-06-01T10:00:00Z","message":{"content":"hi"}}"#,
+            r#"{"type":"assistant","isSidechain":true,"timestamp":"2025-06-01T10:00:01Z","message":{"id":"side"}}"#,
+            r#"{"type":"assistant","timestamp":"2025-06-01T10:00:04Z","message":{"id":"m1","content":[]}}"#,
+        ]
+        .join("\n");
+        let turns = turn_metrics_from_jsonl(&jsonl);
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].message_id.as_deref(), Some("m1"));
+        assert_eq!(turns[0].time_to_first_token_ms, Some(4_000));
+    }
+
+    #[test]
+    fn test_saving_turns_replaces_the_previous_ones() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_runs (id TEXT PRIMARY KEY);
+             INSERT INTO agent_runs (id) VALUES ('run'), ('other');",
+        )
+        .unwrap();
+        init_run_metrics_table(&conn).unwrap();
+        assert!(load_turn_metrics(&conn, "run").unwrap().is_empty());
+
+        let mut turn = TurnMetrics {
+            started_at: at("10:00:00").to_rfc3339(),
+            ended_at: at("10:00:05").to_rfc3339(),
+            duration_ms: 5_000,
+            tool_calls: 2,
+            cache_hit_ratio: Some(0.5),
+            ..Default::default()
+        };
+        turn.tool_calls_by_name.insert("Bash".to_string(), 2);
+        let second = TurnMetrics {
+            turn_index: 1,
+            ..turn.clone()
+        };
+        save_turn_metrics(&mut conn, "run", &[turn.clone(), second]).unwrap();
+        save_turn_metrics(&mut conn, "other", &[turn]).unwrap();
+        assert_eq!(load_turn_metrics(&conn, "run").unwrap().len(), 2);
+
+        save_turn_metrics(&mut conn, "run", &[]).unwrap();
+        assert!(load_turn_metrics(&conn, "run").unwrap().is_empty());
+        let other = load_turn_metrics(&conn, "other").unwrap();
+        assert_eq!(other.len(), 1);
+        assert_eq!(other[0].tool_calls_by_name.get("Bash"), Some(&2));
+        assert_eq!(other[0].cache_hit_ratio, Some(0.5));
+    }
+}
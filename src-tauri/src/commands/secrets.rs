@@ -0,0 +1,68 @@
+use log::info;
+
+use crate::secrets::{self, SecretStoreStatus};
+
+fn status() -> Result<SecretStoreStatus, String> {
+    secrets::with_store(|store| Ok(store.status())).map_err(|e| e.to_string())
+}
+
+/// Whether the secret store is set up and unlocked
+#[tauri::command]
+pub async fn get_secret_store_status() -> Result<SecretStoreStatus, String> {
+    status()
+}
+
+/// Set up the secret store, keyed by a passphrase or, without one, by the
+/// OS keyring
+#[tauri::command]
+pub async fn init_secret_store(passphrase: Option<String>) -> Result<SecretStoreStatus, String> {
+    info!(
+        "Initializing secret store with {}",
+        if passphrase.is_some() {
+            "a passphrase"
+        } else {
+            "the OS keyring"
+        }
+    );
+    secrets::with_store(|store| store.initialize(passphrase.as_deref()))
+        .map_err(|e| e.to_string())?;
+    status()
+}
+
+/// Unlock the secret store for this session
+#[tauri::command]
+pub async fn unlock_secret_store(passphrase: Option<String>) -> Result<SecretStoreStatus, String> {
+    secrets::with_store(|store| store.unlock(passphrase.as_deref())).map_err(|e| e.to_string())?;
+    status()
+}
+
+/// Forget the store key until the next unlock
+#[tauri::command]
+pub async fn lock_secret_store() -> Result<SecretStoreStatus, String> {
+    secrets::with_store(|store| {
+        store.lock();
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    status()
+}
+
+/// Names of stored secrets (values are never returned)
+#[tauri::command]
+pub async fn list_secrets() -> Result<Vec<String>, String> {
+    secrets::with_store(|store| Ok(store.names())).map_err(|e| e.to_string())
+}
+
+/// Store or replace a secret, referenced from configs as `${secret:NAME}`
+#[tauri::command]
+pub async fn set_secret(name: String, value: String) -> Result<(), String> {
+    info!("Storing secret {}", name);
+    secrets::with_store(|store| store.set(&name, &value)).map_err(|e| e.to_string())
+}
+
+/// Delete a secret, returning whether it existed
+#[tauri::command]
+pub async fn delete_secret(name: String) -> Result<bool, String> {
+    info!("Deleting secret {}", name);
+    secrets::with_store(|store| store.remove(&name)).map_err(|e| e.to_string())
+}
@@ -973,7 +973,11 @@ pub async fn storage_list_projects(
     db: State<'_, AgentDb>,
 ) -> Result<Vec<ProjectWithWorkspace>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_projects(&conn)
+}
 
+/// Projects with their workspaces, newest first
+pub fn load_projects(conn: &Connection) -> Result<Vec<ProjectWithWorkspace>, String> {
     let mut stmt = conn
         .prepare(
             "SELECT p.id, p.name, p.project_code, p.description, w.id, w.path, COALESCE(p.initializing, 0) as initializing
@@ -2,16 +2,63 @@ use log::{error, info, warn};
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::process::Stdio;
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Manager, State};
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use tokio::process::Command;
-
-use crate::claude_binary::find_claude_binary;
-use crate::commands::agents::{get_agent, AgentDb};
+use tokio::sync::mpsc;
+
+use crate::checkpoint::manager::CheckpointManager;
+use crate::checkpoint::state::CheckpointState;
+use crate::checkpoint::storage::{CheckpointStorage, FileCheckpointStorage};
+use crate::checkpoint::{Checkpoint, CheckpointPaths, CheckpointStrategy, CheckpointTrigger};
+use crate::claude_binary::find_claude_binary_in;
+use crate::commands::agent_mcp::{write_agent_mcp_config, AgentMcpServer};
+use crate::commands::agents::{load_agent, AgentDb};
+use crate::commands::budget;
 use crate::commands::message::save_message_response_internal;
-use crate::process::ProcessRegistryState;
+use crate::events::EventEmitter;
+use crate::process::{ProcessRegistry, ProcessRegistryState};
+
+/// What running a team needs from the host: the desktop app or the web
+/// server
+#[derive(Clone)]
+pub struct TeamContext {
+    pub db: Arc<Mutex<rusqlite::Connection>>,
+    pub registry: Arc<ProcessRegistry>,
+    pub events: Arc<dyn EventEmitter>,
+    /// Holds generated MCP configs and the database with the stored
+    /// Claude binary path
+    pub app_data_dir: PathBuf,
+    /// Member checkpoint timelines; without it teammates are not
+    /// checkpointed
+    pub checkpoints: Option<CheckpointState>,
+}
+
+impl TeamContext {
+    /// Context of the desktop app
+    pub fn from_app(
+        app: &AppHandle,
+        db: &AgentDb,
+        registry: &ProcessRegistryState,
+    ) -> Result<Self, String> {
+        let app_data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+        Ok(Self {
+            db: db.0.clone(),
+            registry: registry.0.clone(),
+            events: Arc::new(app.clone()),
+            app_data_dir,
+            checkpoints: app
+                .try_state::<CheckpointState>()
+                .map(|state| state.inner().clone()),
+        })
+    }
+}
 
 /// Member status stored in memory
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +69,7 @@ pub struct MemberStatus {
 
 /// Update member status in memory and emit event
 fn update_member_status(
-    app: &AppHandle,
+    events: &dyn EventEmitter,
     project_id: &str,
     project_agent_id: &str,
     status: &str,
@@ -39,8 +86,8 @@ fn update_member_status(
     );
 
     // Emit event to frontend
-    let _ = app.emit(&format!("member-status-update:{}", project_id), ());
-    let _ = app.emit("member-status-update", project_id);
+    events.emit(&format!("member-status-update:{}", project_id), &());
+    events.emit("member-status-update", project_id);
 }
 
 // Global member status storage (lazily initialized)
@@ -168,9 +215,12 @@ pub struct AgentSettings {
     /// Custom skills to preload
     #[serde(default)]
     pub skills: Option<Vec<String>>,
-    /// MCP servers available to this agent
-    #[serde(default)]
-    pub mcp_servers: Option<serde_json::Value>,
+    /// MCP servers available to this agent; all configured servers when unset
+    #[serde(
+        default,
+        deserialize_with = "crate::commands::agent_mcp::deserialize_assignments"
+    )]
+    pub mcp_servers: Option<Vec<AgentMcpServer>>,
 }
 
 /// Hook configuration parsed from JSON
@@ -281,8 +331,8 @@ fn parse_agent_hooks(hooks_json: &Option<String>) -> AgentHooks {
 }
 
 /// Find and return the claude binary path
-fn find_claude_bin(app_handle: &AppHandle) -> Result<String, String> {
-    find_claude_binary(app_handle)
+fn find_claude_bin(app_data_dir: &Path) -> Result<String, String> {
+    find_claude_binary_in(Some(app_data_dir))
 }
 
 /// Create a command with proper environment variables
@@ -348,6 +398,28 @@ pub async fn start_teammate_agent(
     model: Option<String>,
     db: State<'_, AgentDb>,
     registry: State<'_, ProcessRegistryState>,
+) -> Result<String, String> {
+    let ctx = TeamContext::from_app(&app, &db, &registry)?;
+    start_teammate(
+        &ctx,
+        project_agent_id,
+        agent_id,
+        project_path,
+        project_id,
+        model,
+    )
+    .await
+}
+
+/// Start a teammate's Claude process and route its output through the
+/// message middleware. Returns the session id (the project_agent_id).
+pub async fn start_teammate(
+    ctx: &TeamContext,
+    project_agent_id: String,
+    agent_id: String,
+    project_path: String,
+    project_id: String,
+    model: Option<String>,
 ) -> Result<String, String> {
     info!(
         "Starting teammate agent: {} (project_agent_id: {}) in project: {}",
@@ -355,7 +427,14 @@ pub async fn start_teammate_agent(
     );
 
     // Get agent from database
-    let agent = get_agent(db.clone(), agent_id.clone()).await?;
+    let agent = {
+        let conn = ctx.db.lock().map_err(|e| e.to_string())?;
+        // Refuse to start while a stopping budget covering the member is exceeded
+        if let Some(reason) = budget::start_block(&conn, &project_id, &agent_id)? {
+            return Err(reason);
+        }
+        load_agent(&conn, &agent_id)?
+    };
 
     // Determine model to use
     let execution_model = model.unwrap_or_else(|| agent.model.clone());
@@ -368,7 +447,7 @@ pub async fn start_teammate_agent(
     let session_id = project_agent_id.clone();
 
     // Check if this session is already registered and running
-    if registry.0.exists(&session_id)? {
+    if ctx.registry.exists(&session_id)? {
         return Err(format!(
             "Session ID {} is already in use by a running process. Please stop the existing agent first.",
             session_id
@@ -435,8 +514,9 @@ pub async fn start_teammate_agent(
     }
 
     // Add MCP servers if specified
-    if let Some(mcp_servers) = settings.mcp_servers {
-        agent_config["mcpServers"] = mcp_servers;
+    if let Some(mcp_servers) = &settings.mcp_servers {
+        let names: Vec<&str> = mcp_servers.iter().map(|s| s.name.as_str()).collect();
+        agent_config["mcpServers"] = serde_json::json!(names);
     }
 
     // Add hooks if specified
@@ -500,8 +580,22 @@ pub async fn start_teammate_agent(
         args.push("--dangerously-skip-permissions".to_string());
     }
 
+    // Restrict the agent to its assigned MCP servers, and hand over secrets
+    // the servers reference
+    let mcp_config = write_agent_mcp_config(
+        &ctx.app_data_dir,
+        &session_id,
+        &project_path,
+        settings.mcp_servers.as_deref(),
+    )?;
+    if let Some(mcp_config) = &mcp_config {
+        args.push("--mcp-config".to_string());
+        args.push(mcp_config.path.to_string_lossy().to_string());
+        args.push("--strict-mcp-config".to_string());
+    }
+
     // Find Claude binary
-    let claude_path = match find_claude_bin(&app) {
+    let claude_path = match find_claude_bin(&ctx.app_data_dir) {
         Ok(path) => path,
         Err(e) => {
             error!("Failed to find claude binary: {}", e);
@@ -525,6 +619,10 @@ pub async fn start_teammate_agent(
         cmd.env("CLAUDE_CODE_EXPERIMENTAL_AGENT_TEAMS", "1");
     }
 
+    if let Some(mcp_config) = &mcp_config {
+        cmd.envs(&mcp_config.env);
+    }
+
     // Configure process
     cmd.args(&args)
         .current_dir(&project_path)
@@ -549,8 +647,8 @@ pub async fn start_teammate_agent(
     info!("Teammate agent process spawned with PID: {}", pid);
 
     // Clone variables for async tasks
-    let app_handle = app.clone();
-    let registry_clone = registry.0.clone();
+    let events = ctx.events.clone();
+    let registry_clone = ctx.registry.clone();
     let session_id_clone = session_id.clone();
     let project_path_clone = project_path.clone();
     let agent_id_clone = agent_id.clone();
@@ -558,13 +656,23 @@ pub async fn start_teammate_agent(
     let model_clone = execution_model.clone();
 
     // Clone registry for middleware
-    let registry_arc = registry.0.clone();
+    let registry_arc = ctx.registry.clone();
     let project_path_for_middleware = project_path.clone();
     let agent_id_for_middleware = agent_id.clone();
     let project_id_for_middleware = project_id.clone();
 
     // Clone db for message middleware
-    let db_for_output = db.0.clone();
+    let db_for_output = ctx.db.clone();
+
+    // Feed stdout into the member's checkpoint timeline
+    let checkpoint_tx = start_checkpoint_tracking(
+        ctx.checkpoints.as_ref(),
+        ctx.events.clone(),
+        "teammate-checkpoint",
+        &session_id,
+        &project_path,
+    )
+    .await;
 
     // Spawn stdout reader
     let stdout_task = tokio::spawn(async move {
@@ -577,6 +685,10 @@ pub async fn start_teammate_agent(
             // Store live output
             let _ = registry_clone.append_live_output(session_id_clone.clone(), &line);
 
+            if let Some(tx) = &checkpoint_tx {
+                let _ = tx.send(line.clone());
+            }
+
             // 只使用消息中间件传递消息，不直接emit， 也不需要过滤消息，是否过滤消息由消息中间件判断
             // Emit to frontend (skip init messages - they don't need to be displayed but are saved to DB)
             // let should_emit = if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
@@ -597,7 +709,7 @@ pub async fn start_teammate_agent(
             let project_path_clone = project_path_for_middleware.clone();
             let session_id_for_middleware = session_id_clone.clone();
             let project_id_for_mw = project_id_for_middleware.clone();
-            let app_handle_clone = app_handle.clone();
+            let events_clone = events.clone();
 
             tokio::spawn(async move {
                 // Create middleware
@@ -608,7 +720,7 @@ pub async fn start_teammate_agent(
 
                 // Handle outgoing message
                 match middleware.handle_outgoing(
-                    app_handle_clone,
+                    events_clone,
                     session_id_for_middleware.clone(),
                     project_id_for_mw.clone(),
                     line,
@@ -625,7 +737,7 @@ pub async fn start_teammate_agent(
     });
 
     // Spawn stderr reader
-    let app_handle_stderr = app.clone();
+    let events_stderr = ctx.events.clone();
     let session_id_stderr = session_id.clone();
     let stderr_task = tokio::spawn(async move {
         let stderr_reader = TokioBufReader::new(stderr);
@@ -635,16 +747,15 @@ pub async fn start_teammate_agent(
             error!("Teammate stderr: {}", line);
 
             // Emit error to frontend
-            let _ = app_handle_stderr.emit(&format!("teammate-error:{}", session_id_stderr), &line);
-            let _ = app_handle_stderr.emit("teammate-error", &line);
+            events_stderr.emit(&format!("teammate-error:{}", session_id_stderr), &line);
+            events_stderr.emit("teammate-error", &line);
         }
 
         info!("Teammate stderr reader finished");
     });
 
     // Register in process registry
-    registry
-        .0
+    ctx.registry
         .register_teammate_agent(
             session_id.clone(),
             agent_id_clone.clone(),
@@ -661,14 +772,14 @@ pub async fn start_teammate_agent(
     info!("Registered teammate agent with session_id: {}", session_id);
 
     // Update member status to running and emit event
-    update_member_status(&app, &project_id, &project_agent_id, "running");
+    update_member_status(ctx.events.as_ref(), &project_id, &project_agent_id, "running");
 
     // Clone db for saving messages (Arc<Mutex<Connection>> can be cloned)
-    let db_arc = db.0.clone();
+    let db_arc = ctx.db.clone();
 
     // Spawn monitoring task
-    let app_handle_monitor = app.clone();
-    let registry_monitor = registry.0.clone();
+    let events_monitor = ctx.events.clone();
+    let registry_monitor = ctx.registry.clone();
     let session_id_monitor = session_id.clone();
     let project_id_for_event = project_id.clone();
     tokio::spawn(async move {
@@ -736,11 +847,11 @@ pub async fn start_teammate_agent(
         // }
 
         // Emit completion event (新消息已通过 MessageMiddleware 实时 emit，不需要再刷新)
-        let _ = app_handle_monitor.emit(&format!("teammate-complete:{}", session_id_monitor), true);
-        let _ = app_handle_monitor.emit("teammate-complete", true);
+        events_monitor.emit(&format!("teammate-complete:{}", session_id_monitor), &true);
+        events_monitor.emit("teammate-complete", &true);
 
         // Update member status to completed and emit event
-        update_member_status(&app_handle_monitor, &project_id_for_event, &session_id_monitor, "completed");
+        update_member_status(events_monitor.as_ref(), &project_id_for_event, &session_id_monitor, "completed");
 
         // Unregister from registry
         let _ = registry_monitor.unregister_process(session_id_monitor);
@@ -771,15 +882,25 @@ pub async fn stop_teammate_agent(
     project_id: String,
     app: AppHandle,
     registry: State<'_, ProcessRegistryState>,
+) -> Result<bool, String> {
+    stop_teammate(&registry.0, &app, session_id, project_id).await
+}
+
+/// Kill a teammate's process and mark the member stopped
+pub async fn stop_teammate(
+    registry: &ProcessRegistry,
+    events: &dyn EventEmitter,
+    session_id: String,
+    project_id: String,
 ) -> Result<bool, String> {
     info!("Stopping teammate agent: {}", session_id);
 
-    let result = registry.0.kill_process(session_id.clone()).await?;
+    let result = registry.kill_process(session_id.clone()).await?;
 
     if result {
         info!("Successfully stopped teammate agent: {}", session_id);
         // Update member status to stopped and emit event
-        update_member_status(&app, &project_id, &session_id, "stopped");
+        update_member_status(events, &project_id, &session_id, "stopped");
     } else {
         warn!("Failed to stop teammate agent: {}", session_id);
     }
@@ -800,6 +921,201 @@ pub async fn get_teammate_status(
     }
 }
 
+/// Set up an automatic checkpoint timeline for a Claude run
+///
+/// The timeline lives under `session_id` in the Claude project derived from
+/// the run's working directory, next to the session JSONL. Stdout lines are
+/// only used to decide when to checkpoint: the snapshot takes its messages
+/// from the session JSONL, so a restore writes back a real transcript. A
+/// checkpoint waits for the line that follows the triggering tool call so
+/// the tool's file changes are included. Lines are handled in order on a
+/// dedicated task so snapshots never stall the reader; each checkpoint is
+/// emitted as `{event}:{session_id}`.
+pub(crate) async fn start_checkpoint_tracking(
+    checkpoints: Option<&CheckpointState>,
+    events: Arc<dyn EventEmitter>,
+    event: &str,
+    session_id: &str,
+    project_path: &str,
+) -> Option<mpsc::UnboundedSender<String>> {
+    let state = checkpoints?;
+    let checkpoint_project_id = project_path.replace('/', "-");
+
+    let manager = match state
+        .get_or_create_manager(
+            session_id.to_string(),
+            checkpoint_project_id,
+            PathBuf::from(project_path),
+        )
+        .await
+    {
+        Ok(manager) => manager,
+        Err(e) => {
+            warn!("Checkpoints disabled for session {}: {}", session_id, e);
+            return None;
+        }
+    };
+
+    // New timelines checkpoint automatically; existing ones keep their settings
+    let timeline = manager.get_timeline().await;
+    if timeline.total_checkpoints == 0 && !timeline.auto_checkpoint_enabled {
+        if let Err(e) = manager
+            .update_settings(true, CheckpointStrategy::Smart)
+            .await
+        {
+            warn!(
+                "Failed to enable auto-checkpoint for session {}: {}",
+                session_id, e
+            );
+        }
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let event = format!("{}:{}", event, session_id);
+    let session_id = session_id.to_string();
+
+    tokio::spawn(async move {
+        let mut pending = false;
+        while let Some(line) = rx.recv().await {
+            if pending {
+                pending = false;
+                create_auto_checkpoint(&manager, events.as_ref(), &event).await;
+            }
+            if manager.should_auto_checkpoint(&line).await {
+                pending = true;
+            }
+        }
+        if pending {
+            create_auto_checkpoint(&manager, events.as_ref(), &event).await;
+        }
+
+        info!("Checkpoint tracking finished for session {}", session_id);
+    });
+
+    Some(tx)
+}
+
+/// Snapshot the session JSONL and project files as an automatic checkpoint
+async fn create_auto_checkpoint(
+    manager: &CheckpointManager,
+    events: &dyn EventEmitter,
+    event: &str,
+) {
+    if let Err(e) = manager.sync_session_messages().await {
+        warn!("Failed to read session transcript for checkpoint: {}", e);
+        return;
+    }
+
+    match manager
+        .create_checkpoint_with_trigger(None, None, CheckpointTrigger::Auto)
+        .await
+    {
+        Ok(result) => {
+            info!(
+                "Created auto checkpoint {} ({})",
+                result.checkpoint.id, event
+            );
+            events.emit(event, &result.checkpoint);
+        }
+        Err(e) => {
+            warn!("Failed to create auto checkpoint ({}): {}", event, e);
+        }
+    }
+}
+
+/// Checkpoints recorded for one project member
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberCheckpoints {
+    pub project_agent_id: String,
+    pub agent_id: String,
+    pub agent_name: String,
+    pub session_id: String,
+    /// Claude project the timeline is stored under
+    pub checkpoint_project_id: Option<String>,
+    pub current_checkpoint_id: Option<String>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+/// List checkpoints across all members of a project
+#[tauri::command]
+pub async fn list_project_checkpoints(
+    project_id: String,
+    db: State<'_, AgentDb>,
+) -> Result<Vec<MemberCheckpoints>, String> {
+    info!("Listing checkpoints for project: {}", project_id);
+
+    let members = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT pa.id, pa.agent_id, a.name
+                 FROM project_agents pa
+                 INNER JOIN agents a ON a.id = pa.agent_id
+                 WHERE pa.project_id = ?1
+                 ORDER BY pa.created_at ASC"
+            )
+            .map_err(|e| e.to_string())?;
+
+        let members = stmt
+            .query_map([&project_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        members
+    };
+
+    let claude_dir = crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?;
+
+    tokio::task::spawn_blocking(move || {
+        let storage = FileCheckpointStorage::new(claude_dir.clone());
+        let mut result = Vec::new();
+
+        for (project_agent_id, agent_id, agent_name) in members {
+            // Teammates use their project_agent_id as session_id
+            let session_id = project_agent_id.clone();
+            let mut member = MemberCheckpoints {
+                project_agent_id,
+                agent_id,
+                agent_name,
+                session_id: session_id.clone(),
+                checkpoint_project_id: None,
+                current_checkpoint_id: None,
+                checkpoints: Vec::new(),
+            };
+
+            let checkpoint_project_id = storage
+                .find_session_projects(&session_id)
+                .map_err(|e| format!("Failed to locate checkpoints: {}", e))?
+                .into_iter()
+                .next();
+
+            if let Some(checkpoint_project_id) = checkpoint_project_id {
+                let paths = CheckpointPaths::new(&claude_dir, &checkpoint_project_id, &session_id);
+                let timeline = storage
+                    .load_timeline(&paths.timeline_file)
+                    .map_err(|e| format!("Failed to load timeline: {}", e))?;
+
+                member.checkpoints = timeline.list_checkpoints();
+                member.current_checkpoint_id = timeline.current_checkpoint_id;
+                member.checkpoint_project_id = Some(checkpoint_project_id);
+            }
+
+            result.push(member);
+        }
+
+        Ok(result)
+    })
+    .await
+    .map_err(|e| format!("Checkpoint listing task failed: {}", e))?
+}
+
 /// Status of a project member's process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemberProcessStatus {
@@ -813,10 +1129,19 @@ pub async fn get_project_member_statuses(
     project_id: String,
     db: State<'_, AgentDb>,
     registry: State<'_, ProcessRegistryState>,
+) -> Result<Vec<MemberProcessStatus>, String> {
+    project_member_statuses(&db.0, &registry.0, &project_id)
+}
+
+/// Process status of every member of a project, keyed by project_agent_id
+pub fn project_member_statuses(
+    db: &Mutex<rusqlite::Connection>,
+    registry: &ProcessRegistry,
+    project_id: &str,
 ) -> Result<Vec<MemberProcessStatus>, String> {
     // Get all project members (project_agent_id)
     let member_statuses = {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let conn = db.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn
             .prepare(
                 "SELECT pa.id
@@ -826,7 +1151,7 @@ pub async fn get_project_member_statuses(
             .map_err(|e| e.to_string())?;
 
         let members = stmt
-            .query_map([&project_id], |row| {
+            .query_map([project_id], |row| {
                 Ok(row.get::<_, String>(0)?)
             })
             .map_err(|e| e.to_string())?
@@ -838,7 +1163,7 @@ pub async fn get_project_member_statuses(
 
     // Get statuses from memory
     let mem_statuses = MEMBER_STATUS.lock().unwrap();
-    let project_mem_statuses = mem_statuses.get(&project_id);
+    let project_mem_statuses = mem_statuses.get(project_id);
 
     // Check each member's process status
     let mut result: Vec<MemberProcessStatus> = Vec::new();
@@ -848,7 +1173,7 @@ pub async fn get_project_member_statuses(
             if let Some(mem_status) = project_statuses.get(&project_agent_id) {
                 if mem_status.status == "running" {
                     // Verify process is still actually running
-                    if registry.0.exists(&project_agent_id).unwrap_or(false) {
+                    if registry.exists(&project_agent_id).unwrap_or(false) {
                         result.push(MemberProcessStatus {
                             project_agent_id: project_agent_id.clone(),
                             status: "running".to_string(),
@@ -882,3 +1207,72 @@ pub async fn get_project_member_statuses(
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::BroadcastEmitter;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_checkpoint_tracking_snapshots_session_transcript() {
+        let temp = TempDir::new().unwrap();
+        let claude_dir = temp.path().join("claude");
+        let project = temp.path().join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        let project_path = project.to_string_lossy().to_string();
+
+        let state = CheckpointState::new();
+        state.set_claude_dir(claude_dir.clone()).await;
+        let emitter = Arc::new(BroadcastEmitter::new());
+        let mut rx = emitter.subscribe();
+
+        let tx = start_checkpoint_tracking(
+            Some(&state),
+            emitter.clone(),
+            "teammate-checkpoint",
+            "sid",
+            &project_path,
+        )
+        .await
+        .unwrap();
+
+        let init = r#"{"type":"system","subtype":"init","session_id":"sid"}"#;
+        let user = r#"{"type":"user","message":{"role":"user","content":"write a"}}"#;
+        let tool = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","name":"Write","input":{"file_path":"a.txt"}}]}}"#;
+        let tool_result = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","content":"ok"}]}}"#;
+
+        tx.send(init.to_string()).unwrap();
+        tx.send(tool.to_string()).unwrap();
+
+        // Claude writes the transcript and runs the tool before the next line
+        let session_file = claude_dir
+            .join("projects")
+            .join(project_path.replace('/', "-"))
+            .join("sid.jsonl");
+        std::fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        std::fs::write(&session_file, format!("{}\n{}\n", user, tool)).unwrap();
+        std::fs::write(project.join("a.txt"), "written").unwrap();
+        tx.send(tool_result.to_string()).unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.event, "teammate-checkpoint:sid");
+        let checkpoint: Checkpoint = serde_json::from_value(event.payload).unwrap();
+        assert_eq!(checkpoint.trigger, CheckpointTrigger::Auto);
+
+        let manager = state.get_manager("sid").await.unwrap();
+        let (_, files, messages) = manager
+            .storage
+            .load_checkpoint(manager.project_id(), "sid", &checkpoint.id)
+            .unwrap();
+        // The transcript, not the stream-json output, is what a restore writes back
+        assert_eq!(messages, format!("{}\n{}", user, tool));
+        assert!(files
+            .iter()
+            .any(|f| f.file_path == Path::new("a.txt") && f.content == "written"));
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::{params, Connection, Result as SqliteResult, Row};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::PathBuf;
-use tauri::command;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{command, State};
+
+use super::agents::AgentDb;
+use super::pricing::PricingTable;
+use super::usage_index;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UsageEntry {
@@ -21,422 +24,689 @@ pub struct UsageEntry {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsageStats {
-    total_cost: f64,
-    total_tokens: u64,
-    total_input_tokens: u64,
-    total_output_tokens: u64,
-    total_cache_creation_tokens: u64,
-    total_cache_read_tokens: u64,
-    total_sessions: u64,
-    by_model: Vec<ModelUsage>,
-    by_date: Vec<DailyUsage>,
-    by_project: Vec<ProjectUsage>,
+    pub(crate) total_cost: f64,
+    pub(crate) total_tokens: u64,
+    pub(crate) total_input_tokens: u64,
+    pub(crate) total_output_tokens: u64,
+    pub(crate) total_cache_creation_tokens: u64,
+    pub(crate) total_cache_read_tokens: u64,
+    pub(crate) total_sessions: u64,
+    pub(crate) by_model: Vec<ModelUsage>,
+    pub(crate) by_date: Vec<DailyUsage>,
+    pub(crate) by_project: Vec<ProjectUsage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelUsage {
-    model: String,
+    pub(crate) model: String,
+    pub(crate) total_cost: f64,
+    pub(crate) total_tokens: u64,
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cache_creation_tokens: u64,
+    pub(crate) cache_read_tokens: u64,
+    pub(crate) session_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub(crate) date: String,
+    pub(crate) total_cost: f64,
+    pub(crate) total_tokens: u64,
+    pub(crate) models_used: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectUsage {
+    pub(crate) project_path: String,
+    pub(crate) project_name: String,
+    pub(crate) total_cost: f64,
+    pub(crate) total_tokens: u64,
+    pub(crate) session_count: u64,
+    pub(crate) last_used: String,
+}
+
+/// Usage of one vibe project, summed over its team members' sessions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VibeProjectUsage {
+    project_id: String,
+    project_name: String,
+    working_dir: String,
     total_cost: f64,
     total_tokens: u64,
     input_tokens: u64,
     output_tokens: u64,
     cache_creation_tokens: u64,
     cache_read_tokens: u64,
-    session_count: u64,
+    entry_count: u64,
+    member_count: u64,
+    last_used: String,
 }
 
+/// Usage of one team member (a `project_agents` row) in a vibe project
 #[derive(Debug, Serialize, Deserialize)]
-pub struct DailyUsage {
-    date: String,
+pub struct TeamMemberUsage {
+    pub(crate) project_agent_id: String,
+    pub(crate) project_id: String,
+    pub(crate) project_name: String,
+    pub(crate) agent_id: String,
+    pub(crate) agent_name: String,
+    pub(crate) role_type: String,
+    pub(crate) total_cost: f64,
+    pub(crate) total_tokens: u64,
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cache_creation_tokens: u64,
+    pub(crate) cache_read_tokens: u64,
+    pub(crate) entry_count: u64,
+    pub(crate) last_used: String,
+}
+
+/// Usage summed per team role (`teamlead` or `teammate`)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleUsage {
+    role_type: String,
     total_cost: f64,
     total_tokens: u64,
-    models_used: Vec<String>,
+    entry_count: u64,
+    member_count: u64,
 }
 
+/// Usage attributed to vibe projects, team members and roles.
+/// Teammates run with `--session-id` set to their `project_agents.id`, so
+/// sessions are joined back to the team through that id; everything else
+/// (plain Claude sessions) is reported as unattributed.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ProjectUsage {
-    project_path: String,
-    project_name: String,
-    total_cost: f64,
+pub struct TeamUsage {
+    pub(crate) by_project: Vec<VibeProjectUsage>,
+    pub(crate) by_member: Vec<TeamMemberUsage>,
+    pub(crate) by_role: Vec<RoleUsage>,
+    pub(crate) unattributed_cost: f64,
+    pub(crate) unattributed_tokens: u64,
+}
+
+/// One day of usage for one model, the row format of daily exports
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyModelUsage {
+    date: String,
+    model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_creation_tokens: u64,
+    cache_read_tokens: u64,
     total_tokens: u64,
-    session_count: u64,
-    last_used: String,
+    total_cost: f64,
+    entry_count: u64,
 }
 
-// Claude 4 pricing constants (per million tokens)
-const OPUS_4_INPUT_PRICE: f64 = 15.0;
-const OPUS_4_OUTPUT_PRICE: f64 = 75.0;
-const OPUS_4_CACHE_WRITE_PRICE: f64 = 18.75;
-const OPUS_4_CACHE_READ_PRICE: f64 = 1.50;
+/// File format of a usage export
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageExportFormat {
+    Csv,
+    Jsonl,
+}
 
-const SONNET_4_INPUT_PRICE: f64 = 3.0;
-const SONNET_4_OUTPUT_PRICE: f64 = 15.0;
-const SONNET_4_CACHE_WRITE_PRICE: f64 = 3.75;
-const SONNET_4_CACHE_READ_PRICE: f64 = 0.30;
+/// Rows included in a usage export
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageExportKind {
+    /// Raw `UsageEntry` rows
+    Entries,
+    /// Per-day, per-model aggregates
+    Daily,
+}
 
-#[derive(Debug, Deserialize)]
-struct JsonlEntry {
-    timestamp: String,
-    message: Option<MessageData>,
-    #[serde(rename = "sessionId")]
-    session_id: Option<String>,
-    #[serde(rename = "requestId")]
-    request_id: Option<String>,
-    #[serde(rename = "costUSD")]
-    cost_usd: Option<f64>,
-}
-
-#[derive(Debug, Deserialize)]
-struct MessageData {
-    id: Option<String>,
-    model: Option<String>,
-    usage: Option<UsageData>,
-}
-
-#[derive(Debug, Deserialize)]
-struct UsageData {
-    input_tokens: Option<u64>,
-    output_tokens: Option<u64>,
-    cache_creation_input_tokens: Option<u64>,
-    cache_read_input_tokens: Option<u64>,
-}
-
-fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
-    let input_tokens = usage.input_tokens.unwrap_or(0) as f64;
-    let output_tokens = usage.output_tokens.unwrap_or(0) as f64;
-    let cache_creation_tokens = usage.cache_creation_input_tokens.unwrap_or(0) as f64;
-    let cache_read_tokens = usage.cache_read_input_tokens.unwrap_or(0) as f64;
-
-    // Calculate cost based on model
-    let (input_price, output_price, cache_write_price, cache_read_price) =
-        if model.contains("opus-4") || model.contains("claude-opus-4") {
-            (
-                OPUS_4_INPUT_PRICE,
-                OPUS_4_OUTPUT_PRICE,
-                OPUS_4_CACHE_WRITE_PRICE,
-                OPUS_4_CACHE_READ_PRICE,
-            )
-        } else if model.contains("sonnet-4") || model.contains("claude-sonnet-4") {
-            (
-                SONNET_4_INPUT_PRICE,
-                SONNET_4_OUTPUT_PRICE,
-                SONNET_4_CACHE_WRITE_PRICE,
-                SONNET_4_CACHE_READ_PRICE,
-            )
-        } else {
-            // Return 0 for unknown models to avoid incorrect cost estimations.
-            (0.0, 0.0, 0.0, 0.0)
-        };
-
-    // Calculate cost (prices are per million tokens)
-    let cost = (input_tokens * input_price / 1_000_000.0)
-        + (output_tokens * output_price / 1_000_000.0)
-        + (cache_creation_tokens * cache_write_price / 1_000_000.0)
-        + (cache_read_tokens * cache_read_price / 1_000_000.0);
-
-    cost
-}
-
-fn parse_jsonl_file(
-    path: &PathBuf,
-    encoded_project_name: &str,
-    processed_hashes: &mut HashSet<String>,
-) -> Vec<UsageEntry> {
-    let mut entries = Vec::new();
-    let mut actual_project_path: Option<String> = None;
-
-    if let Ok(content) = fs::read_to_string(path) {
-        // Extract session ID from the file path
-        let session_id = path
-            .parent()
-            .and_then(|p| p.file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-
-        for line in content.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
+pub(crate) const TOTAL_TOKENS_SQL: &str =
+    "input_tokens + output_tokens + cache_creation_tokens + cache_read_tokens";
 
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                // Extract the actual project path from cwd if we haven't already
-                if actual_project_path.is_none() {
-                    if let Some(cwd) = json_value.get("cwd").and_then(|v| v.as_str()) {
-                        actual_project_path = Some(cwd.to_string());
-                    }
-                }
+/// Filters on `usage_date`; either bound may be NULL
+pub(crate) const DATE_RANGE_SQL: &str =
+    "(?1 IS NULL OR usage_date >= ?1) AND (?2 IS NULL OR usage_date <= ?2)";
 
-                // Try to parse as JsonlEntry for usage data
-                if let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) {
-                    if let Some(message) = &entry.message {
-                        // Deduplication based on message ID and request ID
-                        if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
-                            let unique_hash = format!("{}:{}", msg_id, req_id);
-                            if processed_hashes.contains(&unique_hash) {
-                                continue; // Skip duplicate entry
-                            }
-                            processed_hashes.insert(unique_hash);
-                        }
-
-                        if let Some(usage) = &message.usage {
-                            // Skip entries without meaningful token usage
-                            if usage.input_tokens.unwrap_or(0) == 0
-                                && usage.output_tokens.unwrap_or(0) == 0
-                                && usage.cache_creation_input_tokens.unwrap_or(0) == 0
-                                && usage.cache_read_input_tokens.unwrap_or(0) == 0
-                            {
-                                continue;
-                            }
-
-                            let cost = entry.cost_usd.unwrap_or_else(|| {
-                                if let Some(model_str) = &message.model {
-                                    calculate_cost(model_str, usage)
-                                } else {
-                                    0.0
-                                }
-                            });
-
-                            // Use actual project path if found, otherwise use encoded name
-                            let project_path = actual_project_path
-                                .clone()
-                                .unwrap_or_else(|| encoded_project_name.to_string());
-
-                            entries.push(UsageEntry {
-                                timestamp: entry.timestamp,
-                                model: message
-                                    .model
-                                    .clone()
-                                    .unwrap_or_else(|| "unknown".to_string()),
-                                input_tokens: usage.input_tokens.unwrap_or(0),
-                                output_tokens: usage.output_tokens.unwrap_or(0),
-                                cache_creation_tokens: usage
-                                    .cache_creation_input_tokens
-                                    .unwrap_or(0),
-                                cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
-                                cost,
-                                session_id: entry.session_id.unwrap_or_else(|| session_id.clone()),
-                                project_path,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-    }
+fn claude_path() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir()
+        .ok_or("Failed to get home directory")?
+        .join(".claude"))
+}
 
-    entries
-}
-
-fn get_earliest_timestamp(path: &PathBuf) -> Option<String> {
-    if let Ok(content) = fs::read_to_string(path) {
-        let mut earliest_timestamp: Option<String> = None;
-        for line in content.lines() {
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                if let Some(timestamp_str) = json_value.get("timestamp").and_then(|v| v.as_str()) {
-                    if let Some(current_earliest) = &earliest_timestamp {
-                        if timestamp_str < current_earliest.as_str() {
-                            earliest_timestamp = Some(timestamp_str.to_string());
-                        }
-                    } else {
-                        earliest_timestamp = Some(timestamp_str.to_string());
-                    }
-                }
-            }
+/// Ingest any JSONL lines appended since the last query
+pub(crate) fn refresh_usage_index(db: &AgentDb) -> Result<(), String> {
+    refresh_usage_index_in(db, &claude_path()?)
+}
+
+/// `refresh_usage_index` from the sessions under `claude_dir` (web mode
+/// resolves it once at startup)
+pub(crate) fn refresh_usage_index_in(db: &AgentDb, claude_dir: &Path) -> Result<(), String> {
+    let pricing = PricingTable::from_db(db)?;
+    usage_index::refresh(db, claude_dir, &pricing)?;
+    Ok(())
+}
+
+pub(crate) fn get_u64(row: &Row, idx: usize) -> SqliteResult<u64> {
+    Ok(row.get::<_, Option<i64>>(idx)?.unwrap_or(0) as u64)
+}
+
+pub(crate) fn project_name(project_path: &str) -> String {
+    project_path
+        .split('/')
+        .next_back()
+        .unwrap_or(project_path)
+        .to_string()
+}
+
+/// Parse an optional YYYY-MM-DD bound of a date range
+pub(crate) fn parse_date_bound(value: Option<String>, label: &str) -> Result<Option<String>, String> {
+    value
+        .map(|v| {
+            NaiveDate::parse_from_str(&v, "%Y-%m-%d")
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .map_err(|e| format!("Invalid {} date: {}", label, e))
+        })
+        .transpose()
+}
+
+/// Attribute indexed usage to vibe projects, team members and roles
+pub(crate) fn query_team_usage(
+    conn: &Connection,
+    project_id: Option<&str>,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> SqliteResult<TeamUsage> {
+    let by_member = conn
+        .prepare(&format!(
+            "SELECT pa.id, pa.project_id, p.name, a.id, a.name, COALESCE(a.role_type, 'teammate'),
+                    SUM(u.cost), SUM({}), SUM(u.input_tokens), SUM(u.output_tokens),
+                    SUM(u.cache_creation_tokens), SUM(u.cache_read_tokens), COUNT(*), MAX(u.timestamp)
+             FROM usage_entries u
+             INNER JOIN project_agents pa ON pa.id = u.session_id
+             INNER JOIN projects p ON p.id = pa.project_id
+             INNER JOIN agents a ON a.id = pa.agent_id
+             WHERE {} AND (?3 IS NULL OR pa.project_id = ?3)
+             GROUP BY pa.id ORDER BY SUM(u.cost) DESC",
+            TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+        ))?
+        .query_map(params![start, end, project_id], |row| {
+            Ok(TeamMemberUsage {
+                project_agent_id: row.get(0)?,
+                project_id: row.get(1)?,
+                project_name: row.get(2)?,
+                agent_id: row.get(3)?,
+                agent_name: row.get(4)?,
+                role_type: row.get(5)?,
+                total_cost: row.get(6)?,
+                total_tokens: get_u64(row, 7)?,
+                input_tokens: get_u64(row, 8)?,
+                output_tokens: get_u64(row, 9)?,
+                cache_creation_tokens: get_u64(row, 10)?,
+                cache_read_tokens: get_u64(row, 11)?,
+                entry_count: get_u64(row, 12)?,
+                last_used: row.get(13)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let working_dirs: HashMap<String, String> = conn
+        .prepare("SELECT id, COALESCE(working_dir, '') FROM projects")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<SqliteResult<_>>()?;
+
+    let mut project_stats: HashMap<String, VibeProjectUsage> = HashMap::new();
+    let mut role_stats: HashMap<String, RoleUsage> = HashMap::new();
+    for member in &by_member {
+        let project = project_stats
+            .entry(member.project_id.clone())
+            .or_insert_with(|| VibeProjectUsage {
+                project_id: member.project_id.clone(),
+                project_name: member.project_name.clone(),
+                working_dir: working_dirs
+                    .get(&member.project_id)
+                    .cloned()
+                    .unwrap_or_default(),
+                total_cost: 0.0,
+                total_tokens: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_tokens: 0,
+                cache_read_tokens: 0,
+                entry_count: 0,
+                member_count: 0,
+                last_used: String::new(),
+            });
+        project.total_cost += member.total_cost;
+        project.total_tokens += member.total_tokens;
+        project.input_tokens += member.input_tokens;
+        project.output_tokens += member.output_tokens;
+        project.cache_creation_tokens += member.cache_creation_tokens;
+        project.cache_read_tokens += member.cache_read_tokens;
+        project.entry_count += member.entry_count;
+        project.member_count += 1;
+        if member.last_used > project.last_used {
+            project.last_used = member.last_used.clone();
         }
-        return earliest_timestamp;
+
+        let role = role_stats
+            .entry(member.role_type.clone())
+            .or_insert_with(|| RoleUsage {
+                role_type: member.role_type.clone(),
+                total_cost: 0.0,
+                total_tokens: 0,
+                entry_count: 0,
+                member_count: 0,
+            });
+        role.total_cost += member.total_cost;
+        role.total_tokens += member.total_tokens;
+        role.entry_count += member.entry_count;
+        role.member_count += 1;
     }
-    None
+
+    let mut by_project: Vec<VibeProjectUsage> = project_stats.into_values().collect();
+    by_project.sort_by(|a, b| b.total_cost.total_cmp(&a.total_cost));
+
+    let mut by_role: Vec<RoleUsage> = role_stats.into_values().collect();
+    by_role.sort_by(|a, b| b.total_cost.total_cmp(&a.total_cost));
+
+    // Only meaningful across all projects
+    let (unattributed_cost, unattributed_tokens) = if project_id.is_none() {
+        conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(cost), 0), SUM({})
+                 FROM usage_entries
+                 WHERE {} AND session_id NOT IN (SELECT id FROM project_agents)",
+                TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+            ),
+            params![start, end],
+            |row| Ok((row.get::<_, f64>(0)?, get_u64(row, 1)?)),
+        )?
+    } else {
+        (0.0, 0)
+    };
+
+    Ok(TeamUsage {
+        by_project,
+        by_member,
+        by_role,
+        unattributed_cost,
+        unattributed_tokens,
+    })
 }
 
-fn get_all_usage_entries(claude_path: &PathBuf) -> Vec<UsageEntry> {
-    let mut all_entries = Vec::new();
-    let mut processed_hashes = HashSet::new();
-    let projects_dir = claude_path.join("projects");
+/// Indexed usage entries in timestamp order, filtered by an inclusive date
+/// range, an exact project path and/or a timestamp prefix
+fn query_usage_entries(
+    conn: &Connection,
+    start: Option<&str>,
+    end: Option<&str>,
+    project_path: Option<&str>,
+    timestamp_prefix: Option<&str>,
+) -> SqliteResult<Vec<UsageEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT timestamp, model, input_tokens, output_tokens, cache_creation_tokens,
+                cache_read_tokens, cost, session_id, project_path
+         FROM usage_entries
+         WHERE {}
+           AND (?3 IS NULL OR project_path = ?3)
+           AND (?4 IS NULL OR substr(timestamp, 1, length(?4)) = ?4)
+         ORDER BY timestamp, id",
+        DATE_RANGE_SQL
+    ))?;
+
+    let entries = stmt
+        .query_map(params![start, end, project_path, timestamp_prefix], |row| {
+            Ok(UsageEntry {
+                timestamp: row.get(0)?,
+                model: row.get(1)?,
+                input_tokens: get_u64(row, 2)?,
+                output_tokens: get_u64(row, 3)?,
+                cache_creation_tokens: get_u64(row, 4)?,
+                cache_read_tokens: get_u64(row, 5)?,
+                cost: row.get(6)?,
+                session_id: row.get(7)?,
+                project_path: row.get(8)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(entries)
+}
 
-    let mut files_to_process: Vec<(PathBuf, String)> = Vec::new();
+/// Per-day, per-model aggregates in date order
+fn query_daily_model_usage(
+    conn: &Connection,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> SqliteResult<Vec<DailyModelUsage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT usage_date, model, SUM(input_tokens), SUM(output_tokens),
+                SUM(cache_creation_tokens), SUM(cache_read_tokens), SUM({}), SUM(cost), COUNT(*)
+         FROM usage_entries WHERE {}
+         GROUP BY usage_date, model ORDER BY usage_date, model",
+        TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+    ))?;
+
+    let rows = stmt
+        .query_map(params![start, end], |row| {
+            Ok(DailyModelUsage {
+                date: row.get(0)?,
+                model: row.get(1)?,
+                input_tokens: get_u64(row, 2)?,
+                output_tokens: get_u64(row, 3)?,
+                cache_creation_tokens: get_u64(row, 4)?,
+                cache_read_tokens: get_u64(row, 5)?,
+                total_tokens: get_u64(row, 6)?,
+                total_cost: row.get(7)?,
+                entry_count: get_u64(row, 8)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(rows)
+}
 
-    if let Ok(projects) = fs::read_dir(&projects_dir) {
-        for project in projects.flatten() {
-            if project.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                let project_name = project.file_name().to_string_lossy().to_string();
-                let project_path = project.path();
+/// Quote a CSV field when it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
-                walkdir::WalkDir::new(&project_path)
-                    .into_iter()
-                    .filter_map(Result::ok)
-                    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
-                    .for_each(|entry| {
-                        files_to_process.push((entry.path().to_path_buf(), project_name.clone()));
-                    });
-            }
-        }
+fn to_jsonl<T: Serialize>(rows: &[T]) -> Result<String, String> {
+    let mut out = String::new();
+    for row in rows {
+        out.push_str(
+            &serde_json::to_string(row).map_err(|e| format!("Failed to serialize row: {}", e))?,
+        );
+        out.push('\n');
     }
+    Ok(out)
+}
 
-    // Sort files by their earliest timestamp to ensure chronological processing
-    // and deterministic deduplication.
-    files_to_process.sort_by_cached_key(|(path, _)| get_earliest_timestamp(path));
+fn entries_to_csv(entries: &[UsageEntry]) -> String {
+    let mut out = String::from(
+        "timestamp,model,input_tokens,output_tokens,cache_creation_tokens,cache_read_tokens,cost_usd,session_id,project_path\n",
+    );
+    for e in entries {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            csv_field(&e.timestamp),
+            csv_field(&e.model),
+            e.input_tokens,
+            e.output_tokens,
+            e.cache_creation_tokens,
+            e.cache_read_tokens,
+            e.cost,
+            csv_field(&e.session_id),
+            csv_field(&e.project_path)
+        ));
+    }
+    out
+}
 
-    for (path, project_name) in files_to_process {
-        let entries = parse_jsonl_file(&path, &project_name, &mut processed_hashes);
-        all_entries.extend(entries);
+fn daily_to_csv(rows: &[DailyModelUsage]) -> String {
+    let mut out = String::from(
+        "date,model,input_tokens,output_tokens,cache_creation_tokens,cache_read_tokens,total_tokens,cost_usd,entries\n",
+    );
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            csv_field(&r.date),
+            csv_field(&r.model),
+            r.input_tokens,
+            r.output_tokens,
+            r.cache_creation_tokens,
+            r.cache_read_tokens,
+            r.total_tokens,
+            r.total_cost,
+            r.entry_count
+        ));
     }
+    out
+}
 
-    // Sort by timestamp
-    all_entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+/// Render an export of indexed usage between two inclusive dates
+fn render_usage_export(
+    conn: &Connection,
+    format: UsageExportFormat,
+    kind: UsageExportKind,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> Result<String, String> {
+    match kind {
+        UsageExportKind::Entries => {
+            let entries = query_usage_entries(conn, start, end, None, None)
+                .map_err(|e| format!("Failed to query usage entries: {}", e))?;
+            match format {
+                UsageExportFormat::Csv => Ok(entries_to_csv(&entries)),
+                UsageExportFormat::Jsonl => to_jsonl(&entries),
+            }
+        }
+        UsageExportKind::Daily => {
+            let rows = query_daily_model_usage(conn, start, end)
+                .map_err(|e| format!("Failed to query daily usage: {}", e))?;
+            match format {
+                UsageExportFormat::Csv => Ok(daily_to_csv(&rows)),
+                UsageExportFormat::Jsonl => to_jsonl(&rows),
+            }
+        }
+    }
+}
 
-    all_entries
+/// Escape a Prometheus label value
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
 
-#[command]
-pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let all_entries = get_all_usage_entries(&claude_path);
-
-    if all_entries.is_empty() {
-        return Ok(UsageStats {
-            total_cost: 0.0,
-            total_tokens: 0,
-            total_input_tokens: 0,
-            total_output_tokens: 0,
-            total_cache_creation_tokens: 0,
-            total_cache_read_tokens: 0,
-            total_sessions: 0,
-            by_model: vec![],
-            by_date: vec![],
-            by_project: vec![],
-        });
+/// Render cumulative token and cost counters in the Prometheus text format,
+/// labelled by model, project and agent. Team sessions are labelled with the
+/// vibe project and agent names; other sessions with their project path and
+/// an empty agent.
+pub(crate) fn render_prometheus_metrics(conn: &Connection) -> Result<String, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT u.model, COALESCE(p.name, u.project_path), COALESCE(a.name, ''),
+                    SUM(u.input_tokens), SUM(u.output_tokens), SUM(u.cache_creation_tokens),
+                    SUM(u.cache_read_tokens), SUM(u.cost), COUNT(*)
+             FROM usage_entries u
+             LEFT JOIN project_agents pa ON pa.id = u.session_id
+             LEFT JOIN projects p ON p.id = pa.project_id
+             LEFT JOIN agents a ON a.id = pa.agent_id
+             GROUP BY 1, 2, 3 ORDER BY 1, 2, 3",
+        )
+        .map_err(|e| e.to_string())?;
+
+    struct MetricRow {
+        model: String,
+        project: String,
+        agent: String,
+        tokens: [u64; 4],
+        cost: f64,
+        requests: u64,
     }
 
-    // Filter by days if specified
-    let filtered_entries = if let Some(days) = days {
-        let cutoff = Local::now().naive_local().date() - chrono::Duration::days(days as i64);
-        all_entries
-            .into_iter()
-            .filter(|e| {
-                if let Ok(dt) = DateTime::parse_from_rfc3339(&e.timestamp) {
-                    dt.naive_local().date() >= cutoff
-                } else {
-                    false
-                }
+    let rows: Vec<MetricRow> = stmt
+        .query_map([], |row| {
+            Ok(MetricRow {
+                model: row.get(0)?,
+                project: row.get(1)?,
+                agent: row.get(2)?,
+                tokens: [
+                    get_u64(row, 3)?,
+                    get_u64(row, 4)?,
+                    get_u64(row, 5)?,
+                    get_u64(row, 6)?,
+                ],
+                cost: row.get(7)?,
+                requests: get_u64(row, 8)?,
             })
-            .collect()
-    } else {
-        all_entries
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<SqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Failed to query usage metrics: {}", e))?;
+
+    let labels = |row: &MetricRow| {
+        format!(
+            "model=\"{}\",project=\"{}\",agent=\"{}\"",
+            prometheus_label(&row.model),
+            prometheus_label(&row.project),
+            prometheus_label(&row.agent)
+        )
     };
-
-    // Calculate aggregated stats
-    let mut total_cost = 0.0;
-    let mut total_input_tokens = 0u64;
-    let mut total_output_tokens = 0u64;
-    let mut total_cache_creation_tokens = 0u64;
-    let mut total_cache_read_tokens = 0u64;
-
-    let mut model_stats: HashMap<String, ModelUsage> = HashMap::new();
-    let mut daily_stats: HashMap<String, DailyUsage> = HashMap::new();
-    let mut project_stats: HashMap<String, ProjectUsage> = HashMap::new();
-
-    for entry in &filtered_entries {
-        // Update totals
-        total_cost += entry.cost;
-        total_input_tokens += entry.input_tokens;
-        total_output_tokens += entry.output_tokens;
-        total_cache_creation_tokens += entry.cache_creation_tokens;
-        total_cache_read_tokens += entry.cache_read_tokens;
-
-        // Update model stats
-        let model_stat = model_stats
-            .entry(entry.model.clone())
-            .or_insert(ModelUsage {
-                model: entry.model.clone(),
-                total_cost: 0.0,
-                total_tokens: 0,
-                input_tokens: 0,
-                output_tokens: 0,
-                cache_creation_tokens: 0,
-                cache_read_tokens: 0,
-                session_count: 0,
-            });
-        model_stat.total_cost += entry.cost;
-        model_stat.input_tokens += entry.input_tokens;
-        model_stat.output_tokens += entry.output_tokens;
-        model_stat.cache_creation_tokens += entry.cache_creation_tokens;
-        model_stat.cache_read_tokens += entry.cache_read_tokens;
-        model_stat.total_tokens = model_stat.input_tokens + model_stat.output_tokens;
-        model_stat.session_count += 1;
-
-        // Update daily stats
-        let date = entry
-            .timestamp
-            .split('T')
-            .next()
-            .unwrap_or(&entry.timestamp)
-            .to_string();
-        let daily_stat = daily_stats.entry(date.clone()).or_insert(DailyUsage {
-            date,
-            total_cost: 0.0,
-            total_tokens: 0,
-            models_used: vec![],
-        });
-        daily_stat.total_cost += entry.cost;
-        daily_stat.total_tokens += entry.input_tokens
-            + entry.output_tokens
-            + entry.cache_creation_tokens
-            + entry.cache_read_tokens;
-        if !daily_stat.models_used.contains(&entry.model) {
-            daily_stat.models_used.push(entry.model.clone());
+    let mut out = String::new();
+    out.push_str("# HELP vibe_usage_tokens_total Tokens consumed, by token type.\n");
+    out.push_str("# TYPE vibe_usage_tokens_total counter\n");
+    for row in &rows {
+        for (token_type, count) in ["input", "output", "cache_creation", "cache_read"]
+            .iter()
+            .zip(row.tokens)
+        {
+            out.push_str(&format!(
+                "vibe_usage_tokens_total{{{},type=\"{}\"}} {}\n",
+                labels(row),
+                token_type,
+                count
+            ));
         }
+    }
 
-        // Update project stats
-        let project_stat =
-            project_stats
-                .entry(entry.project_path.clone())
-                .or_insert(ProjectUsage {
-                    project_path: entry.project_path.clone(),
-                    project_name: entry
-                        .project_path
-                        .split('/')
-                        .last()
-                        .unwrap_or(&entry.project_path)
-                        .to_string(),
-                    total_cost: 0.0,
-                    total_tokens: 0,
-                    session_count: 0,
-                    last_used: entry.timestamp.clone(),
-                });
-        project_stat.total_cost += entry.cost;
-        project_stat.total_tokens += entry.input_tokens
-            + entry.output_tokens
-            + entry.cache_creation_tokens
-            + entry.cache_read_tokens;
-        project_stat.session_count += 1;
-        if entry.timestamp > project_stat.last_used {
-            project_stat.last_used = entry.timestamp.clone();
-        }
+    out.push_str("# HELP vibe_usage_cost_usd_total Estimated cost in USD.\n");
+    out.push_str("# TYPE vibe_usage_cost_usd_total counter\n");
+    for row in &rows {
+        out.push_str(&format!(
+            "vibe_usage_cost_usd_total{{{}}} {}\n",
+            labels(row),
+            row.cost
+        ));
     }
 
-    let total_tokens = total_input_tokens
-        + total_output_tokens
-        + total_cache_creation_tokens
-        + total_cache_read_tokens;
-    let total_sessions = filtered_entries.len() as u64;
+    out.push_str("# HELP vibe_usage_requests_total Usage entries (API responses) recorded.\n");
+    out.push_str("# TYPE vibe_usage_requests_total counter\n");
+    for row in &rows {
+        out.push_str(&format!(
+            "vibe_usage_requests_total{{{}}} {}\n",
+            labels(row),
+            row.requests
+        ));
+    }
 
-    // Convert hashmaps to sorted vectors
-    let mut by_model: Vec<ModelUsage> = model_stats.into_values().collect();
-    by_model.sort_by(|a, b| b.total_cost.partial_cmp(&a.total_cost).unwrap());
+    Ok(out)
+}
 
-    let mut by_date: Vec<DailyUsage> = daily_stats.into_values().collect();
-    by_date.sort_by(|a, b| b.date.cmp(&a.date));
+/// Aggregate indexed usage between two inclusive YYYY-MM-DD dates
+pub(crate) fn query_usage_stats(
+    conn: &Connection,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> SqliteResult<UsageStats> {
+    let (
+        total_cost,
+        total_input_tokens,
+        total_output_tokens,
+        total_cache_creation_tokens,
+        total_cache_read_tokens,
+        total_sessions,
+    ) = conn.query_row(
+        &format!(
+            "SELECT COALESCE(SUM(cost), 0), SUM(input_tokens), SUM(output_tokens),
+                    SUM(cache_creation_tokens), SUM(cache_read_tokens), COUNT(*)
+             FROM usage_entries WHERE {}",
+            DATE_RANGE_SQL
+        ),
+        params![start, end],
+        |row| {
+            Ok((
+                row.get::<_, f64>(0)?,
+                get_u64(row, 1)?,
+                get_u64(row, 2)?,
+                get_u64(row, 3)?,
+                get_u64(row, 4)?,
+                get_u64(row, 5)?,
+            ))
+        },
+    )?;
+
+    let by_model = conn
+        .prepare(&format!(
+            "SELECT model, SUM(cost), SUM(input_tokens), SUM(output_tokens),
+                    SUM(cache_creation_tokens), SUM(cache_read_tokens), COUNT(*)
+             FROM usage_entries WHERE {}
+             GROUP BY model ORDER BY SUM(cost) DESC",
+            DATE_RANGE_SQL
+        ))?
+        .query_map(params![start, end], |row| {
+            let input_tokens = get_u64(row, 2)?;
+            let output_tokens = get_u64(row, 3)?;
+            Ok(ModelUsage {
+                model: row.get(0)?,
+                total_cost: row.get(1)?,
+                total_tokens: input_tokens + output_tokens,
+                input_tokens,
+                output_tokens,
+                cache_creation_tokens: get_u64(row, 4)?,
+                cache_read_tokens: get_u64(row, 5)?,
+                session_count: get_u64(row, 6)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut by_date: Vec<DailyUsage> = Vec::new();
+    {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT usage_date, model, SUM(cost), SUM({})
+             FROM usage_entries WHERE {}
+             GROUP BY usage_date, model ORDER BY usage_date DESC, MIN(timestamp)",
+            TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+        ))?;
+        let mut rows = stmt.query(params![start, end])?;
+        while let Some(row) = rows.next()? {
+            let date: String = row.get(0)?;
+            let model: String = row.get(1)?;
+            let cost: f64 = row.get(2)?;
+            let tokens = get_u64(row, 3)?;
+            match by_date.last_mut() {
+                Some(day) if day.date == date => {
+                    day.total_cost += cost;
+                    day.total_tokens += tokens;
+                    day.models_used.push(model);
+                }
+                _ => by_date.push(DailyUsage {
+                    date,
+                    total_cost: cost,
+                    total_tokens: tokens,
+                    models_used: vec![model],
+                }),
+            }
+        }
+    }
 
-    let mut by_project: Vec<ProjectUsage> = project_stats.into_values().collect();
-    by_project.sort_by(|a, b| b.total_cost.partial_cmp(&a.total_cost).unwrap());
+    let by_project = conn
+        .prepare(&format!(
+            "SELECT project_path, SUM(cost), SUM({}), COUNT(*), MAX(timestamp)
+             FROM usage_entries WHERE {}
+             GROUP BY project_path ORDER BY SUM(cost) DESC",
+            TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+        ))?
+        .query_map(params![start, end], |row| {
+            let project_path: String = row.get(0)?;
+            Ok(ProjectUsage {
+                project_name: project_name(&project_path),
+                project_path,
+                total_cost: row.get(1)?,
+                total_tokens: get_u64(row, 2)?,
+                session_count: get_u64(row, 3)?,
+                last_used: row.get(4)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
 
     Ok(UsageStats {
         total_cost,
-        total_tokens,
+        total_tokens: total_input_tokens
+            + total_output_tokens
+            + total_cache_creation_tokens
+            + total_cache_read_tokens,
         total_input_tokens,
         total_output_tokens,
         total_cache_creation_tokens,
@@ -449,266 +719,439 @@ pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
 }
 
 #[command]
-pub fn get_usage_by_date_range(start_date: String, end_date: String) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+pub fn get_usage_stats(db: State<'_, AgentDb>, days: Option<u32>) -> Result<UsageStats, String> {
+    usage_stats(&db, &claude_path()?, days)
+}
+
+/// Usage totals, optionally over the last `days` days only
+pub fn usage_stats(
+    db: &AgentDb,
+    claude_dir: &Path,
+    days: Option<u32>,
+) -> Result<UsageStats, String> {
+    refresh_usage_index_in(db, claude_dir)?;
 
-    let all_entries = get_all_usage_entries(&claude_path);
+    // Filter by days if specified
+    let start = days.map(|days| {
+        (Local::now().naive_local().date() - chrono::Duration::days(days as i64))
+            .format("%Y-%m-%d")
+            .to_string()
+    });
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_usage_stats(&conn, start.as_deref(), None)
+        .map_err(|e| format!("Failed to query usage stats: {}", e))
+}
+
+#[command]
+pub fn get_usage_by_date_range(
+    db: State<'_, AgentDb>,
+    start_date: String,
+    end_date: String,
+) -> Result<UsageStats, String> {
+    usage_by_date_range(&db, &claude_path()?, &start_date, &end_date)
+}
 
+/// Usage totals between two inclusive dates (YYYY-MM-DD or RFC 3339)
+pub fn usage_by_date_range(
+    db: &AgentDb,
+    claude_dir: &Path,
+    start_date: &str,
+    end_date: &str,
+) -> Result<UsageStats, String> {
     // Parse dates
-    let start = NaiveDate::parse_from_str(&start_date, "%Y-%m-%d").or_else(|_| {
+    let start = NaiveDate::parse_from_str(start_date, "%Y-%m-%d").or_else(|_| {
         // Try parsing ISO datetime format
-        DateTime::parse_from_rfc3339(&start_date)
+        DateTime::parse_from_rfc3339(start_date)
             .map(|dt| dt.naive_local().date())
             .map_err(|e| format!("Invalid start date: {}", e))
     })?;
-    let end = NaiveDate::parse_from_str(&end_date, "%Y-%m-%d").or_else(|_| {
+    let end = NaiveDate::parse_from_str(end_date, "%Y-%m-%d").or_else(|_| {
         // Try parsing ISO datetime format
-        DateTime::parse_from_rfc3339(&end_date)
+        DateTime::parse_from_rfc3339(end_date)
             .map(|dt| dt.naive_local().date())
             .map_err(|e| format!("Invalid end date: {}", e))
     })?;
 
-    // Filter entries by date range
-    let filtered_entries: Vec<_> = all_entries
-        .into_iter()
-        .filter(|e| {
-            if let Ok(dt) = DateTime::parse_from_rfc3339(&e.timestamp) {
-                let date = dt.naive_local().date();
-                date >= start && date <= end
-            } else {
-                false
-            }
-        })
-        .collect();
-
-    if filtered_entries.is_empty() {
-        return Ok(UsageStats {
-            total_cost: 0.0,
-            total_tokens: 0,
-            total_input_tokens: 0,
-            total_output_tokens: 0,
-            total_cache_creation_tokens: 0,
-            total_cache_read_tokens: 0,
-            total_sessions: 0,
-            by_model: vec![],
-            by_date: vec![],
-            by_project: vec![],
-        });
-    }
-
-    // Calculate aggregated stats (same logic as get_usage_stats)
-    let mut total_cost = 0.0;
-    let mut total_input_tokens = 0u64;
-    let mut total_output_tokens = 0u64;
-    let mut total_cache_creation_tokens = 0u64;
-    let mut total_cache_read_tokens = 0u64;
-
-    let mut model_stats: HashMap<String, ModelUsage> = HashMap::new();
-    let mut daily_stats: HashMap<String, DailyUsage> = HashMap::new();
-    let mut project_stats: HashMap<String, ProjectUsage> = HashMap::new();
-
-    for entry in &filtered_entries {
-        // Update totals
-        total_cost += entry.cost;
-        total_input_tokens += entry.input_tokens;
-        total_output_tokens += entry.output_tokens;
-        total_cache_creation_tokens += entry.cache_creation_tokens;
-        total_cache_read_tokens += entry.cache_read_tokens;
-
-        // Update model stats
-        let model_stat = model_stats
-            .entry(entry.model.clone())
-            .or_insert(ModelUsage {
-                model: entry.model.clone(),
-                total_cost: 0.0,
-                total_tokens: 0,
-                input_tokens: 0,
-                output_tokens: 0,
-                cache_creation_tokens: 0,
-                cache_read_tokens: 0,
-                session_count: 0,
-            });
-        model_stat.total_cost += entry.cost;
-        model_stat.input_tokens += entry.input_tokens;
-        model_stat.output_tokens += entry.output_tokens;
-        model_stat.cache_creation_tokens += entry.cache_creation_tokens;
-        model_stat.cache_read_tokens += entry.cache_read_tokens;
-        model_stat.total_tokens = model_stat.input_tokens + model_stat.output_tokens;
-        model_stat.session_count += 1;
-
-        // Update daily stats
-        let date = entry
-            .timestamp
-            .split('T')
-            .next()
-            .unwrap_or(&entry.timestamp)
-            .to_string();
-        let daily_stat = daily_stats.entry(date.clone()).or_insert(DailyUsage {
-            date,
-            total_cost: 0.0,
-            total_tokens: 0,
-            models_used: vec![],
-        });
-        daily_stat.total_cost += entry.cost;
-        daily_stat.total_tokens += entry.input_tokens
-            + entry.output_tokens
-            + entry.cache_creation_tokens
-            + entry.cache_read_tokens;
-        if !daily_stat.models_used.contains(&entry.model) {
-            daily_stat.models_used.push(entry.model.clone());
-        }
+    refresh_usage_index_in(db, claude_dir)?;
 
-        // Update project stats
-        let project_stat =
-            project_stats
-                .entry(entry.project_path.clone())
-                .or_insert(ProjectUsage {
-                    project_path: entry.project_path.clone(),
-                    project_name: entry
-                        .project_path
-                        .split('/')
-                        .last()
-                        .unwrap_or(&entry.project_path)
-                        .to_string(),
-                    total_cost: 0.0,
-                    total_tokens: 0,
-                    session_count: 0,
-                    last_used: entry.timestamp.clone(),
-                });
-        project_stat.total_cost += entry.cost;
-        project_stat.total_tokens += entry.input_tokens
-            + entry.output_tokens
-            + entry.cache_creation_tokens
-            + entry.cache_read_tokens;
-        project_stat.session_count += 1;
-        if entry.timestamp > project_stat.last_used {
-            project_stat.last_used = entry.timestamp.clone();
-        }
-    }
-
-    let total_tokens = total_input_tokens
-        + total_output_tokens
-        + total_cache_creation_tokens
-        + total_cache_read_tokens;
-    let total_sessions = filtered_entries.len() as u64;
-
-    // Convert hashmaps to sorted vectors
-    let mut by_model: Vec<ModelUsage> = model_stats.into_values().collect();
-    by_model.sort_by(|a, b| b.total_cost.partial_cmp(&a.total_cost).unwrap());
-
-    let mut by_date: Vec<DailyUsage> = daily_stats.into_values().collect();
-    by_date.sort_by(|a, b| b.date.cmp(&a.date));
-
-    let mut by_project: Vec<ProjectUsage> = project_stats.into_values().collect();
-    by_project.sort_by(|a, b| b.total_cost.partial_cmp(&a.total_cost).unwrap());
-
-    Ok(UsageStats {
-        total_cost,
-        total_tokens,
-        total_input_tokens,
-        total_output_tokens,
-        total_cache_creation_tokens,
-        total_cache_read_tokens,
-        total_sessions,
-        by_model,
-        by_date,
-        by_project,
-    })
+    let start = start.format("%Y-%m-%d").to_string();
+    let end = end.format("%Y-%m-%d").to_string();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_usage_stats(&conn, Some(&start), Some(&end))
+        .map_err(|e| format!("Failed to query usage stats: {}", e))
 }
 
 #[command]
 pub fn get_usage_details(
+    db: State<'_, AgentDb>,
     project_path: Option<String>,
     date: Option<String>,
 ) -> Result<Vec<UsageEntry>, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let mut all_entries = get_all_usage_entries(&claude_path);
-
-    // Filter by project if specified
-    if let Some(project) = project_path {
-        all_entries.retain(|e| e.project_path == project);
-    }
+    usage_details(
+        &db,
+        &claude_path()?,
+        project_path.as_deref(),
+        date.as_deref(),
+    )
+}
 
-    // Filter by date if specified
-    if let Some(date) = date {
-        all_entries.retain(|e| e.timestamp.starts_with(&date));
-    }
+/// Usage entries, optionally of one project and/or day
+pub fn usage_details(
+    db: &AgentDb,
+    claude_dir: &Path,
+    project_path: Option<&str>,
+    date: Option<&str>,
+) -> Result<Vec<UsageEntry>, String> {
+    refresh_usage_index_in(db, claude_dir)?;
 
-    Ok(all_entries)
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_usage_entries(&conn, None, None, project_path, date)
+        .map_err(|e| format!("Failed to query usage details: {}", e))
 }
 
 #[command]
 pub fn get_session_stats(
+    db: State<'_, AgentDb>,
     since: Option<String>,
     until: Option<String>,
     order: Option<String>,
 ) -> Result<Vec<ProjectUsage>, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let all_entries = get_all_usage_entries(&claude_path);
-
-    let since_date = since.and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok());
-    let until_date = until.and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok());
-
-    let filtered_entries: Vec<_> = all_entries
-        .into_iter()
-        .filter(|e| {
-            if let Ok(dt) = DateTime::parse_from_rfc3339(&e.timestamp) {
-                let date = dt.date_naive();
-                let is_after_since = since_date.map_or(true, |s| date >= s);
-                let is_before_until = until_date.map_or(true, |u| date <= u);
-                is_after_since && is_before_until
-            } else {
-                false
-            }
+    session_stats(&db, &claude_path()?, since, until, order)
+}
+
+/// Usage per session between two optional YYYYMMDD dates
+pub fn session_stats(
+    db: &AgentDb,
+    claude_dir: &Path,
+    since: Option<String>,
+    until: Option<String>,
+    order: Option<String>,
+) -> Result<Vec<ProjectUsage>, String> {
+    refresh_usage_index_in(db, claude_dir)?;
+
+    let since_date = since
+        .and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok())
+        .map(|d| d.format("%Y-%m-%d").to_string());
+    let until_date = until
+        .and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok())
+        .map(|d| d.format("%Y-%m-%d").to_string());
+
+    // Sort by last_used date, descending unless asked otherwise
+    let direction = if order.as_deref() == Some("asc") {
+        "ASC"
+    } else {
+        "DESC"
+    };
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT project_path, session_id, SUM(cost), SUM({}), COUNT(*), MAX(timestamp)
+             FROM usage_entries WHERE {}
+             GROUP BY project_path, session_id
+             ORDER BY MAX(timestamp) {}",
+            TOTAL_TOKENS_SQL, DATE_RANGE_SQL, direction
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let by_session = stmt
+        .query_map(params![since_date, until_date], |row| {
+            Ok(ProjectUsage {
+                project_path: row.get(0)?,
+                project_name: row.get(1)?, // Using session_id as project_name for session view
+                total_cost: row.get(2)?,
+                total_tokens: get_u64(row, 3)?,
+                session_count: get_u64(row, 4)?, // In this context, this counts entries per session
+                last_used: row.get(5)?,
+            })
         })
-        .collect();
-
-    let mut session_stats: HashMap<String, ProjectUsage> = HashMap::new();
-    for entry in &filtered_entries {
-        let session_key = format!("{}/{}", entry.project_path, entry.session_id);
-        let project_stat = session_stats
-            .entry(session_key)
-            .or_insert_with(|| ProjectUsage {
-                project_path: entry.project_path.clone(),
-                project_name: entry.session_id.clone(), // Using session_id as project_name for session view
-                total_cost: 0.0,
-                total_tokens: 0,
-                session_count: 0, // In this context, this will count entries per session
-                last_used: " ".to_string(),
-            });
+        .map_err(|e| e.to_string())?
+        .collect::<SqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Failed to query session stats: {}", e))?;
+
+    Ok(by_session)
+}
+
+/// Export raw usage entries or daily aggregates between two optional
+/// inclusive YYYY-MM-DD dates as CSV or JSONL
+#[command]
+pub fn export_usage(
+    db: State<'_, AgentDb>,
+    format: UsageExportFormat,
+    kind: UsageExportKind,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<String, String> {
+    let start = parse_date_bound(start_date, "start")?;
+    let end = parse_date_bound(end_date, "end")?;
+
+    refresh_usage_index(&db)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    render_usage_export(&conn, format, kind, start.as_deref(), end.as_deref())
+}
+
+/// Export usage to a file (see `export_usage`)
+#[command]
+pub fn export_usage_to_file(
+    db: State<'_, AgentDb>,
+    file_path: String,
+    format: UsageExportFormat,
+    kind: UsageExportKind,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<(), String> {
+    log::info!(
+        "Exporting usage ({:?}, {:?}) to {}",
+        kind,
+        format,
+        file_path
+    );
+    let data = export_usage(db, format, kind, start_date, end_date)?;
+    std::fs::write(&file_path, data).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(())
+}
 
-        project_stat.total_cost += entry.cost;
-        project_stat.total_tokens += entry.input_tokens
-            + entry.output_tokens
-            + entry.cache_creation_tokens
-            + entry.cache_read_tokens;
-        project_stat.session_count += 1;
-        if entry.timestamp > project_stat.last_used {
-            project_stat.last_used = entry.timestamp.clone();
+/// Usage broken down per vibe project, team member and role.
+/// Dates are inclusive YYYY-MM-DD bounds; `project_id` limits the breakdown
+/// to one project.
+#[command]
+pub fn get_team_usage(
+    db: State<'_, AgentDb>,
+    project_id: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<TeamUsage, String> {
+    let start = parse_date_bound(start_date, "start")?;
+    let end = parse_date_bound(end_date, "end")?;
+
+    refresh_usage_index(&db)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_team_usage(
+        &conn,
+        project_id.as_deref(),
+        start.as_deref(),
+        end.as_deref(),
+    )
+    .map_err(|e| format!("Failed to query team usage: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+
+    #[test]
+    fn test_team_usage_joins_sessions_to_project_members() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name, working_dir) VALUES ('p1', 'Demo', '/work/demo');
+             INSERT INTO agents (id, name, icon, system_prompt, role_type) VALUES
+                ('lead', 'Lead', 'bot', '', 'teamlead'),
+                ('dev', 'Dev', 'bot', '', 'teammate');
+             INSERT INTO project_agents (id, project_id, agent_id) VALUES
+                ('pa-lead', 'p1', 'lead'),
+                ('pa-dev', 'p1', 'dev');",
+        )
+        .unwrap();
+
+        for (session, date, cost) in [
+            ("pa-lead", "2025-06-01", 1.0),
+            ("pa-dev", "2025-06-01", 2.0),
+            ("pa-dev", "2025-06-03", 4.0),
+            ("plain-session", "2025-06-01", 8.0),
+        ] {
+            conn.execute(
+                "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path)
+                 VALUES ('f', ?1 || 'T00:00:00Z', ?1, 'claude-sonnet-4', 10, ?2, ?3, '/work/demo')",
+                params![date, cost, session],
+            )
+            .unwrap();
         }
+
+        let usage = query_team_usage(&conn, None, None, Some("2025-06-02")).unwrap();
+        assert_eq!(usage.by_project.len(), 1);
+        assert_eq!(usage.by_project[0].total_cost, 3.0);
+        assert_eq!(usage.by_project[0].member_count, 2);
+        assert_eq!(usage.by_member[0].agent_name, "Dev");
+        assert_eq!(usage.by_role[0].role_type, "teammate");
+        assert_eq!(usage.unattributed_cost, 8.0);
+
+        let usage = query_team_usage(&conn, Some("p1"), None, None).unwrap();
+        assert_eq!(usage.by_project[0].total_cost, 7.0);
+        assert_eq!(usage.unattributed_cost, 0.0);
     }
 
-    let mut by_session: Vec<ProjectUsage> = session_stats.into_values().collect();
+    #[test]
+    fn test_team_usage_of_empty_tables_is_zero() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+
+        // SUM over no rows is NULL
+        let usage = query_team_usage(&conn, None, None, None).unwrap();
+        assert!(usage.by_project.is_empty());
+        assert!(usage.by_member.is_empty());
+        assert!(usage.by_role.is_empty());
+        assert_eq!(usage.unattributed_cost, 0.0);
+        assert_eq!(usage.unattributed_tokens, 0);
+    }
 
-    // Sort by last_used date
-    if let Some(order_str) = order {
-        if order_str == "asc" {
-            by_session.sort_by(|a, b| a.last_used.cmp(&b.last_used));
-        } else {
-            by_session.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+    #[test]
+    fn test_team_usage_counts_members_without_a_role_as_teammates() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Demo');
+             INSERT INTO agents (id, name, icon, system_prompt, role_type) VALUES ('a1', 'Solo', 'bot', '', NULL);
+             INSERT INTO project_agents (id, project_id, agent_id) VALUES ('pa1', 'p1', 'a1');
+             INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path)
+             VALUES ('f', '2025-06-01T00:00:00Z', '2025-06-01', 'claude-sonnet-4', 10, 1.0, 'pa1', '/work');",
+        )
+        .unwrap();
+
+        let usage = query_team_usage(&conn, None, None, None).unwrap();
+        assert_eq!(usage.by_role[0].role_type, "teammate");
+        assert_eq!(usage.by_project[0].working_dir, "");
+    }
+
+    #[test]
+    fn test_parse_date_bound_rejects_bad_dates() {
+        assert_eq!(parse_date_bound(None, "start").unwrap(), None);
+        assert_eq!(
+            parse_date_bound(Some("2025-06-01".to_string()), "start").unwrap(),
+            Some("2025-06-01".to_string())
+        );
+        for bad in ["2025-02-30", "06/01/2025", "2025-06-01T00:00:00Z", ""] {
+            let error = parse_date_bound(Some(bad.to_string()), "end").unwrap_err();
+            assert!(error.starts_with("Invalid end date"), "{}", error);
         }
-    } else {
-        // Default to descending
-        by_session.sort_by(|a, b| b.last_used.cmp(&a.last_used));
     }
 
-    Ok(by_session)
+    #[test]
+    fn test_exports_and_metrics_render_indexed_rows() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute(
+            "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, output_tokens, cost, session_id, project_path)
+             VALUES ('f', '2025-06-01T10:00:00Z', '2025-06-01', 'claude-sonnet-4', 100, 20, 0.5, 's1', '/work/a,b')",
+            [],
+        )
+        .unwrap();
+
+        let csv = render_usage_export(
+            &conn,
+            UsageExportFormat::Csv,
+            UsageExportKind::Entries,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(csv.lines().nth(1).unwrap().ends_with(",s1,\"/work/a,b\""));
+
+        let jsonl = render_usage_export(
+            &conn,
+            UsageExportFormat::Jsonl,
+            UsageExportKind::Daily,
+            Some("2025-06-01"),
+            Some("2025-06-01"),
+        )
+        .unwrap();
+        let row: serde_json::Value = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(row["total_tokens"], 120);
+
+        let metrics = render_prometheus_metrics(&conn).unwrap();
+        assert!(metrics.contains(
+            "vibe_usage_tokens_total{model=\"claude-sonnet-4\",project=\"/work/a,b\",agent=\"\",type=\"input\"} 100"
+        ));
+        assert!(metrics.contains("# TYPE vibe_usage_cost_usd_total counter"));
+    }
+
+    #[test]
+    fn test_exports_and_metrics_of_empty_tables() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+
+        for kind in [UsageExportKind::Entries, UsageExportKind::Daily] {
+            let csv = render_usage_export(&conn, UsageExportFormat::Csv, kind, None, None).unwrap();
+            assert_eq!(csv.lines().count(), 1, "only the header: {csv}");
+            let jsonl =
+                render_usage_export(&conn, UsageExportFormat::Jsonl, kind, None, None).unwrap();
+            assert!(jsonl.is_empty());
+        }
+
+        let metrics = render_prometheus_metrics(&conn).unwrap();
+        assert!(metrics.contains("# TYPE vibe_usage_tokens_total counter"));
+        assert!(metrics.contains("# TYPE vibe_usage_requests_total counter"));
+        assert!(metrics.lines().all(|line| line.starts_with('#')));
+    }
+
+    #[test]
+    fn test_exports_filter_dates_aggregate_days_and_escape_fields() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, output_tokens, cost, session_id, project_path) VALUES
+                ('f', '2025-05-31T23:00:00Z', '2025-05-31', 'm', 1, 1, 0.1, 's0', '/work'),
+                ('f', '2025-06-01T10:00:00Z', '2025-06-01', 'm', 10, 5, 0.25, 'say \"hi\"', '/work'),
+                ('f', '2025-06-01T11:00:00Z', '2025-06-01', 'm', 30, 5, 0.25, 's2', '/work'),
+                ('f', '2025-06-02T09:00:00Z', '2025-06-02', 'm', 100, 0, 1.0, 's3', '/work/\"q\"\\');",
+        )
+        .unwrap();
+
+        let csv = render_usage_export(
+            &conn,
+            UsageExportFormat::Csv,
+            UsageExportKind::Entries,
+            Some("2025-06-01"),
+            Some("2025-06-01"),
+        )
+        .unwrap();
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].ends_with(",\"say \"\"hi\"\"\",/work"));
+
+        let daily = render_usage_export(
+            &conn,
+            UsageExportFormat::Jsonl,
+            UsageExportKind::Daily,
+            Some("2025-06-01"),
+            None,
+        )
+        .unwrap();
+        let days: Vec<serde_json::Value> = daily
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0]["date"], "2025-06-01");
+        assert_eq!(days[0]["total_tokens"], 50);
+        assert_eq!(days[0]["entry_count"], 2);
+        assert_eq!(days[1]["date"], "2025-06-02");
+
+        let metrics = render_prometheus_metrics(&conn).unwrap();
+        assert!(metrics.contains("project=\"/work/\\\"q\\\"\\\\\""));
+    }
+
+    #[test]
+    fn test_metrics_label_team_sessions_with_project_and_agent_names() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name, working_dir) VALUES ('p1', 'Demo', '/work/demo');
+             INSERT INTO agents (id, name, icon, system_prompt, role_type) VALUES ('dev', 'Dev', 'bot', '', 'teammate');
+             INSERT INTO project_agents (id, project_id, agent_id) VALUES ('pa-dev', 'p1', 'dev');
+             INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path) VALUES
+                ('f', '2025-06-01T10:00:00Z', '2025-06-01', 'm', 10, 0.5, 'pa-dev', '/work/demo'),
+                ('f', '2025-06-01T11:00:00Z', '2025-06-01', 'm', 20, 0.5, 'pa-dev', '/work/demo'),
+                ('f', '2025-06-01T12:00:00Z', '2025-06-01', 'm', 5, 0.25, 'solo', '/work/demo');",
+        )
+        .unwrap();
+
+        let metrics = render_prometheus_metrics(&conn).unwrap();
+        let team = "model=\"m\",project=\"Demo\",agent=\"Dev\"";
+        assert!(metrics.contains(&format!(
+            "vibe_usage_tokens_total{{{team},type=\"input\"}} 30"
+        )));
+        assert!(metrics.contains(&format!("vibe_usage_cost_usd_total{{{team}}} 1")));
+        assert!(metrics.contains(&format!("vibe_usage_requests_total{{{team}}} 2")));
+        assert!(metrics.contains(
+            "vibe_usage_requests_total{model=\"m\",project=\"/work/demo\",agent=\"\"} 1"
+        ));
+    }
 }
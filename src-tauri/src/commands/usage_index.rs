@@ -0,0 +1,699 @@
+//! Persistent usage index over the Claude JSONL session logs.
+//!
+//! Every `.jsonl` file under `~/.claude/projects` is tracked in `usage_files`
+//! with its size, mtime and the byte offset ingested so far. A refresh only
+//! reads the bytes appended since the last pass and inserts the usage lines
+//! into `usage_entries`, where the `message.id:requestId` dedup key is unique.
+//! The usage commands then answer with SQL aggregates over that table.
+
+use super::agents::AgentDb;
+use super::pricing::{PricingTable, TokenCounts};
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Serializes refreshes so concurrent dashboard queries don't ingest the
+/// same appended bytes twice.
+static REFRESH_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Deserialize)]
+struct JsonlEntry {
+    timestamp: String,
+    message: Option<MessageData>,
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+    #[serde(rename = "requestId")]
+    request_id: Option<String>,
+    #[serde(rename = "costUSD")]
+    cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageData {
+    id: Option<String>,
+    model: Option<String>,
+    usage: Option<UsageData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageData {
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_creation_input_tokens: Option<u64>,
+    cache_read_input_tokens: Option<u64>,
+}
+
+impl UsageData {
+    fn token_counts(&self) -> TokenCounts {
+        TokenCounts {
+            input_tokens: self.input_tokens.unwrap_or(0),
+            output_tokens: self.output_tokens.unwrap_or(0),
+            cache_creation_tokens: self.cache_creation_input_tokens.unwrap_or(0),
+            cache_read_tokens: self.cache_read_input_tokens.unwrap_or(0),
+        }
+    }
+}
+
+/// What the index knows about a JSONL file from the previous refresh
+#[derive(Debug, Clone)]
+struct IndexedFile {
+    project_path: Option<String>,
+    file_size: u64,
+    file_mtime: i64,
+    byte_offset: u64,
+}
+
+/// A file that changed since it was last indexed
+struct PendingFile {
+    path: PathBuf,
+    encoded_project: String,
+    file_size: u64,
+    file_mtime: i64,
+    previous: Option<IndexedFile>,
+}
+
+/// A usage line parsed from a JSONL file, ready to insert
+struct ParsedEntry {
+    dedup_key: Option<String>,
+    timestamp: String,
+    model: String,
+    tokens: TokenCounts,
+    cost: f64,
+    recorded_cost: Option<f64>,
+    session_id: String,
+}
+
+/// Outcome of a refresh pass
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RefreshSummary {
+    pub files_scanned: usize,
+    pub files_ingested: usize,
+    pub entries_added: usize,
+}
+
+/// Create the usage index tables
+pub fn init_usage_index_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS usage_files (
+            path TEXT PRIMARY KEY,
+            encoded_project TEXT NOT NULL,
+            project_path TEXT,
+            file_size INTEGER NOT NULL DEFAULT 0,
+            file_mtime INTEGER NOT NULL DEFAULT 0,
+            byte_offset INTEGER NOT NULL DEFAULT 0,
+            indexed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS usage_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dedup_key TEXT UNIQUE,
+            file_path TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            usage_date TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_creation_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+            cost REAL NOT NULL DEFAULT 0,
+            recorded_cost REAL,
+            session_id TEXT NOT NULL,
+            project_path TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_entries_date ON usage_entries(usage_date)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_entries_session ON usage_entries(session_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_entries_file ON usage_entries(file_path)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn file_mtime(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn load_indexed_files(conn: &Connection) -> SqliteResult<HashMap<String, IndexedFile>> {
+    let mut stmt = conn.prepare(
+        "SELECT path, project_path, file_size, file_mtime, byte_offset FROM usage_files",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            IndexedFile {
+                project_path: row.get(1)?,
+                file_size: row.get::<_, i64>(2)? as u64,
+                file_mtime: row.get(3)?,
+                byte_offset: row.get::<_, i64>(4)? as u64,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn get_earliest_timestamp(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|json| {
+            json.get("timestamp")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        })
+        .min()
+}
+
+/// Find every JSONL file whose size or mtime differs from the index
+fn collect_pending_files(
+    claude_path: &Path,
+    indexed: &HashMap<String, IndexedFile>,
+) -> (usize, Vec<PendingFile>) {
+    let mut scanned = 0;
+    let mut pending = Vec::new();
+    let projects_dir = claude_path.join("projects");
+
+    if let Ok(projects) = fs::read_dir(&projects_dir) {
+        for project in projects.flatten() {
+            if !project.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let encoded_project = project.file_name().to_string_lossy().to_string();
+
+            for entry in walkdir::WalkDir::new(project.path())
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
+            {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                scanned += 1;
+
+                let path = entry.path().to_path_buf();
+                let file_size = metadata.len();
+                let file_mtime = file_mtime(&metadata);
+                let previous = indexed.get(path.to_string_lossy().as_ref()).cloned();
+                let unchanged = previous.as_ref().is_some_and(|prev| {
+                    prev.file_size == file_size && prev.file_mtime == file_mtime
+                });
+                if unchanged {
+                    continue;
+                }
+
+                pending.push(PendingFile {
+                    path,
+                    encoded_project: encoded_project.clone(),
+                    file_size,
+                    file_mtime,
+                    previous,
+                });
+            }
+        }
+    }
+
+    // Appended files first, then new files by their earliest timestamp so the
+    // first ingest deduplicates deterministically, as the full rescan did.
+    let (mut appended, mut fresh): (Vec<_>, Vec<_>) =
+        pending.into_iter().partition(|p| p.previous.is_some());
+    fresh.sort_by_cached_key(|p| get_earliest_timestamp(&p.path));
+    appended.extend(fresh);
+
+    (scanned, appended)
+}
+
+/// Read the complete lines appended after `offset`.
+/// Returns the text and the new offset; a trailing partial line is left for
+/// the next refresh.
+fn read_appended_lines(path: &Path, offset: u64) -> std::io::Result<(String, u64)> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+
+    match buf.iter().rposition(|b| *b == b'\n') {
+        Some(pos) => Ok((
+            String::from_utf8_lossy(&buf[..=pos]).into_owned(),
+            offset + pos as u64 + 1,
+        )),
+        None => Ok((String::new(), offset)),
+    }
+}
+
+/// Parse usage lines, noting the first `cwd` seen as the real project path
+fn parse_usage_lines(
+    content: &str,
+    fallback_session_id: &str,
+    project_path: &mut Option<String>,
+    pricing: &PricingTable,
+) -> Vec<ParsedEntry> {
+    let mut entries = Vec::new();
+
+    for line in content.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+
+        if project_path.is_none() {
+            *project_path = json_value
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+        }
+
+        let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) else {
+            continue;
+        };
+        let Some(message) = &entry.message else {
+            continue;
+        };
+        let Some(usage) = &message.usage else {
+            continue;
+        };
+
+        let tokens = usage.token_counts();
+        // Skip entries without meaningful token usage
+        if tokens.input_tokens == 0
+            && tokens.output_tokens == 0
+            && tokens.cache_creation_tokens == 0
+            && tokens.cache_read_tokens == 0
+        {
+            continue;
+        }
+
+        let model = message
+            .model
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        // Prefer the cost Claude recorded; otherwise price the entry with the
+        // rate that was effective when it was logged.
+        let cost = entry
+            .cost_usd
+            .unwrap_or_else(|| pricing.cost(&model, &entry.timestamp, &tokens));
+
+        entries.push(ParsedEntry {
+            dedup_key: match (&message.id, &entry.request_id) {
+                (Some(msg_id), Some(req_id)) => Some(format!("{}:{}", msg_id, req_id)),
+                _ => None,
+            },
+            model,
+            tokens,
+            cost,
+            recorded_cost: entry.cost_usd,
+            session_id: entry
+                .session_id
+                .clone()
+                .unwrap_or_else(|| fallback_session_id.to_string()),
+            timestamp: entry.timestamp,
+        });
+    }
+
+    entries
+}
+
+/// Write one file's new entries and its updated offset in a transaction.
+/// Returns the number of entries actually inserted after deduplication.
+fn store_file(
+    conn: &mut Connection,
+    file: &PendingFile,
+    reset: bool,
+    project_path: Option<&str>,
+    byte_offset: u64,
+    entries: &[ParsedEntry],
+) -> SqliteResult<usize> {
+    let path = file.path.to_string_lossy().to_string();
+    let entry_project = project_path.unwrap_or(&file.encoded_project);
+    let tx = conn.transaction()?;
+
+    if reset {
+        tx.execute(
+            "DELETE FROM usage_entries WHERE file_path = ?1",
+            params![path],
+        )?;
+    }
+
+    // Entries stored before the file's cwd was known carry the encoded name
+    if let Some(project_path) = project_path {
+        tx.execute(
+            "UPDATE usage_entries SET project_path = ?1 WHERE file_path = ?2 AND project_path = ?3",
+            params![project_path, path, file.encoded_project],
+        )?;
+    }
+
+    let mut inserted = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO usage_entries (dedup_key, file_path, timestamp, usage_date, model, input_tokens, output_tokens,
+             cache_creation_tokens, cache_read_tokens, cost, recorded_cost, session_id, project_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        for entry in entries {
+            let usage_date = entry
+                .timestamp
+                .split('T')
+                .next()
+                .unwrap_or(&entry.timestamp);
+            inserted += stmt.execute(params![
+                entry.dedup_key,
+                path,
+                entry.timestamp,
+                usage_date,
+                entry.model,
+                entry.tokens.input_tokens as i64,
+                entry.tokens.output_tokens as i64,
+                entry.tokens.cache_creation_tokens as i64,
+                entry.tokens.cache_read_tokens as i64,
+                entry.cost,
+                entry.recorded_cost,
+                entry.session_id,
+                entry_project,
+            ])?;
+        }
+    }
+
+    tx.execute(
+        "INSERT INTO usage_files (path, encoded_project, project_path, file_size, file_mtime, byte_offset, indexed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)
+         ON CONFLICT(path) DO UPDATE SET
+            project_path = excluded.project_path,
+            file_size = excluded.file_size,
+            file_mtime = excluded.file_mtime,
+            byte_offset = excluded.byte_offset,
+            indexed_at = CURRENT_TIMESTAMP",
+        params![
+            path,
+            file.encoded_project,
+            project_path,
+            file.file_size as i64,
+            file.file_mtime,
+            byte_offset as i64
+        ],
+    )?;
+
+    tx.commit()?;
+    Ok(inserted)
+}
+
+/// Bring the index up to date with the JSONL files under `claude_path`.
+///
+/// Files are parsed without holding the database lock; each file's entries
+/// are then written in their own short transaction. Files that shrank below
+/// the indexed offset were rewritten and are re-ingested from the start.
+pub fn refresh(
+    db: &AgentDb,
+    claude_path: &Path,
+    pricing: &PricingTable,
+) -> Result<RefreshSummary, String> {
+    let _guard = REFRESH_LOCK.lock().map_err(|e| e.to_string())?;
+
+    let indexed = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_indexed_files(&conn).map_err(|e| format!("Failed to read usage index: {}", e))?
+    };
+
+    let (files_scanned, pending) = collect_pending_files(claude_path, &indexed);
+    let mut summary = RefreshSummary {
+        files_scanned,
+        ..Default::default()
+    };
+
+    for file in &pending {
+        let (reset, offset, mut project_path) = match &file.previous {
+            Some(prev) if file.file_size >= prev.byte_offset => {
+                (false, prev.byte_offset, prev.project_path.clone())
+            }
+            Some(_) => (true, 0, None),
+            None => (false, 0, None),
+        };
+
+        let (content, byte_offset) = match read_appended_lines(&file.path, offset) {
+            Ok(read) => read,
+            Err(e) => {
+                log::warn!("Failed to read usage log {:?}: {}", file.path, e);
+                continue;
+            }
+        };
+
+        let fallback_session_id = file
+            .path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let entries = parse_usage_lines(&content, &fallback_session_id, &mut project_path, pricing);
+
+        let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+        let inserted = store_file(
+            &mut conn,
+            file,
+            reset,
+            project_path.as_deref(),
+            byte_offset,
+            &entries,
+        )
+        .map_err(|e| format!("Failed to update usage index: {}", e))?;
+
+        summary.files_ingested += 1;
+        summary.entries_added += inserted;
+    }
+
+    if summary.entries_added > 0 {
+        log::info!(
+            "Usage index: {} new entries from {} of {} files",
+            summary.entries_added,
+            summary.files_ingested,
+            summary.files_scanned
+        );
+    }
+
+    Ok(summary)
+}
+
+/// Recompute the cost of every indexed entry without a recorded `costUSD`
+/// using the price effective on the entry's date.
+pub fn reprice(conn: &Connection, pricing: &PricingTable) -> SqliteResult<usize> {
+    let groups: Vec<(String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT model, usage_date FROM usage_entries WHERE recorded_cost IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqliteResult<Vec<_>>>()?
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    let mut updated = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE usage_entries SET cost = (input_tokens * ?1 + output_tokens * ?2
+                + cache_creation_tokens * ?3 + cache_read_tokens * ?4) / 1000000.0
+             WHERE recorded_cost IS NULL AND model = ?5 AND usage_date = ?6",
+        )?;
+        for (model, usage_date) in &groups {
+            let (input, output, cache_write, cache_read) = pricing
+                .price_for(model, usage_date)
+                .map(|p| {
+                    (
+                        p.input_price,
+                        p.output_price,
+                        p.cache_write_price,
+                        p.cache_read_price,
+                    )
+                })
+                .unwrap_or((0.0, 0.0, 0.0, 0.0));
+            updated += stmt.execute(params![
+                input,
+                output,
+                cache_write,
+                cache_read,
+                model,
+                usage_date
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::pricing::init_pricing_table;
+    use std::io::Write;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn usage_line(msg_id: &str, req_id: &str, timestamp: &str, input: u64) -> String {
+        format!(
+            r#"{{"timestamp":"{}","cwd":"/work/demo","sessionId":"s1","requestId":"{}","message":{{"id":"{}","model":"claude-sonnet-4-20250514","usage":{{"input_tokens":{},"output_tokens":0}}}}}}"#,
+            timestamp, req_id, msg_id, input
+        )
+    }
+
+    fn index_db() -> (AgentDb, PricingTable) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_pricing_table(&conn).unwrap();
+        init_usage_index_tables(&conn).unwrap();
+        let pricing = PricingTable::load(&conn).unwrap();
+        (AgentDb(Arc::new(Mutex::new(conn))), pricing)
+    }
+
+    fn entry_count(db: &AgentDb) -> i64 {
+        let conn = db.0.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM usage_entries", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_refresh_ingests_only_appended_lines_and_dedups() {
+        let claude_dir = TempDir::new().unwrap();
+        let project_dir = claude_dir.path().join("projects").join("-work-demo");
+        fs::create_dir_all(&project_dir).unwrap();
+        let log_path = project_dir.join("s1.jsonl");
+        let (db, pricing) = index_db();
+
+        fs::write(
+            &log_path,
+            format!(
+                "{}\n{}\n",
+                usage_line("m1", "r1", "2025-06-01T10:00:00Z", 1_000_000),
+                usage_line("m1", "r1", "2025-06-01T10:00:00Z", 1_000_000)
+            ),
+        )
+        .unwrap();
+        let summary = refresh(&db, claude_dir.path(), &pricing).unwrap();
+        assert_eq!(summary.entries_added, 1);
+
+        // Unchanged files are skipped entirely
+        let summary = refresh(&db, claude_dir.path(), &pricing).unwrap();
+        assert_eq!(summary.files_ingested, 0);
+
+        // A complete appended line is ingested, a partial one waits
+        let mut file = fs::OpenOptions::new().append(true).open(&log_path).unwrap();
+        write!(
+            file,
+            "{}\n{{\"timestamp\":",
+            usage_line("m2", "r2", "2025-06-02T10:00:00Z", 2_000_000)
+        )
+        .unwrap();
+        drop(file);
+        let summary = refresh(&db, claude_dir.path(), &pricing).unwrap();
+        assert_eq!(summary.entries_added, 1);
+        assert_eq!(entry_count(&db), 2);
+
+        let conn = db.0.lock().unwrap();
+        let (cost, project): (f64, String) = conn
+            .query_row(
+                "SELECT SUM(cost), MIN(project_path) FROM usage_entries",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!((cost - 9.0).abs() < 1e-9);
+        assert_eq!(project, "/work/demo");
+
+        // Repricing applies the new rate to entries without a recorded cost
+        let mut cheaper = pricing
+            .price_for("claude-sonnet-4", "2025-06-01")
+            .unwrap()
+            .clone();
+        cheaper.input_price = 1.0;
+        reprice(&conn, &PricingTable::new(vec![cheaper])).unwrap();
+        let cost: f64 = conn
+            .query_row("SELECT SUM(cost) FROM usage_entries", [], |row| row.get(0))
+            .unwrap();
+        assert!((cost - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_refresh_without_projects_dir_indexes_nothing() {
+        let claude_dir = TempDir::new().unwrap();
+        let (db, pricing) = index_db();
+
+        let summary = refresh(&db, &claude_dir.path().join("missing"), &pricing).unwrap();
+        assert_eq!(summary.files_scanned, 0);
+        assert_eq!(summary.entries_added, 0);
+        assert_eq!(entry_count(&db), 0);
+    }
+
+    #[test]
+    fn test_refresh_reingests_rewritten_files() {
+        let claude_dir = TempDir::new().unwrap();
+        let project_dir = claude_dir.path().join("projects").join("-work-demo");
+        fs::create_dir_all(&project_dir).unwrap();
+        let log_path = project_dir.join("s1.jsonl");
+        let (db, pricing) = index_db();
+
+        fs::write(
+            &log_path,
+            format!(
+                "{}\n{}\n",
+                usage_line("m1", "r1", "2025-06-01T10:00:00Z", 1_000_000),
+                usage_line("m2", "r2", "2025-06-01T11:00:00Z", 1_000_000)
+            ),
+        )
+        .unwrap();
+        refresh(&db, claude_dir.path(), &pricing).unwrap();
+        assert_eq!(entry_count(&db), 2);
+
+        // Shorter than the indexed offset: the old entries go
+        fs::write(
+            &log_path,
+            format!(
+                "{}\n",
+                usage_line("m3", "r3", "2025-06-02T10:00:00Z", 1_000)
+            ),
+        )
+        .unwrap();
+        refresh(&db, claude_dir.path(), &pricing).unwrap();
+        assert_eq!(entry_count(&db), 1);
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_and_empty_usage() {
+        let pricing = PricingTable::default();
+        let mut project_path = None;
+        let content = [
+            "not json",
+            r#"{"timestamp":"2025-06-01T10:00:00Z","cwd":"/work/demo"}"#,
+            r#"{"timestamp":"2025-06-01T10:00:00Z","message":{"usage":{"input_tokens":0,"output_tokens":0}}}"#,
+            r#"{"timestamp":"2025-06-01T10:00:00Z","costUSD":0.5,"message":{"usage":{"input_tokens":7}}}"#,
+        ]
+        .join("\n");
+
+        let entries = parse_usage_lines(&content, "fallback", &mut project_path, &pricing);
+        assert_eq!(project_path.as_deref(), Some("/work/demo"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].model, "unknown");
+        assert_eq!(entries[0].session_id, "fallback");
+        assert_eq!(entries[0].recorded_cost, Some(0.5));
+        assert!(entries[0].dedup_key.is_none());
+    }
+}
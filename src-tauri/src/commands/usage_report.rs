@@ -0,0 +1,884 @@
+//! Usage digests.
+//!
+//! Renders a Markdown or HTML summary of indexed usage for a date range —
+//! totals against the previous period of the same length, daily cost, top
+//! projects, agents and sessions, and cache efficiency — on demand or on a
+//! daily/weekly schedule that writes the digest into a configured directory.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+use super::agents::AgentDb;
+use super::usage::{
+    get_u64, parse_date_bound, project_name, query_team_usage, query_usage_stats,
+    refresh_usage_index, DailyUsage, ModelUsage, ProjectUsage, TeamMemberUsage, DATE_RANGE_SQL,
+    TOTAL_TOKENS_SQL,
+};
+
+const SCHEDULE_SETTING_KEY: &str = "usage_report_schedule";
+
+/// Rows shown in each "top" table
+const TOP_N: usize = 5;
+
+/// How often the scheduler checks whether a digest is due
+const SCHEDULER_INTERVAL_SECS: u64 = 15 * 60;
+
+/// Output format of a usage report
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl UsageReportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            UsageReportFormat::Markdown => "md",
+            UsageReportFormat::Html => "html",
+        }
+    }
+}
+
+/// How often the scheduled digest is written
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageReportFrequency {
+    Daily,
+    #[default]
+    Weekly,
+}
+
+/// Schedule of the usage digest, stored as JSON in `app_settings`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageReportSchedule {
+    pub enabled: bool,
+    pub frequency: UsageReportFrequency,
+    /// Day weekly digests are written, 0 = Monday
+    #[serde(default)]
+    pub weekday: u32,
+    /// Local hour digests are written
+    #[serde(default = "default_hour")]
+    pub hour: u32,
+    #[serde(default)]
+    pub format: UsageReportFormat,
+    pub output_dir: Option<String>,
+    /// When the last scheduled digest was written (managed by the scheduler)
+    #[serde(default)]
+    pub last_run_at: Option<String>,
+}
+
+fn default_hour() -> u32 {
+    9
+}
+
+impl Default for UsageReportSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: UsageReportFrequency::default(),
+            weekday: 0,
+            hour: default_hour(),
+            format: UsageReportFormat::default(),
+            output_dir: None,
+            last_run_at: None,
+        }
+    }
+}
+
+/// Session cost, labelled with its team member when it belongs to one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionCost {
+    pub session_id: String,
+    pub project_path: String,
+    pub project_name: String,
+    pub agent_name: Option<String>,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub entry_count: u64,
+    pub last_used: String,
+}
+
+/// How much of the prompt was served from the prompt cache
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEfficiency {
+    pub input_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    /// Cache reads over all prompt tokens
+    pub hit_ratio: Option<f64>,
+    /// Cache reads per cache write
+    pub reads_per_write: Option<f64>,
+}
+
+/// A usage digest for an inclusive date range
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub previous_start_date: String,
+    pub previous_end_date: String,
+    pub generated_at: String,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub total_requests: u64,
+    pub previous_total_cost: f64,
+    pub previous_total_tokens: u64,
+    pub previous_total_requests: u64,
+    /// Percentage change of cost against the previous period
+    pub cost_change_pct: Option<f64>,
+    /// Daily cost, oldest first
+    pub daily: Vec<DailyUsage>,
+    pub by_model: Vec<ModelUsage>,
+    pub top_projects: Vec<ProjectUsage>,
+    pub top_agents: Vec<TeamMemberUsage>,
+    pub top_sessions: Vec<SessionCost>,
+    pub cache: CacheEfficiency,
+}
+
+fn query_top_sessions(
+    conn: &Connection,
+    start: &str,
+    end: &str,
+    limit: usize,
+) -> SqliteResult<Vec<SessionCost>> {
+    conn.prepare(&format!(
+        "SELECT u.session_id, u.project_path, a.name, SUM(u.cost), SUM({}), COUNT(*),
+                MAX(u.timestamp)
+         FROM usage_entries u
+         LEFT JOIN project_agents pa ON pa.id = u.session_id
+         LEFT JOIN agents a ON a.id = pa.agent_id
+         WHERE {}
+         GROUP BY u.session_id, u.project_path ORDER BY SUM(u.cost) DESC LIMIT ?3",
+        TOTAL_TOKENS_SQL, DATE_RANGE_SQL
+    ))?
+    .query_map(params![start, end, limit as i64], |row| {
+        let project_path: String = row.get(1)?;
+        Ok(SessionCost {
+            session_id: row.get(0)?,
+            project_name: project_name(&project_path),
+            project_path,
+            agent_name: row.get(2)?,
+            total_cost: row.get(3)?,
+            total_tokens: get_u64(row, 4)?,
+            entry_count: get_u64(row, 5)?,
+            last_used: row.get(6)?,
+        })
+    })?
+    .collect()
+}
+
+/// Build the digest for an inclusive date range
+fn build_report(conn: &Connection, start: NaiveDate, end: NaiveDate) -> SqliteResult<UsageReport> {
+    let days = (end - start).num_days() + 1;
+    let previous_end = start - Duration::days(1);
+    let previous_start = previous_end - Duration::days(days - 1);
+    let fmt = |d: NaiveDate| d.format("%Y-%m-%d").to_string();
+    let (start_s, end_s) = (fmt(start), fmt(end));
+
+    let stats = query_usage_stats(conn, Some(&start_s), Some(&end_s))?;
+    let previous = query_usage_stats(conn, Some(&fmt(previous_start)), Some(&fmt(previous_end)))?;
+    let team = query_team_usage(conn, None, Some(&start_s), Some(&end_s))?;
+    let top_sessions = query_top_sessions(conn, &start_s, &end_s, TOP_N)?;
+
+    let prompt_tokens = stats.total_input_tokens
+        + stats.total_cache_creation_tokens
+        + stats.total_cache_read_tokens;
+    let cache = CacheEfficiency {
+        input_tokens: stats.total_input_tokens,
+        cache_creation_tokens: stats.total_cache_creation_tokens,
+        cache_read_tokens: stats.total_cache_read_tokens,
+        hit_ratio: (prompt_tokens > 0)
+            .then(|| stats.total_cache_read_tokens as f64 / prompt_tokens as f64),
+        reads_per_write: (stats.total_cache_creation_tokens > 0).then(|| {
+            stats.total_cache_read_tokens as f64 / stats.total_cache_creation_tokens as f64
+        }),
+    };
+
+    let mut daily = stats.by_date;
+    daily.reverse();
+
+    Ok(UsageReport {
+        start_date: start_s,
+        end_date: end_s,
+        previous_start_date: fmt(previous_start),
+        previous_end_date: fmt(previous_end),
+        generated_at: Local::now().to_rfc3339(),
+        total_cost: stats.total_cost,
+        total_tokens: stats.total_tokens,
+        total_requests: stats.total_sessions,
+        previous_total_cost: previous.total_cost,
+        previous_total_tokens: previous.total_tokens,
+        previous_total_requests: previous.total_sessions,
+        cost_change_pct: (previous.total_cost > 0.0)
+            .then(|| (stats.total_cost - previous.total_cost) / previous.total_cost * 100.0),
+        daily,
+        by_model: stats.by_model,
+        top_projects: stats.by_project.into_iter().take(TOP_N).collect(),
+        top_agents: team.by_member.into_iter().take(TOP_N).collect(),
+        top_sessions,
+        cache,
+    })
+}
+
+fn format_cost(cost: f64) -> String {
+    format!("${:.2}", cost)
+}
+
+/// Group digits in thousands: 1234567 -> 1,234,567
+fn format_tokens(tokens: u64) -> String {
+    let digits = tokens.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn format_pct(value: Option<f64>) -> String {
+    value.map_or_else(|| "n/a".to_string(), |v| format!("{:.1}%", v))
+}
+
+fn format_change(value: Option<f64>) -> String {
+    value.map_or_else(|| "n/a".to_string(), |v| format!("{:+.1}%", v))
+}
+
+/// Body of a report section
+enum Block {
+    Table {
+        headers: Vec<&'static str>,
+        rows: Vec<Vec<String>>,
+    },
+    List(Vec<String>),
+}
+
+struct Section {
+    title: &'static str,
+    block: Block,
+}
+
+fn report_sections(report: &UsageReport) -> Vec<Section> {
+    let mut sections = vec![Section {
+        title: "Summary",
+        block: Block::Table {
+            headers: vec!["", "This period", "Previous period", "Change"],
+            rows: vec![
+                vec![
+                    "Cost".to_string(),
+                    format_cost(report.total_cost),
+                    format_cost(report.previous_total_cost),
+                    format_change(report.cost_change_pct),
+                ],
+                vec![
+                    "Tokens".to_string(),
+                    format_tokens(report.total_tokens),
+                    format_tokens(report.previous_total_tokens),
+                    String::new(),
+                ],
+                vec![
+                    "Requests".to_string(),
+                    format_tokens(report.total_requests),
+                    format_tokens(report.previous_total_requests),
+                    String::new(),
+                ],
+            ],
+        },
+    }];
+
+    sections.push(Section {
+        title: "Cost trend",
+        block: Block::Table {
+            headers: vec!["Date", "Cost", "Tokens", "Models"],
+            rows: report
+                .daily
+                .iter()
+                .map(|d| {
+                    vec![
+                        d.date.clone(),
+                        format_cost(d.total_cost),
+                        format_tokens(d.total_tokens),
+                        d.models_used.join(", "),
+                    ]
+                })
+                .collect(),
+        },
+    });
+
+    sections.push(Section {
+        title: "Top projects",
+        block: Block::Table {
+            headers: vec!["Project", "Cost", "Tokens", "Requests", "Last used"],
+            rows: report
+                .top_projects
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.project_name.clone(),
+                        format_cost(p.total_cost),
+                        format_tokens(p.total_tokens),
+                        format_tokens(p.session_count),
+                        p.last_used.clone(),
+                    ]
+                })
+                .collect(),
+        },
+    });
+
+    sections.push(Section {
+        title: "Top agents",
+        block: Block::Table {
+            headers: vec!["Agent", "Project", "Role", "Cost", "Tokens"],
+            rows: report
+                .top_agents
+                .iter()
+                .map(|m| {
+                    vec![
+                        m.agent_name.clone(),
+                        m.project_name.clone(),
+                        m.role_type.clone(),
+                        format_cost(m.total_cost),
+                        format_tokens(m.total_tokens),
+                    ]
+                })
+                .collect(),
+        },
+    });
+
+    sections.push(Section {
+        title: "Most expensive sessions",
+        block: Block::Table {
+            headers: vec!["Session", "Project", "Agent", "Cost", "Tokens", "Last used"],
+            rows: report
+                .top_sessions
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.session_id.clone(),
+                        s.project_name.clone(),
+                        s.agent_name.clone().unwrap_or_default(),
+                        format_cost(s.total_cost),
+                        format_tokens(s.total_tokens),
+                        s.last_used.clone(),
+                    ]
+                })
+                .collect(),
+        },
+    });
+
+    let cache = &report.cache;
+    sections.push(Section {
+        title: "Cache efficiency",
+        block: Block::List(vec![
+            format!(
+                "Hit ratio: {} of prompt tokens served from cache",
+                format_pct(cache.hit_ratio.map(|r| r * 100.0))
+            ),
+            format!(
+                "Cache reads: {} tokens, cache writes: {} tokens, uncached input: {} tokens",
+                format_tokens(cache.cache_read_tokens),
+                format_tokens(cache.cache_creation_tokens),
+                format_tokens(cache.input_tokens)
+            ),
+            format!(
+                "Reads per write: {}",
+                cache
+                    .reads_per_write
+                    .map_or_else(|| "n/a".to_string(), |r| format!("{:.1}", r))
+            ),
+        ]),
+    });
+
+    sections
+}
+
+fn report_title(report: &UsageReport) -> String {
+    format!("Usage digest {} to {}", report.start_date, report.end_date)
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn render_markdown(report: &UsageReport) -> String {
+    let mut out = format!(
+        "# {}\n\nGenerated {}. Compared with {} to {}.\n",
+        report_title(report),
+        report.generated_at,
+        report.previous_start_date,
+        report.previous_end_date
+    );
+    for section in report_sections(report) {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        match section.block {
+            Block::Table { headers, rows } => {
+                if rows.is_empty() {
+                    out.push_str("No usage recorded.\n");
+                    continue;
+                }
+                out.push_str(&format!("| {} |\n", headers.join(" | ")));
+                let align: Vec<&str> = headers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, _)| if i == 0 { "---" } else { "---:" })
+                    .collect();
+                out.push_str(&format!("| {} |\n", align.join(" | ")));
+                for row in rows {
+                    let cells: Vec<String> = row.iter().map(|c| markdown_cell(c)).collect();
+                    out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                }
+            }
+            Block::List(items) => {
+                for item in items {
+                    out.push_str(&format!("- {}\n", item));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(report: &UsageReport) -> String {
+    let title = html_escape(&report_title(report));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:960px;margin:2em auto}}\
+         table{{border-collapse:collapse}}th,td{{border:1px solid #ccc;padding:4px 8px}}\
+         td{{text-align:right}}td:first-child{{text-align:left}}</style>\n</head>\n<body>\n\
+         <h1>{}</h1>\n<p>Generated {}. Compared with {} to {}.</p>\n",
+        title,
+        title,
+        html_escape(&report.generated_at),
+        html_escape(&report.previous_start_date),
+        html_escape(&report.previous_end_date)
+    );
+    for section in report_sections(report) {
+        out.push_str(&format!("<h2>{}</h2>\n", html_escape(section.title)));
+        match section.block {
+            Block::Table { headers, rows } => {
+                if rows.is_empty() {
+                    out.push_str("<p>No usage recorded.</p>\n");
+                    continue;
+                }
+                out.push_str("<table>\n<tr>");
+                for header in headers {
+                    out.push_str(&format!("<th>{}</th>", html_escape(header)));
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        out.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Block::List(items) => {
+                out.push_str("<ul>\n");
+                for item in items {
+                    out.push_str(&format!("<li>{}</li>\n", html_escape(&item)));
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn render_report(report: &UsageReport, format: UsageReportFormat) -> String {
+    match format {
+        UsageReportFormat::Markdown => render_markdown(report),
+        UsageReportFormat::Html => render_html(report),
+    }
+}
+
+fn load_schedule(conn: &Connection) -> Result<UsageReportSchedule, String> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![SCHEDULE_SETTING_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match value {
+        Some(json) => {
+            serde_json::from_str(&json).map_err(|e| format!("Invalid usage report schedule: {}", e))
+        }
+        None => Ok(UsageReportSchedule::default()),
+    }
+}
+
+fn store_schedule(conn: &Connection, schedule: &UsageReportSchedule) -> Result<(), String> {
+    let json = serde_json::to_string(schedule).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![SCHEDULE_SETTING_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save usage report schedule: {}", e))?;
+    Ok(())
+}
+
+/// The most recent scheduled run at or before `now`
+fn last_scheduled_run(schedule: &UsageReportSchedule, now: DateTime<Local>) -> DateTime<Local> {
+    let today = now.date_naive();
+    let at = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(schedule.hour, 0, 0).unwrap_or_default())
+            .earliest()
+            .unwrap_or(now)
+    };
+    match schedule.frequency {
+        UsageReportFrequency::Daily => {
+            let run = at(today);
+            if run <= now {
+                run
+            } else {
+                at(today - Duration::days(1))
+            }
+        }
+        UsageReportFrequency::Weekly => {
+            let days_since = (today.weekday().num_days_from_monday() + 7 - schedule.weekday) % 7;
+            let run = at(today - Duration::days(days_since as i64));
+            if run <= now {
+                run
+            } else {
+                at(today - Duration::days(days_since as i64 + 7))
+            }
+        }
+    }
+}
+
+/// Date range covered by a digest written on `run_date`: the full days
+/// of the period that ended before it
+fn scheduled_range(frequency: UsageReportFrequency, run_date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let end = run_date - Duration::days(1);
+    let days = match frequency {
+        UsageReportFrequency::Daily => 1,
+        UsageReportFrequency::Weekly => 7,
+    };
+    (end - Duration::days(days - 1), end)
+}
+
+/// Write the scheduled digest if one is due; returns the written file
+fn write_due_report(db: &AgentDb, now: DateTime<Local>) -> Result<Option<PathBuf>, String> {
+    let schedule = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_schedule(&conn)?
+    };
+    let Some(output_dir) = schedule.output_dir.clone().filter(|_| schedule.enabled) else {
+        return Ok(None);
+    };
+    let due = last_scheduled_run(&schedule, now);
+    let last_run = schedule
+        .last_run_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    if last_run.is_some_and(|t| t >= due) {
+        return Ok(None);
+    }
+
+    refresh_usage_index(db)?;
+
+    let (start, end) = scheduled_range(schedule.frequency, due.date_naive());
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let report = build_report(&conn, start, end)
+        .map_err(|e| format!("Failed to build usage report: {}", e))?;
+    let dir = PathBuf::from(output_dir);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create report directory: {}", e))?;
+    let path = dir.join(format!(
+        "usage-digest-{}-to-{}.{}",
+        report.start_date,
+        report.end_date,
+        schedule.format.extension()
+    ));
+    std::fs::write(&path, render_report(&report, schedule.format))
+        .map_err(|e| format!("Failed to write usage report: {}", e))?;
+
+    store_schedule(
+        &conn,
+        &UsageReportSchedule {
+            last_run_at: Some(now.to_rfc3339()),
+            ..schedule
+        },
+    )?;
+    Ok(Some(path))
+}
+
+/// Background task writing scheduled digests; emits `usage-report-written`
+/// with the file path after each one
+pub async fn run_report_scheduler(app: AppHandle) {
+    loop {
+        let db = AgentDb(app.state::<AgentDb>().0.clone());
+        let result = tokio::task::spawn_blocking(move || write_due_report(&db, Local::now())).await;
+        match result {
+            Ok(Ok(Some(path))) => {
+                log::info!("Wrote usage digest to {}", path.display());
+                let _ = app.emit("usage-report-written", path.to_string_lossy().to_string());
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => log::warn!("Failed to write scheduled usage digest: {}", e),
+            Err(e) => log::warn!("Usage digest task failed: {}", e),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(SCHEDULER_INTERVAL_SECS)).await;
+    }
+}
+
+/// Render a usage digest for an inclusive YYYY-MM-DD date range
+/// (default: the last 7 days including today)
+#[command]
+pub fn generate_usage_report(
+    db: State<'_, AgentDb>,
+    format: Option<UsageReportFormat>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<String, String> {
+    let parse = |value: Option<String>, label: &str| -> Result<Option<NaiveDate>, String> {
+        Ok(parse_date_bound(value, label)?
+            .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()))
+    };
+    let end = parse(end_date, "end")?.unwrap_or_else(|| Local::now().date_naive());
+    let start = parse(start_date, "start")?.unwrap_or(end - Duration::days(6));
+    if start > end {
+        return Err("Start date must not be after end date".to_string());
+    }
+
+    refresh_usage_index(&db)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let report = build_report(&conn, start, end)
+        .map_err(|e| format!("Failed to build usage report: {}", e))?;
+    Ok(render_report(&report, format.unwrap_or_default()))
+}
+
+/// Get the usage digest schedule
+#[command]
+pub fn get_usage_report_schedule(db: State<'_, AgentDb>) -> Result<UsageReportSchedule, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_schedule(&conn)
+}
+
+/// Save the usage digest schedule
+#[command]
+pub fn save_usage_report_schedule(
+    db: State<'_, AgentDb>,
+    schedule: UsageReportSchedule,
+) -> Result<(), String> {
+    if schedule.hour > 23 {
+        return Err("Hour must be between 0 and 23".to_string());
+    }
+    if schedule.weekday > 6 {
+        return Err("Weekday must be between 0 (Monday) and 6 (Sunday)".to_string());
+    }
+    if schedule.enabled
+        && schedule
+            .output_dir
+            .as_deref()
+            .is_none_or(|d| d.trim().is_empty())
+    {
+        return Err("An output directory is required to schedule usage reports".to_string());
+    }
+    log::info!("Saving usage report schedule: {:?}", schedule);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // The scheduler owns last_run_at; keep it so saving doesn't re-trigger a digest
+    let last_run_at = load_schedule(&conn)?.last_run_at;
+    store_schedule(
+        &conn,
+        &UsageReportSchedule {
+            last_run_at,
+            ..schedule
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+
+    #[test]
+    fn test_weekly_digest_compares_with_previous_week() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO usage_entries (dedup_key, file_path, timestamp, usage_date, model,
+                input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens, cost,
+                session_id, project_path)
+             VALUES
+                ('a', 'f', '2025-06-03T10:00:00Z', '2025-06-03', 'claude-sonnet-4', 100, 50, 0, 800, 3.0, 's1', '/work/alpha'),
+                ('b', 'f', '2025-06-04T10:00:00Z', '2025-06-04', 'claude-sonnet-4', 100, 50, 0, 0, 1.0, 's2', '/work/beta'),
+                ('c', 'f', '2025-05-28T10:00:00Z', '2025-05-28', 'claude-sonnet-4', 100, 50, 0, 0, 2.0, 's3', '/work/alpha');",
+        )
+        .unwrap();
+
+        let start = NaiveDate::from_ymd_opt(2025, 6, 2).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 6, 8).unwrap();
+        let report = build_report(&conn, start, end).unwrap();
+        assert_eq!(report.previous_start_date, "2025-05-26");
+        assert_eq!(report.previous_end_date, "2025-06-01");
+        assert_eq!(report.cost_change_pct, Some(100.0));
+        assert_eq!(report.top_sessions[0].session_id, "s1");
+        assert_eq!(report.cache.hit_ratio, Some(0.8));
+        assert_eq!(report.daily[0].date, "2025-06-03");
+
+        let markdown = render_markdown(&report);
+        assert!(markdown.contains("| Cost | $4.00 | $2.00 | +100.0% |"));
+        assert!(markdown.contains("| alpha | $3.00 | 950 | 1 |"));
+        assert!(render_html(&report).contains("<h2>Cache efficiency</h2>"));
+
+        // A weekly Monday 09:00 schedule checked on Wednesday covers the previous week
+        let schedule = UsageReportSchedule {
+            enabled: true,
+            output_dir: Some("/tmp".to_string()),
+            ..Default::default()
+        };
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        let run = last_scheduled_run(&schedule, now);
+        assert_eq!(
+            run.date_naive(),
+            NaiveDate::from_ymd_opt(2025, 6, 9).unwrap()
+        );
+        assert_eq!(
+            scheduled_range(schedule.frequency, run.date_naive()),
+            (start, end)
+        );
+    }
+
+    #[test]
+    fn test_report_of_empty_tables() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        let day = NaiveDate::from_ymd_opt(2025, 6, 2).unwrap();
+        let report = build_report(&conn, day, day).unwrap();
+        assert_eq!(report.previous_start_date, "2025-06-01");
+        assert_eq!(report.previous_end_date, "2025-06-01");
+        assert_eq!(report.total_cost, 0.0);
+        assert_eq!(report.cost_change_pct, None);
+        assert_eq!(report.cache.hit_ratio, None);
+        assert_eq!(report.cache.reads_per_write, None);
+        assert!(report.daily.is_empty() && report.top_sessions.is_empty());
+
+        let markdown = render_markdown(&report);
+        assert!(markdown.contains("| Cost | $0.00 | $0.00 | n/a |"));
+        assert_eq!(markdown.matches("No usage recorded.").count(), 4);
+        assert!(markdown.contains("Hit ratio: n/a"));
+    }
+
+    #[test]
+    fn test_rendering_escapes_and_groups_digits() {
+        assert_eq!(format_tokens(0), "0");
+        assert_eq!(format_tokens(999), "999");
+        assert_eq!(format_tokens(1_234_567), "1,234,567");
+        assert_eq!(format_change(Some(-12.34)), "-12.3%");
+
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path)
+             VALUES ('f', '2025-06-02T10:00:00Z', '2025-06-02', 'm', 1000, 1.0, 's1', '/work/<a|b>');",
+        )
+        .unwrap();
+        let day = NaiveDate::from_ymd_opt(2025, 6, 2).unwrap();
+        let report = build_report(&conn, day, day).unwrap();
+        assert!(render_markdown(&report).contains("| <a\\|b> | $1.00 | 1,000 |"));
+        let html = render_html(&report);
+        assert!(html.contains("&lt;a|b&gt;"));
+        assert!(!html.contains("<a|b>"));
+    }
+
+    #[test]
+    fn test_scheduled_runs_before_the_hour_fall_back_a_period() {
+        let daily = UsageReportSchedule {
+            frequency: UsageReportFrequency::Daily,
+            ..Default::default()
+        };
+        let early = Local.with_ymd_and_hms(2025, 6, 11, 8, 59, 0).unwrap();
+        let run = last_scheduled_run(&daily, early);
+        assert_eq!(run, Local.with_ymd_and_hms(2025, 6, 10, 9, 0, 0).unwrap());
+        let on_time = Local.with_ymd_and_hms(2025, 6, 11, 9, 0, 0).unwrap();
+        assert_eq!(last_scheduled_run(&daily, on_time), on_time);
+        let day = NaiveDate::from_ymd_opt(2025, 6, 11).unwrap();
+        let yesterday = NaiveDate::from_ymd_opt(2025, 6, 10).unwrap();
+        assert_eq!(
+            scheduled_range(UsageReportFrequency::Daily, day),
+            (yesterday, yesterday)
+        );
+
+        // Monday before 09:00 still belongs to the previous week's run
+        let weekly = UsageReportSchedule::default();
+        let monday = Local.with_ymd_and_hms(2025, 6, 9, 8, 0, 0).unwrap();
+        assert_eq!(
+            last_scheduled_run(&weekly, monday),
+            Local.with_ymd_and_hms(2025, 6, 2, 9, 0, 0).unwrap()
+        );
+        let sunday = UsageReportSchedule {
+            weekday: 6,
+            hour: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            last_scheduled_run(&sunday, monday),
+            Local.with_ymd_and_hms(2025, 6, 8, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_due_reports_skip_disabled_and_already_written_schedules() {
+        let db = AgentDb(std::sync::Arc::new(std::sync::Mutex::new(
+            init_database_with_path(std::path::Path::new(":memory:")).unwrap(),
+        )));
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        assert_eq!(write_due_report(&db, now).unwrap(), None);
+
+        let conn = db.0.lock().unwrap();
+        let schedule = UsageReportSchedule {
+            enabled: false,
+            output_dir: Some("/nonexistent/reports".to_string()),
+            ..Default::default()
+        };
+        store_schedule(&conn, &schedule).unwrap();
+        drop(conn);
+        assert_eq!(write_due_report(&db, now).unwrap(), None);
+
+        let conn = db.0.lock().unwrap();
+        let schedule = UsageReportSchedule {
+            enabled: true,
+            last_run_at: Some(
+                Local
+                    .with_ymd_and_hms(2025, 6, 9, 9, 30, 0)
+                    .unwrap()
+                    .to_rfc3339(),
+            ),
+            ..schedule
+        };
+        store_schedule(&conn, &schedule).unwrap();
+        drop(conn);
+        assert_eq!(write_due_report(&db, now).unwrap(), None);
+
+        let conn = db.0.lock().unwrap();
+        conn.execute(
+            "UPDATE app_settings SET value = '{' WHERE key = ?1",
+            params![SCHEDULE_SETTING_KEY],
+        )
+        .unwrap();
+        drop(conn);
+        let err = write_due_report(&db, now).unwrap_err();
+        assert!(err.contains("Invalid usage report schedule"));
+    }
+}
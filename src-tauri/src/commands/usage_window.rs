@@ -0,0 +1,564 @@
+//! Rolling usage windows.
+//!
+//! Claude subscriptions meter usage in 5-hour windows that open with the
+//! first message. Entries from the usage index are grouped into such
+//! windows: a window starts at the hour of its first entry and a new one
+//! opens once an entry falls past the window's end or after a 5-hour gap.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, State};
+
+use super::agents::AgentDb;
+use super::usage::refresh_usage_index;
+use crate::events::EventEmitter;
+
+/// Length of a usage window
+const WINDOW_HOURS: i64 = 5;
+
+const LIMIT_SETTING_KEY: &str = "usage_window_limit_tokens";
+
+/// Start of the window a `usage-window-warning` was last emitted for
+static LAST_WARNED_WINDOW: Mutex<Option<String>> = Mutex::new(None);
+
+/// Usage of one session inside a window
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WindowSession {
+    pub session_id: String,
+    pub project_path: String,
+    pub tokens: u64,
+    pub cost: f64,
+    pub entry_count: u64,
+}
+
+/// A rolling usage window.
+/// `tokens` counts input and output tokens, which is what the window limit
+/// applies to; cache tokens are reported separately.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageWindow {
+    pub start: String,
+    pub end: String,
+    pub first_entry: String,
+    pub last_entry: String,
+    pub is_active: bool,
+    pub tokens: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cost: f64,
+    pub entry_count: u64,
+    pub models: Vec<String>,
+    /// Sessions consuming the window, largest first
+    pub sessions: Vec<WindowSession>,
+    /// Tokens per minute between the first and last entry
+    pub burn_rate_tokens_per_minute: f64,
+    pub cost_per_hour: f64,
+    /// Tokens at the window's end if the current burn rate holds (active windows only)
+    pub projected_tokens: Option<u64>,
+    pub projected_cost: Option<f64>,
+    pub limit_tokens: Option<u64>,
+    /// When the limit is reached at the current burn rate, if before the window resets
+    pub projected_exhaustion: Option<String>,
+}
+
+/// Window settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UsageWindowSettings {
+    /// Input + output token limit per window; warnings are off when unset
+    pub limit_tokens: Option<u64>,
+}
+
+/// Payload of the `usage-window-warning` event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageWindowWarning {
+    pub window: UsageWindow,
+    pub limit_tokens: u64,
+}
+
+struct WindowEntry {
+    timestamp: DateTime<Utc>,
+    model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_creation_tokens: u64,
+    cache_read_tokens: u64,
+    cost: f64,
+    session_id: String,
+    project_path: String,
+}
+
+fn load_entries(conn: &Connection, since: DateTime<Utc>) -> SqliteResult<Vec<WindowEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, model, input_tokens, output_tokens, cache_creation_tokens,
+                cache_read_tokens, cost, session_id, project_path
+         FROM usage_entries WHERE usage_date >= ?1 ORDER BY timestamp, id",
+    )?;
+    let rows = stmt.query_map(params![since.format("%Y-%m-%d").to_string()], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            WindowEntry {
+                timestamp: DateTime::<Utc>::MIN_UTC,
+                model: row.get(1)?,
+                input_tokens: row.get::<_, i64>(2)? as u64,
+                output_tokens: row.get::<_, i64>(3)? as u64,
+                cache_creation_tokens: row.get::<_, i64>(4)? as u64,
+                cache_read_tokens: row.get::<_, i64>(5)? as u64,
+                cost: row.get(6)?,
+                session_id: row.get(7)?,
+                project_path: row.get(8)?,
+            },
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (timestamp, mut entry) = row?;
+        let Ok(parsed) = DateTime::parse_from_rfc3339(&timestamp) else {
+            continue;
+        };
+        entry.timestamp = parsed.with_timezone(&Utc);
+        if entry.timestamp >= since {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn build_window(
+    start: DateTime<Utc>,
+    entries: &[WindowEntry],
+    now: DateTime<Utc>,
+    limit_tokens: Option<u64>,
+) -> UsageWindow {
+    let window = Duration::hours(WINDOW_HOURS);
+    let end = start + window;
+    let first = entries.first().map(|e| e.timestamp).unwrap_or(start);
+    let last = entries.last().map(|e| e.timestamp).unwrap_or(start);
+
+    let mut input_tokens = 0;
+    let mut output_tokens = 0;
+    let mut cache_creation_tokens = 0;
+    let mut cache_read_tokens = 0;
+    let mut cost = 0.0;
+    let mut models: Vec<String> = Vec::new();
+    let mut sessions: HashMap<String, WindowSession> = HashMap::new();
+    for e in entries {
+        input_tokens += e.input_tokens;
+        output_tokens += e.output_tokens;
+        cache_creation_tokens += e.cache_creation_tokens;
+        cache_read_tokens += e.cache_read_tokens;
+        cost += e.cost;
+        if !models.contains(&e.model) {
+            models.push(e.model.clone());
+        }
+        let session = sessions
+            .entry(e.session_id.clone())
+            .or_insert_with(|| WindowSession {
+                session_id: e.session_id.clone(),
+                project_path: e.project_path.clone(),
+                tokens: 0,
+                cost: 0.0,
+                entry_count: 0,
+            });
+        session.tokens += e.input_tokens + e.output_tokens;
+        session.cost += e.cost;
+        session.entry_count += 1;
+    }
+    let tokens = input_tokens + output_tokens;
+
+    let mut sessions: Vec<WindowSession> = sessions.into_values().collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.tokens));
+
+    // Burn rate over the span actually used; a single entry counts as one minute
+    let elapsed_minutes = ((last - first).num_seconds() as f64 / 60.0).max(1.0);
+    let burn_rate_tokens_per_minute = tokens as f64 / elapsed_minutes;
+    let cost_per_minute = cost / elapsed_minutes;
+
+    let is_active = now < end && now - last < window;
+    let (projected_tokens, projected_cost, projected_exhaustion) = if is_active {
+        let remaining_minutes = (end - now).num_seconds().max(0) as f64 / 60.0;
+        let projected_tokens =
+            tokens + (burn_rate_tokens_per_minute * remaining_minutes).round() as u64;
+        let projected_cost = cost + cost_per_minute * remaining_minutes;
+        let exhaustion = limit_tokens.and_then(|limit| {
+            if tokens >= limit {
+                return Some(last);
+            }
+            if burn_rate_tokens_per_minute <= 0.0 {
+                return None;
+            }
+            let minutes = (limit - tokens) as f64 / burn_rate_tokens_per_minute;
+            let at = now + Duration::seconds((minutes * 60.0) as i64);
+            (at < end).then_some(at)
+        });
+        (Some(projected_tokens), Some(projected_cost), exhaustion)
+    } else {
+        (None, None, None)
+    };
+
+    UsageWindow {
+        start: start.to_rfc3339(),
+        end: end.to_rfc3339(),
+        first_entry: first.to_rfc3339(),
+        last_entry: last.to_rfc3339(),
+        is_active,
+        tokens,
+        input_tokens,
+        output_tokens,
+        cache_creation_tokens,
+        cache_read_tokens,
+        cost,
+        entry_count: entries.len() as u64,
+        models,
+        sessions,
+        burn_rate_tokens_per_minute,
+        cost_per_hour: cost_per_minute * 60.0,
+        projected_tokens,
+        projected_cost,
+        limit_tokens,
+        projected_exhaustion: projected_exhaustion.map(|t| t.to_rfc3339()),
+    }
+}
+
+/// Group time-ordered entries into windows, oldest first
+fn compute_windows(
+    entries: &[WindowEntry],
+    now: DateTime<Utc>,
+    limit_tokens: Option<u64>,
+) -> Vec<UsageWindow> {
+    let window = Duration::hours(WINDOW_HOURS);
+    let mut windows = Vec::new();
+    let mut current: Option<(DateTime<Utc>, usize)> = None;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if let Some((start, first_idx)) = current {
+            let last = entries[i - 1].timestamp;
+            if entry.timestamp - start < window && entry.timestamp - last < window {
+                continue;
+            }
+            windows.push(build_window(
+                start,
+                &entries[first_idx..i],
+                now,
+                limit_tokens,
+            ));
+        }
+        let start = entry
+            .timestamp
+            .duration_trunc(Duration::hours(1))
+            .unwrap_or(entry.timestamp);
+        current = Some((start, i));
+    }
+    if let Some((start, first_idx)) = current {
+        windows.push(build_window(
+            start,
+            &entries[first_idx..],
+            now,
+            limit_tokens,
+        ));
+    }
+
+    windows
+}
+
+fn load_settings(conn: &Connection) -> SqliteResult<UsageWindowSettings> {
+    let limit: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![LIMIT_SETTING_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(UsageWindowSettings {
+        limit_tokens: limit.and_then(|v| v.parse().ok()),
+    })
+}
+
+/// Windows over the last `days` days (the active one last)
+fn query_windows(
+    conn: &Connection,
+    days: u32,
+    now: DateTime<Utc>,
+) -> SqliteResult<Vec<UsageWindow>> {
+    let settings = load_settings(conn)?;
+    let entries = load_entries(conn, now - Duration::days(days as i64))?;
+    Ok(compute_windows(&entries, now, settings.limit_tokens))
+}
+
+/// The window that is currently open, if any
+fn query_active_window(conn: &Connection, now: DateTime<Utc>) -> SqliteResult<Option<UsageWindow>> {
+    let settings = load_settings(conn)?;
+    // A day of history is enough to align the open window with the ones before it
+    let entries = load_entries(conn, now - Duration::days(1))?;
+    Ok(compute_windows(&entries, now, settings.limit_tokens)
+        .pop()
+        .filter(|w| w.is_active))
+}
+
+/// Emit `usage-window-warning` once per window when the projected burn
+/// exceeds the configured limit before the window resets.
+/// Expects the usage index to be fresh.
+pub fn check_active_window(
+    events: &dyn EventEmitter,
+    db: &Arc<Mutex<Connection>>,
+) -> Result<(), String> {
+    let window = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        query_active_window(&conn, Utc::now()).map_err(|e| e.to_string())?
+    };
+    let Some(window) = window else {
+        return Ok(());
+    };
+    let (Some(limit_tokens), Some(projected)) = (window.limit_tokens, window.projected_tokens)
+    else {
+        return Ok(());
+    };
+    if projected < limit_tokens {
+        return Ok(());
+    }
+
+    let mut last_warned = LAST_WARNED_WINDOW.lock().map_err(|e| e.to_string())?;
+    if last_warned.as_deref() == Some(window.start.as_str()) {
+        return Ok(());
+    }
+    *last_warned = Some(window.start.clone());
+
+    log::warn!(
+        "Usage window started {} projected at {} of {} tokens",
+        window.start,
+        projected,
+        limit_tokens
+    );
+    events.emit(
+        "usage-window-warning",
+        &UsageWindowWarning {
+            window,
+            limit_tokens,
+        },
+    );
+    Ok(())
+}
+
+/// Usage windows over the last `days` days (default 7), oldest first
+#[tauri::command]
+pub async fn get_usage_windows(
+    db: State<'_, AgentDb>,
+    days: Option<u32>,
+) -> Result<Vec<UsageWindow>, String> {
+    refresh_usage_index(&db)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_windows(&conn, days.unwrap_or(7), Utc::now())
+        .map_err(|e| format!("Failed to compute usage windows: {}", e))
+}
+
+/// The currently open usage window with burn rate and projections
+#[tauri::command]
+pub async fn get_active_usage_window(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+) -> Result<Option<UsageWindow>, String> {
+    refresh_usage_index(&db)?;
+    check_active_window(&app, &db.0)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    query_active_window(&conn, Utc::now())
+        .map_err(|e| format!("Failed to compute usage window: {}", e))
+}
+
+/// Get the usage window settings
+#[tauri::command]
+pub async fn get_usage_window_settings(
+    db: State<'_, AgentDb>,
+) -> Result<UsageWindowSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_settings(&conn).map_err(|e| format!("Failed to load usage window settings: {}", e))
+}
+
+/// Save the usage window settings
+#[tauri::command]
+pub async fn save_usage_window_settings(
+    db: State<'_, AgentDb>,
+    settings: UsageWindowSettings,
+) -> Result<(), String> {
+    log::info!("Saving usage window settings: {:?}", settings);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    match settings.limit_tokens {
+        Some(limit) => conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![LIMIT_SETTING_KEY, limit.to_string()],
+        ),
+        None => conn.execute(
+            "DELETE FROM app_settings WHERE key = ?1",
+            params![LIMIT_SETTING_KEY],
+        ),
+    }
+    .map_err(|e| format!("Failed to save usage window settings: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+
+    fn entry(timestamp: &str, tokens: u64) -> WindowEntry {
+        WindowEntry {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .unwrap()
+                .with_timezone(&Utc),
+            model: "claude-sonnet-4".to_string(),
+            input_tokens: tokens,
+            output_tokens: 0,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost: 0.0,
+            session_id: "s1".to_string(),
+            project_path: "/work".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_windows_open_at_first_message_hour_and_project_burn() {
+        let entries = vec![
+            entry("2025-06-01T08:20:00Z", 100),
+            entry("2025-06-01T12:59:00Z", 100),
+            // Past 13:00, the end of the first window
+            entry("2025-06-01T13:05:00Z", 1_000),
+            entry("2025-06-01T13:15:00Z", 1_000),
+        ];
+        let now = DateTime::parse_from_rfc3339("2025-06-01T13:15:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let windows = compute_windows(&entries, now, Some(10_000));
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].start, "2025-06-01T08:00:00+00:00");
+        assert!(!windows[0].is_active);
+        assert_eq!(windows[0].tokens, 200);
+
+        let active = &windows[1];
+        assert!(active.is_active);
+        assert_eq!(active.start, "2025-06-01T13:00:00+00:00");
+        // 2000 tokens over 10 minutes, 285 minutes left in the window
+        assert_eq!(active.burn_rate_tokens_per_minute, 200.0);
+        assert_eq!(active.projected_tokens, Some(2_000 + 57_000));
+        // 8000 tokens left at 200/min
+        assert_eq!(
+            active.projected_exhaustion.as_deref(),
+            Some("2025-06-01T13:55:00+00:00")
+        );
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_no_entries_make_no_windows() {
+        assert!(compute_windows(&[], at("2025-06-01T13:00:00Z"), Some(1_000)).is_empty());
+
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        let now = at("2025-06-01T13:00:00Z");
+        assert!(query_windows(&conn, 7, now).unwrap().is_empty());
+        assert!(query_active_window(&conn, now).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_windows_close_at_their_end_and_stale_windows_project_nothing() {
+        let entries = vec![
+            entry("2025-06-01T08:10:00Z", 100),
+            entry("2025-06-01T08:20:00Z", 100),
+            entry("2025-06-01T13:30:00Z", 100),
+        ];
+        let windows = compute_windows(&entries, at("2025-06-02T00:00:00Z"), Some(10));
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1].start, "2025-06-01T13:00:00+00:00");
+        assert_eq!(windows[1].end, "2025-06-01T18:00:00+00:00");
+        for window in &windows {
+            assert!(!window.is_active);
+            assert_eq!(window.projected_tokens, None);
+            assert_eq!(window.projected_cost, None);
+            assert_eq!(window.projected_exhaustion, None);
+        }
+
+        let gap = vec![
+            entry("2025-06-01T08:00:00Z", 1),
+            entry("2025-06-01T12:59:59Z", 1),
+        ];
+        assert_eq!(
+            compute_windows(&gap, at("2025-06-01T13:00:00Z"), None).len(),
+            1
+        );
+        let gap = vec![
+            entry("2025-06-01T08:00:00Z", 1),
+            entry("2025-06-01T13:00:00Z", 1),
+        ];
+        assert_eq!(
+            compute_windows(&gap, at("2025-06-01T13:00:00Z"), None).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_exhaustion_at_the_limit_past_the_reset_and_without_a_limit() {
+        let now = at("2025-06-01T09:00:00Z");
+        let mut entries = vec![
+            entry("2025-06-01T08:30:00Z", 500),
+            entry("2025-06-01T08:40:00Z", 500),
+        ];
+        entries[1].session_id = "s2".to_string();
+        entries[1].output_tokens = 100;
+
+        // Already over the limit: exhausted at the entry that crossed it
+        let window = &compute_windows(&entries, now, Some(1_000))[0];
+        assert_eq!(
+            window.projected_exhaustion.as_deref(),
+            Some("2025-06-01T08:40:00+00:00")
+        );
+        assert_eq!(window.sessions[0].session_id, "s2");
+        assert_eq!(window.sessions[0].tokens, 600);
+
+        // 110 tokens/min with 4 hours left still stays under a large limit
+        let window = &compute_windows(&entries, now, Some(1_000_000))[0];
+        assert!(window.is_active);
+        assert_eq!(window.projected_exhaustion, None);
+        let window = &compute_windows(&entries, now, None)[0];
+        assert_eq!(window.limit_tokens, None);
+        assert_eq!(window.projected_exhaustion, None);
+        assert!(window.projected_tokens.is_some());
+    }
+
+    #[test]
+    fn test_single_entry_burns_over_one_minute() {
+        let windows = compute_windows(
+            &[entry("2025-06-01T08:30:00Z", 300)],
+            at("2025-06-01T08:30:00Z"),
+            None,
+        );
+        assert_eq!(windows[0].burn_rate_tokens_per_minute, 300.0);
+        assert_eq!(windows[0].first_entry, windows[0].last_entry);
+    }
+
+    #[test]
+    fn test_load_skips_unparsable_timestamps_and_bad_limits() {
+        let conn = init_database_with_path(std::path::Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO usage_entries (file_path, timestamp, usage_date, model, input_tokens, cost, session_id, project_path) VALUES
+                ('f', 'yesterday', '2025-06-01', 'm', 10, 0.0, 's1', '/work'),
+                ('f', '2025-06-01T08:30:00Z', '2025-06-01', 'm', 20, 0.0, 's1', '/work');
+             INSERT INTO app_settings (key, value) VALUES ('usage_window_limit_tokens', 'lots');",
+        )
+        .unwrap();
+
+        let windows = query_windows(&conn, 7, at("2025-06-01T09:00:00Z")).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].tokens, 20);
+        assert_eq!(windows[0].limit_tokens, None);
+    }
+}
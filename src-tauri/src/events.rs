@@ -0,0 +1,114 @@
+//! Event emission independent of the host
+//!
+//! The team runtime reports progress (new messages, member status,
+//! teammate errors, budget warnings) as named events. In the desktop app
+//! they go to the webview through `AppHandle::emit`; in web mode they are
+//! published on a broadcast channel that WebSocket clients subscribe to.
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast;
+
+/// Events kept for slow WebSocket subscribers before they start lagging
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// Sink for named events with a JSON payload
+pub trait EventEmitter: Send + Sync {
+    fn emit_value(&self, event: &str, payload: JsonValue);
+}
+
+impl dyn EventEmitter + '_ {
+    /// Serialize `payload` and emit it; failures are logged, not returned,
+    /// as with `AppHandle::emit` results the callers ignore
+    pub fn emit<T: Serialize + ?Sized>(&self, event: &str, payload: &T) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.emit_value(event, payload),
+            Err(e) => log::warn!("Failed to serialize {} event: {}", event, e),
+        }
+    }
+}
+
+impl EventEmitter for AppHandle {
+    fn emit_value(&self, event: &str, payload: JsonValue) {
+        if let Err(e) = Emitter::emit(self, event, payload) {
+            log::debug!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// An emitted event as sent to WebSocket clients
+#[derive(Debug, Clone, Serialize)]
+pub struct AppEvent {
+    pub event: String,
+    pub payload: JsonValue,
+}
+
+impl AppEvent {
+    /// Whether the event belongs to a project: its name is scoped to the
+    /// project or one of `member_ids` (`teammate-error:<member>`), or its
+    /// payload is the project id or carries it as `project_id`
+    pub fn concerns(&self, project_id: &str, member_ids: &[String]) -> bool {
+        if let Some((_, scope)) = self.event.split_once(':') {
+            return scope == project_id || member_ids.iter().any(|m| m == scope);
+        }
+        match &self.payload {
+            JsonValue::String(id) => id == project_id,
+            payload => payload.get("project_id").and_then(|v| v.as_str()) == Some(project_id),
+        }
+    }
+}
+
+/// Publishes events on a broadcast channel (web mode)
+pub struct BroadcastEmitter {
+    tx: broadcast::Sender<AppEvent>,
+}
+
+impl BroadcastEmitter {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for BroadcastEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventEmitter for BroadcastEmitter {
+    fn emit_value(&self, event: &str, payload: JsonValue) {
+        // No subscribers is not an error
+        let _ = self.tx.send(AppEvent {
+            event: event.to_string(),
+            payload,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_broadcast_events_are_scoped_to_projects() {
+        let emitter = BroadcastEmitter::new();
+        let mut rx = emitter.subscribe();
+        let sink: &dyn EventEmitter = &emitter;
+        sink.emit("new-message", &json!({"project_id": "p1", "content": "hi"}));
+        sink.emit("member-status-update", "p2");
+        sink.emit("teammate-error:m1", "boom");
+
+        let members = vec!["m1".to_string()];
+        let received: Vec<bool> = (0..3)
+            .map(|_| rx.try_recv().unwrap().concerns("p1", &members))
+            .collect();
+        assert_eq!(received, vec![true, false, true]);
+    }
+}
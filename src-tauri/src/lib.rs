@@ -5,9 +5,14 @@ pub mod approvals;
 pub mod checkpoint;
 pub mod claude_binary;
 pub mod commands;
+pub mod events;
+pub mod mcp;
 pub mod protocol;
 pub mod process;
+pub mod secrets;
+pub mod web_auth;
 pub mod web_server;
+pub mod web_tls;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -4,9 +4,13 @@
 mod checkpoint;
 mod claude_binary;
 mod commands;
+mod events;
+mod mcp;
 mod process;
+mod secrets;
 
 use checkpoint::state::CheckpointState;
+use commands::agent_mcp::set_agent_mcp_servers;
 use commands::agents::{
     cleanup_finished_processes, create_agent, delete_agent, execute_agent, export_agent,
     export_agent_to_file, fetch_github_agent_content, fetch_github_agents,
@@ -20,7 +24,12 @@ use commands::agents::{
 };
 use commands::claude::{
     cancel_claude_execution, check_auto_checkpoint, check_claude_version, cleanup_old_checkpoints,
-    clear_checkpoint_manager, continue_claude_code, create_checkpoint, create_project,
+    clear_checkpoint_manager, compare_timeline_branches, continue_claude_code, create_checkpoint,
+    create_project, create_timeline_branch, export_session_timeline, import_session_timeline,
+    apply_checkpoint_retention, get_checkpoint_retention_policy, get_checkpoint_storage_usage,
+    update_checkpoint_retention_policy, get_checkpoint_storage_settings,
+    set_checkpoint_storage_backend,
+    list_timeline_branches, prune_timeline_branch, switch_timeline_branch,
     execute_claude_code, find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff,
     get_checkpoint_settings, get_checkpoint_state_stats, get_claude_session_output,
     get_claude_settings, get_home_directory, get_hooks_config, get_project_sessions,
@@ -33,10 +42,22 @@ use commands::claude::{
 };
 use commands::mcp::{
     mcp_add, mcp_add_from_claude_desktop, mcp_add_json, mcp_get, mcp_get_server_status, mcp_list,
-    mcp_read_project_config, mcp_remove, mcp_reset_project_choices, mcp_save_project_config,
-    mcp_serve, mcp_test_connection,
+    mcp_apply_config_fix, mcp_get_server_catalog, mcp_get_tool_catalog, mcp_host_list,
+    mcp_host_logs, mcp_host_restart, mcp_host_start, mcp_host_stop, mcp_invoke_tool,
+    mcp_lint_config, mcp_probe_server, mcp_read_project_config, mcp_remove,
+    mcp_reset_project_choices, mcp_save_project_config, mcp_serve, mcp_test_connection,
 };
 
+use commands::budget::{delete_budget, get_budget_status, list_budgets, save_budget};
+use commands::secrets::{
+    delete_secret, get_secret_store_status, init_secret_store, list_secrets, lock_secret_store,
+    set_secret, unlock_secret_store,
+};
+use commands::pricing::{
+    delete_model_pricing, export_model_pricing, import_model_pricing, list_model_pricing,
+    upsert_model_pricing,
+};
+use commands::run_metrics::{get_agent_run_metric_trends, get_run_turn_metrics};
 use commands::proxy::{apply_proxy_settings, get_proxy_settings, save_proxy_settings};
 use commands::storage::{
     check_directory_status, storage_create_project, storage_list_projects,
@@ -45,10 +66,18 @@ use commands::storage::{
     create_project_team_skill, complete_project_initialization,
 };
 use commands::usage::{
-    get_session_stats, get_usage_by_date_range, get_usage_details, get_usage_stats,
+    export_usage, export_usage_to_file, get_session_stats, get_team_usage,
+    get_usage_by_date_range, get_usage_details, get_usage_stats,
+};
+use commands::usage_report::{
+    generate_usage_report, get_usage_report_schedule, save_usage_report_schedule,
+};
+use commands::usage_window::{
+    get_active_usage_window, get_usage_window_settings, get_usage_windows,
+    save_usage_window_settings,
 };
 use commands::teammate::{
-    get_project_member_statuses, get_teammate_status, send_to_teammate, start_teammate_agent, stop_teammate_agent,
+    get_project_member_statuses, get_teammate_status, list_project_checkpoints, send_to_teammate, start_teammate_agent, stop_teammate_agent,
 };
 use commands::message::{
     get_messages, save_message_response, send_message,
@@ -129,6 +158,16 @@ fn main() {
             let conn = init_database(&app.handle()).expect("Failed to initialize agents database");
             app.manage(AgentDb(std::sync::Arc::new(Mutex::new(conn))));
 
+            // Open the secret store referenced by MCP configs
+            if let Ok(app_data_dir) = app.path().app_data_dir() {
+                secrets::init_global(secrets::store_path(&app_data_dir));
+            }
+
+            // Write scheduled usage digests in the background
+            tauri::async_runtime::spawn(commands::usage_report::run_report_scheduler(
+                app.handle().clone(),
+            ));
+
             // Initialize checkpoint state
             let checkpoint_state = CheckpointState::new();
 
@@ -252,6 +291,19 @@ fn main() {
             get_checkpoint_settings,
             clear_checkpoint_manager,
             get_checkpoint_state_stats,
+            list_timeline_branches,
+            create_timeline_branch,
+            switch_timeline_branch,
+            compare_timeline_branches,
+            prune_timeline_branch,
+            export_session_timeline,
+            import_session_timeline,
+            get_checkpoint_retention_policy,
+            update_checkpoint_retention_policy,
+            apply_checkpoint_retention,
+            get_checkpoint_storage_usage,
+            get_checkpoint_storage_settings,
+            set_checkpoint_storage_backend,
             // Agent Management
             list_agents,
             list_teamleads,
@@ -260,6 +312,7 @@ fn main() {
             is_agent_in_project,
             update_project_agent_session,
             get_project_agent_session,
+            set_agent_mcp_servers,
             create_agent,
             update_agent,
             delete_agent,
@@ -269,6 +322,8 @@ fn main() {
             get_agent_run,
             list_agent_runs_with_metrics,
             get_agent_run_with_real_time_metrics,
+            get_run_turn_metrics,
+            get_agent_run_metric_trends,
             list_running_sessions,
             kill_agent_session,
             get_session_status,
@@ -292,6 +347,32 @@ fn main() {
             get_usage_by_date_range,
             get_usage_details,
             get_session_stats,
+            get_team_usage,
+            export_usage,
+            export_usage_to_file,
+            get_usage_windows,
+            get_active_usage_window,
+            get_usage_window_settings,
+            save_usage_window_settings,
+            generate_usage_report,
+            get_usage_report_schedule,
+            save_usage_report_schedule,
+            list_model_pricing,
+            upsert_model_pricing,
+            delete_model_pricing,
+            import_model_pricing,
+            export_model_pricing,
+            list_budgets,
+            save_budget,
+            delete_budget,
+            get_budget_status,
+            get_secret_store_status,
+            init_secret_store,
+            unlock_secret_store,
+            lock_secret_store,
+            list_secrets,
+            set_secret,
+            delete_secret,
             // MCP (Model Context Protocol)
             mcp_add,
             mcp_list,
@@ -300,11 +381,22 @@ fn main() {
             mcp_add_json,
             mcp_add_from_claude_desktop,
             mcp_serve,
+            mcp_host_start,
+            mcp_host_stop,
+            mcp_host_restart,
+            mcp_host_list,
+            mcp_host_logs,
             mcp_test_connection,
+            mcp_probe_server,
+            mcp_get_tool_catalog,
+            mcp_get_server_catalog,
+            mcp_invoke_tool,
             mcp_reset_project_choices,
             mcp_get_server_status,
             mcp_read_project_config,
             mcp_save_project_config,
+            mcp_lint_config,
+            mcp_apply_config_fix,
             // Storage Management
             storage_list_tables,
             storage_read_table,
@@ -330,6 +422,7 @@ fn main() {
             save_proxy_settings,
             // Teammate Agent
             start_teammate_agent,
+            list_project_checkpoints,
             send_to_teammate,
             stop_teammate_agent,
             get_teammate_status,
@@ -0,0 +1,558 @@
+//! MCP client
+//!
+//! Connects to a configured server, performs the `initialize` handshake and
+//! issues JSON-RPC requests over a `Transport`, answering the server's own
+//! `ping` requests while it waits for a response.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value as JsonValue};
+use std::time::{Duration, Instant};
+
+use super::config::{ServerConfig, TransportKind};
+use super::transport::{HttpTransport, SseTransport, StdioTransport, Transport};
+use super::types::{
+    InitializeResult, JsonRpcError, Prompt, Resource, Tool, METHOD_NOT_FOUND, PROTOCOL_VERSION,
+};
+
+/// Upper bound on pages fetched by one list call
+const MAX_LIST_PAGES: usize = 100;
+
+#[derive(Debug, thiserror::Error)]
+pub enum McpError {
+    #[error("Invalid server configuration: {0}")]
+    Config(String),
+    #[error("Failed to start server: {0}")]
+    Spawn(String),
+    #[error("Connection failed: {0}")]
+    Connection(String),
+    #[error("{0}")]
+    Closed(String),
+    #[error("Timed out after {0} ms waiting for {1}")]
+    Timeout(u128, String),
+    #[error("Invalid message from server: {0}")]
+    Protocol(String),
+    #[error("Server returned error {}: {}", .0.code, .0.message)]
+    Rpc(JsonRpcError),
+}
+
+/// A connected, initialized MCP client
+pub struct McpClient {
+    transport: Box<dyn Transport>,
+    next_id: i64,
+    timeout: Duration,
+    initialize: InitializeResult,
+}
+
+impl McpClient {
+    /// Launch or connect to the server and run the `initialize` handshake.
+    /// `timeout` applies to connecting and to every request unless the
+    /// server config sets its own.
+    pub async fn connect(config: &ServerConfig, timeout: Duration) -> Result<Self, McpError> {
+        let config = config.resolved().map_err(McpError::Config)?;
+        let timeout = config
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(timeout);
+        let url = || {
+            config.url.as_deref().ok_or_else(|| {
+                McpError::Config(format!(
+                    "URL is required for {} transport",
+                    config.transport.as_str().to_uppercase()
+                ))
+            })
+        };
+        let transport: Box<dyn Transport> = match config.transport {
+            TransportKind::Stdio => {
+                let command = config.command.as_deref().ok_or_else(|| {
+                    McpError::Config("Command is required for stdio transport".to_string())
+                })?;
+                Box::new(StdioTransport::spawn(command, &config.args, &config.env)?)
+            }
+            TransportKind::Sse => {
+                let connect = SseTransport::connect(url()?, &config.headers);
+                Box::new(tokio::time::timeout(timeout, connect).await.map_err(|_| {
+                    McpError::Timeout(timeout.as_millis(), "the SSE endpoint".to_string())
+                })??)
+            }
+            TransportKind::Http => Box::new(HttpTransport::new(url()?, &config.headers)?),
+        };
+        Self::initialize(transport, timeout).await
+    }
+
+    /// Run the handshake over an already connected transport
+    pub async fn initialize(
+        transport: Box<dyn Transport>,
+        timeout: Duration,
+    ) -> Result<Self, McpError> {
+        let mut client = Self {
+            transport,
+            next_id: 1,
+            timeout,
+            initialize: InitializeResult {
+                protocol_version: PROTOCOL_VERSION.to_string(),
+                capabilities: Default::default(),
+                server_info: Default::default(),
+                instructions: None,
+            },
+        };
+        let result = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "vibe-agent-team",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await;
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                client.transport.close().await;
+                return Err(e);
+            }
+        };
+        client.initialize = serde_json::from_value(result)
+            .map_err(|e| McpError::Protocol(format!("Invalid initialize result: {}", e)))?;
+        client
+            .notify("notifications/initialized", json!({}))
+            .await?;
+        Ok(client)
+    }
+
+    /// What the server reported during `initialize`
+    pub fn server(&self) -> &InitializeResult {
+        &self.initialize
+    }
+
+    /// Send a request and wait for its response
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: JsonValue,
+    ) -> Result<JsonValue, McpError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        // HTTP transports exchange the response while sending, so the
+        // deadline covers the send too
+        let deadline = Instant::now() + self.timeout;
+        self.send_before(
+            &json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}),
+            deadline,
+            method,
+        )
+        .await?;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = tokio::time::timeout(remaining, self.transport.receive())
+                .await
+                .map_err(|_| McpError::Timeout(self.timeout.as_millis(), method.to_string()))??;
+
+            // Requests from the server carry both a method and an id
+            if let (Some(server_method), Some(server_id)) = (
+                message.get("method").and_then(|m| m.as_str()),
+                message.get("id"),
+            ) {
+                let reply = if server_method == "ping" {
+                    json!({"jsonrpc": "2.0", "id": server_id, "result": {}})
+                } else {
+                    json!({"jsonrpc": "2.0", "id": server_id, "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("Method not found: {}", server_method),
+                    }})
+                };
+                self.send_before(&reply, deadline, method).await?;
+                continue;
+            }
+            if message.get("id").and_then(|v| v.as_i64()) != Some(id) {
+                // Notifications and stale responses
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let error: JsonRpcError = serde_json::from_value(error.clone())
+                    .map_err(|e| McpError::Protocol(format!("Invalid error object: {}", e)))?;
+                return Err(McpError::Rpc(error));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(JsonValue::Null));
+        }
+    }
+
+    /// Send a notification (no response expected)
+    pub async fn notify(&mut self, method: &str, params: JsonValue) -> Result<(), McpError> {
+        let deadline = Instant::now() + self.timeout;
+        self.send_before(
+            &json!({"jsonrpc": "2.0", "method": method, "params": params}),
+            deadline,
+            method,
+        )
+        .await
+    }
+
+    /// Send a message, giving up at `deadline`
+    async fn send_before(
+        &mut self,
+        message: &JsonValue,
+        deadline: Instant,
+        method: &str,
+    ) -> Result<(), McpError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        tokio::time::timeout(remaining, self.transport.send(message))
+            .await
+            .map_err(|_| McpError::Timeout(self.timeout.as_millis(), method.to_string()))?
+    }
+
+    /// Call a tool, returning the raw `tools/call` result. A tool that fails
+    /// reports `isError: true` in the result rather than a JSON-RPC error.
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<JsonValue, McpError> {
+        self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        )
+        .await
+    }
+
+    /// Time a `ping` round trip
+    pub async fn ping(&mut self) -> Result<Duration, McpError> {
+        let started = Instant::now();
+        self.request("ping", json!({})).await?;
+        Ok(started.elapsed())
+    }
+
+    /// Fetch every page of a paginated list method
+    async fn list_all<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        key: &str,
+    ) -> Result<Vec<T>, McpError> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut result = self.request(method, params).await?;
+            let page = result.get_mut(key).map(JsonValue::take).unwrap_or_default();
+            if !page.is_null() {
+                let page: Vec<T> = serde_json::from_value(page)
+                    .map_err(|e| McpError::Protocol(format!("Invalid {} result: {}", method, e)))?;
+                items.extend(page);
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Tools offered by the server (empty if it has no tools capability)
+    pub async fn list_tools(&mut self) -> Result<Vec<Tool>, McpError> {
+        if self.initialize.capabilities.tools.is_none() {
+            return Ok(Vec::new());
+        }
+        self.list_all("tools/list", "tools").await
+    }
+
+    /// Resources offered by the server (empty if it has no resources capability)
+    pub async fn list_resources(&mut self) -> Result<Vec<Resource>, McpError> {
+        if self.initialize.capabilities.resources.is_none() {
+            return Ok(Vec::new());
+        }
+        self.list_all("resources/list", "resources").await
+    }
+
+    /// Prompts offered by the server (empty if it has no prompts capability)
+    pub async fn list_prompts(&mut self) -> Result<Vec<Prompt>, McpError> {
+        if self.initialize.capabilities.prompts.is_none() {
+            return Ok(Vec::new());
+        }
+        self.list_all("prompts/list", "prompts").await
+    }
+
+    /// Disconnect, stopping a launched server
+    pub async fn close(mut self) {
+        self.transport.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+
+    /// Replays canned server messages, recording what the client sent
+    struct ScriptedTransport {
+        incoming: VecDeque<JsonValue>,
+        sent: std::sync::Arc<std::sync::Mutex<Vec<JsonValue>>>,
+    }
+
+    #[async_trait]
+    impl Transport for ScriptedTransport {
+        async fn send(&mut self, message: &JsonValue) -> Result<(), McpError> {
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Result<JsonValue, McpError> {
+            self.incoming
+                .pop_front()
+                .ok_or_else(|| McpError::Closed("script ended".to_string()))
+        }
+
+        async fn close(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_handshake_answers_pings_and_follows_cursors() {
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: VecDeque::from(vec![
+                json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {}}),
+                json!({"jsonrpc": "2.0", "id": 1, "result": {
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "demo", "version": "1.0.0"}
+                }}),
+                json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"}),
+                json!({"jsonrpc": "2.0", "id": 2, "result": {
+                    "tools": [{"name": "read", "inputSchema": {"type": "object"}}],
+                    "nextCursor": "page-2"
+                }}),
+                json!({"jsonrpc": "2.0", "id": 3, "result": {"tools": [{"name": "write"}]}}),
+                json!({"jsonrpc": "2.0", "id": 4, "error": {"code": -32601, "message": "nope"}}),
+            ]),
+            sent: sent.clone(),
+        };
+
+        let mut client = McpClient::initialize(Box::new(transport), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(client.server().server_info.name, "demo");
+
+        let tools = client.list_tools().await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["read", "write"]);
+        // No resources capability, so nothing is requested
+        assert!(client.list_resources().await.unwrap().is_empty());
+
+        let err = client.request("prompts/list", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Rpc(ref e) if e.code == METHOD_NOT_FOUND));
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[1]["method"], "notifications/initialized");
+        assert_eq!(sent[2]["method"], "tools/list");
+        assert_eq!(
+            sent[3],
+            json!({"jsonrpc": "2.0", "id": "srv-1", "result": {}})
+        );
+        assert_eq!(sent[4]["params"]["cursor"], "page-2");
+    }
+
+    fn scripted(
+        incoming: Vec<JsonValue>,
+    ) -> (
+        Box<ScriptedTransport>,
+        std::sync::Arc<std::sync::Mutex<Vec<JsonValue>>>,
+    ) {
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: VecDeque::from(incoming),
+            sent: sent.clone(),
+        };
+        (Box::new(transport), sent)
+    }
+
+    fn initialized(id: i64, capabilities: JsonValue) -> JsonValue {
+        json!({"jsonrpc": "2.0", "id": id, "result": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": capabilities,
+            "serverInfo": {"name": "demo"}
+        }})
+    }
+
+    #[tokio::test]
+    async fn test_initialize_fails_on_bad_results_errors_and_closed_servers() {
+        let timeout = Duration::from_secs(1);
+        let (transport, sent) =
+            scripted(vec![json!({"jsonrpc": "2.0", "id": 1, "result": "ready"})]);
+        let err = McpClient::initialize(transport, timeout)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Protocol(ref m) if m.contains("initialize")));
+        // The handshake stops before `notifications/initialized`
+        assert_eq!(sent.lock().unwrap().len(), 1);
+
+        let (transport, _) = scripted(vec![json!({"jsonrpc": "2.0", "id": 1, "error": {
+            "code": -32602, "message": "Unsupported protocol version"
+        }})]);
+        let err = McpClient::initialize(transport, timeout)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Rpc(ref e) if e.code == -32602));
+
+        let (transport, _) = scripted(Vec::new());
+        let err = McpClient::initialize(transport, timeout)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Closed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_server_requests_and_stale_responses_are_skipped() {
+        let (transport, sent) = scripted(vec![
+            initialized(1, json!({})),
+            json!({"jsonrpc": "2.0", "id": 7, "method": "sampling/createMessage"}),
+            json!({"jsonrpc": "2.0", "id": 1, "result": {"stale": true}}),
+            json!({"jsonrpc": "2.0", "id": 2}),
+        ]);
+        let mut client = McpClient::initialize(transport, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.request("ping", json!({})).await.unwrap(),
+            JsonValue::Null
+        );
+        // Without capabilities nothing is listed or requested
+        assert!(client.list_tools().await.unwrap().is_empty());
+        assert!(client.list_prompts().await.unwrap().is_empty());
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 4);
+        assert_eq!(sent[3]["id"], 7);
+        assert_eq!(sent[3]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_listing_stops_at_the_page_cap_and_rejects_bad_pages() {
+        let mut incoming = vec![initialized(1, json!({"tools": {}, "resources": {}}))];
+        for page in 0..MAX_LIST_PAGES + 5 {
+            incoming.push(json!({"jsonrpc": "2.0", "id": page + 2, "result": {
+                "tools": [{"name": format!("tool-{}", page)}],
+                "nextCursor": format!("page-{}", page + 1)
+            }}));
+        }
+        let (transport, _) = scripted(incoming);
+        let mut client = McpClient::initialize(transport, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(client.list_tools().await.unwrap().len(), MAX_LIST_PAGES);
+
+        let (transport, _) = scripted(vec![
+            initialized(1, json!({"resources": {}})),
+            json!({"jsonrpc": "2.0", "id": 2, "result": {}}),
+            json!({"jsonrpc": "2.0", "id": 3, "result": {"resources": "all of them"}}),
+        ]);
+        let mut client = McpClient::initialize(transport, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(client.list_resources().await.unwrap().is_empty());
+        let err = client.list_resources().await.unwrap_err();
+        assert!(matches!(err, McpError::Protocol(ref m) if m.contains("resources/list")));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_returns_raw_result() {
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: VecDeque::from(vec![
+                json!({"jsonrpc": "2.0", "id": 1, "result": {
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "demo"}
+                }}),
+                json!({"jsonrpc": "2.0", "id": 2, "result": {
+                    "content": [{"type": "text", "text": "no such file"}],
+                    "isError": true
+                }}),
+            ]),
+            sent: sent.clone(),
+        };
+
+        let mut client = McpClient::initialize(Box::new(transport), Duration::from_secs(1))
+            .await
+            .unwrap();
+        let result = client
+            .call_tool("read", json!({"path": "missing.txt"}))
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["content"][0]["text"], "no such file");
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[2]["method"], "tools/call");
+        assert_eq!(
+            sent[2]["params"],
+            json!({"name": "read", "arguments": {"path": "missing.txt"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_surfaces_rpc_errors_and_empty_results() {
+        let (transport, _) = scripted(vec![
+            initialized(1, json!({"tools": {}})),
+            json!({"jsonrpc": "2.0", "id": 2, "error": {
+                "code": -32602, "message": "Unknown tool: nope"
+            }}),
+            json!({"jsonrpc": "2.0", "id": 3, "error": "bad"}),
+            json!({"jsonrpc": "2.0", "id": 4}),
+        ]);
+        let mut client = McpClient::initialize(transport, Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let err = client.call_tool("nope", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Rpc(ref e) if e.message.contains("nope")));
+        let err = client.call_tool("read", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Protocol(ref m) if m.contains("error object")));
+        assert_eq!(
+            client.call_tool("read", json!({})).await.unwrap(),
+            JsonValue::Null
+        );
+        // The script ran out: the server went away mid-call
+        let err = client.call_tool("read", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Closed(_)));
+    }
+
+    /// Accepts nothing: every send hangs like an unresponsive HTTP server
+    struct StalledTransport;
+
+    #[async_trait]
+    impl Transport for StalledTransport {
+        async fn send(&mut self, _message: &JsonValue) -> Result<(), McpError> {
+            std::future::pending().await
+        }
+
+        async fn receive(&mut self) -> Result<JsonValue, McpError> {
+            std::future::pending().await
+        }
+
+        async fn close(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_stalled_send_times_out() {
+        let started = Instant::now();
+        let err = McpClient::initialize(Box::new(StalledTransport), Duration::from_millis(50))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Timeout(50, ref method) if method == "initialize"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
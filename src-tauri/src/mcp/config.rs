@@ -0,0 +1,632 @@
+//! MCP server configuration
+//!
+//! Reads server definitions the way Claude Code stores them: user servers in
+//! `~/.claude.json` under `mcpServers`, local servers under
+//! `projects.<path>.mcpServers` of the same file, and project servers in the
+//! project's `.mcp.json`. When a name is defined more than once, local wins
+//! over project, and project over user.
+//!
+//! String values may reference environment variables as `${VAR}` or
+//! `${VAR:-default}`, e.g. `"Authorization": "Bearer ${GITHUB_TOKEN}"`, and
+//! entries of the local secret store as `${secret:NAME}`, so tokens stay out
+//! of the config files. They are expanded when connecting.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::secrets::SECRET_PREFIX;
+
+/// Where a server is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigScope {
+    Local,
+    Project,
+    User,
+}
+
+impl ConfigScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigScope::Local => "local",
+            ConfigScope::Project => "project",
+            ConfigScope::User => "user",
+        }
+    }
+}
+
+/// How the client talks to a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportKind {
+    /// Newline-delimited JSON-RPC over a child process' stdin/stdout
+    Stdio,
+    /// HTTP POST for requests, a server-sent event stream for responses
+    Sse,
+    /// Streamable HTTP: one endpoint answering POSTs with JSON or an event stream
+    Http,
+}
+
+impl TransportKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Stdio => "stdio",
+            TransportKind::Sse => "sse",
+            TransportKind::Http => "http",
+        }
+    }
+
+    /// Parse a transport name as written in config files and by the CLI
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "stdio" => Some(TransportKind::Stdio),
+            "sse" => Some(TransportKind::Sse),
+            "http" | "streamable-http" | "streamable_http" | "streamablehttp" => {
+                Some(TransportKind::Http)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A configured MCP server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub name: String,
+    pub scope: ConfigScope,
+    pub transport: TransportKind,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub url: Option<String>,
+    /// Extra HTTP headers for the SSE and HTTP transports
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Per-request timeout in milliseconds, overriding the caller's default
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+impl ServerConfig {
+    /// Parse one entry of an `mcpServers` object
+    pub fn from_json(name: &str, scope: ConfigScope, value: &JsonValue) -> Result<Self, String> {
+        let string = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let transport = match string("type") {
+            Some(kind) => TransportKind::parse(&kind)
+                .ok_or_else(|| format!("Unsupported MCP transport '{}'", kind))?,
+            // Entries written before `type` existed are stdio servers
+            None if value.get("command").is_some() => TransportKind::Stdio,
+            None if value.get("url").is_some() => TransportKind::Sse,
+            None => return Err("Server has neither a command nor a url".to_string()),
+        };
+        let string_map = |key: &str| -> HashMap<String, String> {
+            value
+                .get(key)
+                .and_then(|v| v.as_object())
+                .map(|o| {
+                    o.iter()
+                        .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let args = value
+            .get("args")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let config = Self {
+            name: name.to_string(),
+            scope,
+            transport,
+            command: string("command"),
+            args,
+            env: string_map("env"),
+            url: string("url"),
+            headers: string_map("headers"),
+            timeout_ms: value.get("timeout").and_then(|v| v.as_u64()),
+        };
+        match transport {
+            TransportKind::Stdio if config.command.is_none() => {
+                Err("Command is required for stdio transport".to_string())
+            }
+            TransportKind::Sse | TransportKind::Http if config.url.is_none() => Err(format!(
+                "URL is required for {} transport",
+                config.transport.as_str().to_uppercase()
+            )),
+            _ => Ok(config),
+        }
+    }
+
+    /// The `mcpServers` entry for this server, as `from_json` reads it
+    pub fn to_json(&self) -> JsonValue {
+        let mut entry = serde_json::Map::new();
+        entry.insert("type".to_string(), self.transport.as_str().into());
+        match self.transport {
+            TransportKind::Stdio => {
+                entry.insert("command".to_string(), self.command.clone().into());
+                entry.insert("args".to_string(), self.args.clone().into());
+                entry.insert(
+                    "env".to_string(),
+                    serde_json::to_value(&self.env).unwrap_or_default(),
+                );
+            }
+            TransportKind::Sse | TransportKind::Http => {
+                entry.insert("url".to_string(), self.url.clone().into());
+                if !self.headers.is_empty() {
+                    entry.insert(
+                        "headers".to_string(),
+                        serde_json::to_value(&self.headers).unwrap_or_default(),
+                    );
+                }
+            }
+        }
+        if let Some(timeout) = self.timeout_ms {
+            entry.insert("timeout".to_string(), timeout.into());
+        }
+        JsonValue::Object(entry)
+    }
+
+    /// Copy with `${VAR}` placeholders expanded from the process environment
+    /// and `${secret:NAME}` ones from the secret store
+    pub fn resolved(&self) -> Result<Self, String> {
+        let mut secret_error = None;
+        let resolved = self.resolved_with(|name| match name.strip_prefix(SECRET_PREFIX) {
+            Some(secret) => crate::secrets::resolve(secret)
+                .map_err(|e| secret_error.get_or_insert(e).clone())
+                .ok(),
+            None => std::env::var(name).ok(),
+        });
+        // Report why a secret is missing rather than just that it is
+        match (resolved, secret_error) {
+            (Err(_), Some(e)) => Err(e),
+            (resolved, _) => resolved,
+        }
+    }
+
+    /// Copy with placeholders expanded through `lookup`
+    pub fn resolved_with(
+        &self,
+        mut lookup: impl FnMut(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        self.map_strings(|value| expand_placeholders(value, &mut lookup))
+    }
+
+    /// Copy with `f` applied to every string that may hold placeholders:
+    /// command, args, env values, url and header values
+    pub fn map_strings(
+        &self,
+        mut f: impl FnMut(&str) -> Result<String, String>,
+    ) -> Result<Self, String> {
+        let mut map = |map: &HashMap<String, String>| {
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), f(v)?)))
+                .collect::<Result<HashMap<_, _>, String>>()
+        };
+        let env = map(&self.env)?;
+        let headers = map(&self.headers)?;
+        Ok(Self {
+            command: self.command.as_deref().map(&mut f).transpose()?,
+            args: self.args.iter().map(|a| f(a)).collect::<Result<_, _>>()?,
+            env,
+            url: self.url.as_deref().map(&mut f).transpose()?,
+            headers,
+            ..self.clone()
+        })
+    }
+
+    /// Whether any value refers to the secret store
+    pub fn uses_secrets(&self) -> bool {
+        let refers = |v: &String| v.contains("${secret:");
+        self.command.iter().any(refers)
+            || self.args.iter().any(refers)
+            || self.env.values().any(refers)
+            || self.url.iter().any(refers)
+            || self.headers.values().any(refers)
+    }
+}
+
+/// Expand `${VAR}` and `${VAR:-default}` references. A variable that is
+/// unset and has no default is an error, so a missing token is reported
+/// instead of sending an empty `Authorization` header.
+pub fn expand_placeholders(
+    value: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated placeholder in '{}'", value))?;
+        let (name, default) = match after[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&after[..end], None),
+        };
+        match lookup(name).filter(|v| !v.is_empty()) {
+            Some(v) => out.push_str(&v),
+            None => match default {
+                Some(default) => out.push_str(default),
+                None => {
+                    return Err(match name.strip_prefix(SECRET_PREFIX) {
+                        Some(secret) => format!("Secret {} is not in the secret store", secret),
+                        None => format!("Environment variable {} is not set", name),
+                    })
+                }
+            },
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Path of Claude Code's user configuration file
+pub fn claude_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".claude.json"))
+}
+
+/// Parse a JSON file, `None` when it does not exist
+pub fn read_json(path: &Path) -> Result<Option<JsonValue>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn push_servers(servers: &mut Vec<ServerConfig>, scope: ConfigScope, section: Option<&JsonValue>) {
+    let Some(entries) = section.and_then(|s| s.as_object()) else {
+        return;
+    };
+    for (name, value) in entries {
+        match ServerConfig::from_json(name, scope, value) {
+            Ok(config) => servers.push(config),
+            Err(e) => log::warn!("Skipping {} MCP server '{}': {}", scope.as_str(), name, e),
+        }
+    }
+}
+
+/// Servers visible from `project_path` (or only user servers without one),
+/// with local and project definitions shadowing user ones
+pub fn load_servers_from(
+    claude_config: Option<&JsonValue>,
+    project_config: Option<&JsonValue>,
+    project_path: Option<&str>,
+) -> Vec<ServerConfig> {
+    let mut servers: Vec<ServerConfig> = Vec::new();
+    for server in all_servers_from(claude_config, project_config, project_path) {
+        if !servers.iter().any(|s| s.name == server.name) {
+            servers.push(server);
+        }
+    }
+    servers
+}
+
+/// Every server definition visible from `project_path`, including shadowed
+/// ones, in precedence order (local, project, user)
+pub fn all_servers_from(
+    claude_config: Option<&JsonValue>,
+    project_config: Option<&JsonValue>,
+    project_path: Option<&str>,
+) -> Vec<ServerConfig> {
+    let mut servers = Vec::new();
+    for (scope, section) in scope_sections(claude_config, project_config, project_path) {
+        push_servers(&mut servers, scope, section);
+    }
+    servers
+}
+
+/// The `mcpServers` section of each scope, in precedence order
+pub fn scope_sections<'a>(
+    claude_config: Option<&'a JsonValue>,
+    project_config: Option<&'a JsonValue>,
+    project_path: Option<&str>,
+) -> Vec<(ConfigScope, Option<&'a JsonValue>)> {
+    let mut sections = Vec::new();
+    if let Some(path) = project_path {
+        let local = claude_config
+            .and_then(|c| c.get("projects"))
+            .and_then(|p| p.get(path))
+            .and_then(|p| p.get("mcpServers"));
+        sections.push((ConfigScope::Local, local));
+        sections.push((
+            ConfigScope::Project,
+            project_config.and_then(|c| c.get("mcpServers")),
+        ));
+    }
+    sections.push((
+        ConfigScope::User,
+        claude_config.and_then(|c| c.get("mcpServers")),
+    ));
+    sections
+}
+
+/// Path of a project's shared `.mcp.json`
+pub fn project_config_path(project_path: &str) -> PathBuf {
+    Path::new(project_path).join(".mcp.json")
+}
+
+/// Read `~/.claude.json` and, with a project, its `.mcp.json`
+pub fn read_configs(
+    project_path: Option<&str>,
+) -> Result<(Option<JsonValue>, Option<JsonValue>), String> {
+    let claude_config = match claude_config_path() {
+        Some(path) => read_json(&path)?,
+        None => None,
+    };
+    let project_config = match project_path {
+        Some(path) => read_json(&project_config_path(path))?,
+        None => None,
+    };
+    Ok((claude_config, project_config))
+}
+
+/// Servers configured on this machine, see `load_servers_from`
+pub fn load_servers(project_path: Option<&str>) -> Result<Vec<ServerConfig>, String> {
+    let (claude_config, project_config) = read_configs(project_path)?;
+    Ok(load_servers_from(
+        claude_config.as_ref(),
+        project_config.as_ref(),
+        project_path,
+    ))
+}
+
+/// All server definitions on this machine, see `all_servers_from`
+pub fn load_all_servers(project_path: Option<&str>) -> Result<Vec<ServerConfig>, String> {
+    let (claude_config, project_config) = read_configs(project_path)?;
+    Ok(all_servers_from(
+        claude_config.as_ref(),
+        project_config.as_ref(),
+        project_path,
+    ))
+}
+
+/// Look up one server by name
+pub fn find_server(name: &str, project_path: Option<&str>) -> Result<Option<ServerConfig>, String> {
+    Ok(load_servers(project_path)?
+        .into_iter()
+        .find(|s| s.name == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_local_and_project_servers_shadow_user_servers() {
+        let claude = json!({
+            "mcpServers": {
+                "fs": {"type": "stdio", "command": "user-fs"},
+                "search": {"type": "sse", "url": "https://example.com/sse"},
+                "broken": {"type": "stdio"}
+            },
+            "projects": {
+                "/work/app": {"mcpServers": {"fs": {"command": "local-fs", "args": ["-v"]}}}
+            }
+        });
+        let project = json!({
+            "mcpServers": {
+                "fs": {"command": "project-fs"},
+                "db": {"command": "db-server", "env": {"DB_URL": "sqlite://x"}}
+            }
+        });
+
+        let servers = load_servers_from(Some(&claude), Some(&project), Some("/work/app"));
+        let names: Vec<(&str, ConfigScope)> =
+            servers.iter().map(|s| (s.name.as_str(), s.scope)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("fs", ConfigScope::Local),
+                ("db", ConfigScope::Project),
+                ("search", ConfigScope::User),
+            ]
+        );
+        assert_eq!(servers[0].command.as_deref(), Some("local-fs"));
+        assert_eq!(servers[0].args, vec!["-v"]);
+        assert_eq!(
+            servers[1].env.get("DB_URL").map(String::as_str),
+            Some("sqlite://x")
+        );
+        assert_eq!(servers[2].transport, TransportKind::Sse);
+
+        // Without a project only user servers are visible
+        let servers = load_servers_from(Some(&claude), Some(&project), None);
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].command.as_deref(), Some("user-fs"));
+    }
+
+    #[test]
+    fn test_missing_configs_and_sections_have_no_servers() {
+        assert!(load_servers_from(None, None, Some("/work/app")).is_empty());
+        assert!(load_servers_from(None, None, None).is_empty());
+
+        let claude = json!({
+            "mcpServers": ["not", "an", "object"],
+            "projects": {"/work/other": {"mcpServers": {"fs": {"command": "fs"}}}}
+        });
+        assert!(load_servers_from(Some(&claude), Some(&json!({})), Some("/work/app")).is_empty());
+    }
+
+    #[test]
+    fn test_all_servers_keeps_shadowed_definitions_in_precedence_order() {
+        let claude = json!({
+            "mcpServers": {"fs": {"command": "user-fs"}},
+            "projects": {"/work/app": {"mcpServers": {"fs": {"command": "local-fs"}}}}
+        });
+        let project = json!({"mcpServers": {"fs": {"command": "project-fs"}}});
+
+        let all = all_servers_from(Some(&claude), Some(&project), Some("/work/app"));
+        let commands: Vec<(ConfigScope, &str)> = all
+            .iter()
+            .map(|s| (s.scope, s.command.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                (ConfigScope::Local, "local-fs"),
+                (ConfigScope::Project, "project-fs"),
+                (ConfigScope::User, "user-fs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_json_infers_legacy_transports_and_rejects_bad_entries() {
+        let legacy = ServerConfig::from_json(
+            "remote",
+            ConfigScope::User,
+            &json!({"url": "https://example.com/sse"}),
+        )
+        .unwrap();
+        assert_eq!(legacy.transport, TransportKind::Sse);
+
+        let stdio = ServerConfig::from_json(
+            "fs",
+            ConfigScope::User,
+            &json!({"type": "STDIO", "command": "fs", "args": ["-v", 3, null], "env": {"N": 1}}),
+        )
+        .unwrap();
+        assert_eq!(stdio.transport, TransportKind::Stdio);
+        assert_eq!(stdio.args, vec!["-v"]);
+        assert!(stdio.env.is_empty());
+        let round_trip =
+            ServerConfig::from_json("fs", ConfigScope::User, &stdio.to_json()).unwrap();
+        assert_eq!(round_trip.command.as_deref(), Some("fs"));
+        assert_eq!(round_trip.args, stdio.args);
+
+        let unsupported = json!({"type": "websocket", "url": "wss://example.com"});
+        let err = ServerConfig::from_json("x", ConfigScope::User, &unsupported).unwrap_err();
+        assert!(err.contains("websocket"), "{}", err);
+        let err = ServerConfig::from_json("x", ConfigScope::User, &json!({})).unwrap_err();
+        assert!(err.contains("neither"), "{}", err);
+        let err =
+            ServerConfig::from_json("x", ConfigScope::User, &json!({"type": "sse"})).unwrap_err();
+        assert!(err.contains("URL is required for SSE"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_json_of_missing_and_invalid_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(".mcp.json");
+        assert_eq!(read_json(&path).unwrap(), None);
+
+        fs::write(&path, "{\"mcpServers\": {").unwrap();
+        let err = read_json(&path).unwrap_err();
+        assert!(err.starts_with("Failed to parse"), "{}", err);
+
+        fs::write(&path, "{\"mcpServers\": {}}").unwrap();
+        assert_eq!(read_json(&path).unwrap(), Some(json!({"mcpServers": {}})));
+    }
+
+    #[test]
+    fn test_http_servers_expand_header_placeholders() {
+        let config = ServerConfig::from_json(
+            "github",
+            ConfigScope::Project,
+            &json!({
+                "type": "streamable-http",
+                "url": "https://${GH_HOST:-api.example.com}/mcp",
+                "headers": {"Authorization": "Bearer ${GH_TOKEN}"},
+                "timeout": 5000
+            }),
+        )
+        .unwrap();
+        assert_eq!(config.transport, TransportKind::Http);
+        assert_eq!(config.timeout_ms, Some(5000));
+
+        let resolved = config
+            .resolved_with(|name| (name == "GH_TOKEN").then(|| "s3cret".to_string()))
+            .unwrap();
+        assert_eq!(resolved.url.as_deref(), Some("https://api.example.com/mcp"));
+        assert_eq!(resolved.headers["Authorization"], "Bearer s3cret");
+
+        let err = config.resolved_with(|_| None).unwrap_err();
+        assert!(err.contains("GH_TOKEN"), "{}", err);
+        assert!(ServerConfig::from_json("x", ConfigScope::User, &json!({"type": "http"})).is_err());
+    }
+
+    #[test]
+    fn test_placeholders_fall_back_to_defaults_and_report_bad_references() {
+        let lookup = |name: &str| match name {
+            "HOST" => Some("example.com".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_placeholders("https://${HOST}:${PORT:-443}/${EMPTY:-mcp}", lookup).unwrap(),
+            "https://example.com:443/mcp"
+        );
+        assert_eq!(expand_placeholders("${UNSET:-}", lookup).unwrap(), "");
+        assert_eq!(expand_placeholders("no refs", lookup).unwrap(), "no refs");
+
+        let err = expand_placeholders("Bearer ${TOKEN", lookup).unwrap_err();
+        assert!(err.contains("Unterminated"), "{}", err);
+        // Set but empty is as good as unset
+        let err = expand_placeholders("${EMPTY}", lookup).unwrap_err();
+        assert_eq!(err, "Environment variable EMPTY is not set");
+        let err = expand_placeholders("${secret:GH_TOKEN}", lookup).unwrap_err();
+        assert_eq!(err, "Secret GH_TOKEN is not in the secret store");
+    }
+
+    #[test]
+    fn test_http_entries_round_trip_and_expand_every_field() {
+        assert_eq!(
+            TransportKind::parse("Streamable_HTTP"),
+            Some(TransportKind::Http)
+        );
+        assert_eq!(TransportKind::parse("ws"), None);
+
+        let config = ServerConfig::from_json(
+            "api",
+            ConfigScope::Local,
+            &json!({"type": "http", "url": "https://api.example.com/mcp", "headers": {"X-Key": "${KEY}"}}),
+        )
+        .unwrap();
+        assert!(!config.uses_secrets());
+        let entry = config.to_json();
+        assert_eq!(entry["type"], "http");
+        assert_eq!(entry["headers"]["X-Key"], "${KEY}");
+        assert!(entry.get("timeout").is_none() && entry.get("command").is_none());
+
+        let bare = ServerConfig {
+            headers: HashMap::new(),
+            timeout_ms: Some(100),
+            ..config.clone()
+        };
+        let entry = bare.to_json();
+        assert!(entry.get("headers").is_none());
+        assert_eq!(entry["timeout"], 100);
+
+        let stdio = ServerConfig::from_json(
+            "fs",
+            ConfigScope::User,
+            &json!({"command": "${BIN}", "args": ["--root", "${ROOT}"], "env": {"T": "${secret:T}"}}),
+        )
+        .unwrap();
+        assert!(stdio.uses_secrets());
+        let resolved = stdio
+            .resolved_with(|name| Some(format!("<{}>", name)))
+            .unwrap();
+        assert_eq!(resolved.command.as_deref(), Some("<BIN>"));
+        assert_eq!(resolved.args, vec!["--root", "<ROOT>"]);
+        assert_eq!(resolved.env["T"], "<secret:T>");
+    }
+}
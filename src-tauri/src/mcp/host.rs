@@ -0,0 +1,642 @@
+//! Hosted MCP servers
+//!
+//! Long-running MCP servers the app runs itself, such as `claude mcp serve`.
+//! Each one is registered in the `ProcessRegistry` as a
+//! `ProcessType::McpServer` with its stderr captured as live output, and is
+//! watched by a supervisor task that pings it and restarts it (with backoff)
+//! when it exits or stops answering.
+//!
+//! Stdio servers are pinged over their own pipes, which the host keeps open;
+//! servers that listen on HTTP are pinged at their `url`.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::ChildStdin;
+use tokio::task::JoinHandle;
+
+use super::client::McpClient;
+use super::config::{ConfigScope, ServerConfig, TransportKind};
+use super::transport::StdioTransport;
+use crate::process::ProcessRegistry;
+
+/// Live output kept per hosted server
+const LOG_LIMIT_BYTES: usize = 256 * 1024;
+/// Timeout of the handshake and of each health check
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Failed health checks in a row before the server is restarted
+const MAX_HEALTH_FAILURES: u32 = 3;
+/// Restarts in a row without a passing health check before giving up
+const MAX_CONSECUTIVE_RESTARTS: u32 = 5;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_health_interval() -> u64 {
+    30
+}
+
+/// How to run a hosted server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostedServerSpec {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Values may use `${VAR}` and `${secret:NAME}` placeholders
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Endpoint of a server that listens on HTTP; stdio servers leave it unset
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Transport at `url`, streamable HTTP by default
+    #[serde(default)]
+    pub transport: Option<TransportKind>,
+    #[serde(default = "default_true")]
+    pub auto_restart: bool,
+    #[serde(default = "default_health_interval")]
+    pub health_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostedState {
+    Starting,
+    Running,
+    Unhealthy,
+    Restarting,
+    Stopped,
+    /// Exited or kept failing and will not be restarted
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostedServerStatus {
+    pub name: String,
+    pub spec: HostedServerSpec,
+    pub state: HostedState,
+    /// Registry id of the current process
+    pub run_id: Option<String>,
+    pub pid: Option<u32>,
+    /// Unix seconds the current process started
+    pub started_at: Option<u64>,
+    pub restarts: u32,
+    /// Unix seconds of the last health check
+    pub last_health_check: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+struct HostedServer {
+    status: HostedServerStatus,
+    /// Session over the server's stdio pipes, used for pings
+    client: Arc<tokio::sync::Mutex<Option<McpClient>>>,
+    /// Held so HTTP servers that exit on stdin EOF keep running
+    stdin: Option<ChildStdin>,
+    supervisor: Option<JoinHandle<()>>,
+    consecutive_restarts: u32,
+    /// Bumped on every start and stop so stale supervisors exit
+    generation: u64,
+}
+
+// Hosted servers by name
+lazy_static::lazy_static! {
+    static ref HOSTED: Mutex<HashMap<String, HostedServer>> = Mutex::new(HashMap::new());
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn lock_hosted() -> Result<std::sync::MutexGuard<'static, HashMap<String, HostedServer>>, String> {
+    HOSTED.lock().map_err(|e| e.to_string())
+}
+
+/// Delay before the `n`th consecutive restart: 1, 2, 4 … seconds, capped
+fn backoff(n: u32) -> Duration {
+    Duration::from_secs((1u64 << n.min(16).saturating_sub(1)).min(MAX_BACKOFF_SECS))
+}
+
+struct Launched {
+    run_id: String,
+    pid: u32,
+    client: Option<McpClient>,
+    stdin: Option<ChildStdin>,
+}
+
+/// Spawn the process, register it and, for stdio servers, run the handshake
+async fn launch(
+    registry: &Arc<ProcessRegistry>,
+    spec: &HostedServerSpec,
+) -> Result<Launched, String> {
+    // Expand placeholders only now, at spawn time
+    let resolved = ServerConfig {
+        name: spec.name.clone(),
+        scope: ConfigScope::Local,
+        transport: TransportKind::Stdio,
+        command: Some(spec.command.clone()),
+        args: spec.args.clone(),
+        env: spec.env.clone(),
+        url: None,
+        headers: HashMap::new(),
+        timeout_ms: None,
+    }
+    .resolved()?;
+
+    let mut cmd: tokio::process::Command =
+        crate::claude_binary::create_command_with_env(&spec.command).into();
+    cmd.args(&resolved.args)
+        .envs(&resolved.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cwd) = &spec.cwd {
+        cmd.current_dir(cwd);
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", spec.command, e))?;
+    let pid = child.id().unwrap_or(0);
+    let stdin = child.stdin.take().ok_or("Failed to open server stdin")?;
+    let stdout = child.stdout.take().ok_or("Failed to open server stdout")?;
+    let stderr = child.stderr.take();
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let command_line = std::iter::once(spec.command.as_str())
+        .chain(spec.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    registry.register_mcp_server(
+        run_id.clone(),
+        spec.name.clone(),
+        pid,
+        spec.cwd.clone().unwrap_or_default(),
+        command_line,
+        child,
+    )?;
+    info!("Hosted MCP server {} started (PID: {})", spec.name, pid);
+
+    if let Some(stderr) = stderr {
+        let registry = registry.clone();
+        let run_id = run_id.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = registry.append_live_output_bounded(&run_id, &line, LOG_LIMIT_BYTES);
+            }
+        });
+    }
+
+    if spec.url.is_some() {
+        // Not a stdio server: its stdout is just more log output
+        let registry = registry.clone();
+        let log_run_id = run_id.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = registry.append_live_output_bounded(&log_run_id, &line, LOG_LIMIT_BYTES);
+            }
+        });
+        return Ok(Launched {
+            run_id,
+            pid,
+            client: None,
+            stdin: Some(stdin),
+        });
+    }
+
+    let transport = Box::new(StdioTransport::attach(stdin, stdout));
+    match McpClient::initialize(transport, HEALTH_TIMEOUT).await {
+        Ok(client) => Ok(Launched {
+            run_id,
+            pid,
+            client: Some(client),
+            stdin: None,
+        }),
+        Err(e) => {
+            let _ = registry.kill_process(run_id).await;
+            Err(format!("{} failed the MCP handshake: {}", spec.name, e))
+        }
+    }
+}
+
+/// Ping the server over its pipes or at its url
+async fn health_check(
+    spec: &HostedServerSpec,
+    client: &tokio::sync::Mutex<Option<McpClient>>,
+) -> Result<Duration, String> {
+    match &spec.url {
+        Some(url) => {
+            let config = ServerConfig {
+                name: spec.name.clone(),
+                scope: ConfigScope::Local,
+                transport: spec.transport.unwrap_or(TransportKind::Http),
+                command: None,
+                args: Vec::new(),
+                env: HashMap::new(),
+                url: Some(url.clone()),
+                headers: HashMap::new(),
+                timeout_ms: None,
+            };
+            let mut client = McpClient::connect(&config, HEALTH_TIMEOUT)
+                .await
+                .map_err(|e| e.to_string())?;
+            let latency = client.ping().await.map_err(|e| e.to_string());
+            client.close().await;
+            latency
+        }
+        None => {
+            let mut client = client.lock().await;
+            let client = client.as_mut().ok_or("No connection to the server")?;
+            client.ping().await.map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Apply `f` to the server's entry if it still belongs to `generation`
+fn update<T>(name: &str, generation: u64, f: impl FnOnce(&mut HostedServer) -> T) -> Option<T> {
+    let mut hosted = HOSTED.lock().ok()?;
+    hosted
+        .get_mut(name)
+        .filter(|server| server.generation == generation)
+        .map(f)
+}
+
+fn install(server: &mut HostedServer, launched: Launched) {
+    server.status.run_id = Some(launched.run_id);
+    server.status.pid = Some(launched.pid);
+    server.status.started_at = Some(now_secs());
+    server.status.state = HostedState::Running;
+    server.status.consecutive_failures = 0;
+    server.client = Arc::new(tokio::sync::Mutex::new(launched.client));
+    server.stdin = launched.stdin;
+}
+
+/// Watch one server until it is stopped or replaced
+async fn supervise(registry: Arc<ProcessRegistry>, name: String, generation: u64) {
+    loop {
+        let Some((spec, run_id, client)) = update(&name, generation, |s| {
+            (
+                s.status.spec.clone(),
+                s.status.run_id.clone(),
+                s.client.clone(),
+            )
+        }) else {
+            return;
+        };
+        tokio::time::sleep(Duration::from_secs(spec.health_interval_secs.max(1))).await;
+
+        let alive = match &run_id {
+            Some(run_id) => registry
+                .is_process_running(run_id.clone())
+                .await
+                .unwrap_or(false),
+            None => false,
+        };
+        let failure = if alive {
+            let result = health_check(&spec, &client).await;
+            let failures = update(&name, generation, |s| {
+                s.status.last_health_check = Some(now_secs());
+                match &result {
+                    Ok(latency) => {
+                        s.status.state = HostedState::Running;
+                        s.status.latency_ms = Some(latency.as_millis() as u64);
+                        s.status.consecutive_failures = 0;
+                        s.consecutive_restarts = 0;
+                    }
+                    Err(e) => {
+                        s.status.state = HostedState::Unhealthy;
+                        s.status.consecutive_failures += 1;
+                        s.status.last_error = Some(e.clone());
+                    }
+                }
+                s.status.consecutive_failures
+            });
+            match (result, failures) {
+                (_, None) => return,
+                (Err(e), Some(n)) if n >= MAX_HEALTH_FAILURES => {
+                    Some(format!("{} failed health checks in a row: {}", n, e))
+                }
+                _ => None,
+            }
+        } else {
+            if let Some(run_id) = &run_id {
+                let _ = registry.unregister_process(run_id.clone());
+            }
+            Some("Server process exited".to_string())
+        };
+
+        let Some(reason) = failure else {
+            continue;
+        };
+        warn!("Hosted MCP server {}: {}", name, reason);
+
+        if alive {
+            // Hung rather than exited: take it down before deciding what next
+            if let Some(run_id) = run_id {
+                let _ = registry.kill_process(run_id).await;
+            }
+        }
+        let attempt = update(&name, generation, |s| {
+            s.status.last_error = Some(reason.clone());
+            s.status.run_id = None;
+            s.status.pid = None;
+            s.client = Arc::new(tokio::sync::Mutex::new(None));
+            s.stdin = None;
+            if !s.status.spec.auto_restart || s.consecutive_restarts >= MAX_CONSECUTIVE_RESTARTS {
+                s.status.state = HostedState::Failed;
+                return None;
+            }
+            s.status.state = HostedState::Restarting;
+            s.consecutive_restarts += 1;
+            s.status.restarts += 1;
+            Some(s.consecutive_restarts)
+        });
+        let Some(Some(attempt)) = attempt else {
+            return;
+        };
+
+        tokio::time::sleep(backoff(attempt)).await;
+        if update(&name, generation, |_| ()).is_none() {
+            return;
+        }
+        info!(
+            "Restarting hosted MCP server {} (attempt {})",
+            name, attempt
+        );
+        match launch(&registry, &spec).await {
+            Ok(launched) => {
+                let run_id = launched.run_id.clone();
+                let mut launched = Some(launched);
+                let installed = update(&name, generation, |s| {
+                    if let Some(launched) = launched.take() {
+                        install(s, launched);
+                    }
+                })
+                .is_some();
+                if !installed {
+                    // Stopped while we were restarting
+                    let _ = registry.kill_process(run_id).await;
+                    return;
+                }
+            }
+            Err(e) => {
+                // Counts as another exit on the next pass
+                update(&name, generation, |s| s.status.last_error = Some(e));
+            }
+        }
+    }
+}
+
+/// Start hosting a server. Fails if one with the same name is already
+/// running; a stopped or failed one is replaced.
+pub async fn start(
+    registry: Arc<ProcessRegistry>,
+    spec: HostedServerSpec,
+) -> Result<HostedServerStatus, String> {
+    if spec.name.trim().is_empty() {
+        return Err("Server name is required".to_string());
+    }
+    if spec.command.trim().is_empty() {
+        return Err("Server command is required".to_string());
+    }
+    let name = spec.name.clone();
+    let generation = {
+        let mut hosted = lock_hosted()?;
+        let generation = match hosted.get(&name) {
+            Some(server)
+                if !matches!(
+                    server.status.state,
+                    HostedState::Stopped | HostedState::Failed
+                ) =>
+            {
+                return Err(format!("MCP server '{}' is already running", name));
+            }
+            Some(server) => server.generation + 1,
+            None => 1,
+        };
+        let restarts = hosted.get(&name).map_or(0, |s| s.status.restarts);
+        hosted.insert(
+            name.clone(),
+            HostedServer {
+                status: HostedServerStatus {
+                    name: name.clone(),
+                    spec: spec.clone(),
+                    state: HostedState::Starting,
+                    run_id: None,
+                    pid: None,
+                    started_at: None,
+                    restarts,
+                    last_health_check: None,
+                    latency_ms: None,
+                    consecutive_failures: 0,
+                    last_error: None,
+                },
+                client: Arc::new(tokio::sync::Mutex::new(None)),
+                stdin: None,
+                supervisor: None,
+                consecutive_restarts: 0,
+                generation,
+            },
+        );
+        generation
+    };
+
+    match launch(&registry, &spec).await {
+        Ok(launched) => {
+            let run_id = launched.run_id.clone();
+            let mut launched = Some(launched);
+            let installed = update(&name, generation, |s| {
+                if let Some(launched) = launched.take() {
+                    install(s, launched);
+                }
+            });
+            if installed.is_none() {
+                let _ = registry.kill_process(run_id).await;
+                return Err(format!("MCP server '{}' was stopped while starting", name));
+            }
+        }
+        Err(e) => {
+            update(&name, generation, |s| {
+                s.status.state = HostedState::Failed;
+                s.status.last_error = Some(e.clone());
+            });
+            return Err(e);
+        }
+    }
+
+    let supervisor = tokio::spawn(supervise(registry, name.clone(), generation));
+    update(&name, generation, |s| {
+        s.supervisor = Some(supervisor);
+        s.status.clone()
+    })
+    .ok_or_else(|| format!("MCP server '{}' was stopped while starting", name))
+}
+
+/// Stop a hosted server and its supervisor
+pub async fn stop(
+    registry: Arc<ProcessRegistry>,
+    name: &str,
+) -> Result<HostedServerStatus, String> {
+    let (run_id, status) = {
+        let mut hosted = lock_hosted()?;
+        let server = hosted
+            .get_mut(name)
+            .ok_or_else(|| format!("MCP server '{}' is not hosted", name))?;
+        if let Some(supervisor) = server.supervisor.take() {
+            supervisor.abort();
+        }
+        server.generation += 1;
+        server.client = Arc::new(tokio::sync::Mutex::new(None));
+        server.stdin = None;
+        server.status.state = HostedState::Stopped;
+        server.status.pid = None;
+        (server.status.run_id.take(), server.status.clone())
+    };
+    if let Some(run_id) = run_id {
+        let _ = registry.kill_process(run_id).await;
+    }
+    info!("Hosted MCP server {} stopped", name);
+    Ok(status)
+}
+
+/// Stop and start a hosted server with its current spec
+pub async fn restart(
+    registry: Arc<ProcessRegistry>,
+    name: &str,
+) -> Result<HostedServerStatus, String> {
+    let spec = get(name)
+        .ok_or_else(|| format!("MCP server '{}' is not hosted", name))?
+        .spec;
+    stop(registry.clone(), name).await?;
+    start(registry, spec).await
+}
+
+/// Status of one hosted server
+pub fn get(name: &str) -> Option<HostedServerStatus> {
+    HOSTED.lock().ok()?.get(name).map(|s| s.status.clone())
+}
+
+/// Status of every hosted server, by name
+pub fn list() -> Vec<HostedServerStatus> {
+    let mut servers: Vec<HostedServerStatus> = HOSTED
+        .lock()
+        .map(|hosted| hosted.values().map(|s| s.status.clone()).collect())
+        .unwrap_or_default();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+    servers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_backoff_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=8).map(|n| backoff(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff(40).as_secs(), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_specs_default_to_supervised_restarts() {
+        let spec: HostedServerSpec =
+            serde_json::from_value(serde_json::json!({"name": "claude", "command": "claude"}))
+                .unwrap();
+        assert!(spec.auto_restart);
+        assert_eq!(spec.health_interval_secs, 30);
+        assert!(spec.url.is_none());
+    }
+
+    fn spec(name: &str, command: &str, script: &str) -> HostedServerSpec {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "command": command,
+            "args": ["-c", script],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_start_rejects_blank_specs_and_unknown_servers() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let err = start(registry.clone(), spec(" ", "sh", ""))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Server name is required");
+        let err = start(registry.clone(), spec("host-test-blank", "", ""))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Server command is required");
+        assert!(get("host-test-blank").is_none());
+
+        let err = stop(registry.clone(), "host-test-unknown")
+            .await
+            .unwrap_err();
+        assert!(err.contains("not hosted"), "{}", err);
+        let err = restart(registry, "host-test-unknown").await.unwrap_err();
+        assert!(err.contains("not hosted"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_failed_launches_leave_a_failed_entry_that_can_be_replaced() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let missing = spec("host-test-missing", "/nonexistent/mcp-server", "");
+        let err = start(registry.clone(), missing.clone()).await.unwrap_err();
+        assert!(err.starts_with("Failed to start"), "{}", err);
+        let status = get("host-test-missing").unwrap();
+        assert_eq!(status.state, HostedState::Failed);
+        assert_eq!(status.last_error.as_deref(), Some(err.as_str()));
+        // Failed servers are replaced rather than reported as running
+        assert!(start(registry.clone(), missing)
+            .await
+            .unwrap_err()
+            .starts_with("Failed to start"));
+
+        let silent = spec("host-test-silent", "sh", "exit 0");
+        let err = start(registry.clone(), silent).await.unwrap_err();
+        assert!(err.contains("failed the MCP handshake"), "{}", err);
+        assert_eq!(get("host-test-silent").unwrap().state, HostedState::Failed);
+        assert!(registry.get_all_running_processes().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_running_servers_refuse_a_second_start_and_stop() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let server = HostedServerSpec {
+            health_interval_secs: 3600,
+            ..spec(
+                "host-test-running",
+                "sh",
+                r#"read line; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'; exec sleep 30"#,
+            )
+        };
+        let status = start(registry.clone(), server.clone()).await.unwrap();
+        assert_eq!(status.state, HostedState::Running);
+        assert!(status.pid.is_some() && status.run_id.is_some());
+        assert!(list().iter().any(|s| s.name == "host-test-running"));
+
+        let err = start(registry.clone(), server).await.unwrap_err();
+        assert!(err.contains("already running"), "{}", err);
+
+        let run_id = status.run_id.unwrap();
+        let stopped = stop(registry.clone(), "host-test-running").await.unwrap();
+        assert_eq!(stopped.state, HostedState::Stopped);
+        assert_eq!(stopped.pid, None);
+        assert!(!registry.is_process_running(run_id).await.unwrap_or(false));
+    }
+}
@@ -0,0 +1,854 @@
+//! MCP configuration linting
+//!
+//! Reads every scope directly and explains what Claude will actually load
+//! for a project: each definition with the file it comes from and whether it
+//! takes effect, plus problems that keep servers from starting (invalid
+//! entries, shadowed or duplicate definitions, commands missing from PATH,
+//! unset variables, unreachable URLs, project servers that are disabled or
+//! not yet approved). Issues that have a mechanical fix carry a `ConfigFix`
+//! that `apply_fix` writes back to the right file.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::config::{self, ConfigScope, ServerConfig, TransportKind};
+use crate::secrets::SECRET_PREFIX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The server will not load or start
+    Error,
+    /// It loads but probably does not behave as intended
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    InvalidEntry,
+    /// Overridden by a different definition in a higher-precedence scope
+    Shadowed,
+    /// Overridden by an identical definition
+    Duplicate,
+    MissingCommand,
+    UnsetVariable,
+    MissingSecret,
+    UnreachableUrl,
+    ProjectServerDisabled,
+    /// Claude will ask before starting it
+    ProjectServerPending,
+}
+
+/// A change that resolves an issue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ConfigFix {
+    /// Delete one definition of a server
+    RemoveDefinition { server: String, scope: ConfigScope },
+    /// Point a stdio server at an absolute command path
+    SetCommand {
+        server: String,
+        scope: ConfigScope,
+        command: String,
+    },
+    /// Approve a `.mcp.json` server for this project
+    EnableProjectServer { server: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub server: String,
+    pub scope: ConfigScope,
+    pub severity: Severity,
+    pub kind: IssueKind,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<ConfigFix>,
+}
+
+/// One definition of a server and where it comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedServer {
+    #[serde(flatten)]
+    pub config: ServerConfig,
+    /// File the definition lives in
+    pub source: String,
+    /// Whether this is the definition Claude uses
+    pub effective: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadowed_by: Option<ConfigScope>,
+}
+
+/// Every definition in precedence order, and what is wrong with them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpConfigReport {
+    pub servers: Vec<MergedServer>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+/// Where each scope is stored
+fn source(scope: ConfigScope, project_path: Option<&str>) -> String {
+    let claude = config::claude_config_path()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "~/.claude.json".to_string());
+    match (scope, project_path) {
+        (ConfigScope::Local, Some(path)) => format!("{} (projects[\"{}\"])", claude, path),
+        (ConfigScope::Project, Some(path)) => {
+            config::project_config_path(path).display().to_string()
+        }
+        _ => claude,
+    }
+}
+
+/// `${...}` references in a value, with whether each has a default
+fn placeholders(value: &str) -> Vec<(&str, bool)> {
+    let mut refs = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        refs.push(match after[..end].split_once(":-") {
+            Some((name, _)) => (name, true),
+            None => (&after[..end], false),
+        });
+        rest = &after[end + 1..];
+    }
+    refs
+}
+
+/// Look a placeholder up in the environment or the secret store
+pub fn lookup_placeholder(name: &str) -> Result<String, String> {
+    match name.strip_prefix(SECRET_PREFIX) {
+        Some(secret) => crate::secrets::resolve(secret),
+        None => std::env::var(name)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| format!("Environment variable {} is not set", name)),
+    }
+}
+
+/// Directories where CLIs are commonly installed but which are often missing
+/// from the PATH of a GUI app
+fn extra_command_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/opt/homebrew/bin"),
+        PathBuf::from("/usr/local/bin"),
+    ];
+    if let Some(home) = dirs::home_dir() {
+        for dir in [
+            ".local/bin",
+            ".cargo/bin",
+            ".bun/bin",
+            ".npm-global/bin",
+            ".volta/bin",
+        ] {
+            dirs.push(home.join(dir));
+        }
+        if let Ok(versions) = fs::read_dir(home.join(".nvm/versions/node")) {
+            dirs.extend(versions.flatten().map(|v| v.path().join("bin")));
+        }
+    }
+    dirs
+}
+
+/// `Ok` when `command` resolves; otherwise where it was found off PATH, if
+/// anywhere
+fn check_command(command: &str, project_path: Option<&str>) -> Result<(), Option<String>> {
+    if command.contains('/') || command.contains('\\') {
+        let path = match project_path {
+            Some(dir) if Path::new(command).is_relative() => Path::new(dir).join(command),
+            _ => PathBuf::from(command),
+        };
+        return if path.exists() { Ok(()) } else { Err(None) };
+    }
+    if which::which(command).is_ok() {
+        return Ok(());
+    }
+    let search = std::env::join_paths(extra_command_dirs()).ok();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    Err(which::which_in(command, search, cwd)
+        .ok()
+        .map(|p| p.display().to_string()))
+}
+
+/// Strings of a definition that may hold placeholders, with their location
+fn labelled_strings(server: &ServerConfig) -> Vec<(String, &str)> {
+    let mut strings = Vec::new();
+    if let Some(command) = &server.command {
+        strings.push(("command".to_string(), command.as_str()));
+    }
+    for (i, arg) in server.args.iter().enumerate() {
+        strings.push((format!("args[{}]", i), arg.as_str()));
+    }
+    for (key, value) in &server.env {
+        strings.push((format!("env.{}", key), value.as_str()));
+    }
+    if let Some(url) = &server.url {
+        strings.push(("url".to_string(), url.as_str()));
+    }
+    for (key, value) in &server.headers {
+        strings.push((format!("headers.{}", key), value.as_str()));
+    }
+    strings.sort();
+    strings
+}
+
+/// Lint the given config files. `lookup` resolves placeholders, see
+/// `lookup_placeholder`. URLs are not contacted; see `check_urls`.
+pub fn lint_from(
+    claude_config: Option<&JsonValue>,
+    project_config: Option<&JsonValue>,
+    project_path: Option<&str>,
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> McpConfigReport {
+    let mut report = McpConfigReport::default();
+    let issue = |server: &ServerConfig, severity, kind, message: String| ConfigIssue {
+        server: server.name.clone(),
+        scope: server.scope,
+        severity,
+        kind,
+        message,
+        fix: None,
+    };
+
+    for (scope, section) in config::scope_sections(claude_config, project_config, project_path) {
+        let Some(entries) = section.and_then(|s| s.as_object()) else {
+            continue;
+        };
+        for (name, value) in entries {
+            match ServerConfig::from_json(name, scope, value) {
+                Ok(server) => {
+                    let shadowing = report
+                        .servers
+                        .iter()
+                        .find(|s| s.effective && s.config.name == server.name);
+                    report.servers.push(MergedServer {
+                        source: source(scope, project_path),
+                        effective: shadowing.is_none(),
+                        shadowed_by: shadowing.map(|s| s.config.scope),
+                        config: server,
+                    });
+                }
+                Err(e) => report.issues.push(ConfigIssue {
+                    server: name.clone(),
+                    scope,
+                    severity: Severity::Error,
+                    kind: IssueKind::InvalidEntry,
+                    message: format!(
+                        "Invalid definition in {}: {}",
+                        source(scope, project_path),
+                        e
+                    ),
+                    fix: None,
+                }),
+            }
+        }
+    }
+
+    let local_project = match (claude_config, project_path) {
+        (Some(config), Some(path)) => config.get("projects").and_then(|p| p.get(path)),
+        _ => None,
+    };
+    let listed = |key: &str, name: &str| {
+        local_project
+            .and_then(|p| p.get(key))
+            .and_then(|l| l.as_array())
+            .is_some_and(|l| l.iter().any(|v| v.as_str() == Some(name)))
+    };
+    let enable_all = local_project
+        .and_then(|p| p.get("enableAllProjectMcpServers"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    for merged in &report.servers {
+        let server = &merged.config;
+        if let Some(winner_scope) = merged.shadowed_by {
+            let winner = report
+                .servers
+                .iter()
+                .find(|s| s.effective && s.config.name == server.name)
+                .map(|s| &s.config);
+            let identical = winner.is_some_and(|w| w.to_json() == server.to_json());
+            let mut found = if identical {
+                issue(
+                    server,
+                    Severity::Info,
+                    IssueKind::Duplicate,
+                    format!(
+                        "'{}' is defined identically in {} and {} scope",
+                        server.name,
+                        server.scope.as_str(),
+                        winner_scope.as_str()
+                    ),
+                )
+            } else {
+                issue(
+                    server,
+                    Severity::Warning,
+                    IssueKind::Shadowed,
+                    format!(
+                        "The {} definition of '{}' is ignored here: the {} one takes precedence",
+                        server.scope.as_str(),
+                        server.name,
+                        winner_scope.as_str()
+                    ),
+                )
+            };
+            // A redundant local copy is private to this machine and safe to
+            // drop; shared and user definitions matter elsewhere
+            if identical && winner_scope == ConfigScope::Local {
+                found.fix = Some(ConfigFix::RemoveDefinition {
+                    server: server.name.clone(),
+                    scope: ConfigScope::Local,
+                });
+            }
+            report.issues.push(found);
+            continue;
+        }
+
+        let mut missing = HashSet::new();
+        for (location, value) in labelled_strings(server) {
+            for (name, has_default) in placeholders(value) {
+                if has_default || missing.contains(name) {
+                    continue;
+                }
+                if let Err(e) = lookup(name) {
+                    let kind = if name.starts_with(SECRET_PREFIX) {
+                        IssueKind::MissingSecret
+                    } else {
+                        IssueKind::UnsetVariable
+                    };
+                    report.issues.push(issue(
+                        server,
+                        Severity::Error,
+                        kind,
+                        format!("{} (used in {})", e, location),
+                    ));
+                    missing.insert(name);
+                }
+            }
+        }
+
+        // Unexpandable commands are already reported as unset variables
+        let command = server
+            .command
+            .as_deref()
+            .filter(|_| server.transport == TransportKind::Stdio)
+            .and_then(|c| config::expand_placeholders(c, |name| lookup(name).ok()).ok());
+        let unresolved = command.and_then(|command| {
+            check_command(&command, project_path)
+                .err()
+                .map(|found| (command, found))
+        });
+        if let Some((command, found)) = unresolved {
+            let mut missing = issue(
+                server,
+                Severity::Error,
+                IssueKind::MissingCommand,
+                match &found {
+                    Some(path) => format!(
+                        "Command '{}' is not on PATH, but was found at {}",
+                        command, path
+                    ),
+                    None => format!("Command '{}' was not found", command),
+                },
+            );
+            missing.fix = found.map(|path| ConfigFix::SetCommand {
+                server: server.name.clone(),
+                scope: server.scope,
+                command: path,
+            });
+            report.issues.push(missing);
+        }
+
+        if server.scope == ConfigScope::Project {
+            let fix = Some(ConfigFix::EnableProjectServer {
+                server: server.name.clone(),
+            });
+            if listed("disabledMcpjsonServers", &server.name) {
+                report.issues.push(ConfigIssue {
+                    fix,
+                    ..issue(
+                        server,
+                        Severity::Warning,
+                        IssueKind::ProjectServerDisabled,
+                        format!(
+                            "'{}' from .mcp.json is disabled for this project",
+                            server.name
+                        ),
+                    )
+                });
+            } else if !enable_all && !listed("enabledMcpjsonServers", &server.name) {
+                report.issues.push(ConfigIssue {
+                    fix,
+                    ..issue(
+                        server,
+                        Severity::Info,
+                        IssueKind::ProjectServerPending,
+                        format!(
+                            "'{}' from .mcp.json has not been approved; Claude will ask before starting it",
+                            server.name
+                        ),
+                    )
+                });
+            }
+        }
+    }
+    report
+}
+
+/// Lint the configuration visible from `project_path` on this machine
+pub fn lint(project_path: Option<&str>) -> Result<McpConfigReport, String> {
+    let (claude_config, project_config) = config::read_configs(project_path)?;
+    Ok(lint_from(
+        claude_config.as_ref(),
+        project_config.as_ref(),
+        project_path,
+        lookup_placeholder,
+    ))
+}
+
+/// Add an issue for each effective remote server that does not answer at
+/// all. Any HTTP response, even an error status, counts as reachable.
+pub async fn check_urls(report: &mut McpConfigReport, timeout: Duration) {
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            log::warn!("Failed to build HTTP client for URL checks: {}", e);
+            return;
+        }
+    };
+    let targets: Vec<(ServerConfig, String)> = report
+        .servers
+        .iter()
+        .filter(|s| s.effective && s.config.transport != TransportKind::Stdio)
+        .filter_map(|s| {
+            let url = s.config.url.as_deref()?;
+            let url =
+                config::expand_placeholders(url, |name| lookup_placeholder(name).ok()).ok()?;
+            Some((s.config.clone(), url))
+        })
+        .collect();
+    let results = join_all(
+        targets
+            .iter()
+            .map(|(_, url)| client.get(url.as_str()).send()),
+    )
+    .await;
+    for ((server, url), result) in targets.into_iter().zip(results) {
+        if let Err(e) = result {
+            report.issues.push(ConfigIssue {
+                server: server.name.clone(),
+                scope: server.scope,
+                severity: Severity::Warning,
+                kind: IssueKind::UnreachableUrl,
+                message: format!("{} is unreachable: {}", url, root_cause(&e)),
+                fix: None,
+            });
+        }
+    }
+}
+
+/// Innermost error, e.g. "Connection refused" rather than "error sending
+/// request"
+fn root_cause(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut cause = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// Apply a fix to the config files on this machine
+pub fn apply_fix(fix: &ConfigFix, project_path: Option<&str>) -> Result<(), String> {
+    let claude_path = config::claude_config_path().ok_or("Could not find home directory")?;
+    apply_fix_to(fix, &claude_path, project_path)
+}
+
+/// Read, change and atomically rewrite a JSON config file
+fn update_json_file(
+    path: &Path,
+    update: impl FnOnce(&mut JsonValue) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut value =
+        config::read_json(path)?.ok_or_else(|| format!("{} does not exist", path.display()))?;
+    update(&mut value)?;
+    let content = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+/// Object at `keys` under `value`, created if missing
+fn object_at<'a>(
+    value: &'a mut JsonValue,
+    keys: &[&str],
+) -> Result<&'a mut serde_json::Map<String, JsonValue>, String> {
+    let mut current = value;
+    for key in keys {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| format!("Expected an object above '{}'", key))?;
+        current = object
+            .entry(key.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+    }
+    current
+        .as_object_mut()
+        .ok_or_else(|| format!("Expected '{}' to be an object", keys.join(".")))
+}
+
+fn as_strs(keys: &[String]) -> Vec<&str> {
+    keys.iter().map(String::as_str).collect()
+}
+
+fn apply_fix_to(
+    fix: &ConfigFix,
+    claude_path: &Path,
+    project_path: Option<&str>,
+) -> Result<(), String> {
+    let need_project = || project_path.ok_or("This fix needs a project path".to_string());
+    // File and key path of a scope's mcpServers section
+    let section = |scope: ConfigScope| -> Result<(PathBuf, Vec<String>), String> {
+        Ok(match scope {
+            ConfigScope::User => (claude_path.to_path_buf(), vec!["mcpServers".to_string()]),
+            ConfigScope::Local => (
+                claude_path.to_path_buf(),
+                vec![
+                    "projects".to_string(),
+                    need_project()?.to_string(),
+                    "mcpServers".to_string(),
+                ],
+            ),
+            ConfigScope::Project => (
+                config::project_config_path(need_project()?),
+                vec!["mcpServers".to_string()],
+            ),
+        })
+    };
+
+    match fix {
+        ConfigFix::RemoveDefinition { server, scope } => {
+            let (path, section) = section(*scope)?;
+            update_json_file(&path, |value| {
+                object_at(value, &as_strs(&section))?
+                    .remove(server)
+                    .map(|_| ())
+                    .ok_or_else(|| format!("No {} definition of '{}'", scope.as_str(), server))
+            })
+        }
+        ConfigFix::SetCommand {
+            server,
+            scope,
+            command,
+        } => {
+            let (path, section) = section(*scope)?;
+            update_json_file(&path, |value| {
+                let entry = object_at(value, &as_strs(&section))?
+                    .get_mut(server)
+                    .and_then(|e| e.as_object_mut())
+                    .ok_or_else(|| format!("No {} definition of '{}'", scope.as_str(), server))?;
+                entry.insert("command".to_string(), JsonValue::String(command.clone()));
+                Ok(())
+            })
+        }
+        ConfigFix::EnableProjectServer { server } => {
+            let project = need_project()?;
+            update_json_file(claude_path, |value| {
+                let settings = object_at(value, &["projects", project])?;
+                let names = |key: &str| -> Vec<JsonValue> {
+                    settings
+                        .get(key)
+                        .and_then(|l| l.as_array())
+                        .cloned()
+                        .unwrap_or_default()
+                };
+                let mut enabled = names("enabledMcpjsonServers");
+                let mut disabled = names("disabledMcpjsonServers");
+                if !enabled.iter().any(|v| v.as_str() == Some(server)) {
+                    enabled.push(JsonValue::String(server.clone()));
+                }
+                disabled.retain(|v| v.as_str() != Some(server));
+                settings.insert("enabledMcpjsonServers".to_string(), enabled.into());
+                settings.insert("disabledMcpjsonServers".to_string(), disabled.into());
+                Ok(())
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn lookup(name: &str) -> Result<String, String> {
+        match name {
+            "GITHUB_TOKEN" => Ok("ghp".to_string()),
+            _ => Err(format!("Environment variable {} is not set", name)),
+        }
+    }
+
+    #[test]
+    fn test_flags_shadowing_missing_commands_and_unset_variables() {
+        let claude = json!({
+            "mcpServers": {
+                "github": {"command": "sh", "env": {"TOKEN": "${GITHUB_TOKEN}"}},
+                "search": {"type": "http", "url": "https://example.com/mcp",
+                           "headers": {"Authorization": "Bearer ${SEARCH_TOKEN}"}},
+                "broken": {"type": "carrier-pigeon", "command": "x"}
+            },
+            "projects": {"/app": {
+                "mcpServers": {"github": {"command": "sh", "env": {"TOKEN": "${GITHUB_TOKEN}"}}},
+                "disabledMcpjsonServers": ["db"]
+            }}
+        });
+        let project = json!({"mcpServers": {
+            "db": {"command": "definitely-not-a-real-command-vibe"},
+            "docs": {"command": "sh", "args": ["${DOCS_DIR:-docs}"]}
+        }});
+        let report = lint_from(Some(&claude), Some(&project), Some("/app"), lookup);
+
+        let github: Vec<_> = report
+            .servers
+            .iter()
+            .filter(|s| s.config.name == "github")
+            .collect();
+        assert_eq!(github.len(), 2);
+        assert!(github[0].effective && github[0].config.scope == ConfigScope::Local);
+        assert_eq!(github[1].shadowed_by, Some(ConfigScope::Local));
+
+        let find = |kind| report.issues.iter().find(|i| i.kind == kind).unwrap();
+        assert_eq!(
+            find(IssueKind::Duplicate).fix,
+            Some(ConfigFix::RemoveDefinition {
+                server: "github".to_string(),
+                scope: ConfigScope::Local
+            })
+        );
+        assert_eq!(find(IssueKind::InvalidEntry).server, "broken");
+        assert_eq!(find(IssueKind::MissingCommand).server, "db");
+        let unset = find(IssueKind::UnsetVariable);
+        assert_eq!(unset.server, "search");
+        assert!(
+            unset.message.contains("headers.Authorization"),
+            "{}",
+            unset.message
+        );
+        assert_eq!(find(IssueKind::ProjectServerDisabled).server, "db");
+        assert_eq!(find(IssueKind::ProjectServerPending).server, "docs");
+        // Defaults cover unset variables
+        assert!(!report
+            .issues
+            .iter()
+            .any(|i| i.server == "docs" && i.kind == IssueKind::UnsetVariable));
+    }
+
+    #[test]
+    fn test_fixes_edit_the_owning_file() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().join("app");
+        fs::create_dir(&project).unwrap();
+        let project_path = project.to_str().unwrap();
+        let claude_path = dir.path().join(".claude.json");
+        fs::write(
+            &claude_path,
+            json!({
+                "projects": {project_path: {
+                    "mcpServers": {"github": {"command": "gh"}},
+                    "disabledMcpjsonServers": ["db"]
+                }}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        fs::write(
+            config::project_config_path(project_path),
+            json!({"mcpServers": {"db": {"command": "pg"}}}).to_string(),
+        )
+        .unwrap();
+
+        let fixes = [
+            ConfigFix::RemoveDefinition {
+                server: "github".to_string(),
+                scope: ConfigScope::Local,
+            },
+            ConfigFix::SetCommand {
+                server: "db".to_string(),
+                scope: ConfigScope::Project,
+                command: "/usr/local/bin/pg".to_string(),
+            },
+            ConfigFix::EnableProjectServer {
+                server: "db".to_string(),
+            },
+        ];
+        for fix in &fixes {
+            apply_fix_to(fix, &claude_path, Some(project_path)).unwrap();
+        }
+
+        let claude = config::read_json(&claude_path).unwrap().unwrap();
+        let local = &claude["projects"][project_path];
+        assert_eq!(local["mcpServers"], json!({}));
+        assert_eq!(local["enabledMcpjsonServers"], json!(["db"]));
+        assert_eq!(local["disabledMcpjsonServers"], json!([]));
+        let shared = config::read_json(&config::project_config_path(project_path))
+            .unwrap()
+            .unwrap();
+        assert_eq!(shared["mcpServers"]["db"]["command"], "/usr/local/bin/pg");
+
+        // Removing what is not there is an error, not a silent no-op
+        assert!(apply_fix_to(&fixes[0], &claude_path, Some(project_path)).is_err());
+    }
+
+    #[test]
+    fn test_placeholders_note_defaults_and_stop_at_unclosed_braces() {
+        assert_eq!(
+            placeholders("${A}/${B:-x}${C:-}-${D"),
+            [("A", false), ("B", true), ("C", true)]
+        );
+        assert!(placeholders("no refs, $HOME or {braces}").is_empty());
+    }
+
+    #[test]
+    fn test_empty_and_approved_configs_have_no_issues() {
+        let report = lint_from(None, None, Some("/app"), lookup);
+        assert!(report.servers.is_empty() && report.issues.is_empty());
+
+        let project = json!({"mcpServers": {"docs": {"command": "sh"}}});
+        for settings in [
+            json!({"enableAllProjectMcpServers": true}),
+            json!({"enabledMcpjsonServers": ["docs"]}),
+        ] {
+            let claude = json!({"projects": {"/app": settings}});
+            let report = lint_from(Some(&claude), Some(&project), Some("/app"), lookup);
+            assert_eq!(report.servers.len(), 1);
+            assert!(report.issues.is_empty(), "{:?}", report.issues);
+        }
+
+        // Without a project path only user servers count
+        let report = lint_from(None, Some(&project), None, lookup);
+        assert!(report.servers.is_empty() && report.issues.is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_definitions_and_unresolved_commands() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("run.sh"), "").unwrap();
+        let app = dir.path().to_str().unwrap();
+        let claude = json!({
+            "mcpServers": {
+                "github": {"command": "sh", "args": ["--user"]},
+                "docs": {"command": "sh"},
+                "vault": {"command": "sh", "env": {
+                    "A": "${secret:vault_token}", "B": "${secret:vault_token}"
+                }},
+                "tool": {"command": "${TOOL_BIN}/tool"}
+            },
+            "projects": {app: {
+                "mcpServers": {"github": {"command": "sh"}},
+                "enableAllProjectMcpServers": true
+            }}
+        });
+        let project = json!({"mcpServers": {
+            "docs": {"command": "sh"},
+            "local": {"command": "./run.sh"},
+            "gone": {"command": "./missing.sh"}
+        }});
+        let report = lint_from(Some(&claude), Some(&project), Some(app), lookup);
+        let issues = |server: &str| -> Vec<&ConfigIssue> {
+            report
+                .issues
+                .iter()
+                .filter(|i| i.server == server)
+                .collect()
+        };
+
+        // A different definition is shadowed and left alone
+        let github = issues("github");
+        assert_eq!(github.len(), 1);
+        assert_eq!(github[0].kind, IssueKind::Shadowed);
+        assert_eq!(github[0].scope, ConfigScope::User);
+        assert!(github[0].fix.is_none());
+
+        // Identical to a shared definition, so it may matter elsewhere
+        let docs = issues("docs");
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].kind, IssueKind::Duplicate);
+        assert!(docs[0].fix.is_none());
+
+        // One issue per secret, however often it is used
+        let vault = issues("vault");
+        assert_eq!(vault.len(), 1);
+        assert_eq!(vault[0].kind, IssueKind::MissingSecret);
+
+        // An unexpandable command is only an unset variable
+        let tool = issues("tool");
+        assert_eq!(tool.len(), 1);
+        assert_eq!(tool[0].kind, IssueKind::UnsetVariable);
+        assert!(tool[0].message.contains("(used in command)"));
+
+        // Relative commands resolve against the project
+        assert!(issues("local").is_empty());
+        let gone = issues("gone");
+        assert_eq!(gone.len(), 1);
+        assert_eq!(gone[0].kind, IssueKind::MissingCommand);
+        assert!(gone[0].fix.is_none());
+    }
+
+    #[test]
+    fn test_fixes_reject_missing_files_projects_and_definitions() {
+        let dir = TempDir::new().unwrap();
+        let claude_path = dir.path().join(".claude.json");
+        let enable = ConfigFix::EnableProjectServer {
+            server: "db".to_string(),
+        };
+
+        let missing = apply_fix_to(&enable, &claude_path, Some("/app")).unwrap_err();
+        assert!(missing.contains("does not exist"), "{}", missing);
+
+        fs::write(&claude_path, json!({"projects": {"/app": []}}).to_string()).unwrap();
+        let local = ConfigFix::RemoveDefinition {
+            server: "github".to_string(),
+            scope: ConfigScope::Local,
+        };
+        assert_eq!(
+            apply_fix_to(&local, &claude_path, None).unwrap_err(),
+            "This fix needs a project path"
+        );
+        assert!(apply_fix_to(&local, &claude_path, Some("/app"))
+            .unwrap_err()
+            .contains("Expected an object"));
+
+        fs::write(&claude_path, json!({"mcpServers": {}}).to_string()).unwrap();
+        let set = ConfigFix::SetCommand {
+            server: "github".to_string(),
+            scope: ConfigScope::User,
+            command: "/bin/gh".to_string(),
+        };
+        assert_eq!(
+            apply_fix_to(&set, &claude_path, None).unwrap_err(),
+            "No user definition of 'github'"
+        );
+
+        // Enabling twice lists the server once, and nothing is left behind
+        apply_fix_to(&enable, &claude_path, Some("/app")).unwrap();
+        apply_fix_to(&enable, &claude_path, Some("/app")).unwrap();
+        let claude = config::read_json(&claude_path).unwrap().unwrap();
+        assert_eq!(
+            claude["projects"]["/app"]["enabledMcpjsonServers"],
+            json!(["db"])
+        );
+        assert!(!claude_path.with_extension("json.tmp").exists());
+    }
+}
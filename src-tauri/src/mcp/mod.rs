@@ -0,0 +1,19 @@
+//! Native MCP (Model Context Protocol) client
+//!
+//! Launches or connects to configured MCP servers over stdio, HTTP+SSE or
+//! streamable HTTP, performs the `initialize` handshake and lists what they
+//! offer, so servers can be checked without going through the Claude CLI.
+//! `lint` explains what a project's configuration resolves to. Servers the
+//! app runs itself are supervised by `host`, and `team_server`
+//! goes the other way, exposing the agent team to outside MCP clients.
+
+pub mod client;
+pub mod config;
+pub mod host;
+pub mod lint;
+pub mod team_server;
+pub mod transport;
+pub mod types;
+
+pub use client::{McpClient, McpError};
+pub use config::{ConfigScope, ServerConfig, TransportKind};
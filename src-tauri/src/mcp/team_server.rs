@@ -0,0 +1,750 @@
+//! The agent team as an MCP server
+//!
+//! Lets outside MCP clients (other Claude Code sessions, IDEs) browse
+//! projects and their members, read a project's conversation and message
+//! agents. Messages are sent exactly like those typed in the app, starting
+//! the target agent if needed. `VibeAgentTeamWeb` serves this over
+//! streamable HTTP at `POST /mcp`, sharing the team context of the agents it
+//! runs.
+//!
+//! It can also serve over stdio (`--mcp-stdio`), but that process runs no
+//! agents, so it is read-only: tools that need running agents
+//! (`LIVE_TOOLS`) are left out and member status is not reported.
+
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use super::types::{
+    Implementation, InitializeResult, ServerCapabilities, Tool, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_NOT_FOUND, PARSE_ERROR, PROTOCOL_VERSION,
+};
+use crate::commands::message::{load_messages, send_team_message};
+use crate::commands::teammate::{project_member_statuses, TeamContext};
+
+/// Messages returned by `get_messages` when no limit is given
+const DEFAULT_MESSAGE_LIMIT: usize = 50;
+/// Sender recorded for messages sent through this server
+const SENDER_ID: &str = "mcp";
+/// Tools that need the running agents
+const LIVE_TOOLS: &[&str] = &["send_message_to_agent", "get_member_status"];
+
+#[derive(Debug, Serialize)]
+struct ProjectSummary {
+    id: String,
+    name: String,
+    description: Option<String>,
+    working_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TeamMember {
+    /// project_agents id, also the member's session id
+    member_id: String,
+    agent_id: String,
+    name: String,
+    role_type: Option<String>,
+    model: Option<String>,
+    /// Process status; unknown to a read-only server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+}
+
+/// Serves the team over MCP
+#[derive(Clone)]
+pub struct TeamServer {
+    db: Arc<Mutex<Connection>>,
+    /// Context of the running agents; `None` for a read-only server
+    team: Option<TeamContext>,
+}
+
+impl TeamServer {
+    pub fn new(team: TeamContext) -> Self {
+        Self {
+            db: team.db.clone(),
+            team: Some(team),
+        }
+    }
+
+    /// A server for a process that runs no agents. It only reads the
+    /// database, so it offers none of the `LIVE_TOOLS`.
+    pub fn read_only(db: Arc<Mutex<Connection>>) -> Self {
+        Self { db, team: None }
+    }
+
+    /// Tools offered by the server
+    pub fn tools(&self) -> Vec<Tool> {
+        let live = self.team.is_some();
+        Self::all_tools(live)
+            .into_iter()
+            .filter(|t| live || !LIVE_TOOLS.contains(&t.name.as_str()))
+            .collect()
+    }
+
+    fn all_tools(live: bool) -> Vec<Tool> {
+        let project_id = json!({"type": "string", "description": "Project id from list_projects"});
+        vec![
+            Tool {
+                name: "list_projects".to_string(),
+                description: Some("List the team's projects".to_string()),
+                input_schema: json!({"type": "object", "properties": {}}),
+            },
+            Tool {
+                name: "list_team_members".to_string(),
+                description: Some(if live {
+                    "List the agents on a project's team and their status".to_string()
+                } else {
+                    "List the agents on a project's team".to_string()
+                }),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {"project_id": project_id},
+                    "required": ["project_id"]
+                }),
+            },
+            Tool {
+                name: "send_message_to_agent".to_string(),
+                description: Some(
+                    "Send a message to a project's team. It goes to the named agent, \
+                     to an agent @mentioned in the content, or else to the TeamLead, \
+                     starting it if it is not running."
+                        .to_string(),
+                ),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "project_id": project_id,
+                        "content": {"type": "string", "description": "Message text"},
+                        "agent_name": {"type": "string", "description": "Agent to address"},
+                        "sender_name": {"type": "string", "description": "Name shown as the sender"}
+                    },
+                    "required": ["project_id", "content"]
+                }),
+            },
+            Tool {
+                name: "get_messages".to_string(),
+                description: Some("Read a project's conversation, oldest first".to_string()),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "project_id": project_id,
+                        "limit": {
+                            "type": "integer",
+                            "description": format!("Most recent messages to return (default {})", DEFAULT_MESSAGE_LIMIT)
+                        },
+                        "since": {
+                            "type": "string",
+                            "description": "Only messages created after this RFC 3339 timestamp"
+                        }
+                    },
+                    "required": ["project_id"]
+                }),
+            },
+            Tool {
+                name: "get_member_status".to_string(),
+                description: Some(
+                    "Process status (pending, running, completed, stopped, error) of a \
+                     project's members"
+                        .to_string(),
+                ),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "project_id": project_id,
+                        "agent_name": {"type": "string", "description": "Only this agent"}
+                    },
+                    "required": ["project_id"]
+                }),
+            },
+        ]
+    }
+
+    /// Handle one JSON-RPC message or batch. Returns `None` when there is
+    /// nothing to send back (notifications and responses).
+    pub async fn handle(&self, message: JsonValue) -> Option<JsonValue> {
+        match message {
+            JsonValue::Array(batch) => {
+                let mut replies = Vec::new();
+                for message in batch {
+                    if let Some(reply) = self.handle_one(message).await {
+                        replies.push(reply);
+                    }
+                }
+                (!replies.is_empty()).then_some(JsonValue::Array(replies))
+            }
+            message => self.handle_one(message).await,
+        }
+    }
+
+    async fn handle_one(&self, message: JsonValue) -> Option<JsonValue> {
+        let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+            // A response (we never send requests, so ignore it) or garbage
+            return message
+                .get("id")
+                .is_none()
+                .then(|| error_reply(JsonValue::Null, INVALID_REQUEST, "Invalid request"));
+        };
+        // Notifications (no id) get no reply
+        let id = message.get("id").cloned()?;
+        let params = message.get("params").cloned().unwrap_or(JsonValue::Null);
+
+        let result = match method {
+            "initialize" => {
+                let client = params
+                    .pointer("/clientInfo/name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("unknown");
+                info!("MCP client connected: {}", client);
+                serde_json::to_value(InitializeResult {
+                    protocol_version: PROTOCOL_VERSION.to_string(),
+                    capabilities: ServerCapabilities {
+                        tools: Some(json!({})),
+                        ..Default::default()
+                    },
+                    server_info: Implementation {
+                        name: "vibe-agent-team".to_string(),
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                    },
+                    instructions: Some(
+                        if self.team.is_some() {
+                            "Use list_projects to find a project, then list_team_members, \
+                             get_messages and send_message_to_agent to work with its team."
+                        } else {
+                            "Use list_projects to find a project, then list_team_members and \
+                             get_messages to read its team. This server is read-only; \
+                             message agents through the running app's /mcp endpoint."
+                        }
+                        .to_string(),
+                    ),
+                })
+                .map_err(|e| (INVALID_REQUEST, e.to_string()))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tools() })),
+            "tools/call" => self.handle_tool_call(&params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_reply(id, code, &message),
+        })
+    }
+
+    /// Run `tools/call`. Unknown tools are protocol errors; a tool that fails
+    /// reports `isError` in its result so the model can see why.
+    async fn handle_tool_call(&self, params: &JsonValue) -> Result<JsonValue, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        if !self.tools().iter().any(|t| t.name == name) {
+            return Err((INVALID_PARAMS, format!("Unknown tool: {}", name)));
+        }
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        Ok(match self.call_tool(name, &arguments).await {
+            Ok(value) => json!({
+                "content": [{
+                    "type": "text",
+                    "text": serde_json::to_string_pretty(&value).unwrap_or_default()
+                }],
+                "isError": false
+            }),
+            Err(e) => {
+                warn!("MCP tool {} failed: {}", name, e);
+                json!({"content": [{"type": "text", "text": e}], "isError": true})
+            }
+        })
+    }
+
+    async fn call_tool(&self, name: &str, arguments: &JsonValue) -> Result<JsonValue, String> {
+        match name {
+            "list_projects" => to_value(self.list_projects()?),
+            "list_team_members" => {
+                to_value(self.list_team_members(required_str(arguments, "project_id")?)?)
+            }
+            "send_message_to_agent" => {
+                let project_id = required_str(arguments, "project_id")?;
+                let mut content = required_str(arguments, "content")?.to_string();
+                // Address the agent the way the app does, with an @mention
+                if let Some(agent) = optional_str(arguments, "agent_name") {
+                    let mention = format!("@{}", agent);
+                    if !content.contains(&mention) {
+                        content = format!("{} {}", mention, content);
+                    }
+                }
+                let sender_name = optional_str(arguments, "sender_name").unwrap_or("MCP client");
+                let team = self.team.as_ref().ok_or("This server is read-only")?;
+                let message = send_team_message(
+                    team,
+                    project_id.to_string(),
+                    content,
+                    SENDER_ID.to_string(),
+                    sender_name.to_string(),
+                )
+                .await?;
+                // The app reloads the conversation after its own sends; its
+                // subscribers learn of messages sent here from the event
+                team.events.emit("new-message", &message);
+                to_value(message)
+            }
+            "get_messages" => {
+                let project_id = required_str(arguments, "project_id")?;
+                let limit = match arguments.get("limit") {
+                    None | Some(JsonValue::Null) => DEFAULT_MESSAGE_LIMIT,
+                    Some(limit) => limit
+                        .as_u64()
+                        .ok_or("limit must be a non-negative integer")?
+                        as usize,
+                };
+                let since = optional_str(arguments, "since");
+                let messages = {
+                    let conn = self.db.lock().map_err(|e| e.to_string())?;
+                    load_messages(&conn, project_id)?
+                };
+                let mut messages: Vec<_> = messages
+                    .into_iter()
+                    .filter(|m| since.is_none_or(|since| m.created_at.as_str() > since))
+                    .collect();
+                let skip = messages.len().saturating_sub(limit);
+                to_value(messages.split_off(skip))
+            }
+            "get_member_status" => {
+                let project_id = required_str(arguments, "project_id")?;
+                let mut members = self.list_team_members(project_id)?;
+                if let Some(agent) = optional_str(arguments, "agent_name") {
+                    members.retain(|m| m.name == agent);
+                    if members.is_empty() {
+                        return Err(format!("Agent @{} not found in project", agent));
+                    }
+                }
+                let statuses: Vec<JsonValue> = members
+                    .into_iter()
+                    .map(|m| json!({"member_id": m.member_id, "name": m.name, "status": m.status}))
+                    .collect();
+                Ok(JsonValue::Array(statuses))
+            }
+            _ => Err(format!("Unknown tool: {}", name)),
+        }
+    }
+
+    fn list_projects(&self) -> Result<Vec<ProjectSummary>, String> {
+        let conn = self.db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, description, working_dir
+                 FROM projects
+                 ORDER BY created_at DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let projects = stmt
+            .query_map([], |row| {
+                Ok(ProjectSummary {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    working_dir: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(projects)
+    }
+
+    fn list_team_members(&self, project_id: &str) -> Result<Vec<TeamMember>, String> {
+        let mut members = {
+            let conn = self.db.lock().map_err(|e| e.to_string())?;
+            let exists: bool = conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ?1)",
+                    params![project_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if !exists {
+                return Err(format!("Project not found: {}", project_id));
+            }
+            let mut stmt = conn
+                .prepare(
+                    "SELECT pa.id, a.id, a.name, a.role_type, a.model
+                     FROM project_agents pa
+                     INNER JOIN agents a ON pa.agent_id = a.id
+                     WHERE pa.project_id = ?1
+                     ORDER BY pa.created_at ASC",
+                )
+                .map_err(|e| e.to_string())?;
+            stmt.query_map(params![project_id], |row| {
+                Ok(TeamMember {
+                    member_id: row.get(0)?,
+                    agent_id: row.get(1)?,
+                    name: row.get(2)?,
+                    role_type: row.get(3)?,
+                    model: row.get(4)?,
+                    status: None,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+        };
+
+        let Some(team) = &self.team else {
+            return Ok(members);
+        };
+        let statuses: HashMap<String, String> =
+            project_member_statuses(&self.db, &team.registry, project_id)?
+                .into_iter()
+                .map(|s| (s.project_agent_id, s.status))
+                .collect();
+        for member in &mut members {
+            member.status = Some(
+                statuses
+                    .get(&member.member_id)
+                    .cloned()
+                    .unwrap_or_else(|| "pending".to_string()),
+            );
+        }
+        Ok(members)
+    }
+}
+
+fn to_value(value: impl Serialize) -> Result<JsonValue, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+fn required_str<'a>(arguments: &'a JsonValue, key: &str) -> Result<&'a str, String> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| format!("Missing required argument: {}", key))
+}
+
+fn optional_str<'a>(arguments: &'a JsonValue, key: &str) -> Option<&'a str> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+fn error_reply(id: JsonValue, code: i64, message: &str) -> JsonValue {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Serve newline-delimited JSON-RPC on stdin/stdout until stdin closes.
+/// Logs go to stderr so they never mix with the protocol stream.
+pub async fn serve_stdio(server: TeamServer) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<JsonValue>(&line) {
+            Ok(message) => server.handle(message).await,
+            Err(e) => Some(error_reply(
+                JsonValue::Null,
+                PARSE_ERROR,
+                &format!("Parse error: {}", e),
+            )),
+        };
+        if let Some(reply) = reply {
+            let mut out = reply.to_string();
+            out.push('\n');
+            stdout.write_all(out.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+    use crate::events::BroadcastEmitter;
+    use crate::process::ProcessRegistry;
+    use tempfile::TempDir;
+
+    fn server(dir: &TempDir) -> TeamServer {
+        TeamServer::new(TeamContext {
+            db: team_db(dir),
+            registry: Arc::new(ProcessRegistry::new()),
+            events: Arc::new(BroadcastEmitter::new()),
+            app_data_dir: dir.path().to_path_buf(),
+            checkpoints: None,
+        })
+    }
+
+    fn team_db(dir: &TempDir) -> Arc<Mutex<Connection>> {
+        let conn = init_database_with_path(&dir.path().join("test.db")).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name, working_dir) VALUES ('p1', 'Demo', '/tmp/demo')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agents (id, name, icon, system_prompt, role_type) VALUES ('a1', 'alice', 'bot', '', 'teamlead')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO project_agents (id, project_id, agent_id) VALUES ('m1', 'p1', 'a1')",
+            [],
+        )
+        .unwrap();
+        Arc::new(Mutex::new(conn))
+    }
+
+    async fn call(server: &TeamServer, tool: &str, arguments: JsonValue) -> JsonValue {
+        server
+            .handle(json!({
+                "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                "params": {"name": tool, "arguments": arguments}
+            }))
+            .await
+            .unwrap()["result"]
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_serves_team_tools_over_json_rpc() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+
+        let init = server
+            .handle(json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {}}))
+            .await
+            .unwrap();
+        assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(server
+            .handle(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await
+            .is_none());
+
+        let listed = server
+            .handle(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}))
+            .await
+            .unwrap();
+        assert_eq!(listed["result"]["tools"].as_array().unwrap().len(), 5);
+
+        let members = call(&server, "list_team_members", json!({"project_id": "p1"})).await;
+        assert_eq!(members["isError"], false);
+        let text = members["content"][0]["text"].as_str().unwrap();
+        let members: JsonValue = serde_json::from_str(text).unwrap();
+        assert_eq!(members[0]["name"], "alice");
+        assert_eq!(members[0]["status"], "pending");
+
+        // Delivery failures are tool errors
+        let sent = call(
+            &server,
+            "send_message_to_agent",
+            json!({"project_id": "p1", "content": "hello", "agent_name": "bob"}),
+        )
+        .await;
+        assert_eq!(sent["isError"], true);
+        assert!(sent["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("@bob not found"));
+
+        let unknown = server
+            .handle(json!({
+                "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                "params": {"name": "nope"}
+            }))
+            .await
+            .unwrap();
+        assert_eq!(unknown["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_server_leaves_out_live_tools() {
+        let dir = TempDir::new().unwrap();
+        let server = TeamServer::read_only(team_db(&dir));
+
+        let listed = server
+            .handle(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+            .await
+            .unwrap();
+        let names: Vec<&str> = listed["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["list_projects", "list_team_members", "get_messages"]
+        );
+
+        // No registry, so no made-up status
+        let members = call(&server, "list_team_members", json!({"project_id": "p1"})).await;
+        let text = members["content"][0]["text"].as_str().unwrap();
+        let members: JsonValue = serde_json::from_str(text).unwrap();
+        assert_eq!(members[0]["name"], "alice");
+        assert!(members[0].get("status").is_none());
+
+        for tool in LIVE_TOOLS {
+            let rejected = server
+                .handle(json!({
+                    "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+                    "params": {"name": tool, "arguments": {"project_id": "p1", "content": "hi"}}
+                }))
+                .await
+                .unwrap();
+            assert_eq!(rejected["error"]["code"], INVALID_PARAMS);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_responses_and_unknown_methods() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+
+        // Only requests with an id are answered, in order
+        let replies = server
+            .handle(json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "ping"},
+                {"jsonrpc": "2.0", "method": "notifications/initialized"},
+                {"jsonrpc": "2.0", "id": 7, "result": {}},
+                {"jsonrpc": "2.0", "id": 2, "method": "resources/list"}
+            ]))
+            .await
+            .unwrap();
+        let replies = replies.as_array().unwrap();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"], json!({}));
+        assert_eq!(replies[1]["id"], 2);
+        assert_eq!(replies[1]["error"]["code"], METHOD_NOT_FOUND);
+
+        // A batch with nothing to answer sends nothing back
+        assert!(server
+            .handle(json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}]))
+            .await
+            .is_none());
+        assert!(server.handle(json!([])).await.is_none());
+
+        // Responses are ignored; messages with neither method nor id are invalid
+        assert!(server
+            .handle(json!({"jsonrpc": "2.0", "id": 9, "error": {"code": -1, "message": "x"}}))
+            .await
+            .is_none());
+        let garbage = server.handle(json!({"jsonrpc": "2.0"})).await.unwrap();
+        assert_eq!(garbage["id"], JsonValue::Null);
+        assert_eq!(garbage["error"]["code"], INVALID_REQUEST);
+
+        let nameless = server
+            .handle(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {}}))
+            .await
+            .unwrap();
+        assert_eq!(nameless["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_bad_tool_arguments_are_tool_errors() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        let error_text = |result: &JsonValue| {
+            assert_eq!(result["isError"], true);
+            result["content"][0]["text"].as_str().unwrap().to_string()
+        };
+
+        let missing = call(&server, "list_team_members", json!({})).await;
+        assert!(error_text(&missing).contains("Missing required argument: project_id"));
+
+        let unknown = call(&server, "list_team_members", json!({"project_id": "p9"})).await;
+        assert!(error_text(&unknown).contains("Project not found: p9"));
+
+        for limit in [json!(-1), json!("5"), json!(1.5)] {
+            let bad = call(
+                &server,
+                "get_messages",
+                json!({"project_id": "p1", "limit": limit}),
+            )
+            .await;
+            assert!(error_text(&bad).contains("limit must be a non-negative integer"));
+        }
+
+        let stranger = call(
+            &server,
+            "get_member_status",
+            json!({"project_id": "p1", "agent_name": "bob"}),
+        )
+        .await;
+        assert!(error_text(&stranger).contains("Agent @bob not found in project"));
+
+        let everyone = call(&server, "get_member_status", json!({"project_id": "p1"})).await;
+        assert_eq!(everyone["isError"], false);
+        let text = everyone["content"][0]["text"].as_str().unwrap();
+        let statuses: JsonValue = serde_json::from_str(text).unwrap();
+        assert_eq!(statuses[0]["member_id"], "m1");
+        assert_eq!(statuses[0]["name"], "alice");
+    }
+
+    #[tokio::test]
+    async fn test_get_messages_keeps_the_latest_after_since() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        {
+            let conn = server.db.lock().unwrap();
+            for (id, at, kind) in [
+                ("x1", "2026-01-01 10:00:00", "text"),
+                ("x2", "2026-01-01 11:00:00", "result"),
+                ("x3", "2026-01-01 12:00:00", "text"),
+                ("x4", "2026-01-01 13:00:00", "text"),
+            ] {
+                conn.execute(
+                    "INSERT INTO messages (id, project_id, sender_id, sender_name, target_id, content, message_type, created_at)
+                     VALUES (?1, 'p1', 'a1', 'alice', 'all', ?1, ?3, ?2)",
+                    rusqlite::params![id, at, kind],
+                )
+                .unwrap();
+            }
+        }
+        let ids = |result: &JsonValue| -> Vec<String> {
+            let text = result["content"][0]["text"].as_str().unwrap();
+            let messages: JsonValue = serde_json::from_str(text).unwrap();
+            messages
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| m["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        // Result messages are hidden from the chat history
+        let all = call(&server, "get_messages", json!({"project_id": "p1"})).await;
+        assert_eq!(ids(&all), ["x1", "x3", "x4"]);
+
+        let latest = call(
+            &server,
+            "get_messages",
+            json!({"project_id": "p1", "limit": 2}),
+        )
+        .await;
+        assert_eq!(ids(&latest), ["x3", "x4"]);
+
+        let none = call(
+            &server,
+            "get_messages",
+            json!({"project_id": "p1", "limit": 0}),
+        )
+        .await;
+        assert!(ids(&none).is_empty());
+
+        let since = call(
+            &server,
+            "get_messages",
+            json!({"project_id": "p1", "since": "2026-01-01 12:00:00", "limit": null}),
+        )
+        .await;
+        assert_eq!(ids(&since), ["x4"]);
+    }
+}
@@ -0,0 +1,577 @@
+//! MCP transports
+//!
+//! A transport moves JSON-RPC messages between the client and one server;
+//! the client on top matches responses to requests.
+
+use async_trait::async_trait;
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+use super::client::McpError;
+
+/// Lines of server stderr kept for error reports
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Session header of the streamable HTTP transport
+const SESSION_HEADER: &str = "mcp-session-id";
+
+#[async_trait]
+pub trait Transport: Send {
+    /// Send one JSON-RPC message
+    async fn send(&mut self, message: &JsonValue) -> Result<(), McpError>;
+
+    /// Wait for the next message from the server
+    async fn receive(&mut self) -> Result<JsonValue, McpError>;
+
+    /// Shut the connection down
+    async fn close(&mut self);
+}
+
+/// A server launched as a child process, speaking newline-delimited JSON
+pub struct StdioTransport {
+    /// None when attached to a process owned elsewhere
+    child: Option<Child>,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl StdioTransport {
+    pub fn spawn(
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Self, McpError> {
+        let mut cmd: tokio::process::Command =
+            crate::claude_binary::create_command_with_env(command).into();
+        cmd.args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| McpError::Spawn(format!("{}: {}", command, e)))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| McpError::Spawn("Failed to open server stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| McpError::Spawn("Failed to open server stdout".to_string()))?;
+
+        // Servers log to stderr; keep the tail so failures can say why
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        if let Some(stderr) = child.stderr.take() {
+            let tail = stderr_tail.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("mcp stderr: {}", line);
+                    if let Ok(mut tail) = tail.lock() {
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(line);
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
+            child: Some(child),
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            stderr_tail,
+        })
+    }
+
+    /// Talk over the pipes of a process someone else owns and supervises;
+    /// `close` then leaves the process running
+    pub fn attach(stdin: ChildStdin, stdout: ChildStdout) -> Self {
+        Self {
+            child: None,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            stderr_tail: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn closed_error(&mut self) -> McpError {
+        let status = self
+            .child
+            .as_mut()
+            .and_then(|child| child.try_wait().ok().flatten())
+            .map(|s| format!(" ({})", s))
+            .unwrap_or_default();
+        let stderr = self
+            .stderr_tail
+            .lock()
+            .map(|t| t.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default();
+        if stderr.is_empty() {
+            McpError::Closed(format!("Server process exited{}", status))
+        } else {
+            McpError::Closed(format!("Server process exited{}: {}", status, stderr))
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for StdioTransport {
+    async fn send(&mut self, message: &JsonValue) -> Result<(), McpError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        if self.stdin.write_all(line.as_bytes()).await.is_err() || self.stdin.flush().await.is_err()
+        {
+            return Err(self.closed_error());
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<JsonValue, McpError> {
+        loop {
+            match self.stdout.next_line().await {
+                Ok(Some(line)) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    return serde_json::from_str(line).map_err(|e| {
+                        McpError::Protocol(format!("{} in {:?}", e, truncate(line, 200)))
+                    });
+                }
+                Ok(None) | Err(_) => return Err(self.closed_error()),
+            }
+        }
+    }
+
+    async fn close(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.start_kill();
+            let _ = child.wait().await;
+        }
+    }
+}
+
+fn truncate(value: &str, max: usize) -> &str {
+    match value.char_indices().nth(max) {
+        Some((i, _)) => &value[..i],
+        None => value,
+    }
+}
+
+/// One server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: String,
+    pub data: String,
+}
+
+/// Incremental parser for a `text/event-stream` body
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed a chunk of the body, returning the events it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                // A blank line dispatches the event
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        event: self.event.take().unwrap_or_else(|| "message".to_string()),
+                        data: self.data.join("\n"),
+                    });
+                }
+                self.event = None;
+                self.data.clear();
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// Build the configured extra headers
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, McpError> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| McpError::Config(format!("Invalid header name '{}': {}", name, e)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| McpError::Config(format!("Invalid value for header {}: {}", name, e)))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+fn http_client(headers: &HashMap<String, String>) -> Result<reqwest::Client, McpError> {
+    reqwest::Client::builder()
+        .default_headers(header_map(headers)?)
+        .build()
+        .map_err(|e| McpError::Connection(e.to_string()))
+}
+
+/// Turn a non-success HTTP status into an error, calling out auth failures
+fn check_status(response: &reqwest::Response, what: &str) -> Result<(), McpError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let hint = match status.as_u16() {
+        401 | 403 => " (check the server's auth headers)",
+        _ => "",
+    };
+    Err(McpError::Connection(format!(
+        "{} returned HTTP {}{}",
+        what, status, hint
+    )))
+}
+
+/// The HTTP+SSE transport: the server announces a POST endpoint in an
+/// `endpoint` event and sends responses as `message` events on the stream
+pub struct SseTransport {
+    http: reqwest::Client,
+    stream: reqwest::Response,
+    endpoint: Url,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+}
+
+impl SseTransport {
+    pub async fn connect(url: &str, headers: &HashMap<String, String>) -> Result<Self, McpError> {
+        let base = Url::parse(url).map_err(|e| McpError::Config(format!("Invalid URL: {}", e)))?;
+        let http = http_client(headers)?;
+        let stream = http
+            .get(base.clone())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await
+            .map_err(|e| McpError::Connection(e.to_string()))?;
+        check_status(&stream, "SSE stream")?;
+
+        let mut transport = Self {
+            http,
+            stream,
+            endpoint: base.clone(),
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        };
+        let endpoint = loop {
+            let event = transport.next_event().await?;
+            if event.event == "endpoint" {
+                break event.data;
+            }
+        };
+        transport.endpoint = base
+            .join(endpoint.trim())
+            .map_err(|e| McpError::Protocol(format!("Invalid endpoint: {}", e)))?;
+        Ok(transport)
+    }
+
+    async fn next_event(&mut self) -> Result<SseEvent, McpError> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            match self.stream.chunk().await {
+                Ok(Some(chunk)) => self.pending.extend(self.parser.push(&chunk)),
+                Ok(None) => {
+                    return Err(McpError::Closed("SSE stream ended".to_string()));
+                }
+                Err(e) => return Err(McpError::Connection(e.to_string())),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for SseTransport {
+    async fn send(&mut self, message: &JsonValue) -> Result<(), McpError> {
+        let response = self
+            .http
+            .post(self.endpoint.clone())
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| McpError::Connection(e.to_string()))?;
+        check_status(&response, &format!("POST {}", self.endpoint))
+    }
+
+    async fn receive(&mut self) -> Result<JsonValue, McpError> {
+        loop {
+            let event = self.next_event().await?;
+            if event.event != "message" {
+                continue;
+            }
+            return serde_json::from_str(&event.data).map_err(|e| {
+                McpError::Protocol(format!("{} in {:?}", e, truncate(&event.data, 200)))
+            });
+        }
+    }
+
+    async fn close(&mut self) {}
+}
+
+/// The streamable HTTP transport: every message is POSTed to one endpoint,
+/// which answers with a JSON body or an event stream of messages ending with
+/// the response. The session id from `initialize` is echoed on later requests.
+pub struct HttpTransport {
+    http: reqwest::Client,
+    url: Url,
+    session_id: Option<String>,
+    pending: VecDeque<JsonValue>,
+    /// Event streams of in-flight requests, read in order
+    streams: VecDeque<(reqwest::Response, SseParser)>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str, headers: &HashMap<String, String>) -> Result<Self, McpError> {
+        Ok(Self {
+            http: http_client(headers)?,
+            url: Url::parse(url).map_err(|e| McpError::Config(format!("Invalid URL: {}", e)))?,
+            session_id: None,
+            pending: VecDeque::new(),
+            streams: VecDeque::new(),
+        })
+    }
+
+    /// Queue the messages of a JSON body, which may be a single message or a batch
+    fn queue_body(&mut self, body: &[u8]) -> Result<(), McpError> {
+        let value: JsonValue = serde_json::from_slice(body).map_err(|e| {
+            McpError::Protocol(format!(
+                "{} in {:?}",
+                e,
+                truncate(&String::from_utf8_lossy(body), 200)
+            ))
+        })?;
+        match value {
+            JsonValue::Array(messages) => self.pending.extend(messages),
+            message => self.pending.push_back(message),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(&mut self, message: &JsonValue) -> Result<(), McpError> {
+        let mut request = self
+            .http
+            .post(self.url.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        if let Some(session_id) = &self.session_id {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| McpError::Connection(e.to_string()))?;
+        check_status(&response, &format!("POST {}", self.url))?;
+
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            self.session_id = Some(session_id.to_string());
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with("text/event-stream") {
+            self.streams.push_back((response, SseParser::default()));
+        } else {
+            // 202 Accepted for notifications and responses carries no body
+            let body = response
+                .bytes()
+                .await
+                .map_err(|e| McpError::Connection(e.to_string()))?;
+            if !body.iter().all(u8::is_ascii_whitespace) {
+                self.queue_body(&body)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<JsonValue, McpError> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(message);
+            }
+            let Some((stream, parser)) = self.streams.front_mut() else {
+                return Err(McpError::Closed(
+                    "No response pending from the HTTP server".to_string(),
+                ));
+            };
+            match stream.chunk().await {
+                Ok(Some(chunk)) => {
+                    for event in parser.push(&chunk) {
+                        if event.event == "message" {
+                            self.queue_body(event.data.as_bytes())?;
+                        }
+                    }
+                }
+                Ok(None) => {
+                    self.streams.pop_front();
+                }
+                Err(e) => {
+                    self.streams.pop_front();
+                    return Err(McpError::Connection(e.to_string()));
+                }
+            }
+        }
+    }
+
+    async fn close(&mut self) {
+        self.streams.clear();
+        // Let the server drop the session; servers without sessions ignore this
+        if let Some(session_id) = self.session_id.take() {
+            let _ = self
+                .http
+                .delete(self.url.clone())
+                .header(SESSION_HEADER, session_id)
+                .send()
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks_and_multiline_data() {
+        let mut parser = SseParser::default();
+        assert!(parser
+            .push(b": keep-alive\n\nevent: endpoint\r\nda")
+            .is_empty());
+        let events = parser.push(b"ta: /messages?session=1\r\n\r\ndata: {\"a\":\ndata: 1}\n\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: "endpoint".to_string(),
+                    data: "/messages?session=1".to_string(),
+                },
+                SseEvent {
+                    event: "message".to_string(),
+                    data: "{\"a\":\n1}".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sse_parser_drops_empty_events_and_waits_for_the_blank_line() {
+        let mut parser = SseParser::default();
+        // An event without data is not dispatched, and its type does not leak
+        assert!(parser.push(b"event: endpoint\n\n").is_empty());
+        assert!(parser.push(b"data:{}\nid: 7\nretry\n").is_empty());
+        assert_eq!(
+            parser.push(b"\n"),
+            vec![SseEvent {
+                event: "message".to_string(),
+                data: "{}".to_string(),
+            }]
+        );
+        // A field without a colon has an empty value
+        assert_eq!(parser.push(b"data\ndata\n\n")[0].data, "\n");
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        assert_eq!(truncate("héllo", 2), "hé");
+        assert_eq!(truncate("short", 200), "short");
+    }
+
+    #[tokio::test]
+    async fn test_stdio_reports_bad_lines_exits_and_missing_commands() {
+        let args = |script: &str| vec!["-c".to_string(), script.to_string()];
+        let mut transport =
+            StdioTransport::spawn("sh", &args("echo; echo not-json"), &HashMap::new()).unwrap();
+        let err = transport.receive().await.unwrap_err();
+        assert!(matches!(err, McpError::Protocol(ref m) if m.contains("not-json")));
+        let err = transport.receive().await.unwrap_err();
+        assert!(matches!(err, McpError::Closed(ref m) if m.starts_with("Server process exited")));
+        transport.close().await;
+
+        let err = StdioTransport::spawn("/nonexistent/mcp-server", &[], &HashMap::new())
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Spawn(ref m) if m.contains("/nonexistent/mcp-server")));
+    }
+
+    #[test]
+    fn test_http_transport_rejects_bad_urls_and_headers() {
+        let err = HttpTransport::new("not a url", &HashMap::new())
+            .err()
+            .unwrap();
+        assert!(matches!(err, McpError::Config(ref m) if m.starts_with("Invalid URL")));
+
+        let headers = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        let err = header_map(&headers).unwrap_err();
+        assert!(matches!(err, McpError::Config(ref m) if m.contains("Bad Header")));
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer\nx".to_string())]);
+        let err = header_map(&headers).unwrap_err();
+        assert!(matches!(err, McpError::Config(ref m) if m.contains("authorization")));
+    }
+
+    #[tokio::test]
+    async fn test_http_transport_queues_batches_and_reports_nothing_pending() {
+        let mut transport = HttpTransport::new("http://127.0.0.1:9/mcp", &HashMap::new()).unwrap();
+        transport
+            .queue_body(br#"[{"id": 1, "result": {}}, {"method": "notifications/progress"}]"#)
+            .unwrap();
+        transport.queue_body(br#"{"id": 2, "result": {}}"#).unwrap();
+        assert_eq!(transport.receive().await.unwrap()["id"], 1);
+        assert_eq!(
+            transport.receive().await.unwrap()["method"],
+            "notifications/progress"
+        );
+        assert_eq!(transport.receive().await.unwrap()["id"], 2);
+
+        let err = transport.receive().await.unwrap_err();
+        assert!(matches!(err, McpError::Closed(_)));
+        let err = transport.queue_body(b"<html>").unwrap_err();
+        assert!(matches!(err, McpError::Protocol(ref m) if m.contains("<html>")));
+    }
+}
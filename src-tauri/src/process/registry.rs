@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::process::ExitStatus;
 use std::sync::{Arc, Mutex};
 use tokio::process::{Child, ChildStdin};
 use uuid::Uuid;
@@ -269,6 +270,9 @@ pub enum ProcessType {
         agent_id: String,
         agent_name: String,
     },
+    McpServer {
+        server_name: String,
+    },
 }
 
 /// Information about a running agent process
@@ -452,6 +456,29 @@ impl ProcessRegistry {
         Ok(run_id)
     }
 
+    /// Register a long-running MCP server hosted by the app
+    pub fn register_mcp_server(
+        &self,
+        run_id: String,
+        server_name: String,
+        pid: u32,
+        project_path: String,
+        command_line: String,
+        child: Child,
+    ) -> Result<(), String> {
+        let process_info = ProcessInfo {
+            run_id: run_id.clone(),
+            process_type: ProcessType::McpServer { server_name },
+            pid,
+            started_at: Utc::now(),
+            project_path,
+            task: command_line,
+            model: String::new(),
+        };
+
+        self.register_process_internal(run_id, process_info, child)
+    }
+
     /// Get all running teammate agents
     pub fn get_running_teammate_agents(&self) -> Result<Vec<ProcessInfo>, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
@@ -811,6 +838,40 @@ impl ProcessRegistry {
         }
     }
 
+    /// Wait for a registered process to exit and return its status. `None`
+    /// when it is killed or unregistered first, as the handle goes with it.
+    pub async fn wait_for_exit(&self, run_id: &str) -> Result<Option<ExitStatus>, String> {
+        loop {
+            let child_arc = {
+                let processes = self.processes.lock().map_err(|e| e.to_string())?;
+                match processes.get(run_id) {
+                    Some(handle) => handle.child.clone(),
+                    None => return Ok(None),
+                }
+            };
+            {
+                let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
+                let Some(child) = child_guard.as_mut() else {
+                    return Ok(None);
+                };
+                if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                    *child_guard = None;
+                    return Ok(Some(status));
+                }
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Close a process's stdin so it sees the end of its input
+    pub fn close_stdin(&self, run_id: &str) -> Result<(), String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get(run_id) {
+            handle.stdin.lock().map_err(|e| e.to_string())?.take();
+        }
+        Ok(())
+    }
+
     /// Append to live output for a process
     pub fn append_live_output(&self, run_id: String, output: &str) -> Result<(), String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
@@ -822,6 +883,34 @@ impl ProcessRegistry {
         Ok(())
     }
 
+    /// Append to live output, dropping the oldest lines beyond `max_bytes`
+    /// so long-running processes don't grow it without bound
+    pub fn append_live_output_bounded(
+        &self,
+        run_id: &str,
+        output: &str,
+        max_bytes: usize,
+    ) -> Result<(), String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get(run_id) {
+            let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
+            live_output.push_str(output);
+            live_output.push('\n');
+            if live_output.len() > max_bytes {
+                let mut excess = live_output.len() - max_bytes;
+                // Slicing inside a multi-byte character would panic
+                while !live_output.is_char_boundary(excess) {
+                    excess += 1;
+                }
+                let cut = live_output[excess..]
+                    .find('\n')
+                    .map_or(live_output.len(), |i| excess + i + 1);
+                live_output.drain(..cut);
+            }
+        }
+        Ok(())
+    }
+
     /// Get live output for a process
     pub fn get_live_output(&self, run_id: String) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
@@ -908,3 +997,48 @@ impl Default for ProcessRegistryState {
         Self(Arc::new(ProcessRegistry::new()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_live_output_cuts_at_char_boundaries() {
+        let registry = ProcessRegistry::new();
+        registry
+            .register_sidecar_process(
+                "run".to_string(),
+                "agent".to_string(),
+                "Agent".to_string(),
+                0,
+                "/work".to_string(),
+                "task".to_string(),
+                "sonnet".to_string(),
+            )
+            .unwrap();
+
+        // 5 bytes, then 8 more: the cap falls inside the second 'é'
+        registry
+            .append_live_output_bounded("run", "éé", 10)
+            .unwrap();
+        registry
+            .append_live_output_bounded("run", "abcdefg", 10)
+            .unwrap();
+        assert_eq!(
+            registry.get_live_output("run".to_string()).unwrap(),
+            "abcdefg\n"
+        );
+
+        // A single line over the cap is dropped whole
+        registry
+            .append_live_output_bounded("run", "ééééééé", 10)
+            .unwrap();
+        assert_eq!(registry.get_live_output("run".to_string()).unwrap(), "");
+
+        // The registry is still usable afterwards
+        registry
+            .append_live_output_bounded("run", "ok", 10)
+            .unwrap();
+        assert_eq!(registry.get_live_output("run".to_string()).unwrap(), "ok\n");
+    }
+}
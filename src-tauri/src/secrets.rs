@@ -0,0 +1,699 @@
+//! Local secret store
+//!
+//! Secrets such as MCP API keys live in one JSON file, each value encrypted
+//! with AES-256-GCM under a 32-byte store key. The key is kept in the OS
+//! keyring where one is available (macOS Keychain, Windows Credential
+//! Manager) or derived from a passphrase with Argon2id, which works on
+//! headless Linux; `VIBE_SECRETS_PASSPHRASE` unlocks such a store at startup.
+//!
+//! Config values refer to secrets as `${secret:NAME}`. References are only
+//! resolved when a process is spawned, so the values never reach config files.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::{info, warn};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Prefix of secret references inside `${...}` placeholders
+pub const SECRET_PREFIX: &str = "secret:";
+
+/// Environment variable that unlocks a passphrase store at startup
+pub const PASSPHRASE_ENV: &str = "VIBE_SECRETS_PASSPHRASE";
+
+/// Prefix of the environment variables secrets are handed to Claude in
+pub const SECRET_ENV_PREFIX: &str = "VIBE_SECRET_";
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+const KEYRING_SERVICE: &str = "vibe-agent-team";
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+const KEYRING_USER: &str = "secret-store-key";
+const FORMAT_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+/// Encrypted under the store key to check a passphrase on unlock
+const CHECK_PLAINTEXT: &[u8] = b"vibe-agent-team secret store";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("The secret store has not been set up")]
+    NotInitialized,
+    #[error("The secret store is already set up")]
+    AlreadyInitialized,
+    #[error("The secret store is locked")]
+    Locked,
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("No OS keyring is available on this platform; use a passphrase")]
+    KeyringUnavailable,
+    #[error("Keyring error: {0}")]
+    Keyring(String),
+    #[error("Invalid secret name '{0}': use letters, digits, '_', '-' and '.'")]
+    InvalidName(String),
+    #[error("Secrets '{0}' and '{1}' would both be exported as {2}; rename one of them")]
+    EnvCollision(String, String, String),
+    #[error("Secret store file is corrupt: {0}")]
+    Corrupt(String),
+    #[error("Secret store I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Where the store key comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Keyring,
+    Passphrase,
+}
+
+/// Argon2id parameters used to derive the key from a passphrase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// On-disk layout; values are base64 of nonce followed by ciphertext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    key_source: KeySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    check: String,
+    #[serde(default)]
+    secrets: BTreeMap<String, String>,
+}
+
+/// What the UI needs to know about the store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretStoreStatus {
+    pub initialized: bool,
+    pub key_source: Option<KeySource>,
+    pub unlocked: bool,
+    pub keyring_available: bool,
+    pub secret_count: usize,
+}
+
+pub struct SecretStore {
+    path: PathBuf,
+    file: Option<StoreFile>,
+    key: Option<[u8; 32]>,
+    argon2_params: argon2::Params,
+}
+
+impl SecretStore {
+    /// Load the store at `path`; a missing file is an uninitialized store
+    pub fn open(path: PathBuf) -> Result<Self, SecretError> {
+        let file = if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            let file: StoreFile =
+                serde_json::from_str(&content).map_err(|e| SecretError::Corrupt(e.to_string()))?;
+            if file.version > FORMAT_VERSION {
+                return Err(SecretError::Corrupt(format!(
+                    "unsupported version {}",
+                    file.version
+                )));
+            }
+            Some(file)
+        } else {
+            None
+        };
+        Ok(Self {
+            path,
+            file,
+            key: None,
+            argon2_params: argon2::Params::default(),
+        })
+    }
+
+    pub fn status(&self) -> SecretStoreStatus {
+        SecretStoreStatus {
+            initialized: self.file.is_some(),
+            key_source: self.file.as_ref().map(|f| f.key_source),
+            unlocked: self.key.is_some(),
+            keyring_available: keyring_available(),
+            secret_count: self.file.as_ref().map_or(0, |f| f.secrets.len()),
+        }
+    }
+
+    /// Create the store, keyed by `passphrase` or, without one, by a random
+    /// key kept in the OS keyring
+    pub fn initialize(&mut self, passphrase: Option<&str>) -> Result<(), SecretError> {
+        if self.file.is_some() {
+            return Err(SecretError::AlreadyInitialized);
+        }
+        let (key, key_source, kdf) = match passphrase {
+            Some(passphrase) => {
+                let mut salt = [0u8; 16];
+                rand::rngs::OsRng.fill_bytes(&mut salt);
+                let kdf = KdfParams {
+                    salt: BASE64.encode(salt),
+                    m_cost: self.argon2_params.m_cost(),
+                    t_cost: self.argon2_params.t_cost(),
+                    p_cost: self.argon2_params.p_cost(),
+                };
+                (
+                    derive_key(passphrase, &kdf)?,
+                    KeySource::Passphrase,
+                    Some(kdf),
+                )
+            }
+            None => {
+                let mut key = [0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut key);
+                keyring_store(&key)?;
+                (key, KeySource::Keyring, None)
+            }
+        };
+        self.file = Some(StoreFile {
+            version: FORMAT_VERSION,
+            key_source,
+            kdf,
+            check: encrypt(&key, CHECK_PLAINTEXT, b"")?,
+            secrets: BTreeMap::new(),
+        });
+        self.key = Some(key);
+        self.save()
+    }
+
+    /// Unlock with a passphrase, or from the keyring when `passphrase` is None
+    pub fn unlock(&mut self, passphrase: Option<&str>) -> Result<(), SecretError> {
+        let file = self.file.as_ref().ok_or(SecretError::NotInitialized)?;
+        let key = match (file.key_source, passphrase, &file.kdf) {
+            (KeySource::Passphrase, Some(passphrase), Some(kdf)) => derive_key(passphrase, kdf)?,
+            (KeySource::Passphrase, None, _) => return Err(SecretError::Locked),
+            (KeySource::Passphrase, Some(_), None) => {
+                return Err(SecretError::Corrupt(
+                    "missing key derivation parameters".into(),
+                ))
+            }
+            (KeySource::Keyring, _, _) => keyring_load()?,
+        };
+        if decrypt(&key, &file.check, b"").ok().as_deref() != Some(CHECK_PLAINTEXT) {
+            return Err(match file.key_source {
+                KeySource::Passphrase => SecretError::WrongPassphrase,
+                KeySource::Keyring => SecretError::Corrupt("keyring key does not match".into()),
+            });
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    pub fn lock(&mut self) {
+        self.key = None;
+    }
+
+    /// Names of stored secrets; available while locked
+    pub fn names(&self) -> Vec<String> {
+        self.file
+            .as_ref()
+            .map(|f| f.secrets.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>, SecretError> {
+        let (file, key) = self.unlocked()?;
+        file.secrets
+            .get(name)
+            .map(|value| {
+                let plain = decrypt(key, value, name.as_bytes())?;
+                String::from_utf8(plain).map_err(|e| SecretError::Corrupt(e.to_string()))
+            })
+            .transpose()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SecretError> {
+        validate_name(name)?;
+        let (file, key) = self.unlocked()?;
+        let var = env_var_name(name);
+        if let Some(other) = file
+            .secrets
+            .keys()
+            .find(|other| *other != name && env_var_name(other) == var)
+        {
+            return Err(SecretError::EnvCollision(
+                other.clone(),
+                name.to_string(),
+                var,
+            ));
+        }
+        let encrypted = encrypt(key, value.as_bytes(), name.as_bytes())?;
+        if let Some(file) = self.file.as_mut() {
+            file.secrets.insert(name.to_string(), encrypted);
+        }
+        self.save()
+    }
+
+    /// Remove a secret, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> Result<bool, SecretError> {
+        self.unlocked()?;
+        let removed = self
+            .file
+            .as_mut()
+            .is_some_and(|f| f.secrets.remove(name).is_some());
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    fn unlocked(&self) -> Result<(&StoreFile, &[u8; 32]), SecretError> {
+        let file = self.file.as_ref().ok_or(SecretError::NotInitialized)?;
+        let key = self.key.as_ref().ok_or(SecretError::Locked)?;
+        Ok((file, key))
+    }
+
+    /// Write the file atomically, readable only by the current user
+    fn save(&self) -> Result<(), SecretError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let content =
+            serde_json::to_string_pretty(file).map_err(|e| SecretError::Corrupt(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), SecretError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(SecretError::InvalidName(name.to_string()))
+    }
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<[u8; 32], SecretError> {
+    let salt = BASE64
+        .decode(&kdf.salt)
+        .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+    let params = argon2::Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+    Ok(key)
+}
+
+/// Encrypt with a fresh nonce; `aad` binds the value to its secret name
+fn encrypt(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<String, SecretError> {
+    let cipher = Aes256Gcm::new(key.into());
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| SecretError::Corrupt("encryption failed".into()))?;
+    let mut out = nonce.to_vec();
+    out.extend(ciphertext);
+    Ok(BASE64.encode(out))
+}
+
+fn decrypt(key: &[u8; 32], encoded: &str, aad: &[u8]) -> Result<Vec<u8>, SecretError> {
+    let bytes = BASE64
+        .decode(encoded)
+        .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+    if bytes.len() < NONCE_LEN {
+        return Err(SecretError::Corrupt("value too short".into()));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    Aes256Gcm::new(key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| SecretError::Corrupt("value failed to decrypt".into()))
+}
+
+pub fn keyring_available() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn keyring_entry() -> Result<keyring::Entry, SecretError> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .map_err(|e| SecretError::Keyring(e.to_string()))
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn keyring_store(key: &[u8; 32]) -> Result<(), SecretError> {
+    keyring_entry()?
+        .set_password(&BASE64.encode(key))
+        .map_err(|e| SecretError::Keyring(e.to_string()))
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn keyring_load() -> Result<[u8; 32], SecretError> {
+    let encoded = keyring_entry()?
+        .get_password()
+        .map_err(|e| SecretError::Keyring(e.to_string()))?;
+    BASE64
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| SecretError::Keyring("stored key is malformed".into()))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn keyring_store(_key: &[u8; 32]) -> Result<(), SecretError> {
+    Err(SecretError::KeyringUnavailable)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn keyring_load() -> Result<[u8; 32], SecretError> {
+    Err(SecretError::KeyringUnavailable)
+}
+
+// The process-wide store, opened at startup
+lazy_static::lazy_static! {
+    static ref STORE: Mutex<Option<SecretStore>> = Mutex::new(None);
+}
+
+/// Open the store at `path` for this process and unlock it if the keyring
+/// or `VIBE_SECRETS_PASSPHRASE` allows
+pub fn init_global(path: PathBuf) {
+    let mut store = match SecretStore::open(path) {
+        Ok(store) => store,
+        Err(e) => {
+            warn!("Failed to open secret store: {}", e);
+            return;
+        }
+    };
+    if let Some(key_source) = store.status().key_source {
+        let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+        let result = match key_source {
+            KeySource::Keyring => store.unlock(None),
+            KeySource::Passphrase if passphrase.is_some() => store.unlock(passphrase.as_deref()),
+            KeySource::Passphrase => Err(SecretError::Locked),
+        };
+        match result {
+            Ok(()) => info!("Secret store unlocked"),
+            Err(e) => info!("Secret store not unlocked at startup: {}", e),
+        }
+    }
+    if let Ok(mut global) = STORE.lock() {
+        *global = Some(store);
+    }
+}
+
+/// Run `f` against the process-wide store
+pub fn with_store<T>(
+    f: impl FnOnce(&mut SecretStore) -> Result<T, SecretError>,
+) -> Result<T, SecretError> {
+    let mut global = STORE
+        .lock()
+        .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+    let store = global.as_mut().ok_or(SecretError::NotInitialized)?;
+    f(store)
+}
+
+/// Value of a stored secret, or an error saying why it is unavailable
+pub fn resolve(name: &str) -> Result<String, String> {
+    with_store(|store| store.get(name))
+        .map_err(|e| format!("Secret {} is unavailable: {}", name, e))?
+        .ok_or_else(|| format!("Secret {} is not in the secret store", name))
+}
+
+/// Environment variable a secret is exported as for a child process.
+/// Distinct names can share one (`a-b` and `A_B`), which `set` and
+/// `export_refs` refuse.
+pub fn env_var_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", SECRET_ENV_PREFIX, sanitized)
+}
+
+/// Environment a child process needs for the secrets its config refers to
+#[derive(Debug, Default)]
+pub struct SecretEnv {
+    /// Secret values by environment variable
+    pub vars: HashMap<String, String>,
+    /// Secret each variable was exported for
+    names: HashMap<String, String>,
+}
+
+/// Rewrite `${secret:NAME}` references in `value` to `${VIBE_SECRET_NAME}`,
+/// adding each resolved value to `env`. Used for configs handed to Claude,
+/// which expands environment references but knows nothing of the store.
+pub fn export_refs(
+    value: &str,
+    env: &mut SecretEnv,
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${secret:") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2 + SECRET_PREFIX.len()..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated placeholder in '{}'", value))?;
+        let name = &after[..end];
+        let var = env_var_name(name);
+        match env.names.get(&var) {
+            Some(exported) if exported != name => {
+                return Err(
+                    SecretError::EnvCollision(exported.clone(), name.to_string(), var).to_string(),
+                );
+            }
+            Some(_) => {}
+            None => {
+                env.vars.insert(var.clone(), lookup(name)?);
+                env.names.insert(var.clone(), name.to_string());
+            }
+        }
+        out.push_str(&format!("${{{}}}", var));
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Path of the store file inside a data directory
+pub fn store_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("secrets.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cheap_store(path: PathBuf) -> SecretStore {
+        let mut store = SecretStore::open(path).unwrap();
+        store.argon2_params = argon2::Params::new(256, 1, 1, Some(32)).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_passphrase_store_round_trips_and_rejects_wrong_passphrase() {
+        let dir = TempDir::new().unwrap();
+        let path = store_path(dir.path());
+
+        let mut store = cheap_store(path.clone());
+        store.initialize(Some("correct horse")).unwrap();
+        store.set("GITHUB_TOKEN", "ghp_123").unwrap();
+        assert!(matches!(
+            store.set("bad name", "x"),
+            Err(SecretError::InvalidName(_))
+        ));
+
+        // Nothing readable on disk
+        let on_disk = fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("ghp_123"));
+
+        let mut reopened = SecretStore::open(path).unwrap();
+        assert_eq!(reopened.names(), vec!["GITHUB_TOKEN"]);
+        assert!(matches!(
+            reopened.get("GITHUB_TOKEN"),
+            Err(SecretError::Locked)
+        ));
+        assert!(matches!(
+            reopened.unlock(Some("wrong")),
+            Err(SecretError::WrongPassphrase)
+        ));
+        reopened.unlock(Some("correct horse")).unwrap();
+        assert_eq!(
+            reopened.get("GITHUB_TOKEN").unwrap().as_deref(),
+            Some("ghp_123")
+        );
+        assert!(reopened.remove("GITHUB_TOKEN").unwrap());
+        assert!(reopened.names().is_empty());
+    }
+
+    #[test]
+    fn test_export_refs_rewrites_to_env_references() {
+        let mut env = SecretEnv::default();
+        let lookup = |name: &str| match name {
+            "PG_PASSWORD" => Ok("hunter2".to_string()),
+            other => Err(format!("missing {}", other)),
+        };
+        let value = export_refs(
+            "postgres://app:${secret:PG_PASSWORD}@db/${DB}",
+            &mut env,
+            lookup,
+        )
+        .unwrap();
+        assert_eq!(value, "postgres://app:${VIBE_SECRET_PG_PASSWORD}@db/${DB}");
+        assert_eq!(env.vars["VIBE_SECRET_PG_PASSWORD"], "hunter2");
+        assert!(export_refs("${secret:OTHER}", &mut env, lookup).is_err());
+    }
+
+    #[test]
+    fn test_export_refs_rejects_names_sharing_a_variable() {
+        let mut env = SecretEnv::default();
+        let lookup = |name: &str| Ok(format!("value of {}", name));
+        export_refs("${secret:api-key}", &mut env, lookup).unwrap();
+        // Repeating the same secret is fine
+        export_refs("${secret:api-key}", &mut env, lookup).unwrap();
+
+        let err = export_refs("${secret:API_KEY}", &mut env, lookup).unwrap_err();
+        assert!(err.contains("'api-key'") && err.contains("'API_KEY'"));
+        assert!(err.contains("VIBE_SECRET_API_KEY"));
+        assert_eq!(env.vars["VIBE_SECRET_API_KEY"], "value of api-key");
+    }
+
+    #[test]
+    fn test_set_rejects_names_sharing_a_variable() {
+        let dir = TempDir::new().unwrap();
+        let mut store = cheap_store(store_path(dir.path()));
+        store.initialize(Some("pass")).unwrap();
+        store.set("a-b", "one").unwrap();
+        store.set("a-b", "two").unwrap();
+
+        assert!(matches!(
+            store.set("A_B", "three"),
+            Err(SecretError::EnvCollision(ref existing, ref new, ref var))
+                if existing == "a-b" && new == "A_B" && var == "VIBE_SECRET_A_B"
+        ));
+        assert_eq!(store.names(), vec!["a-b"]);
+    }
+
+    #[test]
+    fn test_store_states_without_setup_or_unlock() {
+        let dir = TempDir::new().unwrap();
+        let path = store_path(dir.path());
+        let mut store = cheap_store(path.clone());
+        assert!(!store.status().initialized);
+        assert!(store.names().is_empty());
+        assert!(matches!(store.get("X"), Err(SecretError::NotInitialized)));
+        assert!(matches!(
+            store.unlock(Some("pass")),
+            Err(SecretError::NotInitialized)
+        ));
+        assert!(!path.exists());
+
+        store.initialize(Some("pass")).unwrap();
+        assert!(matches!(
+            store.initialize(Some("again")),
+            Err(SecretError::AlreadyInitialized)
+        ));
+        assert_eq!(store.get("MISSING").unwrap(), None);
+        assert!(!store.remove("MISSING").unwrap());
+
+        store.lock();
+        assert!(!store.status().unlocked);
+        assert!(matches!(store.set("X", "1"), Err(SecretError::Locked)));
+        assert!(matches!(store.unlock(None), Err(SecretError::Locked)));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_files_and_swapped_values_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let path = store_path(dir.path());
+
+        fs::write(&path, "{not json").unwrap();
+        assert!(matches!(
+            SecretStore::open(path.clone()),
+            Err(SecretError::Corrupt(_))
+        ));
+
+        let mut store = cheap_store(dir.path().join("store.json"));
+        store.initialize(Some("pass")).unwrap();
+        store.set("A", "alpha").unwrap();
+        store.set("B", "beta").unwrap();
+        let mut file = store.file.clone().unwrap();
+
+        file.version = FORMAT_VERSION + 1;
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        let err = SecretStore::open(path.clone()).err().unwrap();
+        assert!(matches!(err, SecretError::Corrupt(ref m) if m.contains("version")));
+
+        // Values are bound to their names, so moving one under another fails
+        file.version = FORMAT_VERSION;
+        let a = file.secrets["A"].clone();
+        file.secrets.insert("B".to_string(), a);
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        let mut reopened = SecretStore::open(path).unwrap();
+        reopened.unlock(Some("pass")).unwrap();
+        assert_eq!(reopened.get("A").unwrap().as_deref(), Some("alpha"));
+        assert!(matches!(reopened.get("B"), Err(SecretError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_names_and_env_variables() {
+        for bad in ["", "has space", "dollar$", "naïve"] {
+            assert!(matches!(
+                validate_name(bad),
+                Err(SecretError::InvalidName(_))
+            ));
+        }
+        assert!(validate_name("gh.token-2_prod").is_ok());
+        assert_eq!(env_var_name("gh.token-2"), "VIBE_SECRET_GH_TOKEN_2");
+
+        let mut env = SecretEnv::default();
+        let err = export_refs("${secret:TOKEN", &mut env, |_| Ok(String::new())).unwrap_err();
+        assert!(err.contains("Unterminated"), "{}", err);
+        assert_eq!(
+            export_refs("plain ${HOME}", &mut env, |_| unreachable!()).unwrap(),
+            "plain ${HOME}"
+        );
+        assert!(env.vars.is_empty());
+    }
+}
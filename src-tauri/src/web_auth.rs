@@ -0,0 +1,581 @@
+//! Authentication for the web server
+//!
+//! Every request except the UI shell and the pairing endpoints must carry
+//! either `Authorization: Bearer <token>` or a session cookie. Tokens are
+//! the pairing token generated at startup (valid until the server stops) or
+//! long-lived API tokens, which are kept only as SHA-256 hashes in
+//! `app_settings`. Pairing exchanges a token for an `HttpOnly`,
+//! `SameSite=Strict` session cookie so the browser UI and its WebSockets
+//! authenticate without handling tokens. WebSocket upgrades must also come
+//! from the server's own origin or an allowed one.
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Name of the browser session cookie
+pub const SESSION_COOKIE: &str = "vat_session";
+/// Prefix of generated tokens, so they are recognisable in configs and logs
+const TOKEN_PREFIX: &str = "vat_";
+/// `app_settings` key holding the API tokens
+const API_TOKENS_KEY: &str = "web_api_tokens";
+const SESSION_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Paths served without authentication: the UI shell, so it can show the
+/// pairing screen, and the endpoints that pairing needs
+fn is_public(path: &str) -> bool {
+    matches!(
+        path,
+        "/" | "/index.html" | "/vite.svg" | "/api/auth/pair" | "/api/auth/status"
+    ) || path.starts_with("/assets/")
+}
+
+/// A long-lived API token, without its secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// Leading characters of the token, to tell tokens apart
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    hash: String,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// A fresh random token
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 24];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, BASE64_URL.encode(bytes))
+}
+
+fn load_tokens(conn: &Connection) -> Result<Vec<StoredToken>, String> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![API_TOKENS_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match value {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid API tokens: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn store_tokens(conn: &Connection, tokens: &[StoredToken]) -> Result<(), String> {
+    let json = serde_json::to_string(tokens).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![API_TOKENS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save API tokens: {}", e))?;
+    Ok(())
+}
+
+/// API tokens, newest first
+pub fn list_api_tokens(conn: &Connection) -> Result<Vec<ApiToken>, String> {
+    let mut tokens: Vec<ApiToken> = load_tokens(conn)?.into_iter().map(|t| t.token).collect();
+    tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    Ok(tokens)
+}
+
+/// Create an API token. The secret is returned here and never again.
+pub fn create_api_token(conn: &Connection, name: &str) -> Result<(ApiToken, String), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Token name is required".to_string());
+    }
+    let secret = generate_token();
+    let token = ApiToken {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        prefix: secret.chars().take(TOKEN_PREFIX.len() + 6).collect(),
+        created_at: Utc::now(),
+        last_used_at: None,
+    };
+    let mut tokens = load_tokens(conn)?;
+    tokens.push(StoredToken {
+        token: token.clone(),
+        hash: sha256_hex(secret.as_bytes()),
+    });
+    store_tokens(conn, &tokens)?;
+    Ok((token, secret))
+}
+
+/// Revoke an API token, returning whether it existed
+pub fn revoke_api_token(conn: &Connection, id: &str) -> Result<bool, String> {
+    let mut tokens = load_tokens(conn)?;
+    let before = tokens.len();
+    tokens.retain(|t| t.token.id != id);
+    if tokens.len() == before {
+        return Ok(false);
+    }
+    store_tokens(conn, &tokens)?;
+    Ok(true)
+}
+
+/// Authentication state of a running web server
+pub struct WebAuth {
+    db: Arc<Mutex<Connection>>,
+    enabled: bool,
+    pairing_hash: String,
+    /// Hashes of session ids and when they expire
+    sessions: Mutex<HashMap<String, Instant>>,
+    /// Origins besides the server's own that may call the API
+    allowed_origins: Vec<String>,
+    /// Mark session cookies `Secure` (served over HTTPS)
+    secure_cookies: bool,
+}
+
+impl WebAuth {
+    /// Set up authentication, returning the pairing token to show the user
+    pub fn new(
+        db: Arc<Mutex<Connection>>,
+        enabled: bool,
+        allowed_origins: Vec<String>,
+    ) -> (Self, String) {
+        let pairing_token = generate_token();
+        let auth = Self {
+            db,
+            enabled,
+            pairing_hash: sha256_hex(pairing_token.as_bytes()),
+            sessions: Mutex::new(HashMap::new()),
+            allowed_origins: allowed_origins
+                .into_iter()
+                .map(|o| o.trim_end_matches('/').to_string())
+                .collect(),
+            secure_cookies: false,
+        };
+        (auth, pairing_token)
+    }
+
+    /// Mark session cookies `Secure`, for servers behind HTTPS
+    pub fn with_secure_cookies(mut self, secure: bool) -> Self {
+        self.secure_cookies = secure;
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn allowed_origins(&self) -> &[String] {
+        &self.allowed_origins
+    }
+
+    /// Whether `token` is the pairing token or a live API token
+    pub fn verify_token(&self, token: &str) -> bool {
+        let hash = sha256_hex(token.as_bytes());
+        if hash == self.pairing_hash {
+            return true;
+        }
+        let Ok(conn) = self.db.lock() else {
+            return false;
+        };
+        let Ok(mut tokens) = load_tokens(&conn) else {
+            return false;
+        };
+        let Some(stored) = tokens.iter_mut().find(|t| t.hash == hash) else {
+            return false;
+        };
+        // Record use at most once a minute
+        let now = Utc::now();
+        if stored
+            .token
+            .last_used_at
+            .is_none_or(|last| now - last > chrono::Duration::minutes(1))
+        {
+            stored.token.last_used_at = Some(now);
+            if let Err(e) = store_tokens(&conn, &tokens) {
+                log::warn!("Failed to record API token use: {}", e);
+            }
+        }
+        true
+    }
+
+    /// Start a browser session, returning its id
+    pub fn create_session(&self) -> String {
+        let id = generate_token();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            let now = Instant::now();
+            sessions.retain(|_, expires| *expires > now);
+            sessions.insert(sha256_hex(id.as_bytes()), now + SESSION_TTL);
+        }
+        id
+    }
+
+    /// `Set-Cookie` value for a session; `None` clears the cookie
+    pub fn session_cookie(&self, id: Option<&str>) -> HeaderValue {
+        session_cookie(id, self.secure_cookies)
+    }
+
+    pub fn end_session(&self, id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(&sha256_hex(id.as_bytes()));
+        }
+    }
+
+    fn session_valid(&self, id: &str) -> bool {
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|s| s.get(&sha256_hex(id.as_bytes())).copied())
+            .is_some_and(|expires| expires > Instant::now())
+    }
+
+    /// Whether a request carries a valid bearer token or session cookie
+    pub fn is_authenticated(&self, headers: &HeaderMap) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        if let Some(token) = bearer_token(headers) {
+            return self.verify_token(token);
+        }
+        session_id(headers).is_some_and(|id| self.session_valid(id))
+    }
+
+    /// Whether the request's `Origin`, if any, is the server itself or an
+    /// allowed origin
+    pub fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok()) else {
+            return true;
+        };
+        let origin = origin.trim_end_matches('/');
+        let same_origin = headers
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|host| {
+                origin
+                    .split_once("://")
+                    .is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host))
+            });
+        same_origin || self.allowed_origins.iter().any(|o| o == origin)
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Session id from the request's cookies
+pub fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+/// `Set-Cookie` value for a session; `None` clears the cookie
+pub fn session_cookie(id: Option<&str>, secure: bool) -> HeaderValue {
+    let (value, max_age) = match id {
+        Some(id) => (id, SESSION_TTL.as_secs()),
+        None => ("", 0),
+    };
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        SESSION_COOKIE,
+        value,
+        max_age,
+        if secure { "; Secure" } else { "" }
+    );
+    HeaderValue::from_str(&cookie).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+fn reject(status: StatusCode, message: &str) -> Response {
+    let body = Json(serde_json::json!({"success": false, "data": null, "error": message}));
+    let mut response = (status, body).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Middleware rejecting unauthenticated requests and cross-origin
+/// WebSocket upgrades
+pub async fn require_auth(
+    State(auth): State<Arc<WebAuth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    if is_websocket_upgrade(headers) && !auth.origin_allowed(headers) {
+        log::warn!(
+            "Rejected WebSocket upgrade from origin {:?}",
+            headers.get(header::ORIGIN)
+        );
+        return reject(StatusCode::FORBIDDEN, "Origin not allowed");
+    }
+    if !is_public(request.uri().path()) && !auth.is_authenticated(headers) {
+        return reject(StatusCode::UNAUTHORIZED, "Authentication required");
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+    use tempfile::TempDir;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_tokens_and_sessions_authenticate() {
+        let dir = TempDir::new().unwrap();
+        let conn = init_database_with_path(&dir.path().join("test.db")).unwrap();
+        let db = Arc::new(Mutex::new(conn));
+        let (auth, pairing) = WebAuth::new(db.clone(), true, vec![]);
+
+        assert!(!auth.is_authenticated(&HeaderMap::new()));
+        let bearer =
+            |token: &str| headers(&[(header::AUTHORIZATION, &format!("Bearer {}", token))]);
+        assert!(auth.is_authenticated(&bearer(&pairing)));
+        assert!(!auth.is_authenticated(&bearer("vat_wrong")));
+
+        let (token, secret) = create_api_token(&db.lock().unwrap(), "ci").unwrap();
+        assert!(secret.starts_with(&token.prefix));
+        assert!(auth.is_authenticated(&bearer(&secret)));
+        // Only the hash is stored
+        let stored: String = db
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![API_TOKENS_KEY],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!stored.contains(&secret));
+        assert!(list_api_tokens(&db.lock().unwrap()).unwrap()[0]
+            .last_used_at
+            .is_some());
+        assert!(revoke_api_token(&db.lock().unwrap(), &token.id).unwrap());
+        assert!(!auth.is_authenticated(&bearer(&secret)));
+
+        let session = auth.create_session();
+        let cookie = headers(&[(
+            header::COOKIE,
+            &format!("theme=dark; {}={}", SESSION_COOKIE, session),
+        )]);
+        assert!(auth.is_authenticated(&cookie));
+        auth.end_session(&session);
+        assert!(!auth.is_authenticated(&cookie));
+    }
+
+    #[test]
+    fn test_websocket_origins_must_match_the_server_or_the_allow_list() {
+        let dir = TempDir::new().unwrap();
+        let conn = init_database_with_path(&dir.path().join("test.db")).unwrap();
+        let (auth, _) = WebAuth::new(
+            Arc::new(Mutex::new(conn)),
+            true,
+            vec!["http://localhost:1420/".to_string()],
+        );
+        let request =
+            |origin: &str| headers(&[(header::HOST, "192.168.1.5:8080"), (header::ORIGIN, origin)]);
+        assert!(auth.origin_allowed(&request("http://192.168.1.5:8080")));
+        assert!(auth.origin_allowed(&request("http://localhost:1420")));
+        assert!(!auth.origin_allowed(&request("https://evil.example")));
+        assert!(auth.origin_allowed(&headers(&[(header::HOST, "x")])));
+    }
+
+    fn test_db(dir: &TempDir) -> Arc<Mutex<Connection>> {
+        let conn = init_database_with_path(&dir.path().join("test.db")).unwrap();
+        Arc::new(Mutex::new(conn))
+    }
+
+    #[test]
+    fn test_disabled_auth_and_public_paths() {
+        let dir = TempDir::new().unwrap();
+        let (auth, _) = WebAuth::new(test_db(&dir), false, vec![]);
+        assert!(auth.is_authenticated(&HeaderMap::new()));
+
+        for path in ["/", "/index.html", "/assets/app.js", "/api/auth/pair"] {
+            assert!(is_public(path), "{}", path);
+        }
+        for path in ["/api/projects", "/assets", "/ws", "/index.html/x"] {
+            assert!(!is_public(path), "{}", path);
+        }
+
+        assert_eq!(
+            reject(StatusCode::UNAUTHORIZED, "no").headers()[header::WWW_AUTHENTICATE],
+            "Bearer"
+        );
+        assert!(reject(StatusCode::FORBIDDEN, "no")
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .is_none());
+    }
+
+    #[test]
+    fn test_token_names_order_and_corrupt_lists() {
+        let dir = TempDir::new().unwrap();
+        let db = test_db(&dir);
+        let (auth, pairing) = WebAuth::new(db.clone(), true, vec![]);
+        let conn = db.lock().unwrap();
+
+        assert_eq!(
+            create_api_token(&conn, "  ").unwrap_err(),
+            "Token name is required"
+        );
+        let (first, _) = create_api_token(&conn, " ci ").unwrap();
+        let (second, _) = create_api_token(&conn, "laptop").unwrap();
+        assert_eq!(first.name, "ci");
+        let listed: Vec<String> = list_api_tokens(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(listed, [second.id.clone(), first.id.clone()]);
+        assert!(!revoke_api_token(&conn, "missing").unwrap());
+
+        conn.execute(
+            "UPDATE app_settings SET value = 'not json' WHERE key = ?1",
+            params![API_TOKENS_KEY],
+        )
+        .unwrap();
+        assert!(list_api_tokens(&conn)
+            .unwrap_err()
+            .starts_with("Invalid API tokens"));
+        assert!(revoke_api_token(&conn, &first.id).is_err());
+        drop(conn);
+        // The pairing token does not depend on the stored tokens
+        assert!(!auth.verify_token("vat_anything"));
+        assert!(auth.verify_token(&pairing));
+    }
+
+    #[test]
+    fn test_credentials_must_be_well_formed() {
+        let dir = TempDir::new().unwrap();
+        let (auth, pairing) = WebAuth::new(test_db(&dir), true, vec![]);
+        let session = auth.create_session();
+        let cookie = format!("{}={}", SESSION_COOKIE, session);
+
+        for value in [format!("bearer {}", pairing), format!("Basic {}", pairing)] {
+            assert!(!auth.is_authenticated(&headers(&[(header::AUTHORIZATION, &value)])));
+        }
+        assert!(auth.is_authenticated(&headers(&[(
+            header::AUTHORIZATION,
+            &format!("Bearer  {} ", pairing)
+        )])));
+        // A bad bearer token is not rescued by a valid cookie
+        assert!(!auth.is_authenticated(&headers(&[
+            (header::AUTHORIZATION, "Bearer vat_wrong"),
+            (header::COOKIE, &cookie),
+        ])));
+
+        // Cookies may span several headers; names must match exactly
+        let spread = headers(&[(header::COOKIE, "theme=dark"), (header::COOKIE, &cookie)]);
+        assert_eq!(session_id(&spread), Some(session.as_str()));
+        let lookalike = headers(&[(
+            header::COOKIE,
+            &format!("{}_old={}", SESSION_COOKIE, session),
+        )]);
+        assert_eq!(session_id(&lookalike), None);
+        assert!(!auth.is_authenticated(&headers(&[(
+            header::COOKIE,
+            &format!("{}=vat_unknown", SESSION_COOKIE)
+        )])));
+    }
+
+    #[test]
+    fn test_sessions_expire_and_cookies_clear() {
+        let dir = TempDir::new().unwrap();
+        let (auth, _) = WebAuth::new(test_db(&dir), true, vec![]);
+        let auth = auth.with_secure_cookies(true);
+
+        let expired = generate_token();
+        auth.sessions
+            .lock()
+            .unwrap()
+            .insert(sha256_hex(expired.as_bytes()), Instant::now());
+        assert!(!auth.session_valid(&expired));
+        // Starting a session sweeps out expired ones
+        let live = auth.create_session();
+        assert_eq!(auth.sessions.lock().unwrap().len(), 1);
+        assert!(auth.session_valid(&live));
+
+        let set = auth.session_cookie(Some(&live));
+        let set = set.to_str().unwrap();
+        assert!(set.contains(&format!("Max-Age={}", SESSION_TTL.as_secs())));
+        assert!(set.ends_with("; Secure"));
+        let cleared = session_cookie(None, false);
+        assert_eq!(
+            cleared.to_str().unwrap(),
+            format!(
+                "{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0",
+                SESSION_COOKIE
+            )
+        );
+    }
+
+    #[test]
+    fn test_origins_compare_hosts_case_insensitively() {
+        let dir = TempDir::new().unwrap();
+        let (auth, _) = WebAuth::new(test_db(&dir), true, vec![]);
+        let request =
+            |host: &str, origin: &str| headers(&[(header::HOST, host), (header::ORIGIN, origin)]);
+        assert!(auth.origin_allowed(&request("Example.com:8080", "https://example.COM:8080/")));
+        assert!(!auth.origin_allowed(&request("example.com:8080", "https://example.com")));
+        assert!(!auth.origin_allowed(&request("example.com", "null")));
+        // Without a Host header only the allow list counts
+        assert!(!auth.origin_allowed(&headers(&[(header::ORIGIN, "http://example.com")])));
+
+        assert!(is_websocket_upgrade(&headers(&[(
+            header::UPGRADE,
+            "WebSocket"
+        )])));
+        assert!(!is_websocket_upgrade(&headers(&[(header::UPGRADE, "h2c")])));
+        assert!(!is_websocket_upgrade(&HeaderMap::new()));
+    }
+}
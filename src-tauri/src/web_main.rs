@@ -3,8 +3,13 @@ use clap::Parser;
 mod checkpoint;
 mod claude_binary;
 mod commands;
+mod events;
+mod mcp;
 mod process;
+mod secrets;
+mod web_auth;
 mod web_server;
+mod web_tls;
 
 use commands::agents::{init_database_with_path, AgentDb};
 
@@ -19,6 +24,43 @@ struct Args {
     /// Host to bind to (0.0.0.0 for all interfaces)
     #[arg(short = 'H', long, default_value = "0.0.0.0")]
     host: String,
+
+    /// Directory holding the database (VibeAgentTeam.db), the secret store
+    /// and generated MCP configs; created if missing
+    #[arg(long, value_name = "PATH", default_value = ".")]
+    data_dir: std::path::PathBuf,
+
+    /// Serve the agent team as a read-only MCP server over stdio instead of
+    /// starting the web server. It runs no agents, so it cannot message them
+    /// or report their status; use the web server's /mcp endpoint for that.
+    #[arg(long)]
+    mcp_stdio: bool,
+
+    /// Extra origin allowed to call the API, e.g. https://team.example.com
+    /// (repeatable)
+    #[arg(long = "allow-origin")]
+    allow_origins: Vec<String>,
+
+    /// Disable authentication (only on a trusted network)
+    #[arg(long)]
+    no_auth: bool,
+
+    /// Serve HTTPS with this PEM certificate (requires --tls-key)
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<std::path::PathBuf>,
+
+    /// Private key for --tls-cert (PEM)
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+
+    /// Serve HTTPS with a self-signed certificate generated once and kept
+    /// in the app data dir
+    #[arg(long, conflicts_with = "tls_cert")]
+    tls_self_signed: bool,
+
+    /// Create a long-lived API token with this name, print it and exit
+    #[arg(long, value_name = "NAME")]
+    create_api_token: Option<String>,
 }
 
 #[tokio::main]
@@ -27,14 +69,22 @@ async fn main() {
 
     let args = Args::parse();
 
-    println!("🚀 Starting Web Server...");
-    println!(
-        "📱 Will be accessible from phones at: http://{}:{}",
-        args.host, args.port
-    );
+    // stdout carries the protocol in MCP mode, so no banner there
+    if !args.mcp_stdio && args.create_api_token.is_none() {
+        println!("🚀 Starting Web Server...");
+    }
+
+    if let Err(e) = std::fs::create_dir_all(&args.data_dir) {
+        eprintln!(
+            "❌ Failed to create data dir {}: {}",
+            args.data_dir.display(),
+            e
+        );
+        std::process::exit(1);
+    }
 
     // Initialize database (using a temporary app handle for web mode)
-    let db_path = std::path::PathBuf::from("VibeAgentTeam.db");
+    let db_path = args.data_dir.join("VibeAgentTeam.db");
     let conn = match init_database_with_path(&db_path) {
         Ok(c) => c,
         Err(e) => {
@@ -44,7 +94,49 @@ async fn main() {
     };
     let db = AgentDb(std::sync::Arc::new(std::sync::Mutex::new(conn)));
 
-    if let Err(e) = web_server::start_web_mode(Some(args.port), db).await {
+    // Secret store next to the database; unlocked via VIBE_SECRETS_PASSPHRASE
+    secrets::init_global(secrets::store_path(&args.data_dir));
+
+    if let Some(name) = &args.create_api_token {
+        let conn = db.0.lock().unwrap();
+        match web_auth::create_api_token(&conn, name) {
+            Ok((_, secret)) => {
+                println!("{}", secret);
+                eprintln!("Store this token now; it cannot be shown again.");
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to create API token: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.mcp_stdio {
+        let server = mcp::team_server::TeamServer::read_only(db.0.clone());
+        if let Err(e) = mcp::team_server::serve_stdio(server).await {
+            eprintln!("❌ MCP server failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let tls = match (args.tls_cert, args.tls_key) {
+        (Some(cert), Some(key)) => Some(web_tls::TlsSource::Files { cert, key }),
+        _ if args.tls_self_signed => Some(web_tls::TlsSource::SelfSigned {
+            dir: web_tls::default_cert_dir(),
+        }),
+        _ => None,
+    };
+    let config = web_server::WebServerConfig {
+        host: args.host,
+        port: args.port,
+        tls,
+        auth_enabled: !args.no_auth,
+        allowed_origins: args.allow_origins,
+        data_dir: args.data_dir,
+    };
+    if let Err(e) = web_server::start_web_mode(config, db).await {
         eprintln!("❌ Failed to start web server: {}", e);
         std::process::exit(1);
     }
@@ -1,9 +1,10 @@
 use axum::extract::ws::{Message, WebSocket};
-use axum::http::Method;
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
 use axum::{
-    extract::{Path, State as AxumState, WebSocketUpgrade},
-    response::{Html, Json, Response},
-    routing::get,
+    extract::{Path, Query, State as AxumState, WebSocketUpgrade},
+    middleware,
+    response::{Html, IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
 use chrono;
@@ -11,16 +12,23 @@ use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::sync::Mutex;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 use which;
 
-use crate::commands::agents::AgentDb;
+use crate::checkpoint::state::CheckpointState;
 use crate::commands;
+use crate::commands::agents::AgentDb;
+use crate::commands::teammate::TeamContext;
+use crate::events::{AppEvent, BroadcastEmitter};
+use crate::mcp::team_server::TeamServer;
 use crate::process;
+use crate::web_auth::{self, WebAuth};
+use crate::web_tls::{self, TlsSource};
 
 // Find Claude binary for web mode - use bundled binary first
 fn find_claude_binary_web() -> Result<String, String> {
@@ -70,6 +78,43 @@ pub struct AppState {
     pub process_registry: Arc<process::ProcessRegistry>,
     // Database for agents (Arc-wrapped for sharing across requests)
     pub db: Arc<AgentDb>,
+    // Tokens and browser sessions
+    pub auth: Arc<WebAuth>,
+    // Team runtime, emitting on `events`
+    pub team: TeamContext,
+    // Events for /ws/projects/{id} subscribers
+    pub events: Arc<BroadcastEmitter>,
+    // Claude's settings and session files (~/.claude)
+    pub claude_dir: PathBuf,
+}
+
+/// Options of the web server
+#[derive(Debug, Clone)]
+pub struct WebServerConfig {
+    /// Address or host name to bind
+    pub host: String,
+    pub port: u16,
+    /// Serve HTTPS with this certificate
+    pub tls: Option<TlsSource>,
+    /// Require a token or session cookie (on unless explicitly disabled)
+    pub auth_enabled: bool,
+    /// Origins besides the server's own that may call the API
+    pub allowed_origins: Vec<String>,
+    /// Directory of the database; generated MCP configs go here too
+    pub data_dir: PathBuf,
+}
+
+impl Default for WebServerConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("."),
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            tls: None,
+            auth_enabled: true,
+            allowed_origins: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -207,9 +252,85 @@ async fn get_sessions(
     }
 }
 
-/// Simple agents endpoint - return empty for now (needs DB state)
-async fn get_agents() -> Json<ApiResponse<Vec<serde_json::Value>>> {
-    Json(ApiResponse::success(vec![]))
+/// All agents, newest first
+async fn get_agents(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<commands::agents::Agent>>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::load_agents(&conn) {
+        Ok(agents) => Json(ApiResponse::success(agents)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn create_agent(
+    AxumState(state): AxumState<AppState>,
+    Json(agent): Json<commands::agents::NewAgent>,
+) -> Json<ApiResponse<commands::agents::Agent>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::insert_agent(&conn, agent) {
+        Ok(agent) => Json(ApiResponse::success(agent)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn get_agent(
+    AxumState(state): AxumState<AppState>,
+    Path(id): Path<String>,
+) -> Json<ApiResponse<commands::agents::Agent>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::load_agent(&conn, &id) {
+        Ok(agent) => Json(ApiResponse::success(agent)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn update_agent(
+    AxumState(state): AxumState<AppState>,
+    Path(id): Path<String>,
+    Json(update): Json<commands::agents::AgentUpdate>,
+) -> Json<ApiResponse<commands::agents::Agent>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::update_agent_fields(&conn, &id, update) {
+        Ok(agent) => Json(ApiResponse::success(agent)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn delete_agent(
+    AxumState(state): AxumState<AppState>,
+    Path(id): Path<String>,
+) -> Json<ApiResponse<()>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::remove_agent(&conn, &id) {
+        Ok(()) => Json(ApiResponse::success(())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
 /// Teamleads endpoint - fetch teamleads from database
@@ -265,33 +386,155 @@ async fn get_teamleads(AxumState(state): AxumState<AppState>) -> Json<ApiRespons
     }
 }
 
-/// Simple usage endpoint - return empty for now
-async fn get_usage() -> Json<ApiResponse<Vec<serde_json::Value>>> {
-    Json(ApiResponse::success(vec![]))
+#[derive(Deserialize)]
+struct UsageQuery {
+    days: Option<u32>,
 }
 
-/// Get Claude settings - return basic defaults for web mode
-async fn get_claude_settings() -> Json<ApiResponse<serde_json::Value>> {
-    let default_settings = serde_json::json!({
-        "data": {
-            "model": "claude-3-5-sonnet-20241022",
-            "max_tokens": 8192,
-            "temperature": 0.0,
-            "auto_save": true,
-            "theme": "dark"
-        }
-    });
-    Json(ApiResponse::success(default_settings))
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageRangeQuery {
+    start_date: String,
+    end_date: String,
 }
 
-/// Check Claude version - return mock status for web mode
-async fn check_claude_version() -> Json<ApiResponse<serde_json::Value>> {
-    let version_status = serde_json::json!({
-        "status": "ok",
-        "version": "web-mode",
-        "message": "Running in web server mode"
-    });
-    Json(ApiResponse::success(version_status))
+#[derive(Deserialize)]
+struct SessionStatsQuery {
+    since: Option<String>,
+    until: Option<String>,
+    order: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageDetailsQuery {
+    project_path: Option<String>,
+    date: Option<String>,
+}
+
+/// Run a usage query off the async runtime; refreshing the index reads the
+/// Claude session files
+async fn usage_response<T, F>(state: AppState, query: F) -> Json<ApiResponse<T>>
+where
+    F: FnOnce(&AgentDb, &std::path::Path) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    let (db, claude_dir) = (state.db, state.claude_dir);
+    match tokio::task::spawn_blocking(move || query(&db, &claude_dir)).await {
+        Ok(Ok(value)) => Json(ApiResponse::success(value)),
+        Ok(Err(e)) => Json(ApiResponse::error(e)),
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
+/// Usage totals, optionally over the last `days` days
+async fn get_usage(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Json<ApiResponse<commands::usage::UsageStats>> {
+    usage_response(state, move |db, claude_dir| {
+        commands::usage::usage_stats(db, claude_dir, query.days)
+    })
+    .await
+}
+
+async fn get_usage_by_date_range(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<UsageRangeQuery>,
+) -> Json<ApiResponse<commands::usage::UsageStats>> {
+    usage_response(state, move |db, claude_dir| {
+        commands::usage::usage_by_date_range(db, claude_dir, &query.start_date, &query.end_date)
+    })
+    .await
+}
+
+async fn get_session_stats(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<SessionStatsQuery>,
+) -> Json<ApiResponse<Vec<commands::usage::ProjectUsage>>> {
+    usage_response(state, move |db, claude_dir| {
+        commands::usage::session_stats(db, claude_dir, query.since, query.until, query.order)
+    })
+    .await
+}
+
+async fn get_usage_details(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<UsageDetailsQuery>,
+) -> Json<ApiResponse<Vec<commands::usage::UsageEntry>>> {
+    usage_response(state, move |db, claude_dir| {
+        commands::usage::usage_details(
+            db,
+            claude_dir,
+            query.project_path.as_deref(),
+            query.date.as_deref(),
+        )
+    })
+    .await
+}
+
+/// Prometheus metrics endpoint: token and cost counters from the usage index
+async fn get_metrics(AxumState(state): AxumState<AppState>) -> Response {
+    let (db, claude_dir) = (state.db, state.claude_dir);
+    let result = tokio::task::spawn_blocking(move || {
+        commands::usage::refresh_usage_index_in(&db, &claude_dir)?;
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        commands::usage::render_prometheus_metrics(&conn)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    match result {
+        Ok(body) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+            body,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Contents of ~/.claude/settings.json
+async fn get_claude_settings(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<commands::claude::ClaudeSettings>> {
+    match commands::claude::read_claude_settings(&state.claude_dir) {
+        Ok(settings) => Json(ApiResponse::success(settings)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveSettingsRequest {
+    settings: serde_json::Value,
+}
+
+async fn save_claude_settings(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<SaveSettingsRequest>,
+) -> Json<ApiResponse<String>> {
+    match commands::claude::write_claude_settings(&state.claude_dir, &request.settings) {
+        Ok(message) => Json(ApiResponse::success(message)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Version of the Claude binary the server would run
+async fn check_claude_version(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<commands::claude::ClaudeVersionStatus>> {
+    let app_data_dir = state.team.app_data_dir.clone();
+    let status = tokio::task::spawn_blocking(move || {
+        commands::claude::check_claude_version_in(Some(&app_data_dir))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    match status {
+        Ok(status) => Json(ApiResponse::success(status)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
 /// List all available Claude installations on the system
@@ -308,12 +551,26 @@ async fn list_claude_installations(
     }
 }
 
-/// Get system prompt - return default for web mode
+/// Contents of ~/.claude/CLAUDE.md
 async fn get_system_prompt() -> Json<ApiResponse<String>> {
-    let default_prompt =
-        "You are Claude, an AI assistant created by Anthropic. You are running in web server mode."
-            .to_string();
-    Json(ApiResponse::success(default_prompt))
+    match commands::claude::get_system_prompt().await {
+        Ok(prompt) => Json(ApiResponse::success(prompt)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveSystemPromptRequest {
+    content: String,
+}
+
+async fn save_system_prompt(
+    Json(request): Json<SaveSystemPromptRequest>,
+) -> Json<ApiResponse<String>> {
+    match commands::claude::save_system_prompt(request.content).await {
+        Ok(message) => Json(ApiResponse::success(message)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
 /// Open new session - mock for web mode
@@ -342,33 +599,212 @@ async fn load_session_history(
     }
 }
 
-/// List running Claude sessions
-async fn list_running_claude_sessions() -> Json<ApiResponse<Vec<serde_json::Value>>> {
-    // Return empty for web mode - no actual Claude processes in web mode
-    Json(ApiResponse::success(vec![]))
+/// Claude runs started over /ws/claude or the REST routes
+async fn list_running_claude_sessions(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<process::ProcessInfo>>> {
+    match state.process_registry.get_running_claude_sessions() {
+        Ok(sessions) => Json(ApiResponse::success(sessions)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
-/// Execute Claude code - mock for web mode
-async fn execute_claude_code() -> Json<ApiResponse<serde_json::Value>> {
-    Json(ApiResponse::error("Claude execution is not available in web mode. Please use the desktop app for running Claude commands.".to_string()))
+/// Body of the REST execute/continue/resume routes
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClaudeRunRequest {
+    project_path: String,
+    prompt: String,
+    #[serde(default)]
+    model: Option<String>,
+    /// Claude session to resume
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Stop the run after this many seconds (default `DEFAULT_RUN_TIMEOUT_SECS`)
+    #[serde(default)]
+    timeout_secs: Option<u64>,
 }
 
-/// Continue Claude code - mock for web mode
-async fn continue_claude_code() -> Json<ApiResponse<serde_json::Value>> {
-    Json(ApiResponse::error("Claude execution is not available in web mode. Please use the desktop app for running Claude commands.".to_string()))
+/// How long a REST run may take unless the request says otherwise
+const DEFAULT_RUN_TIMEOUT_SECS: u64 = 600;
+
+/// A finished REST run: the stream-json messages Claude printed
+#[derive(Serialize)]
+struct ClaudeRunOutput {
+    session_id: String,
+    messages: Vec<serde_json::Value>,
 }
 
-/// Resume Claude code - mock for web mode  
-async fn resume_claude_code() -> Json<ApiResponse<serde_json::Value>> {
-    Json(ApiResponse::error("Claude execution is not available in web mode. Please use the desktop app for running Claude commands.".to_string()))
+#[derive(Clone, Copy)]
+enum ClaudeRunKind {
+    Execute,
+    Continue,
+    Resume,
 }
 
-/// Cancel Claude execution
-async fn cancel_claude_execution(Path(session_id): Path<String>) -> Json<ApiResponse<()>> {
-    // In web mode, we don't have a way to cancel the subprocess cleanly
-    // The WebSocket closing should handle cleanup
-    println!("[TRACE] Cancel request for session: {}", session_id);
-    Json(ApiResponse::success(()))
+/// Run Claude to completion and collect its output. Streaming clients use
+/// /ws/claude; this is for callers that just want the result. The run is
+/// stopped once it exceeds its timeout.
+async fn run_claude(
+    state: AppState,
+    kind: ClaudeRunKind,
+    request: ClaudeRunRequest,
+) -> Result<ClaudeRunOutput, String> {
+    let model = request.model.unwrap_or_else(|| "sonnet".to_string());
+    let session_id = uuid::Uuid::new_v4().to_string();
+
+    // Output arrives through the same per-session channel the WebSocket
+    // handler forwards from
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(100);
+    state
+        .active_sessions
+        .lock()
+        .await
+        .insert(session_id.clone(), tx);
+    let collector = tokio::spawn(async move {
+        let mut messages = Vec::new();
+        while let Some(message) = rx.recv().await {
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(&message) else {
+                continue;
+            };
+            if let (Some("output"), Some(line)) =
+                (message["type"].as_str(), message["content"].as_str())
+            {
+                messages.push(serde_json::from_str(line).unwrap_or_else(|_| json!(line)));
+            }
+        }
+        messages
+    });
+
+    let timeout =
+        std::time::Duration::from_secs(request.timeout_secs.unwrap_or(DEFAULT_RUN_TIMEOUT_SECS));
+    let run = {
+        let (state, session_id) = (state.clone(), session_id.clone());
+        async move {
+            match (kind, request.session_id) {
+                (ClaudeRunKind::Execute, _) => {
+                    execute_claude_command(
+                        request.project_path,
+                        request.prompt,
+                        model,
+                        session_id,
+                        state,
+                        false,
+                    )
+                    .await
+                }
+                (ClaudeRunKind::Continue, _) => {
+                    continue_claude_command(
+                        request.project_path,
+                        request.prompt,
+                        model,
+                        session_id,
+                        state,
+                        false,
+                    )
+                    .await
+                }
+                (ClaudeRunKind::Resume, Some(claude_session_id)) => {
+                    resume_claude_command(
+                        request.project_path,
+                        claude_session_id,
+                        request.prompt,
+                        model,
+                        session_id,
+                        state,
+                        false,
+                    )
+                    .await
+                }
+                (ClaudeRunKind::Resume, None) => Err("sessionId is required to resume".to_string()),
+            }
+        }
+    };
+    let result = run_with_timeout(&state, &session_id, timeout, run).await;
+
+    // Dropping the sender ends the collector
+    state.active_sessions.lock().await.remove(&session_id);
+    let messages = collector.await.map_err(|e| e.to_string())?;
+    result.map(|()| ClaudeRunOutput {
+        session_id,
+        messages,
+    })
+}
+
+async fn execute_claude_code(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<ClaudeRunRequest>,
+) -> Json<ApiResponse<ClaudeRunOutput>> {
+    match run_claude(state, ClaudeRunKind::Execute, request).await {
+        Ok(output) => Json(ApiResponse::success(output)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn continue_claude_code(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<ClaudeRunRequest>,
+) -> Json<ApiResponse<ClaudeRunOutput>> {
+    match run_claude(state, ClaudeRunKind::Continue, request).await {
+        Ok(output) => Json(ApiResponse::success(output)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn resume_claude_code(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<ClaudeRunRequest>,
+) -> Json<ApiResponse<ClaudeRunOutput>> {
+    match run_claude(state, ClaudeRunKind::Resume, request).await {
+        Ok(output) => Json(ApiResponse::success(output)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Await `run`, stopping the Claude process of `session_id` if it takes
+/// longer than `timeout`
+async fn run_with_timeout(
+    state: &AppState,
+    session_id: &str,
+    timeout: std::time::Duration,
+    run: impl std::future::Future<Output = Result<(), String>>,
+) -> Result<(), String> {
+    match tokio::time::timeout(timeout, run).await {
+        Ok(result) => result,
+        Err(_) => {
+            if let Ok(run_id) = claude_run_id(state, session_id) {
+                state.process_registry.kill_process(run_id).await?;
+            }
+            Err(format!(
+                "Claude run timed out after {} seconds",
+                timeout.as_secs()
+            ))
+        }
+    }
+}
+
+/// Registry run id of the Claude process started for `session_id`
+fn claude_run_id(state: &AppState, session_id: &str) -> Result<String, String> {
+    state
+        .process_registry
+        .get_claude_session_by_id(session_id)?
+        .map(|info| info.run_id)
+        .ok_or_else(|| format!("No running Claude session {}", session_id))
+}
+
+/// Stop a running Claude session; its run then reports that it was stopped
+async fn cancel_claude_execution(
+    AxumState(state): AxumState<AppState>,
+    Path(session_id): Path<String>,
+) -> Json<ApiResponse<()>> {
+    let result = match claude_run_id(&state, &session_id) {
+        Ok(run_id) => state.process_registry.kill_process(run_id).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => Json(ApiResponse::success(())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
 /// Get Claude session output
@@ -458,6 +894,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         model.unwrap_or_default(),
                                         session_id_clone.clone(),
                                         state_clone.clone(),
+                                        true,
                                     ).await;
 
                                     // 发送完成消息
@@ -476,7 +913,10 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                 tokio::spawn(async move {
                                     // 从 session_id 获取 registry 中的进程
                                     let registry = state_clone.process_registry.clone();
-                                    let result = registry.send_to_process_async(&session_id_clone, &content).await;
+                                    let result = match claude_run_id(&state_clone, &session_id_clone) {
+                                        Ok(run_id) => registry.send_to_process_async(&run_id, &content).await,
+                                        Err(e) => Err(e),
+                                    };
 
                                     if let Some(sender) = state_clone.active_sessions.lock().await.get(&session_id_clone) {
                                         match result {
@@ -498,7 +938,9 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                 tokio::spawn(async move {
                                     let registry = state_for_exit.process_registry.clone();
                                     // 使用 kill_process 方法
-                                    let _ = registry.kill_process(session_id_for_exit.clone()).await;
+                                    if let Ok(run_id) = claude_run_id(&state_for_exit, &session_id_for_exit) {
+                                        let _ = registry.kill_process(run_id).await;
+                                    }
 
                                     if let Some(sender) = state_for_exit.active_sessions.lock().await.get(&session_id_for_exit) {
                                         let _ = sender.send(json!({ "type": "exited" }).to_string()).await;
@@ -515,6 +957,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         model.unwrap_or_default(),
                                         session_id_clone.clone(),
                                         state_clone.clone(),
+                                        true,
                                     ).await;
 
                                     if let Some(sender) = state_clone.active_sessions.lock().await.get(&session_id_clone) {
@@ -537,6 +980,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         model.unwrap_or_default(),
                                         session_id_clone.clone(),
                                         state_clone.clone(),
+                                        true,
                                     ).await;
 
                                     if let Some(sender) = state_clone.active_sessions.lock().await.get(&session_id_clone) {
@@ -594,14 +1038,39 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
 }
 
 // Claude command execution functions for WebSocket streaming
+/// Hand the secrets the project's MCP servers refer to over to a Claude run
+fn attach_mcp_secrets(
+    state: &AppState,
+    cmd: &mut tokio::process::Command,
+    project_path: &str,
+) -> Result<(), String> {
+    if let Some(mcp_config) =
+        commands::agent_mcp::write_project_mcp_config(&state.team.app_data_dir, project_path)?
+    {
+        mcp_config.apply(cmd);
+    }
+    Ok(())
+}
+
+/// Output flags shared by the Claude runs; only `interactive` runs take
+/// further stream-json messages on stdin
+fn stream_json_args(interactive: bool) -> Vec<&'static str> {
+    let mut args = vec!["--output-format", "stream-json"];
+    if interactive {
+        args.extend(["--input-format", "stream-json"]);
+    }
+    args.extend(["--verbose", "--dangerously-skip-permissions"]);
+    args
+}
+
 async fn execute_claude_command(
     project_path: String,
     prompt: String,
     model: String,
     session_id: String,
     state: AppState,
+    interactive: bool,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     println!("[TRACE] execute_claude_command called:");
@@ -635,88 +1104,29 @@ async fn execute_claude_command(
     // Create Claude command
     println!("[TRACE] Creating Claude command...");
     let mut cmd = Command::new(&claude_path);
-    let args = [
-        "-p",
-        &prompt,
-        "--model",
-        &model,
-        "--output-format",
-        "stream-json",
-        "--input-format",
-        "stream-json",
-        "--verbose",
-        "--dangerously-skip-permissions",
-    ];
-    cmd.args(args);
+    let mut args = vec!["-p", &prompt, "--model", &model];
+    args.extend(stream_json_args(interactive));
+    cmd.args(&args);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
+    attach_mcp_secrets(&state, &mut cmd, &project_path)?;
 
     println!(
         "[TRACE] Command: {} {:?} (in dir: {})",
         claude_path, args, project_path
     );
 
-    // Spawn Claude process
-    println!("[TRACE] Spawning Claude process...");
-    let mut child = cmd.spawn().map_err(|e| {
-        let error = format!("Failed to spawn Claude: {}", e);
-        println!("[TRACE] Spawn error: {}", error);
-        error
-    })?;
-    println!("[TRACE] Claude process spawned successfully");
-
-    // Get stdout for streaming
-    let stdout = child.stdout.take().ok_or_else(|| {
-        println!("[TRACE] Failed to get stdout from child process");
-        "Failed to get stdout".to_string()
-    })?;
-    let stdout_reader = BufReader::new(stdout);
-
-    println!("[TRACE] Starting to read Claude output...");
-    // Stream output line by line
-    let mut lines = stdout_reader.lines();
-    let mut line_count = 0;
-    while let Ok(Some(line)) = lines.next_line().await {
-        line_count += 1;
-        println!("[TRACE] Claude output line {}: {}", line_count, line);
-
-        // Send each line to WebSocket
-        let message = json!({
-            "type": "output",
-            "content": line
-        })
-        .to_string();
-        println!("[TRACE] Sending output message to session: {}", message);
-        send_to_session(&state, &session_id, message).await;
-    }
-
-    println!(
-        "[TRACE] Finished reading Claude output ({} lines total)",
-        line_count
-    );
-
-    // Wait for process to complete
-    println!("[TRACE] Waiting for Claude process to complete...");
-    let exit_status = child.wait().await.map_err(|e| {
-        let error = format!("Failed to wait for Claude: {}", e);
-        println!("[TRACE] Wait error: {}", error);
-        error
-    })?;
-
-    println!(
-        "[TRACE] Claude process completed with status: {:?}",
-        exit_status
-    );
-
-    if !exit_status.success() {
-        let error = format!(
-            "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        );
-        println!("[TRACE] Claude execution failed: {}", error);
-        return Err(error);
-    }
+    run_claude_process(
+        &state,
+        cmd,
+        &session_id,
+        &project_path,
+        &prompt,
+        &model,
+        interactive,
+    )
+    .await?;
 
     println!("[TRACE] execute_claude_command completed successfully");
     Ok(())
@@ -728,8 +1138,8 @@ async fn continue_claude_command(
     model: String,
     session_id: String,
     state: AppState,
+    interactive: bool,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     send_to_session(
@@ -755,48 +1165,23 @@ async fn continue_claude_command(
         &prompt,
         "--model",
         &model,
-        "--output-format",
-        "stream-json",
-        "--verbose",
-        "--dangerously-skip-permissions",
     ]);
+    cmd.args(stream_json_args(interactive));
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
+    attach_mcp_secrets(&state, &mut cmd, &project_path)?;
 
-    // Spawn and stream output
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
-    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stdout_reader = BufReader::new(stdout);
-
-    let mut lines = stdout_reader.lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        send_to_session(
-            &state,
-            &session_id,
-            json!({
-                "type": "output",
-                "content": line
-            })
-            .to_string(),
-        )
-        .await;
-    }
-
-    let exit_status = child
-        .wait()
-        .await
-        .map_err(|e| format!("Failed to wait for Claude: {}", e))?;
-    if !exit_status.success() {
-        return Err(format!(
-            "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        ));
-    }
-
-    Ok(())
+    run_claude_process(
+        &state,
+        cmd,
+        &session_id,
+        &project_path,
+        &prompt,
+        &model,
+        interactive,
+    )
+    .await
 }
 
 async fn resume_claude_command(
@@ -806,8 +1191,8 @@ async fn resume_claude_command(
     model: String,
     session_id: String,
     state: AppState,
+    interactive: bool,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     println!("[resume_claude_command] Starting with project_path: {}, claude_session_id: {}, prompt: {}, model: {}", 
@@ -836,46 +1221,94 @@ async fn resume_claude_command(
     // Create resume command
     println!("[resume_claude_command] Creating command...");
     let mut cmd = Command::new(&claude_path);
-    let args = [
-        "--resume",
-        &claude_session_id,
-        "-p",
-        &prompt,
-        "--model",
-        &model,
-        "--output-format",
-        "stream-json",
-        "--input-format",
-        "stream-json",
-        "--verbose",
-        "--dangerously-skip-permissions",
-    ];
-    cmd.args(args);
+    let mut args = vec!["--resume", &claude_session_id, "-p", &prompt, "--model", &model];
+    args.extend(stream_json_args(interactive));
+    cmd.args(&args);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
+    attach_mcp_secrets(&state, &mut cmd, &project_path)?;
 
     println!(
         "[resume_claude_command] Command: {} {:?} (in dir: {})",
         claude_path, args, project_path
     );
 
-    // Spawn and stream output
-    println!("[resume_claude_command] Spawning process...");
-    let mut child = cmd.spawn().map_err(|e| {
-        let error = format!("Failed to spawn Claude: {}", e);
-        println!("[resume_claude_command] Spawn error: {}", error);
-        error
-    })?;
-    println!("[resume_claude_command] Process spawned successfully");
+    run_claude_process(
+        &state,
+        cmd,
+        &session_id,
+        &project_path,
+        &prompt,
+        &model,
+        interactive,
+    )
+    .await
+}
+
+/// Spawn a Claude command and stream its stdout, and its stderr as errors,
+/// to `session_id` until it exits. Meanwhile it is registered under `session_id`, so it is listed at
+/// /api/sessions/running and can be cancelled; `interactive` runs keep stdin
+/// open for WebSocket `Send` messages.
+async fn run_claude_process(
+    state: &AppState,
+    mut cmd: tokio::process::Command,
+    session_id: &str,
+    project_path: &str,
+    prompt: &str,
+    model: &str,
+    interactive: bool,
+) -> Result<(), String> {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    cmd.stdin(std::process::Stdio::piped());
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stdout_reader = BufReader::new(stdout);
+    let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
+    let pid = child.id().unwrap_or(0);
+
+    // Drain stderr as it comes, so a chatty run cannot block on a full pipe
+    let stderr = child.stderr.take().map(|stderr| {
+        let state = state.clone();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                send_to_session(
+                    &state,
+                    &session_id,
+                    json!({
+                        "type": "error",
+                        "message": line
+                    })
+                    .to_string(),
+                )
+                .await;
+            }
+        })
+    });
 
-    let mut lines = stdout_reader.lines();
+    let registry = &state.process_registry;
+    let run_id = registry.register_claude_session(
+        session_id.to_string(),
+        pid,
+        project_path.to_string(),
+        prompt.to_string(),
+        model.to_string(),
+        child,
+        stdin,
+    )?;
+    if !interactive {
+        registry.close_stdin(&run_id)?;
+    }
+
+    let mut lines = BufReader::new(stdout).lines();
     while let Ok(Some(line)) = lines.next_line().await {
         send_to_session(
-            &state,
-            &session_id,
+            state,
+            session_id,
             json!({
                 "type": "output",
                 "content": line
@@ -885,18 +1318,20 @@ async fn resume_claude_command(
         .await;
     }
 
-    let exit_status = child
-        .wait()
-        .await
-        .map_err(|e| format!("Failed to wait for Claude: {}", e))?;
-    if !exit_status.success() {
-        return Err(format!(
+    let exit_status = registry.wait_for_exit(&run_id).await;
+    registry.unregister_process(run_id)?;
+    if let Some(stderr) = stderr {
+        let _ = stderr.await;
+    }
+    match exit_status? {
+        Some(status) if status.success() => Ok(()),
+        Some(status) if status.code().is_some() => Err(format!(
             "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        ));
+            status.code()
+        )),
+        // Killed by a signal, or cancelled and unregistered
+        _ => Err("Claude run was stopped".to_string()),
     }
-
-    Ok(())
 }
 
 async fn send_to_session(state: &AppState, session_id: &str, message: String) {
@@ -922,21 +1357,427 @@ async fn send_to_session(state: &AppState, session_id: &str, message: String) {
     }
 }
 
+/// Team MCP server over streamable HTTP: one JSON-RPC message or batch per
+/// POST, answered with JSON, or 202 when it held only notifications
+async fn team_mcp(
+    AxumState(state): AxumState<AppState>,
+    Json(message): Json<serde_json::Value>,
+) -> Response {
+    let server = TeamServer::new(state.team.clone());
+    match server.handle(message).await {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendMessageRequest {
+    content: String,
+    #[serde(default)]
+    sender: Option<String>,
+    #[serde(default)]
+    sender_name: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct StartMemberRequest {
+    #[serde(default)]
+    model: Option<String>,
+}
+
+/// Team projects with their workspaces
+async fn list_team_projects(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<commands::storage::ProjectWithWorkspace>>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::storage::load_projects(&conn) {
+        Ok(projects) => Json(ApiResponse::success(projects)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Agents that are members of a project
+async fn list_team_agents(
+    AxumState(state): AxumState<AppState>,
+    Path(project_id): Path<String>,
+) -> Json<ApiResponse<Vec<commands::agents::Agent>>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::agents::load_project_agents(&conn, &project_id) {
+        Ok(agents) => Json(ApiResponse::success(agents)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn get_member_statuses(
+    AxumState(state): AxumState<AppState>,
+    Path(project_id): Path<String>,
+) -> Json<ApiResponse<Vec<commands::teammate::MemberProcessStatus>>> {
+    match commands::teammate::project_member_statuses(
+        &state.team.db,
+        &state.team.registry,
+        &project_id,
+    ) {
+        Ok(statuses) => Json(ApiResponse::success(statuses)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn get_team_messages(
+    AxumState(state): AxumState<AppState>,
+    Path(project_id): Path<String>,
+) -> Json<ApiResponse<Vec<commands::message::Message>>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match commands::message::load_messages(&conn, &project_id) {
+        Ok(messages) => Json(ApiResponse::success(messages)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Send a message to the project's @mentioned agent or TeamLead, starting
+/// it if needed
+async fn send_team_message(
+    AxumState(state): AxumState<AppState>,
+    Path(project_id): Path<String>,
+    Json(request): Json<SendMessageRequest>,
+) -> Json<ApiResponse<commands::message::Message>> {
+    match commands::message::send_team_message(
+        &state.team,
+        project_id,
+        request.content,
+        request.sender.unwrap_or_else(|| "user".to_string()),
+        request.sender_name.unwrap_or_else(|| "You".to_string()),
+    )
+    .await
+    {
+        Ok(message) => Json(ApiResponse::success(message)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Start a member's process. The agent and working directory come from the
+/// database, never from the request.
+async fn start_member(
+    AxumState(state): AxumState<AppState>,
+    Path((project_id, member_id)): Path<(String, String)>,
+    request: Option<Json<StartMemberRequest>>,
+) -> Json<ApiResponse<String>> {
+    let Json(request) = request.unwrap_or_default();
+    let member = {
+        let conn = match state.db.0.lock() {
+            Ok(c) => c,
+            Err(e) => {
+                return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+            }
+        };
+        conn.query_row(
+            "SELECT pa.agent_id, p.working_dir
+             FROM project_agents pa
+             INNER JOIN projects p ON p.id = pa.project_id
+             WHERE pa.id = ?1 AND pa.project_id = ?2",
+            rusqlite::params![member_id, project_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+    };
+    let (agent_id, working_dir) = match member {
+        Ok((agent_id, Some(working_dir))) => (agent_id, working_dir),
+        Ok((_, None)) => {
+            return Json(ApiResponse::error(
+                "Project has no working directory".to_string(),
+            ))
+        }
+        Err(e) => {
+            return Json(ApiResponse::error(format!(
+                "Project member not found: {}",
+                e
+            )))
+        }
+    };
+    match commands::teammate::start_teammate(
+        &state.team,
+        member_id,
+        agent_id,
+        working_dir,
+        project_id,
+        request.model,
+    )
+    .await
+    {
+        Ok(session_id) => Json(ApiResponse::success(session_id)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn stop_member(
+    AxumState(state): AxumState<AppState>,
+    Path((project_id, member_id)): Path<(String, String)>,
+) -> Json<ApiResponse<bool>> {
+    match commands::teammate::stop_teammate(
+        &state.team.registry,
+        state.team.events.as_ref(),
+        member_id,
+        project_id,
+    )
+    .await
+    {
+        Ok(stopped) => Json(ApiResponse::success(stopped)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Event stream of one project: every event the desktop app would receive
+/// for it, as `{"event": ..., "payload": ...}` text frames
+async fn project_events_websocket(
+    ws: WebSocketUpgrade,
+    AxumState(state): AxumState<AppState>,
+    Path(project_id): Path<String>,
+) -> Response {
+    ws.on_upgrade(move |socket| project_events(socket, state, project_id))
+}
+
+fn project_member_ids(state: &AppState, project_id: &str) -> Vec<String> {
+    let Ok(conn) = state.db.0.lock() else {
+        return Vec::new();
+    };
+    conn.prepare("SELECT id FROM project_agents WHERE project_id = ?1")
+        .and_then(|mut stmt| {
+            stmt.query_map([project_id], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_default()
+}
+
+async fn project_events(socket: WebSocket, state: AppState, project_id: String) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = state.events.subscribe();
+    let mut member_ids = project_member_ids(&state, &project_id);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        // The client missed events and should refetch
+                        AppEvent {
+                            event: "events-lagged".to_string(),
+                            payload: json!(skipped),
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                // Members may have joined since the connection opened
+                if event.event == format!("member-status-update:{}", project_id) {
+                    member_ids = project_member_ids(&state, &project_id);
+                }
+                if event.event != "events-lagged" && !event.concerns(&project_id, &member_ids) {
+                    continue;
+                }
+                let Ok(text) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if sender.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PairRequest {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct CreateTokenRequest {
+    name: String,
+}
+
+/// Whether the caller is signed in and whether it needs to be
+async fn auth_status(
+    AxumState(state): AxumState<AppState>,
+    headers: HeaderMap,
+) -> Json<serde_json::Value> {
+    Json(json!({
+        "authenticated": state.auth.is_authenticated(&headers),
+        "auth_required": state.auth.enabled(),
+    }))
+}
+
+/// Exchange the pairing token (or an API token) for a session cookie
+async fn auth_pair(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<PairRequest>,
+) -> Response {
+    if !state.auth.verify_token(request.token.trim()) {
+        // Slow down guessing
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::<()>::error(
+                "Invalid pairing token".to_string(),
+            )),
+        )
+            .into_response();
+    }
+    let session = state.auth.create_session();
+    (
+        [(
+            header::SET_COOKIE,
+            state.auth.session_cookie(Some(&session)),
+        )],
+        Json(ApiResponse::success(())),
+    )
+        .into_response()
+}
+
+/// End the browser session
+async fn auth_logout(AxumState(state): AxumState<AppState>, headers: HeaderMap) -> Response {
+    if let Some(id) = web_auth::session_id(&headers) {
+        state.auth.end_session(id);
+    }
+    (
+        [(header::SET_COOKIE, state.auth.session_cookie(None))],
+        Json(ApiResponse::success(())),
+    )
+        .into_response()
+}
+
+async fn list_api_tokens(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<web_auth::ApiToken>>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match web_auth::list_api_tokens(&conn) {
+        Ok(tokens) => Json(ApiResponse::success(tokens)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Create an API token; its secret is only returned here
+async fn create_api_token(
+    AxumState(state): AxumState<AppState>,
+    Json(request): Json<CreateTokenRequest>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match web_auth::create_api_token(&conn, &request.name) {
+        Ok((token, secret)) => Json(ApiResponse::success(
+            json!({ "token": token, "secret": secret }),
+        )),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn revoke_api_token(
+    AxumState(state): AxumState<AppState>,
+    Path(id): Path<String>,
+) -> Json<ApiResponse<bool>> {
+    let conn = match state.db.0.lock() {
+        Ok(c) => c,
+        Err(e) => {
+            return Json(ApiResponse::error(format!("Failed to lock database: {}", e)));
+        }
+    };
+    match web_auth::revoke_api_token(&conn, &id) {
+        Ok(revoked) => Json(ApiResponse::success(revoked)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 /// Create the web server
-pub async fn create_web_server(port: u16, db: AgentDb) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn create_web_server(
+    config: WebServerConfig,
+    db: AgentDb,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let port = config.port;
+    let addr = tokio::net::lookup_host((config.host.as_str(), port))
+        .await?
+        .next()
+        .ok_or_else(|| format!("Cannot resolve host {}", config.host))?;
+    let tls = match &config.tls {
+        Some(source) => Some(web_tls::prepare(
+            source,
+            std::slice::from_ref(&config.host),
+        )?),
+        None => None,
+    };
+
+    let mut allowed_origins = config.allowed_origins;
+    if is_dev_mode() {
+        allowed_origins.push("http://localhost:1420".to_string());
+    }
+    let (auth, pairing_token) = WebAuth::new(db.0.clone(), config.auth_enabled, allowed_origins);
+    let auth = Arc::new(auth.with_secure_cookies(tls.is_some()));
+
+    // Checkpoint timelines live next to the Claude sessions
+    let checkpoints = CheckpointState::new();
+    match commands::claude::get_claude_dir() {
+        Ok(claude_dir) => checkpoints.set_claude_dir(claude_dir).await,
+        Err(e) => log::warn!("Teammate checkpoints disabled: {}", e),
+    }
+
+    let process_registry = Arc::new(process::ProcessRegistry::new());
+    let events = Arc::new(BroadcastEmitter::new());
+    let team = TeamContext {
+        db: db.0.clone(),
+        registry: process_registry.clone(),
+        events: events.clone(),
+        app_data_dir: config.data_dir,
+        checkpoints: Some(checkpoints),
+    };
     let state = AppState {
         active_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
-        process_registry: Arc::new(process::ProcessRegistry::new()),
+        process_registry,
         db: Arc::new(db),
+        auth: auth.clone(),
+        team,
+        events,
+        claude_dir: dirs::home_dir()
+            .ok_or("Could not find home directory")?
+            .join(".claude"),
     };
 
-    // CORS layer to allow requests from phone browsers
+    // CORS: same-origin requests need no header; other origins must be
+    // listed explicitly
+    let origins: Vec<HeaderValue> = auth
+        .allowed_origins()
+        .iter()
+        .filter_map(|o| HeaderValue::from_str(o).ok())
+        .collect();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(AllowOrigin::list(origins))
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers(Any);
-
-    // Create router with API endpoints
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+        .allow_credentials(true);
     let app = Router::new()
         // Frontend routes
         .route("/", get(serve_frontend))
@@ -944,36 +1785,84 @@ pub async fn create_web_server(port: u16, db: AgentDb) -> Result<(), Box<dyn std
         // API routes (REST API equivalent of Tauri commands)
         .route("/api/projects", get(get_projects))
         .route("/api/projects/{project_id}/sessions", get(get_sessions))
-        .route("/api/agents", get(get_agents))
+        .route("/api/agents", get(get_agents).post(create_agent))
         .route("/api/agents/teamleads", get(get_teamleads))
+        .route(
+            "/api/agents/{id}",
+            get(get_agent).put(update_agent).delete(delete_agent),
+        )
         .route("/api/usage", get(get_usage))
+        .route("/api/usage/range", get(get_usage_by_date_range))
+        .route("/api/usage/sessions", get(get_session_stats))
+        .route("/api/usage/details", get(get_usage_details))
+        .route("/metrics", get(get_metrics))
         // Settings and configuration
-        .route("/api/settings/claude", get(get_claude_settings))
+        .route(
+            "/api/settings/claude",
+            get(get_claude_settings).post(save_claude_settings),
+        )
         .route("/api/settings/claude/version", get(check_claude_version))
         .route(
             "/api/settings/claude/installations",
             get(list_claude_installations),
         )
-        .route("/api/settings/system-prompt", get(get_system_prompt))
+        .route(
+            "/api/settings/system-prompt",
+            get(get_system_prompt).post(save_system_prompt),
+        )
+        // Authentication
+        .route("/api/auth/status", get(auth_status))
+        .route("/api/auth/pair", post(auth_pair))
+        .route("/api/auth/logout", post(auth_logout))
+        .route(
+            "/api/auth/tokens",
+            get(list_api_tokens).post(create_api_token),
+        )
+        .route("/api/auth/tokens/{id}", delete(revoke_api_token))
         // Session management
         .route("/api/sessions/new", get(open_new_session))
         // Slash commands
         .route("/api/slash-commands", get(list_slash_commands))
+        // Team projects, members and messages
+        .route("/api/team/projects", get(list_team_projects))
+        .route(
+            "/api/team/projects/{project_id}/agents",
+            get(list_team_agents),
+        )
+        .route(
+            "/api/team/projects/{project_id}/members/status",
+            get(get_member_statuses),
+        )
+        .route(
+            "/api/team/projects/{project_id}/members/{member_id}/start",
+            post(start_member),
+        )
+        .route(
+            "/api/team/projects/{project_id}/members/{member_id}/stop",
+            post(stop_member),
+        )
+        .route(
+            "/api/team/projects/{project_id}/messages",
+            get(get_team_messages).post(send_team_message),
+        )
+        .route("/ws/projects/{project_id}", get(project_events_websocket))
         // MCP
         .route("/api/mcp/servers", get(mcp_list))
+        // The agent team as an MCP server (streamable HTTP)
+        .route("/mcp", post(team_mcp))
         // Session history
         .route(
             "/api/sessions/{session_id}/history/{project_id}",
             get(load_session_history),
         )
         .route("/api/sessions/running", get(list_running_claude_sessions))
-        // Claude execution endpoints (read-only in web mode)
-        .route("/api/sessions/execute", get(execute_claude_code))
-        .route("/api/sessions/continue", get(continue_claude_code))
-        .route("/api/sessions/resume", get(resume_claude_code))
+        // Claude execution endpoints: run to completion and return the output
+        .route("/api/sessions/execute", post(execute_claude_code))
+        .route("/api/sessions/continue", post(continue_claude_code))
+        .route("/api/sessions/resume", post(resume_claude_code))
         .route(
             "/api/sessions/{sessionId}/cancel",
-            get(cancel_claude_execution),
+            post(cancel_claude_execution),
         )
         .route(
             "/api/sessions/{sessionId}/output",
@@ -984,27 +1873,440 @@ pub async fn create_web_server(port: u16, db: AgentDb) -> Result<(), Box<dyn std
         // Serve static assets (in dev mode, these should be empty as Vite serves them)
         .nest_service("/assets", ServeDir::new("../dist/assets"))
         .nest_service("/vite.svg", ServeDir::new("../dist/vite.svg"))
+        .layer(middleware::from_fn_with_state(
+            auth.clone(),
+            web_auth::require_auth,
+        ))
         .layer(cors)
         .with_state(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    println!("🌐 Web server running on http://0.0.0.0:{}", port);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    println!("🌐 Web server running on {}://{}", scheme, addr);
     if is_dev_mode() {
         println!("🔧 Dev mode: frontend served from Vite at http://localhost:1420");
+    }
+    if let Some(tls) = &tls {
+        if tls.self_signed {
+            println!("🔒 Self-signed certificate: {}", tls.cert.display());
+        } else {
+            println!("🔒 Certificate: {}", tls.cert.display());
+        }
+        println!("   SHA-256 fingerprint: {}", tls.fingerprint);
+    }
+
+    // Address phones can reach: the bound one, or this machine's LAN address
+    let public_host = if addr.ip().is_unspecified() {
+        web_tls::lan_ip()
+            .map(|ip| SocketAddr::new(ip, port).to_string())
+            .unwrap_or_else(|| format!("YOUR_PC_IP:{}", port))
     } else {
-        println!("📱 Access from phone: http://YOUR_PC_IP:{}", port);
+        addr.to_string()
+    };
+    if auth.enabled() {
+        println!("🔑 Pairing token: {}", pairing_token);
+        println!("📱 Open on your phone (or encode as a QR code):");
+        println!("{}://{}/?pair={}", scheme, public_host, pairing_token);
+    } else {
+        println!("📱 Access from phone: {}://{}", scheme, public_host);
+        println!("⚠️  AUTHENTICATION DISABLED: anyone who can reach this port can run Claude on this machine");
     }
 
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    match tls {
+        Some(tls) => {
+            // rustls is built without a default provider; pick ring
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let rustls_config =
+                axum_server::tls_rustls::RustlsConfig::from_pem_file(&tls.cert, &tls.key).await?;
+            axum_server::bind_rustls(addr, rustls_config)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        None => {
+            let listener = TcpListener::bind(addr).await?;
+            axum::serve(listener, app).await?;
+        }
+    }
 
     Ok(())
 }
 
 /// Start web server mode (alternative to Tauri GUI)
-pub async fn start_web_mode(port: Option<u16>, db: AgentDb) -> Result<(), Box<dyn std::error::Error>> {
-    let port = port.unwrap_or(8080);
-
+pub async fn start_web_mode(
+    config: WebServerConfig,
+    db: AgentDb,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("🚀 Starting Vibe Agent Team in web server mode...");
-    create_web_server(port, db).await
+    create_web_server(config, db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_database_with_path;
+    use crate::process::ProcessType;
+    use tempfile::TempDir;
+
+    fn state(dir: &TempDir) -> AppState {
+        let conn = init_database_with_path(&dir.path().join("test.db")).unwrap();
+        let db = AgentDb(Arc::new(std::sync::Mutex::new(conn)));
+        let (auth, _) = WebAuth::new(db.0.clone(), false, Vec::new());
+        let process_registry = Arc::new(process::ProcessRegistry::new());
+        let events = Arc::new(BroadcastEmitter::new());
+        let claude_dir = dir.path().join(".claude");
+        std::fs::create_dir_all(claude_dir.join("projects")).unwrap();
+        AppState {
+            active_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            process_registry: process_registry.clone(),
+            team: TeamContext {
+                db: db.0.clone(),
+                registry: process_registry,
+                events: events.clone(),
+                app_data_dir: dir.path().to_path_buf(),
+                checkpoints: None,
+            },
+            db: Arc::new(db),
+            auth: Arc::new(auth),
+            events,
+            claude_dir,
+        }
+    }
+
+    /// A stand-in for Claude that prints like one
+    fn fake_claude(script: &str) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", script]);
+        cmd.stdout(std::process::Stdio::piped());
+        cmd.stderr(std::process::Stdio::piped());
+        cmd
+    }
+
+    async fn running_sessions(state: &AppState) -> Vec<process::ProcessInfo> {
+        list_running_claude_sessions(AxumState(state.clone()))
+            .await
+            .0
+            .data
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_agent_routes_create_read_update_delete() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let new_agent = serde_json::from_value(json!({
+            "name": "alice",
+            "icon": "bot",
+            "systemPrompt": "Review code",
+            "roleType": "teammate"
+        }))
+        .unwrap();
+        let created = create_agent(AxumState(state.clone()), Json(new_agent))
+            .await
+            .0
+            .data
+            .unwrap();
+        let id = created.id.clone().unwrap();
+        assert_eq!(created.model, "sonnet");
+
+        let agents = get_agents(AxumState(state.clone())).await.0.data.unwrap();
+        assert_eq!(agents.len(), 1);
+
+        let update = serde_json::from_value(json!({
+            "name": "alice",
+            "icon": "bot",
+            "systemPrompt": "Review code carefully",
+            "model": "opus"
+        }))
+        .unwrap();
+        let updated = update_agent(AxumState(state.clone()), Path(id.clone()), Json(update))
+            .await
+            .0
+            .data
+            .unwrap();
+        assert_eq!(updated.system_prompt, "Review code carefully");
+        assert_eq!(updated.model, "opus");
+        let fetched = get_agent(AxumState(state.clone()), Path(id.clone()))
+            .await
+            .0
+            .data
+            .unwrap();
+        assert_eq!(fetched.model, "opus");
+
+        let deleted = delete_agent(AxumState(state.clone()), Path(id.clone())).await;
+        assert!(deleted.0.success);
+        let agents = get_agents(AxumState(state.clone())).await.0.data.unwrap();
+        assert!(agents.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_agent_routes_report_unknown_agents() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let fetched = get_agent(AxumState(state.clone()), Path("missing".to_string())).await;
+        assert!(!fetched.0.success);
+        assert!(fetched.0.error.is_some());
+        let update = serde_json::from_value(json!({
+            "name": "alice",
+            "icon": "bot",
+            "systemPrompt": ""
+        }))
+        .unwrap();
+        let updated = update_agent(
+            AxumState(state.clone()),
+            Path("missing".to_string()),
+            Json(update),
+        )
+        .await;
+        assert!(!updated.0.success);
+    }
+
+    #[tokio::test]
+    async fn test_usage_routes_read_the_claude_dir() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+        let project_dir = state.claude_dir.join("projects").join("-work-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(
+            project_dir.join("s1.jsonl"),
+            r#"{"timestamp":"2025-06-01T10:00:00Z","cwd":"/work/demo","sessionId":"s1","requestId":"r1","message":{"id":"m1","model":"claude-sonnet-4-20250514","usage":{"input_tokens":1000,"output_tokens":10}}}"#
+                .to_string()
+                + "\n",
+        )
+        .unwrap();
+
+        let usage = get_usage(AxumState(state.clone()), Query(UsageQuery { days: None }))
+            .await
+            .0
+            .data
+            .unwrap();
+        assert_eq!(usage.total_tokens, 1010);
+        assert_eq!(usage.by_project[0].project_path, "/work/demo");
+
+        let range = get_usage_by_date_range(
+            AxumState(state.clone()),
+            Query(UsageRangeQuery {
+                start_date: "2025-06-02".to_string(),
+                end_date: "2025-06-30".to_string(),
+            }),
+        )
+        .await
+        .0
+        .data
+        .unwrap();
+        assert_eq!(range.total_tokens, 0);
+
+        let details = get_usage_details(
+            AxumState(state.clone()),
+            Query(UsageDetailsQuery {
+                project_path: Some("/work/demo".to_string()),
+                date: None,
+            }),
+        )
+        .await
+        .0
+        .data
+        .unwrap();
+        assert_eq!(details.len(), 1);
+
+        let sessions = get_session_stats(
+            AxumState(state.clone()),
+            Query(SessionStatsQuery {
+                since: Some("20250601".to_string()),
+                until: None,
+                order: None,
+            }),
+        )
+        .await
+        .0
+        .data
+        .unwrap();
+        assert_eq!(sessions.len(), 1);
+
+        let metrics = get_metrics(AxumState(state.clone())).await;
+        assert_eq!(metrics.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_usage_range_rejects_bad_dates() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let range = get_usage_by_date_range(
+            AxumState(state),
+            Query(UsageRangeQuery {
+                start_date: "June 1st".to_string(),
+                end_date: "2025-06-30".to_string(),
+            }),
+        )
+        .await;
+        assert!(!range.0.success);
+        assert!(range.0.error.unwrap().contains("Invalid start date"));
+    }
+
+    #[tokio::test]
+    async fn test_settings_routes_round_trip_settings_json() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        // No settings file yet
+        let settings = get_claude_settings(AxumState(state.clone()))
+            .await
+            .0
+            .data
+            .unwrap();
+        assert_eq!(settings.data, json!({}));
+
+        let saved = save_claude_settings(
+            AxumState(state.clone()),
+            Json(SaveSettingsRequest {
+                settings: json!({"model": "opus"}),
+            }),
+        )
+        .await;
+        assert!(saved.0.success);
+        assert!(state.claude_dir.join("settings.json").exists());
+
+        let settings = get_claude_settings(AxumState(state.clone()))
+            .await
+            .0
+            .data
+            .unwrap();
+        assert_eq!(settings.data, json!({"model": "opus"}));
+    }
+
+    #[test]
+    fn test_only_interactive_runs_take_stream_json_input() {
+        assert!(!stream_json_args(false).contains(&"--input-format"));
+        let args = stream_json_args(true);
+        let at = args.iter().position(|a| *a == "--input-format").unwrap();
+        assert_eq!(args[at + 1], "stream-json");
+        assert!(args.ends_with(&["--verbose", "--dangerously-skip-permissions"]));
+    }
+
+    #[tokio::test]
+    async fn test_claude_run_streams_output_and_reports_exit_code() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        state
+            .active_sessions
+            .lock()
+            .await
+            .insert("s1".to_string(), tx);
+
+        let result = run_claude_process(
+            &state,
+            fake_claude(r#"echo '{"type":"result"}'; echo 'bad key' >&2; exit 3"#),
+            "s1",
+            "/tmp",
+            "hi",
+            "sonnet",
+            false,
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            "Claude execution failed with exit code: Some(3)"
+        );
+
+        // stdout and stderr are read side by side, so either may come first
+        let mut sent: Vec<serde_json::Value> = (0..2)
+            .map(|_| serde_json::from_str(&rx.try_recv().unwrap()).unwrap())
+            .collect();
+        sent.sort_by_key(|m| m["type"].to_string());
+        assert_eq!(sent[0], json!({"type": "error", "message": "bad key"}));
+        assert_eq!(sent[1]["type"], "output");
+        assert_eq!(sent[1]["content"], r#"{"type":"result"}"#);
+        assert!(running_sessions(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_claude_runs_are_listed_and_cancelled() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let run = tokio::spawn({
+            let state = state.clone();
+            async move {
+                run_claude_process(
+                    &state,
+                    fake_claude("exec sleep 30"),
+                    "s1",
+                    "/tmp",
+                    "hi",
+                    "sonnet",
+                    false,
+                )
+                .await
+            }
+        });
+        let sessions = loop {
+            let sessions = running_sessions(&state).await;
+            if !sessions.is_empty() {
+                break sessions;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        assert!(matches!(
+            &sessions[0].process_type,
+            ProcessType::ClaudeSession { session_id } if session_id == "s1"
+        ));
+        assert_eq!(sessions[0].task, "hi");
+
+        let cancelled =
+            cancel_claude_execution(AxumState(state.clone()), Path("s1".to_string())).await;
+        assert!(cancelled.0.success);
+        assert_eq!(run.await.unwrap().unwrap_err(), "Claude run was stopped");
+        assert!(running_sessions(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_of_unknown_session_fails() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let cancelled = cancel_claude_execution(AxumState(state), Path("nope".to_string())).await;
+        assert!(!cancelled.0.success);
+        assert_eq!(cancelled.0.error.unwrap(), "No running Claude session nope");
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout_stops_the_process() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let result = run_with_timeout(
+            &state,
+            "s1",
+            std::time::Duration::from_secs(1),
+            run_claude_process(
+                &state,
+                fake_claude("exec sleep 30"),
+                "s1",
+                "/tmp",
+                "hi",
+                "sonnet",
+                false,
+            ),
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), "Claude run timed out after 1 seconds");
+        assert!(running_sessions(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_route_requires_session_id() {
+        let dir = TempDir::new().unwrap();
+        let state = state(&dir);
+
+        let request = serde_json::from_value(json!({
+            "projectPath": "/tmp",
+            "prompt": "hi"
+        }))
+        .unwrap();
+        let resumed = resume_claude_code(AxumState(state.clone()), Json(request)).await;
+        assert_eq!(resumed.0.error.unwrap(), "sessionId is required to resume");
+        assert!(state.active_sessions.lock().await.is_empty());
+    }
 }
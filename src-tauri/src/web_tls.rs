@@ -0,0 +1,155 @@
+//! HTTPS for the web server
+//!
+//! Either a certificate and key supplied by the user or a self-signed pair
+//! generated on first use and kept under the app data dir, so phones only
+//! have to accept it once. The SHA-256 fingerprint is printed at startup
+//! for comparing against what the browser shows.
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+
+/// Bundle identifier, matching the Tauri app's data dir
+const APP_IDENTIFIER: &str = "com.jaxon.vibe-agent-team";
+
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// Where the certificate comes from
+#[derive(Debug, Clone)]
+pub enum TlsSource {
+    /// PEM files supplied by the user
+    Files { cert: PathBuf, key: PathBuf },
+    /// Self-signed pair kept in this directory
+    SelfSigned { dir: PathBuf },
+}
+
+/// Certificate and key ready to serve
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Colon-separated SHA-256 of the certificate
+    pub fingerprint: String,
+    pub self_signed: bool,
+}
+
+/// Directory for the self-signed certificate
+pub fn default_cert_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_IDENTIFIER)
+        .join("web-tls")
+}
+
+/// Resolve `source` to PEM files, generating a self-signed pair if needed.
+/// `hosts` become subject alternative names of a new certificate.
+pub fn prepare(source: &TlsSource, hosts: &[String]) -> Result<TlsFiles, String> {
+    let (cert, key, self_signed) = match source {
+        TlsSource::Files { cert, key } => (cert.clone(), key.clone(), false),
+        TlsSource::SelfSigned { dir } => {
+            let (cert, key) = (dir.join(CERT_FILE), dir.join(KEY_FILE));
+            if !cert.exists() || !key.exists() {
+                generate_self_signed(dir, hosts)?;
+            }
+            (cert, key, true)
+        }
+    };
+    for path in [&cert, &key] {
+        if !path.is_file() {
+            return Err(format!("{} not found", path.display()));
+        }
+    }
+    let fingerprint = fingerprint(&cert)?;
+    Ok(TlsFiles {
+        cert,
+        key,
+        fingerprint,
+        self_signed,
+    })
+}
+
+fn generate_self_signed(dir: &Path, hosts: &[String]) -> Result<(), String> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    names.extend(hosts.iter().cloned());
+    if let Some(ip) = lan_ip() {
+        names.push(ip.to_string());
+    }
+    names.retain(|name| name != "0.0.0.0" && name != "::");
+    names.sort();
+    names.dedup();
+
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    write_private(&dir.join(KEY_FILE), &certified.key_pair.serialize_pem())?;
+    std::fs::write(dir.join(CERT_FILE), certified.cert.pem())
+        .map_err(|e| format!("Failed to write certificate: {}", e))?;
+    log::info!("Generated self-signed certificate in {}", dir.display());
+    Ok(())
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Colon-separated SHA-256 of the first certificate in a PEM file
+pub fn fingerprint(cert: &Path) -> Result<String, String> {
+    let der = CertificateDer::from_pem_file(cert)
+        .map_err(|e| format!("Failed to read {}: {}", cert.display(), e))?;
+    Ok(Sha256::digest(der.as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+/// Address of the interface used for outbound traffic, i.e. the one phones
+/// on the same network can reach. Nothing is sent.
+pub fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_is_generated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = TlsSource::SelfSigned {
+            dir: dir.path().to_path_buf(),
+        };
+
+        let first = prepare(&source, &["vibe.local".to_string()]).unwrap();
+        assert!(first.self_signed);
+        assert_eq!(first.fingerprint.split(':').count(), 32);
+
+        // Reused, so phones keep trusting the same certificate
+        let second = prepare(&source, &[]).unwrap();
+        assert_eq!(first.fingerprint, second.fingerprint);
+
+        let missing = TlsSource::Files {
+            cert: dir.path().join("nope.pem"),
+            key: dir.path().join(KEY_FILE),
+        };
+        assert!(prepare(&missing, &[]).is_err());
+    }
+}
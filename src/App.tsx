@@ -4,7 +4,7 @@ import { Bot, FolderCode, Layout } from "lucide-react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { api, type Project, type Session, type ClaudeMdFile } from "@/lib/api";
-import { initializeWebMode } from "@/lib/apiAdapter";
+import { apiCall, initializeWebMode } from "@/lib/apiAdapter";
 import { TabProvider } from "@/contexts/TabContext";
 import { ThemeProvider } from "@/contexts/ThemeContext";
 import { Card } from "@/components/ui/card";
@@ -124,7 +124,7 @@ function AppContent() {
     if (view === "three-level") {
       const loadDbProjects = async () => {
         try {
-          const result = await invoke<DbProject[]>("storage_list_projects");
+          const result = await apiCall<DbProject[]>("storage_list_projects");
           setDbProjects(result);
         } catch (error) {
           console.error("加载项目列表失败:", error);
@@ -142,7 +142,7 @@ function AppContent() {
       console.log("Project initialized event received:", event.payload);
       // Refresh project list when a project finishes initialization
       try {
-        const result = await invoke<DbProject[]>("storage_list_projects");
+        const result = await apiCall<DbProject[]>("storage_list_projects");
         // 保留本地正在进行的项目状态，只更新已完成的项目
         setDbProjects(prev => {
           const localInProgress = prev.filter(p => p.initializing && p.progress && !p.progress.message.includes('完成'));
@@ -1,7 +1,5 @@
 import React, { useEffect, useState } from 'react';
 import { motion } from 'framer-motion';
-import { invoke } from '@tauri-apps/api/core';
-import { listen } from '@tauri-apps/api/event';
 import {
   FolderOpen, FileText, Users, BarChart, MessageSquare, Settings,
   Search, Plus, MoreVertical, UserPlus, Smile, Scissors,
@@ -28,6 +26,7 @@ import { FloatingPromptInput } from './FloatingPromptInput';
 import { Teammates } from './Teammates';
 import { ThinkingWidget } from './ToolWidgets';
 import { api, type Agent, type Message } from '@/lib/api';
+import { apiCall, listenEvent, subscribeProjectEvents } from '@/lib/apiAdapter';
 // 项目进度类型
 interface ProjectProgress {
   step: string;
@@ -219,12 +218,18 @@ export const ThreeLevelLayout: React.FC<ThreeLevelLayoutProps> = ({
     console.log('current selectedNav', selectedNav)
   }, [selectedNav])
 
+  // Web mode: stream the selected project's events from the server
+  useEffect(() => {
+    if (!selectedProject) return;
+    return subscribeProjectEvents(selectedProject.project_id);
+  }, [selectedProject]);
+
   // 当选中项目变化时，获取项目成员
   useEffect(() => {
     const fetchProjectMembers = async () => {
       if (selectedProject) {
         try {
-          const agents = await invoke<Array<{
+          const agents = await apiCall<Array<{
             id: string | null;
             name: string;
             icon: string;
@@ -289,7 +294,7 @@ export const ThreeLevelLayout: React.FC<ThreeLevelLayoutProps> = ({
 
     let unlisten: (() => void) | undefined;
     const setupListener = async () => {
-      unlisten = await listen(`member-status-update:${selectedProject.project_id}`, () => {
+      unlisten = await listenEvent(`member-status-update:${selectedProject.project_id}`, () => {
         console.log('[ThreeLevelLayout] Member status updated, refreshing...');
         fetchMemberStatuses();
       });
@@ -334,7 +339,7 @@ export const ThreeLevelLayout: React.FC<ThreeLevelLayoutProps> = ({
     // 监听新消息事件，直接添加到消息列表
     let unlisten: (() => void) | undefined;
     const setupListener = async () => {
-      unlisten = await listen<Message>('new-message', (event) => {
+      unlisten = await listenEvent<Message>('new-message', (event) => {
         console.log('[ThreeLevelLayout] Received new message:', event.payload);
         // 只有当前选中的项目匹配时才添加消息
         if (selectedProject && event.payload.project_id === selectedProject.project_id) {
@@ -8,6 +8,7 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type EventCallback, type UnlistenFn } from "@tauri-apps/api/event";
 
 // Extend Window interface for Tauri
 declare global {
@@ -72,7 +73,7 @@ interface ApiResponse<T> {
 /**
  * Make a REST API call to our web server
  */
-async function restApiCall<T>(endpoint: string, params?: any): Promise<T> {
+async function restApiCall<T>(endpoint: string, params?: any, method: string = 'GET'): Promise<T> {
   // First handle path parameters in the endpoint string
   let processedEndpoint = endpoint;
   console.log(`[REST API] Original endpoint: ${endpoint}, params:`, params);
@@ -101,7 +102,8 @@ async function restApiCall<T>(endpoint: string, params?: any): Promise<T> {
   const baseUrl = window.location.origin || 'http://localhost:8080';
   const url = new URL(processedEndpoint, baseUrl);
   
-  // Add remaining params as query parameters for GET requests (if no placeholders remain)
+  // Remaining params go in the query for GET and in the JSON body otherwise
+  const body: Record<string, any> = {};
   if (params && !processedEndpoint.includes('{')) {
     Object.keys(params).forEach(key => {
       // Only add as query param if it wasn't used as a path param
@@ -110,19 +112,29 @@ async function restApiCall<T>(endpoint: string, params?: any): Promise<T> {
           !endpoint.includes(`{${key.charAt(0).toUpperCase() + key.slice(1)}}`) &&
           params[key] !== undefined && 
           params[key] !== null) {
-        url.searchParams.append(key, String(params[key]));
+        if (method === 'GET') {
+          url.searchParams.append(key, String(params[key]));
+        } else {
+          body[key] = params[key];
+        }
       }
     });
   }
 
   try {
+    await pairing;
     const response = await fetch(url.toString(), {
-      method: 'GET',
+      method,
       headers: {
         'Content-Type': 'application/json',
       },
+      body: method === 'GET' ? undefined : JSON.stringify(body),
     });
 
+    if (response.status === 401) {
+      throw new Error('Not signed in: open the pairing link printed by the web server');
+    }
+
     if (!response.ok) {
       throw new Error(`HTTP error! status: ${response.status}`);
     }
@@ -141,7 +153,8 @@ async function restApiCall<T>(endpoint: string, params?: any): Promise<T> {
 }
 
 /**
- * Commands that must use Tauri invoke (never REST API)
+ * Commands whose desktop invoke errors are thrown rather than retried over
+ * REST, which has no server to reach inside the app
  */
 const TAURI_ONLY_COMMANDS = [
   'list_teamleads',
@@ -152,6 +165,21 @@ const TAURI_ONLY_COMMANDS = [
   'get_agent',
 ];
 
+/**
+ * HTTP method of commands that change state in web mode (others are GET)
+ */
+const COMMAND_METHODS: Record<string, string> = {
+  'create_agent': 'POST',
+  'update_agent': 'PUT',
+  'delete_agent': 'DELETE',
+  'save_claude_settings': 'POST',
+  'save_system_prompt': 'POST',
+  'send_message': 'POST',
+  'start_teammate_agent': 'POST',
+  'stop_teammate_agent': 'POST',
+  'cancel_claude_execution': 'POST',
+};
+
 /**
  * Unified API adapter that works in both Tauri and web environments
  */
@@ -159,7 +187,7 @@ export async function apiCall<T>(command: string, params?: any): Promise<T> {
   const isWeb = !detectEnvironment();
   const isTauriOnly = TAURI_ONLY_COMMANDS.includes(command);
 
-  if (!isWeb || isTauriOnly) {
+  if (!isWeb) {
     // Tauri environment - try invoke
     console.log(`[Tauri] Calling: ${command}`, params);
     try {
@@ -191,7 +219,8 @@ export async function apiCall<T>(command: string, params?: any): Promise<T> {
   
   // Map Tauri commands to REST endpoints
   const endpoint = mapCommandToEndpoint(command, params);
-  return await restApiCall<T>(endpoint, params);
+  const method = COMMAND_METHODS[command] ?? 'GET';
+  return await restApiCall<T>(endpoint, params, method);
 }
 
 /**
@@ -202,6 +231,15 @@ function mapCommandToEndpoint(command: string, _params?: any): string {
     // Project and session commands
     'list_projects': '/api/projects',
     'get_project_sessions': '/api/projects/{projectId}/sessions',
+
+    // Team projects, members and messages
+    'storage_list_projects': '/api/team/projects',
+    'list_project_agents': '/api/team/projects/{projectId}/agents',
+    'get_project_member_statuses': '/api/team/projects/{projectId}/members/status',
+    'start_teammate_agent': '/api/team/projects/{projectId}/members/{projectAgentId}/start',
+    'stop_teammate_agent': '/api/team/projects/{projectId}/members/{sessionId}/stop',
+    'get_messages': '/api/team/projects/{projectId}/messages',
+    'send_message': '/api/team/projects/{projectId}/messages',
     
     // Agent commands
     'list_agents': '/api/agents',
@@ -315,6 +353,63 @@ export function getEnvironmentInfo() {
   };
 }
 
+/**
+ * Listen for a backend event: a Tauri event on desktop, a window event
+ * dispatched by subscribeProjectEvents in web mode
+ */
+export async function listenEvent<T>(eventName: string, callback: EventCallback<T>): Promise<UnlistenFn> {
+  if (detectEnvironment()) {
+    return listen<T>(eventName, callback);
+  }
+  const handler = (e: Event) =>
+    callback({ event: eventName, id: 0, payload: (e as CustomEvent<T>).detail });
+  window.addEventListener(eventName, handler);
+  return () => window.removeEventListener(eventName, handler);
+}
+
+/**
+ * Stream a project's team events from the web server and re-dispatch them
+ * as window events. Reconnects until the returned function is called; a
+ * no-op on desktop, where Tauri delivers the events.
+ */
+export function subscribeProjectEvents(projectId: string): () => void {
+  if (detectEnvironment()) {
+    return () => {};
+  }
+
+  let ws: WebSocket | null = null;
+  let closed = false;
+  let retry: ReturnType<typeof setTimeout> | undefined;
+
+  const connect = async () => {
+    await pairing;
+    if (closed) return;
+    const wsProtocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+    ws = new WebSocket(`${wsProtocol}//${window.location.host}/ws/projects/${encodeURIComponent(projectId)}`);
+    ws.onmessage = (event) => {
+      try {
+        const { event: name, payload } = JSON.parse(event.data);
+        window.dispatchEvent(new CustomEvent(name, { detail: payload }));
+      } catch (error) {
+        console.error('[Web] Failed to parse project event:', error);
+      }
+    };
+    ws.onclose = () => {
+      if (!closed) {
+        retry = setTimeout(connect, 2000);
+      }
+    };
+  };
+
+  connect();
+
+  return () => {
+    closed = true;
+    clearTimeout(retry);
+    ws?.close();
+  };
+}
+
 /**
  * Handle streaming commands via WebSocket in web mode
  */
@@ -446,6 +541,9 @@ async function handleStreamingCommand<T>(command: string, params?: any): Promise
   });
 }
 
+/** Resolves once a pairing token from the URL has been exchanged */
+let pairing: Promise<void> = Promise.resolve();
+
 /**
  * Initialize web mode compatibility
  * Sets up mocks for Tauri APIs when running in web mode
@@ -476,5 +574,24 @@ export function initializeWebMode() {
         }
       };
     }
+
+    // Exchange a pairing token from the startup link for a session cookie
+    const url = new URL(window.location.href);
+    const pairingToken = url.searchParams.get('pair');
+    if (pairingToken) {
+      url.searchParams.delete('pair');
+      window.history.replaceState(null, '', url.toString());
+      pairing = fetch('/api/auth/pair', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ token: pairingToken }),
+      })
+        .then((response) => {
+          if (!response.ok) {
+            console.error('[auth] Pairing failed, status:', response.status);
+          }
+        })
+        .catch((error) => console.error('[auth] Pairing failed:', error));
+    }
   }
 }
\ No newline at end of file